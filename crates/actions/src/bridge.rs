@@ -12,20 +12,46 @@ use crate::traits::{ActionContext, ActionResult as ActionsActionResult};
 /// `SkillEngine` can drive real actions.
 ///
 /// Holds both the dispatcher and a pre-configured `ActionContext`.
+/// An optional allowlist restricts which actions are exposed to the LLM
+/// and which may be executed.
 pub struct BridgedExecutor {
     dispatcher: ActionDispatcher,
     context: ActionContext,
+    allowed_actions: Option<Vec<String>>,
 }
 
 impl BridgedExecutor {
     pub fn new(dispatcher: ActionDispatcher, context: ActionContext) -> Self {
-        Self { dispatcher, context }
+        Self {
+            dispatcher,
+            context,
+            allowed_actions: None,
+        }
+    }
+
+    /// Restrict the executor to the given action names.
+    pub fn with_allowed_actions(mut self, actions: Vec<String>) -> Self {
+        self.allowed_actions = Some(actions);
+        self
+    }
+
+    fn is_allowed(&self, name: &str) -> bool {
+        match &self.allowed_actions {
+            Some(allowed) => allowed.iter().any(|a| a == name),
+            None => true,
+        }
     }
 }
 
 #[async_trait]
 impl ActionExecutor for BridgedExecutor {
     async fn execute(&self, name: &str, args: &serde_json::Value) -> CoreActionResult {
+        if !self.is_allowed(name) {
+            return ActionsActionResult::error(&format!(
+                "Action '{name}' is not available to this agent"
+            ))
+            .into();
+        }
         let actions_result = self.dispatcher.execute(name, args, &self.context).await;
         actions_result.into()
     }
@@ -34,6 +60,7 @@ impl ActionExecutor for BridgedExecutor {
         self.dispatcher
             .get_definitions(&[])
             .into_iter()
+            .filter(|d| self.is_allowed(&d.name))
             .map(|d| ToolDefinition {
                 name: d.name,
                 description: d.description,
@@ -124,9 +124,7 @@ impl Action for GenerateInnerVoiceAction {
                 turn_number: None,
                 metadata_json: None,
             };
-            if let Ok(conn) = ctx.db.lock() {
-                let _ = opencrab_db::queries::insert_session_log(&conn, &log);
-            }
+            let _ = ctx.record_session_log(&log);
         }
 
         ActionResult::success(json!({
@@ -285,11 +283,42 @@ impl Action for DeclareDoneAction {
         })
     }
 
-    async fn execute(&self, args: &serde_json::Value, _ctx: &ActionContext) -> ActionResult {
+    async fn execute(&self, args: &serde_json::Value, ctx: &ActionContext) -> ActionResult {
         let reason = args["reason"].as_str().unwrap_or("議論が十分に行われた");
+
+        // セッション内なら終了宣言を記録する（全員が宣言するとセッションが終了する）
+        let done_count = match &ctx.session_id {
+            Some(session_id) => {
+                let conn = ctx.db.lock().unwrap();
+                // 作業完了までの select_llm の切り替えはここで解除する
+                let override_duration =
+                    opencrab_db::queries::get_agent_model_override(&conn, &ctx.agent_id, session_id)
+                        .ok()
+                        .flatten()
+                        .map(|(_, duration)| duration);
+                if override_duration.as_deref() == Some("until_task_complete") {
+                    opencrab_db::queries::set_agent_model_override(
+                        &conn,
+                        &ctx.agent_id,
+                        session_id,
+                        None,
+                        None,
+                    )
+                    .ok();
+                }
+                match opencrab_db::queries::set_done_declared(&conn, &ctx.agent_id, session_id, true)
+                {
+                    Ok(count) => Some(count),
+                    Err(e) => return ActionResult::error(&format!("Failed to record done: {e}")),
+                }
+            }
+            None => None,
+        };
+
         ActionResult::success(json!({
             "done": true,
             "reason": reason,
+            "done_count": done_count,
         }))
     }
 }
@@ -320,6 +349,10 @@ mod tests {
                 gateway: "test".to_string(),
             })),
             gateway_admin: None,
+            embedder: None,
+            memory_policy: Default::default(),
+            model_policy: None,
+            session_log_sink: None,
         };
         (dir, ctx)
     }
@@ -349,10 +382,58 @@ mod tests {
         assert!(result.success);
     }
 
+    #[tokio::test]
+    async fn test_inner_voice_goes_through_log_sink() {
+        struct RecordingSink(std::sync::Mutex<Vec<String>>);
+        impl SessionLogSink for RecordingSink {
+            fn record(&self, log: &opencrab_db::queries::SessionLogRow) -> anyhow::Result<i64> {
+                self.0.lock().unwrap().push(log.log_type.clone());
+                Ok(1)
+            }
+        }
+
+        let (_dir, mut ctx) = test_context();
+        let sink = std::sync::Arc::new(RecordingSink(std::sync::Mutex::new(Vec::new())));
+        ctx.session_log_sink = Some(sink.clone());
+        let result = GenerateInnerVoiceAction
+            .execute(&json!({"thought": "hmm"}), &ctx)
+            .await;
+        assert!(result.success);
+        assert_eq!(*sink.0.lock().unwrap(), vec!["inner_voice".to_string()]);
+    }
+
     #[tokio::test]
     async fn test_declare_done() {
         let (_dir, ctx) = test_context();
         let result = DeclareDoneAction.execute(&json!({"reason": "done"}), &ctx).await;
         assert!(result.success);
+        assert_eq!(result.data.unwrap()["done_count"], 1);
+
+        let conn = ctx.db.lock().unwrap();
+        let rows = opencrab_db::queries::list_agent_sessions(&conn, "session-1").unwrap();
+        assert!(rows.iter().any(|r| r.agent_id == "agent-1" && r.done_declared));
+    }
+
+    #[tokio::test]
+    async fn test_declare_done_clears_task_model_override() {
+        let (_dir, ctx) = test_context();
+        {
+            let conn = ctx.db.lock().unwrap();
+            opencrab_db::queries::set_agent_model_override(
+                &conn,
+                "agent-1",
+                "session-1",
+                Some("openai:o1"),
+                Some("until_task_complete"),
+            )
+            .unwrap();
+        }
+        DeclareDoneAction.execute(&json!({}), &ctx).await;
+
+        let conn = ctx.db.lock().unwrap();
+        assert_eq!(
+            opencrab_db::queries::get_agent_model_override(&conn, "agent-1", "session-1").unwrap(),
+            None
+        );
     }
 }
@@ -305,6 +305,10 @@ mod tests {
                 gateway: "test".to_string(),
             })),
             gateway_admin: None,
+            embedder: None,
+            memory_policy: Default::default(),
+            model_policy: None,
+            session_log_sink: None,
         };
         (dir, ctx)
     }
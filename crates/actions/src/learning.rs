@@ -70,6 +70,7 @@ impl Action for LearnFromExperienceAction {
             source_type: "experience".to_string(),
             source_context: args["experience"].as_str().map(|s| s.to_string()),
             file_path: None,
+            version: None,
             effectiveness: None,
             usage_count: 0,
             is_active: true,
@@ -155,6 +156,7 @@ impl Action for LearnFromPeerAction {
             source_type: "peer".to_string(),
             source_context: Some(source_context),
             file_path: None,
+            version: None,
             effectiveness: None,
             usage_count: 0,
             is_active: true,
@@ -263,6 +265,10 @@ mod tests {
                 gateway: "test".to_string(),
             })),
             gateway_admin: None,
+            embedder: None,
+            memory_policy: Default::default(),
+            model_policy: None,
+            session_log_sink: None,
         };
         (dir, ctx)
     }
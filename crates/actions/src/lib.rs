@@ -13,3 +13,4 @@ pub mod discord_admin;
 pub use traits::*;
 pub use dispatcher::ActionDispatcher;
 pub use bridge::BridgedExecutor;
+pub use llm_selection::{ModelSelectionPolicy, SelectionDuration};
@@ -157,7 +157,7 @@ impl Action for RecallModelExperiencesAction {
 
         let metrics: Vec<serde_json::Value> = model_stats
             .iter()
-            .filter(|s| model_filter.map_or(true, |f| s.model == f))
+            .filter(|s| model_filter.is_none_or(|f| s.model == f))
             .map(|s| {
                 json!({
                     "provider": s.provider,
@@ -179,7 +179,7 @@ impl Action for RecallModelExperiencesAction {
 
         let by_purpose: Vec<serde_json::Value> = purpose_stats
             .iter()
-            .filter(|s| model_filter.map_or(true, |f| s.model == f))
+            .filter(|s| model_filter.is_none_or(|f| s.model == f))
             .map(|s| {
                 json!({
                     "provider": s.provider,
@@ -474,6 +474,10 @@ mod tests {
                 gateway: "test".to_string(),
             })),
             gateway_admin: None,
+            embedder: None,
+            memory_policy: Default::default(),
+            model_policy: None,
+            session_log_sink: None,
         };
         (dir, ctx)
     }
@@ -498,6 +502,10 @@ mod tests {
                 gateway: "test".to_string(),
             })),
             gateway_admin: None,
+            embedder: None,
+            memory_policy: Default::default(),
+            model_policy: None,
+            session_log_sink: None,
         };
         (dir, ctx)
     }
@@ -570,7 +578,7 @@ mod tests {
         assert!(!evals.is_empty());
         // Evaluations contain free-text Japanese.
         let has_japanese_eval = evals.iter().any(|e|
-            e["evaluation"].as_str().map_or(false, |t| t.contains("丁寧"))
+            e["evaluation"].as_str().is_some_and(|t| t.contains("丁寧"))
         );
         assert!(has_japanese_eval, "Evaluations should contain free-text feedback");
 
@@ -579,7 +587,7 @@ mod tests {
         assert_eq!(notes.len(), 4);
         // Notes contain qualitative observations.
         let has_insight = notes.iter().any(|n|
-            n["observation"].as_str().map_or(false, |t| t.contains("ステップバイステップ"))
+            n["observation"].as_str().is_some_and(|t| t.contains("ステップバイステップ"))
         );
         assert!(has_insight, "Notes should contain qualitative observations");
 
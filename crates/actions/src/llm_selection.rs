@@ -1,8 +1,112 @@
+use std::sync::Arc;
+
 use async_trait::async_trait;
+use opencrab_llm::router::LlmRouter;
 use serde_json::json;
 
 use crate::traits::{Action, ActionContext, ActionResult, SideEffect};
 
+/// select_llm による切り替えの有効期間
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionDuration {
+    /// 今回の応答（SkillEngine の1回の実行）のみ
+    ThisTurn,
+    /// セッションが続く間
+    ThisSession,
+    /// declare_done で作業の完了を宣言するまで
+    UntilTaskComplete,
+}
+
+impl SelectionDuration {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "this_turn" => Some(Self::ThisTurn),
+            "this_session" => Some(Self::ThisSession),
+            "until_task_complete" => Some(Self::UntilTaskComplete),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ThisTurn => "this_turn",
+            Self::ThisSession => "this_session",
+            Self::UntilTaskComplete => "until_task_complete",
+        }
+    }
+}
+
+/// select_llm で選べるモデルの制約。
+///
+/// サーバーが `[llm.self_selection]` とエージェント別設定（`agent_llm_config`）から組み立てる。
+pub struct ModelSelectionPolicy {
+    /// モデル名の解決と登録済みプロバイダーの確認に使う
+    pub router: Arc<LlmRouter>,
+    /// false なら選択を常に拒否する
+    pub enabled: bool,
+    /// 選択を許可するエイリアスまたは `provider:model`（`[llm.self_selection]`）。空なら登録済みプロバイダーの全モデル
+    pub allowed: Vec<String>,
+    /// エージェント別の許可リスト（`selectable_models`）。空なら制限なし。
+    /// `allowed` を広げることはできず、両方に含まれるモデルだけを選べる
+    pub agent_allowed: Vec<String>,
+    /// 許可リストに関係なく選べるモデル（既定モデルへ戻す場合など）
+    pub always_allowed: Vec<String>,
+}
+
+impl ModelSelectionPolicy {
+    /// 選択を検証し、解決済みの `provider:model` を返す。拒否する場合は理由を返す。
+    pub fn check(&self, requested: &str) -> Result<String, String> {
+        if !self.enabled {
+            return Err("Model self-selection is disabled for this agent".to_string());
+        }
+        let (provider, model) = self
+            .router
+            .resolve_model(requested)
+            .map_err(|e| format!("Unknown model '{requested}': {e}"))?;
+        if !self.router.provider_names().contains(&provider.as_str()) {
+            return Err(format!(
+                "Unknown model '{requested}': provider '{provider}' is not configured"
+            ));
+        }
+
+        let candidate = (provider, model);
+        let permitted = self.always_allowed.iter().any(|entry| self.matches(entry, &candidate))
+            || (self.permits(&self.allowed, &candidate) && self.permits(&self.agent_allowed, &candidate));
+        if !permitted {
+            return Err(format!(
+                "Model '{requested}' is not allowed. Choose one of: {}",
+                self.choices().join(", ")
+            ));
+        }
+        Ok(format!("{}:{}", candidate.0, candidate.1))
+    }
+
+    /// 許可リスト `list` が `candidate` を許すか（空なら制限なし）
+    fn permits(&self, list: &[String], candidate: &(String, String)) -> bool {
+        list.is_empty() || list.iter().any(|entry| self.matches(entry, candidate))
+    }
+
+    fn matches(&self, entry: &str, candidate: &(String, String)) -> bool {
+        self.router.resolve_model(entry).is_ok_and(|pair| pair == *candidate)
+    }
+
+    /// 両方の許可リストを満たす選択肢
+    fn choices(&self) -> Vec<String> {
+        if self.agent_allowed.is_empty() {
+            return self.allowed.clone();
+        }
+        self.agent_allowed
+            .iter()
+            .filter(|entry| {
+                self.router
+                    .resolve_model(entry)
+                    .is_ok_and(|pair| self.permits(&self.allowed, &pair))
+            })
+            .cloned()
+            .collect()
+    }
+}
+
 /// LLM選択アクション — エージェントが自ら使用モデルを切り替える
 pub struct SelectLlmAction;
 
@@ -13,7 +117,7 @@ impl Action for SelectLlmAction {
     }
 
     fn description(&self) -> &str {
-        "タスクに応じて使用するLLMモデルを切り替える。provider:model形式（例: openai:gpt-4o-mini）またはエイリアス（fast, smart等）で指定。"
+        "タスクに応じて使用するLLMモデルを切り替える。provider:model形式（例: openai:gpt-4o-mini）またはエイリアス（fast, smart等）で指定。許可されていないモデルは選べない。"
     }
 
     fn parameters(&self) -> serde_json::Value {
@@ -35,7 +139,8 @@ impl Action for SelectLlmAction {
                 },
                 "duration": {
                     "type": "string",
-                    "description": "この設定の有効期間（自由記述。例: this_turn, this_session, until_task_complete）"
+                    "enum": ["this_turn", "this_session", "until_task_complete"],
+                    "description": "この設定の有効期間。this_turn: 今回の応答のみ（既定）、this_session: このセッションの間、until_task_complete: declare_done するまで"
                 }
             }
         })
@@ -45,11 +150,42 @@ impl Action for SelectLlmAction {
         let purpose = args["purpose"].as_str().unwrap_or("conversation");
         let model_alias = args["model_alias"].as_str().unwrap_or("smart");
         let reason = args["reason"].as_str().unwrap_or("");
-        let duration = args["duration"].as_str().unwrap_or("this_turn");
+        let duration_arg = args["duration"].as_str().unwrap_or("this_turn");
+        let Some(duration) = SelectionDuration::parse(duration_arg) else {
+            return ActionResult::error(&format!(
+                "Invalid duration '{duration_arg}': use this_turn, this_session or until_task_complete"
+            ));
+        };
+
+        let selected = match &ctx.model_policy {
+            Some(policy) => match policy.check(model_alias) {
+                Ok(model) => model,
+                Err(e) => return ActionResult::error(&e),
+            },
+            None => model_alias.to_string(),
+        };
+
+        // セッション内なら次回以降の応答にも引き継ぐ
+        let persisted = match (&ctx.session_id, duration) {
+            (Some(session_id), SelectionDuration::ThisSession | SelectionDuration::UntilTaskComplete) => {
+                let conn = ctx.db.lock().unwrap();
+                if let Err(e) = opencrab_db::queries::set_agent_model_override(
+                    &conn,
+                    &ctx.agent_id,
+                    session_id,
+                    Some(&selected),
+                    Some(duration.as_str()),
+                ) {
+                    return ActionResult::error(&format!("Failed to save model selection: {e}"));
+                }
+                true
+            }
+            _ => false,
+        };
 
         // Update the shared model_override so SkillEngine uses this model.
         if let Ok(mut current) = ctx.model_override.lock() {
-            *current = Some(model_alias.to_string());
+            *current = Some(selected.clone());
         }
 
         // Update the shared current_purpose so metrics are tagged correctly.
@@ -59,14 +195,16 @@ impl Action for SelectLlmAction {
 
         ActionResult::success(json!({
             "switched": true,
-            "selected": model_alias,
+            "selected": selected,
+            "requested": model_alias,
             "purpose": purpose,
             "reason": reason,
-            "duration": duration,
+            "duration": duration.as_str(),
+            "persisted": persisted,
         }))
         .with_side_effect(SideEffect::LlmSwitched {
             purpose: purpose.to_string(),
-            model: model_alias.to_string(),
+            model: selected,
         })
     }
 }
@@ -74,7 +212,7 @@ impl Action for SelectLlmAction {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::sync::Arc;
+    use opencrab_llm::providers::{OllamaProvider, OpenAiProvider};
 
     fn test_context() -> (tempfile::TempDir, ActionContext) {
         let conn = opencrab_db::init_memory().unwrap();
@@ -96,6 +234,10 @@ mod tests {
                 gateway: "test".to_string(),
             })),
             gateway_admin: None,
+            embedder: None,
+            memory_policy: Default::default(),
+            model_policy: None,
+            session_log_sink: None,
         };
         (dir, ctx)
     }
@@ -149,4 +291,123 @@ mod tests {
             _ => panic!("Expected LlmSwitched side effect"),
         }
     }
+
+    fn test_policy(allowed: &[&str]) -> ModelSelectionPolicy {
+        let mut router = LlmRouter::new();
+        router.add_provider(Arc::new(OpenAiProvider::new("test-key")));
+        router.add_provider(Arc::new(OllamaProvider::new()));
+        router.set_default_provider("openai");
+        router.add_model_mapping("fast", "openai:gpt-4o-mini");
+        router.add_model_mapping("reasoning", "openai:o1");
+        ModelSelectionPolicy {
+            router: Arc::new(router),
+            enabled: true,
+            allowed: allowed.iter().map(|s| s.to_string()).collect(),
+            agent_allowed: Vec::new(),
+            always_allowed: vec!["openai:gpt-4o".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_policy_check() {
+        let policy = test_policy(&["fast", "ollama:llama3.1:8b"]);
+        assert_eq!(policy.check("fast").unwrap(), "openai:gpt-4o-mini");
+        // 解決後のモデルで比較する
+        assert_eq!(policy.check("openai:gpt-4o-mini").unwrap(), "openai:gpt-4o-mini");
+        assert_eq!(policy.check("ollama:llama3.1:8b").unwrap(), "ollama:llama3.1:8b");
+        // 既定モデルには常に戻せる
+        assert_eq!(policy.check("gpt-4o").unwrap(), "openai:gpt-4o");
+
+        assert!(policy.check("reasoning").unwrap_err().contains("not allowed"));
+        assert!(policy.check("openai:o1").unwrap_err().contains("not allowed"));
+        assert!(policy.check("anthropic:claude").unwrap_err().contains("not configured"));
+
+        // 許可リストが空なら登録済みプロバイダーの全モデル
+        let open = test_policy(&[]);
+        assert_eq!(open.check("openai:o1").unwrap(), "openai:o1");
+        assert!(open.check("google:gemini").is_err());
+
+        let disabled = ModelSelectionPolicy {
+            enabled: false,
+            ..test_policy(&[])
+        };
+        assert!(disabled.check("fast").unwrap_err().contains("disabled"));
+    }
+
+    #[test]
+    fn test_agent_list_cannot_widen_server_list() {
+        let policy = ModelSelectionPolicy {
+            agent_allowed: vec!["openai:o1".to_string(), "openai:gpt-4o-mini".to_string()],
+            ..test_policy(&["fast"])
+        };
+        assert_eq!(policy.check("fast").unwrap(), "openai:gpt-4o-mini");
+        let err = policy.check("reasoning").unwrap_err();
+        assert!(err.contains("not allowed"), "{err}");
+        assert!(err.ends_with("Choose one of: openai:gpt-4o-mini"), "{err}");
+        // 既定モデルには常に戻せる
+        assert_eq!(policy.check("gpt-4o").unwrap(), "openai:gpt-4o");
+
+        // サーバー側が無制限ならエージェント別の許可リストだけで絞る
+        let narrowed = ModelSelectionPolicy {
+            agent_allowed: vec!["reasoning".to_string()],
+            ..test_policy(&[])
+        };
+        assert_eq!(narrowed.check("openai:o1").unwrap(), "openai:o1");
+        assert!(narrowed.check("fast").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_select_llm_rejects_disallowed_model() {
+        let (_dir, mut ctx) = test_context();
+        ctx.model_policy = Some(Arc::new(test_policy(&["fast"])));
+
+        let result = SelectLlmAction
+            .execute(&json!({"model_alias": "openai:o1", "reason": "Hard problem"}), &ctx)
+            .await;
+        assert!(!result.success);
+        assert!(result.side_effects.is_empty());
+        assert!(ctx.model_override.lock().unwrap().is_none());
+
+        let result = SelectLlmAction
+            .execute(&json!({"model_alias": "fast", "reason": "Simple"}), &ctx)
+            .await;
+        assert!(result.success);
+        assert_eq!(
+            ctx.model_override.lock().unwrap().as_deref(),
+            Some("openai:gpt-4o-mini")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_select_llm_duration() {
+        let (_dir, ctx) = test_context();
+
+        let result = SelectLlmAction
+            .execute(&json!({"model_alias": "fast", "reason": "r", "duration": "forever"}), &ctx)
+            .await;
+        assert!(!result.success);
+
+        let result = SelectLlmAction
+            .execute(&json!({"model_alias": "fast", "reason": "r"}), &ctx)
+            .await;
+        assert_eq!(result.data.as_ref().unwrap()["persisted"], false);
+        {
+            let conn = ctx.db.lock().unwrap();
+            let saved =
+                opencrab_db::queries::get_agent_model_override(&conn, "agent-1", "session-1").unwrap();
+            assert_eq!(saved, None);
+        }
+
+        let result = SelectLlmAction
+            .execute(
+                &json!({"model_alias": "smart", "reason": "r", "duration": "this_session"}),
+                &ctx,
+            )
+            .await;
+        assert_eq!(result.data.as_ref().unwrap()["persisted"], true);
+        let conn = ctx.db.lock().unwrap();
+        let saved =
+            opencrab_db::queries::get_agent_model_override(&conn, "agent-1", "session-1").unwrap();
+        assert_eq!(saved, Some(("smart".to_string(), "this_session".to_string())));
+    }
 }
@@ -13,7 +13,7 @@ impl Action for SearchMyHistoryAction {
     }
 
     fn description(&self) -> &str {
-        "自分の過去のやりとりを検索する"
+        "自分の過去のやりとりと記憶を検索する（キーワード＋意味の近さ）"
     }
 
     fn parameters(&self) -> serde_json::Value {
@@ -41,13 +41,15 @@ impl Action for SearchMyHistoryAction {
         };
         let limit = args["limit"].as_u64().unwrap_or(10) as usize;
 
-        let results = if let Ok(conn) = ctx.db.lock() {
-            match opencrab_db::queries::search_session_logs(&conn, &ctx.agent_id, query, limit) {
-                Ok(r) => r,
-                Err(e) => return ActionResult::error(&format!("Search failed: {e}")),
-            }
-        } else {
-            return ActionResult::error("Failed to acquire DB lock");
+        // キーワード（BM25）とベクトル類似度のハイブリッド検索。埋め込みモデルが無ければキーワードのみ
+        let memory = opencrab_core::MemoryManager::new(&ctx.agent_id, ctx.db.clone())
+            .with_policy(ctx.memory_policy.clone());
+        let results = match memory
+            .hybrid_search(query, limit, ctx.embedder.as_deref(), &ctx.memory_policy.search)
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => return ActionResult::error(&format!("Search failed: {e}")),
         };
 
         ActionResult::success(json!({
@@ -205,6 +207,7 @@ impl Action for CreateMySkillAction {
                     source_type: "self_created".to_string(),
                     source_context: None,
                     file_path: Some(file_path.clone()),
+                    version: None,
                     effectiveness: None,
                     usage_count: 0,
                     is_active: true,
@@ -253,6 +256,10 @@ mod tests {
                 gateway: "test".to_string(),
             })),
             gateway_admin: None,
+            embedder: None,
+            memory_policy: Default::default(),
+            model_policy: None,
+            session_log_sink: None,
         };
         (dir, ctx)
     }
@@ -44,6 +44,14 @@ pub enum SideEffect {
     LlmSwitched { purpose: String, model: String },
 }
 
+/// セッションログの記録先トレイト
+///
+/// 実装はserverクレート側で行い、保存と同時にWebSocketの購読者へ配信する。
+pub trait SessionLogSink: Send + Sync {
+    /// ログを保存し、そのIDを返す
+    fn record(&self, log: &opencrab_db::queries::SessionLogRow) -> anyhow::Result<i64>;
+}
+
 /// Discord/ゲートウェイ管理トレイト
 ///
 /// サーバー一覧・チャンネル一覧の取得など、ゲートウェイ管理操作を抽象化する。
@@ -91,6 +99,28 @@ pub struct ActionContext {
     /// Gateway admin operations (Discord guild/channel management).
     /// None when running via REST API or in tests.
     pub gateway_admin: Option<Arc<dyn GatewayAdmin>>,
+    /// 埋め込みモデル（search_my_history のベクトル検索用）。
+    /// None ならキーワード検索のみ。
+    pub embedder: Option<Arc<dyn opencrab_core::Embedder>>,
+    /// 記憶の順位付けと検索の重み（`[agent.memory]`）
+    pub memory_policy: opencrab_core::MemoryPolicy,
+    /// select_llm で選べるモデルの制約。None なら制限なし
+    pub model_policy: Option<Arc<crate::llm_selection::ModelSelectionPolicy>>,
+    /// セッションログの記録先。None ならDBに直接保存する（購読者への配信なし）
+    pub session_log_sink: Option<Arc<dyn SessionLogSink>>,
+}
+
+impl ActionContext {
+    /// セッションログを記録する（記録先があればそれを通し、なければDBに直接保存する）
+    pub fn record_session_log(&self, log: &opencrab_db::queries::SessionLogRow) -> anyhow::Result<i64> {
+        match &self.session_log_sink {
+            Some(sink) => sink.record(log),
+            None => {
+                let conn = self.db.lock().map_err(|_| anyhow::anyhow!("DB lock poisoned"))?;
+                opencrab_db::queries::insert_session_log(&conn, log)
+            }
+        }
+    }
 }
 
 /// エージェントの実行環境情報
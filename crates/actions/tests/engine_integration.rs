@@ -66,6 +66,10 @@ fn setup() -> (tempfile::TempDir, BridgedExecutor) {
             gateway: "test".to_string(),
         })),
         gateway_admin: None,
+        embedder: None,
+        memory_policy: Default::default(),
+        model_policy: None,
+        session_log_sink: None,
     };
 
     let executor = BridgedExecutor::new(ActionDispatcher::new(), ctx);
@@ -108,6 +112,10 @@ fn setup_with_data() -> (tempfile::TempDir, BridgedExecutor, Arc<Mutex<rusqlite:
             gateway: "test".to_string(),
         })),
         gateway_admin: None,
+        embedder: None,
+        memory_policy: Default::default(),
+        model_policy: None,
+        session_log_sink: None,
     };
 
     let executor = BridgedExecutor::new(ActionDispatcher::new(), ctx);
@@ -259,6 +267,31 @@ async fn test_engine_lists_all_tools() {
     assert!(tools.len() >= 18, "expected 18+ tools, got {}", tools.len());
 }
 
+/// An allowlist limits both the advertised tools and what may be executed.
+#[tokio::test]
+async fn test_allowlist_restricts_tools_and_execution() {
+    let (_dir, executor) = setup();
+    let executor = executor.with_allowed_actions(vec![
+        "send_speech".to_string(),
+        "ws_read".to_string(),
+        "not_registered".to_string(),
+    ]);
+
+    use opencrab_core::ActionExecutor;
+    let mut names: Vec<String> = executor.list_tools().into_iter().map(|t| t.name).collect();
+    names.sort();
+    assert_eq!(names, vec!["send_speech", "ws_read"]);
+
+    let result = executor
+        .execute("ws_delete", &serde_json::json!({"path": "x.txt"}))
+        .await;
+    assert!(!result.success);
+    assert!(result.error.unwrap().contains("not available"));
+
+    let empty = setup().1.with_allowed_actions(vec![]);
+    assert!(empty.list_tools().is_empty());
+}
+
 /// Unknown action returns error result, engine continues and produces final text.
 #[tokio::test]
 async fn test_engine_unknown_action_handled() {
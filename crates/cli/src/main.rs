@@ -58,7 +58,7 @@ async fn main() -> anyhow::Result<()> {
     let db = Arc::new(Mutex::new(conn));
 
     // Build LLM router
-    let _llm_router = opencrab_server::config::build_llm_router(&cfg.llm)?;
+    let llm_router = opencrab_server::config::build_llm_router(&cfg.llm)?;
 
     loop {
         print!("opencrab> ");
@@ -81,6 +81,12 @@ async fn main() -> anyhow::Result<()> {
                 println!("  agents show <id|name>    - Show agent details");
                 println!("  agents update <id|name>  - Update agent (interactive)");
                 println!("  agents delete <id|name>  - Delete an agent");
+                println!("  llm show <id|name>       - Show the agent's LLM config and resolved models");
+                println!("  llm set <id|name>        - Set the agent's LLM config (interactive)");
+                println!("  llm reset <id|name>      - Remove the agent's LLM config (use server default)");
+                println!("  pricing list             - List model prices (built-in and stored)");
+                println!("  pricing import <src>     - Import prices from an OpenRouter /models JSON file or provider");
+                println!("  skills reload [id|name]  - Reload standard skill files (all agents if omitted)");
                 println!("  sessions list            - List all sessions");
                 println!("  sessions create          - Create a new session (interactive)");
                 println!("  help                     - Show this help");
@@ -107,11 +113,9 @@ async fn main() -> anyhow::Result<()> {
                     })
                     .unwrap();
                 let mut count = 0;
-                for row in rows {
-                    if let Ok((id, name, role, persona)) = row {
-                        println!("  {} - {} [{}] ({})", &id[..8], name, role, persona);
-                        count += 1;
-                    }
+                for (id, name, role, persona) in rows.flatten() {
+                    println!("  {} - {} [{}] ({})", &id[..8], name, role, persona);
+                    count += 1;
                 }
                 if count == 0 {
                     println!("  (no agents found)");
@@ -272,6 +276,234 @@ async fn main() -> anyhow::Result<()> {
                 println!("Usage: agents delete <id|name>");
             }
 
+            // ── llm show <query> ──
+            ["llm", "show", query] => {
+                let conn = db.lock().unwrap();
+                if let Some(agent_id) = resolve_agent(&conn, query) {
+                    let config = opencrab_core::AgentLlmConfig::load(&conn, &agent_id)?;
+                    let server_default =
+                        format!("{}:{}", cfg.llm.default_provider, cfg.llm.default_model);
+                    match &config {
+                        Some(c) => println!("  Default:      {}", c.default_model_ref()),
+                        None => println!("  Default:      {} (server default)", server_default),
+                    }
+                    for purpose in opencrab_core::MODEL_PURPOSES {
+                        let model = match &config {
+                            Some(c) if *purpose == "embedding" => c
+                                .models
+                                .embedding
+                                .as_ref()
+                                .map(|m| m.to_string())
+                                .or_else(|| cfg.llm.embedding_model()),
+                            None if *purpose == "embedding" => cfg.llm.embedding_model(),
+                            Some(c) => Some(c.model_for(purpose).to_string()),
+                            None => Some(server_default.clone()),
+                        };
+                        println!(
+                            "  {:<13} {}",
+                            format!("{}:", purpose),
+                            model.as_deref().unwrap_or("(none)")
+                        );
+                    }
+                    if let Some(c) = &config {
+                        println!("  Self-select:  {}", c.allow_self_selection);
+                        if !c.selectable_models.is_empty() {
+                            let models: Vec<String> =
+                                c.selectable_models.iter().map(|m| m.to_string()).collect();
+                            println!("  Selectable:   {}", models.join(", "));
+                        }
+                    }
+                }
+            }
+
+            // ── llm set <query> ──
+            ["llm", "set", query] => {
+                let (agent_id, current) = {
+                    let conn = db.lock().unwrap();
+                    let Some(agent_id) = resolve_agent(&conn, query) else { continue };
+                    let current = opencrab_core::AgentLlmConfig::load(&conn, &agent_id)?;
+                    (agent_id, current)
+                };
+                let mut config = current.unwrap_or_else(|| opencrab_core::AgentLlmConfig {
+                    default_provider: cfg.llm.default_provider.clone(),
+                    default_model: cfg.llm.default_model.clone(),
+                    ..Default::default()
+                });
+
+                println!("Models are written as provider:model.");
+                println!("Press Enter to keep current value, '-' to clear a purpose model.\n");
+
+                let default = prompt_default("Default model", &config.default_model_ref().to_string());
+                let Some(default) = opencrab_core::ModelRef::parse(&default) else {
+                    println!("Invalid model '{}'. Cancelled.", default);
+                    continue;
+                };
+                config.default_provider = default.provider;
+                config.default_model = default.model;
+
+                let mut invalid = false;
+                for purpose in opencrab_core::MODEL_PURPOSES {
+                    let slot = match *purpose {
+                        "thinking" => &mut config.models.thinking,
+                        "conversation" => &mut config.models.conversation,
+                        "analysis" => &mut config.models.analysis,
+                        "tool_calling" => &mut config.models.tool_calling,
+                        _ => &mut config.models.embedding,
+                    };
+                    let current = slot.as_ref().map(|m| m.to_string()).unwrap_or_else(|| "-".to_string());
+                    let value = prompt_default(&format!("{} model", purpose), &current);
+                    if value == "-" {
+                        *slot = None;
+                    } else if let Some(model) = opencrab_core::ModelRef::parse(&value) {
+                        *slot = Some(model);
+                    } else {
+                        println!("Invalid model '{}'.", value);
+                        invalid = true;
+                        break;
+                    }
+                }
+                if invalid {
+                    println!("Cancelled.");
+                    continue;
+                }
+
+                let allow = prompt_default(
+                    "Allow self-selection (yes/no)",
+                    if config.allow_self_selection { "yes" } else { "no" },
+                );
+                config.allow_self_selection = allow == "yes" || allow == "y";
+                let current: Vec<String> =
+                    config.selectable_models.iter().map(|m| m.to_string()).collect();
+                let selectable = prompt_default(
+                    "Selectable models (comma-separated)",
+                    &if current.is_empty() { "-".to_string() } else { current.join(",") },
+                );
+                config.selectable_models = if selectable == "-" {
+                    Vec::new()
+                } else {
+                    selectable
+                        .split(',')
+                        .filter_map(opencrab_core::ModelRef::parse)
+                        .collect()
+                };
+
+                let conn = db.lock().unwrap();
+                config.save(&conn, &agent_id)?;
+                println!("Updated LLM config for {}", &agent_id[..8]);
+            }
+
+            // ── llm reset <query> ──
+            ["llm", "reset", query] => {
+                let conn = db.lock().unwrap();
+                if let Some(agent_id) = resolve_agent(&conn, query) {
+                    if opencrab_db::queries::delete_agent_llm_config(&conn, &agent_id)? {
+                        println!("Removed LLM config; {} now uses the server default.", &agent_id[..8]);
+                    } else {
+                        println!("No LLM config set for {}.", &agent_id[..8]);
+                    }
+                }
+            }
+            ["llm", ..] => {
+                println!("Usage: llm show|set|reset <id|name>");
+            }
+
+            // ── pricing list ──
+            ["pricing", "list"] => {
+                let conn = db.lock().unwrap();
+                let stored = opencrab_db::queries::list_model_pricing(&conn)?;
+                let registry = opencrab_server::pricing::load_registry(&conn)?;
+                for p in registry.list() {
+                    let from_db = stored.iter().any(|r| r.provider == p.provider && r.model == p.model);
+                    println!(
+                        "  {}:{} - in ${:.4} / out ${:.4} per 1M{}",
+                        p.provider,
+                        p.model,
+                        p.input_per_million,
+                        p.output_per_million,
+                        if from_db { " (db)" } else { "" }
+                    );
+                }
+            }
+
+            // ── pricing import <file|provider> ──
+            ["pricing", "import", source] => {
+                let prices = if let Some(provider) = llm_router.get_provider(source) {
+                    match provider.model_pricing().await {
+                        Ok(prices) => prices
+                            .into_iter()
+                            .map(|p| opencrab_llm::ModelPricing { provider: source.to_string(), ..p })
+                            .collect(),
+                        Err(e) => {
+                            println!("Error: {}", e);
+                            continue;
+                        }
+                    }
+                } else {
+                    let body = match std::fs::read_to_string(source) {
+                        Ok(body) => body,
+                        Err(e) => {
+                            println!("Cannot read '{}': {}", source, e);
+                            continue;
+                        }
+                    };
+                    match serde_json::from_str(&body) {
+                        Ok(json) => opencrab_llm::parse_openrouter_models(&json),
+                        Err(e) => {
+                            println!("Invalid JSON in '{}': {}", source, e);
+                            continue;
+                        }
+                    }
+                };
+                let conn = db.lock().unwrap();
+                let imported = opencrab_server::pricing::import(&conn, &prices)?;
+                println!("Imported {} prices.", imported);
+            }
+            ["pricing", ..] => {
+                println!("Usage: pricing list | pricing import <file|provider>");
+            }
+
+            // ── skills reload ──
+            ["skills", "reload"] => {
+                match opencrab_server::skill_sync::sync_all_agents(&db, &cfg.skills.dir) {
+                    Ok(reports) => {
+                        for (agent_id, report) in reports {
+                            println!(
+                                "  {} - added {}, updated {}, unchanged {}",
+                                &agent_id[..8.min(agent_id.len())],
+                                report.added.len(),
+                                report.updated.len(),
+                                report.unchanged.len()
+                            );
+                        }
+                    }
+                    Err(e) => println!("Error: {}", e),
+                }
+            }
+            ["skills", "reload", query] => {
+                let agent_id = {
+                    let conn = db.lock().unwrap();
+                    resolve_agent(&conn, query)
+                };
+                let Some(agent_id) = agent_id else { continue };
+                match opencrab_server::skill_sync::sync_agent_skills(&db, &cfg.skills.dir, &agent_id) {
+                    Ok(report) => {
+                        for name in &report.added {
+                            println!("  + {}", name);
+                        }
+                        for change in &report.updated {
+                            println!(
+                                "  ~ {} ({} -> {})",
+                                change.name,
+                                change.from.as_deref().unwrap_or("?"),
+                                change.to
+                            );
+                        }
+                        println!("  {} unchanged", report.unchanged.len());
+                    }
+                    Err(e) => println!("Error: {}", e),
+                }
+            }
+
             // ── sessions list ──
             ["sessions", "list"] => {
                 let conn = db.lock().unwrap();
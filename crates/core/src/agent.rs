@@ -1,6 +1,7 @@
 use anyhow::{Context, Result};
 use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::sync::{Arc, Mutex};
 
 use crate::heartbeat::HeartbeatConfig;
@@ -13,7 +14,7 @@ use crate::workspace::Workspace;
 use opencrab_db::queries;
 
 /// Reference to a specific LLM provider and model combination.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ModelRef {
     /// Provider name (e.g., "openai", "anthropic", "ollama").
     pub provider: String,
@@ -21,10 +22,45 @@ pub struct ModelRef {
     pub model: String,
 }
 
+impl ModelRef {
+    pub fn new(provider: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            provider: provider.into(),
+            model: model.into(),
+        }
+    }
+
+    /// Parse a `provider:model` string. Both parts must be non-empty.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (provider, model) = s.trim().split_once(':')?;
+        let (provider, model) = (provider.trim(), model.trim());
+        if provider.is_empty() || model.is_empty() {
+            return None;
+        }
+        Some(Self::new(provider, model))
+    }
+}
+
+/// Formats as `provider:model`, the form the LLM router resolves.
+impl fmt::Display for ModelRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.provider, self.model)
+    }
+}
+
+/// Task types that can have their own model.
+pub const MODEL_PURPOSES: &[&str] = &[
+    "thinking",
+    "conversation",
+    "analysis",
+    "tool_calling",
+    "embedding",
+];
+
 /// Model assignments for different task types.
 ///
 /// Each field is optional; when `None`, the default model is used.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AgentModels {
     /// Model for deep thinking and reasoning.
     pub thinking: Option<ModelRef>,
@@ -38,18 +74,35 @@ pub struct AgentModels {
     pub embedding: Option<ModelRef>,
 }
 
+impl AgentModels {
+    /// The model assigned to a purpose (see [`MODEL_PURPOSES`]).
+    pub fn for_purpose(&self, purpose: &str) -> Option<&ModelRef> {
+        match purpose {
+            "thinking" => self.thinking.as_ref(),
+            "conversation" => self.conversation.as_ref(),
+            "analysis" => self.analysis.as_ref(),
+            "tool_calling" => self.tool_calling.as_ref(),
+            "embedding" => self.embedding.as_ref(),
+            _ => None,
+        }
+    }
+}
+
 /// LLM configuration for an agent.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AgentLlmConfig {
     /// Default LLM provider to use.
     pub default_provider: String,
     /// Default model identifier.
     pub default_model: String,
     /// Task-specific model assignments.
+    #[serde(default)]
     pub models: AgentModels,
     /// Whether the agent can dynamically select models based on task complexity.
+    #[serde(default)]
     pub allow_self_selection: bool,
     /// Models the agent is allowed to select from (when self-selection is enabled).
+    #[serde(default)]
     pub selectable_models: Vec<ModelRef>,
 }
 
@@ -65,6 +118,52 @@ impl Default for AgentLlmConfig {
     }
 }
 
+impl AgentLlmConfig {
+    /// The default model as a reference.
+    pub fn default_model_ref(&self) -> ModelRef {
+        ModelRef::new(&self.default_provider, &self.default_model)
+    }
+
+    /// The model to use for a purpose, falling back to the default model.
+    pub fn model_for(&self, purpose: &str) -> ModelRef {
+        self.models
+            .for_purpose(purpose)
+            .cloned()
+            .unwrap_or_else(|| self.default_model_ref())
+    }
+
+    /// Load the stored configuration for an agent, if any.
+    pub fn load(conn: &Connection, agent_id: &str) -> Result<Option<Self>> {
+        let Some(row) = queries::get_agent_llm_config(conn, agent_id)? else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            default_provider: row.default_provider,
+            default_model: row.default_model,
+            models: serde_json::from_str(&row.models_json)
+                .context("Invalid models_json in agent_llm_config")?,
+            allow_self_selection: row.allow_self_selection,
+            selectable_models: serde_json::from_str(&row.selectable_models_json)
+                .context("Invalid selectable_models_json in agent_llm_config")?,
+        }))
+    }
+
+    /// Store this configuration for an agent, replacing any previous one.
+    pub fn save(&self, conn: &Connection, agent_id: &str) -> Result<()> {
+        queries::upsert_agent_llm_config(
+            conn,
+            &queries::AgentLlmConfigRow {
+                agent_id: agent_id.to_string(),
+                default_provider: self.default_provider.clone(),
+                default_model: self.default_model.clone(),
+                models_json: serde_json::to_string(&self.models)?,
+                allow_self_selection: self.allow_self_selection,
+                selectable_models_json: serde_json::to_string(&self.selectable_models)?,
+            },
+        )
+    }
+}
+
 /// The main Agent struct, combining all components.
 ///
 /// An Agent is the central entity in the OpenCrab framework. It has a soul
@@ -147,18 +246,7 @@ impl Agent {
             let identity_row = queries::get_identity(&db, agent_id)?
                 .with_context(|| format!("Identity not found for agent: {}", agent_id))?;
 
-            let soul = Soul {
-                persona_name: soul_row.persona_name,
-                social_style: serde_json::from_str(&soul_row.social_style_json)
-                    .unwrap_or_default(),
-                personality: serde_json::from_str(&soul_row.personality_json)
-                    .unwrap_or_default(),
-                thinking_style: serde_json::from_str(&soul_row.thinking_style_json)
-                    .unwrap_or_default(),
-                custom_traits: soul_row
-                    .custom_traits_json
-                    .and_then(|s| serde_json::from_str(&s).ok()),
-            };
+            let soul = Soul::from_row(&soul_row);
 
             let identity = Identity {
                 agent_id: identity_row.agent_id,
@@ -289,6 +377,35 @@ mod tests {
         assert_eq!(agent.soul.persona_name, "LoadedPersona");
     }
 
+    #[test]
+    fn test_llm_config_model_for_and_persistence() {
+        assert_eq!(
+            ModelRef::parse("ollama:llama3:8b"),
+            Some(ModelRef::new("ollama", "llama3:8b"))
+        );
+        assert_eq!(ModelRef::parse("gpt-4o"), None);
+        assert_eq!(ModelRef::parse(":gpt-4o"), None);
+        assert_eq!(ModelRef::new("openai", "gpt-4o").to_string(), "openai:gpt-4o");
+
+        let config = AgentLlmConfig {
+            default_provider: "ollama".to_string(),
+            default_model: "llama3".to_string(),
+            models: AgentModels {
+                analysis: Some(ModelRef::new("openai", "gpt-4o")),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(config.model_for("analysis").to_string(), "openai:gpt-4o");
+        assert_eq!(config.model_for("conversation").to_string(), "ollama:llama3");
+        assert_eq!(config.model_for("unknown").to_string(), "ollama:llama3");
+
+        let conn = opencrab_db::init_memory().unwrap();
+        assert_eq!(AgentLlmConfig::load(&conn, "agent-1").unwrap(), None);
+        config.save(&conn, "agent-1").unwrap();
+        assert_eq!(AgentLlmConfig::load(&conn, "agent-1").unwrap(), Some(config));
+    }
+
     #[test]
     fn test_agent_build_context() {
         let dir = tempfile::TempDir::new().unwrap();
@@ -0,0 +1,486 @@
+//! Memory context assembly for system prompts.
+//!
+//! The [`ContextAssembler`] packs what an agent has learned — impressions of
+//! the people it is talking to, curated memories and related past
+//! conversation — into a markdown block that fits a token budget. Every
+//! injected item is reported back so callers can log what the model saw.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::context::{estimate_tokens, truncate_to_tokens};
+use crate::embedding::Embedder;
+use crate::memory::{HybridSearchConfig, MemoryManager};
+
+/// Log types considered conversation when retrieving related history.
+const RELATED_LOG_TYPES: &[&str] = &["message", "speech"];
+/// Maximum number of search terms taken from the query text.
+const MAX_QUERY_TERMS: usize = 8;
+
+/// Limits for [`ContextAssembler`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContextBudget {
+    /// Total tokens available for the injected block (0 disables injection).
+    pub max_tokens: usize,
+    /// Curated memory categories in priority order. Categories not listed
+    /// here follow in alphabetical order.
+    pub categories: Vec<String>,
+    /// Maximum curated memories per category.
+    pub max_per_category: usize,
+    /// Maximum impressions of session participants.
+    pub max_impressions: usize,
+    /// Maximum related past log entries.
+    pub max_related_logs: usize,
+}
+
+impl Default for ContextBudget {
+    fn default() -> Self {
+        Self {
+            max_tokens: 1200,
+            categories: ["preferences", "facts", "decisions", "reflection"]
+                .iter()
+                .map(|c| c.to_string())
+                .collect(),
+            max_per_category: 5,
+            max_impressions: 5,
+            max_related_logs: 3,
+        }
+    }
+}
+
+impl ContextBudget {
+    /// Largest share of the budget a single item may take before it is truncated.
+    fn per_item_tokens(&self) -> usize {
+        (self.max_tokens / 4).max(16)
+    }
+}
+
+/// What to assemble context for.
+#[derive(Debug, Clone, Default)]
+pub struct ContextRequest {
+    /// Current session; its logs are excluded from related history and its
+    /// impressions are preferred over older ones.
+    pub session_id: Option<String>,
+    /// Other agents or users currently in the session.
+    pub participant_ids: Vec<String>,
+    /// Text to retrieve related history for (usually the latest message).
+    pub query: Option<String>,
+}
+
+/// One memory that was injected into the prompt.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InjectedItem {
+    /// `"impression"`, `"curated"` or `"related_log"`.
+    pub kind: String,
+    /// Impression ID, curated memory ID or session log ID.
+    pub id: String,
+    /// Target name, curated category or source session.
+    pub label: String,
+    pub tokens: usize,
+    /// Whether the content was shortened to fit the per-item limit.
+    pub truncated: bool,
+}
+
+/// Result of [`ContextAssembler::assemble`].
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AssembledContext {
+    /// Markdown block to append to the system prompt (empty if nothing fits).
+    pub text: String,
+    pub items: Vec<InjectedItem>,
+    pub used_tokens: usize,
+    pub budget_tokens: usize,
+    /// Candidates dropped because the budget was exhausted.
+    pub skipped: usize,
+}
+
+impl AssembledContext {
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+/// A candidate line before budgeting.
+struct Candidate {
+    kind: &'static str,
+    id: String,
+    label: String,
+    line: String,
+}
+
+/// Builds the memory block of an agent's system prompt within a token budget.
+///
+/// Sections are filled in priority order: impressions of the current
+/// participants, curated memories by category, then related past logs.
+/// Items that do not fit are skipped so smaller ones later may still be used.
+pub struct ContextAssembler<'a> {
+    memory: &'a MemoryManager,
+    budget: &'a ContextBudget,
+    embedder: Option<&'a dyn Embedder>,
+}
+
+impl<'a> ContextAssembler<'a> {
+    pub fn new(memory: &'a MemoryManager, budget: &'a ContextBudget) -> Self {
+        Self {
+            memory,
+            budget,
+            embedder: None,
+        }
+    }
+
+    /// Use vector similarity in addition to keywords for related history.
+    pub fn with_embedder(mut self, embedder: Option<&'a dyn Embedder>) -> Self {
+        self.embedder = embedder;
+        self
+    }
+
+    pub async fn assemble(&self, request: &ContextRequest) -> Result<AssembledContext> {
+        let mut out = AssembledContext {
+            budget_tokens: self.budget.max_tokens,
+            ..Default::default()
+        };
+        if self.budget.max_tokens == 0 {
+            return Ok(out);
+        }
+
+        let sections = [
+            ("## People in this conversation", self.impressions(request)?),
+            ("## What you remember", self.curated()?),
+            (
+                "## Related past conversations",
+                self.related_logs(request).await?,
+            ),
+        ];
+        for (heading, candidates) in sections {
+            self.pack(&mut out, heading, candidates);
+        }
+        Ok(out)
+    }
+
+    fn impressions(&self, request: &ContextRequest) -> Result<Vec<Candidate>> {
+        if self.budget.max_impressions == 0 {
+            return Ok(Vec::new());
+        }
+        let impressions = self
+            .memory
+            .get_impressions(&request.participant_ids, request.session_id.as_deref())?;
+        Ok(impressions
+            .into_iter()
+            .take(self.budget.max_impressions)
+            .map(|imp| {
+                let details: Vec<String> = [
+                    ("personality", &imp.personality),
+                    ("style", &imp.communication_style),
+                    ("recently", &imp.recent_behavior),
+                    ("agreement", &imp.agreement),
+                    ("notes", &imp.notes),
+                ]
+                .iter()
+                .filter(|(_, v)| !v.trim().is_empty())
+                .map(|(k, v)| format!("{}: {}", k, v.trim()))
+                .collect();
+                Candidate {
+                    kind: "impression",
+                    id: imp.id,
+                    line: format!("- {} — {}", imp.target_name, details.join("; ")),
+                    label: imp.target_name,
+                }
+            })
+            .collect())
+    }
+
+    fn curated(&self) -> Result<Vec<Candidate>> {
+        let memories = self.memory.get_curated(None)?;
+        let mut categories: Vec<String> = memories.iter().map(|m| m.category.clone()).collect();
+        categories.sort_by(|a, b| {
+            let rank = |c: &String| self.budget.categories.iter().position(|p| p == c);
+            match (rank(a), rank(b)) {
+                (Some(x), Some(y)) => x.cmp(&y),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => a.cmp(b),
+            }
+        });
+        categories.dedup();
+
+        let mut candidates = Vec::new();
+        for category in categories {
+            candidates.extend(
+                memories
+                    .iter()
+                    .filter(|m| m.category == category && !m.content.trim().is_empty())
+                    .take(self.budget.max_per_category)
+                    .map(|m| Candidate {
+                        kind: "curated",
+                        id: m.id.clone(),
+                        label: m.category.clone(),
+                        line: format!("- [{}] {}", m.category, m.content.trim()),
+                    }),
+            );
+        }
+        Ok(candidates)
+    }
+
+    async fn related_logs(&self, request: &ContextRequest) -> Result<Vec<Candidate>> {
+        if self.budget.max_related_logs == 0 {
+            return Ok(Vec::new());
+        }
+        let Some(query) = request
+            .query
+            .as_deref()
+            .map(search_terms)
+            .filter(|q| !q.is_empty())
+        else {
+            return Ok(Vec::new());
+        };
+        let config = HybridSearchConfig {
+            match_any: true,
+            ..self.memory.policy().search.clone()
+        };
+        // Over-fetch: hits from the current session and curated memories are dropped.
+        let hits = self
+            .memory
+            .hybrid_search(
+                &query,
+                self.budget.max_related_logs * 4,
+                self.embedder,
+                &config,
+            )
+            .await?;
+        Ok(hits
+            .into_iter()
+            .filter(|h| h.session_id.is_some() && h.session_id != request.session_id)
+            .filter(|h| {
+                h.log_type
+                    .as_deref()
+                    .is_some_and(|t| RELATED_LOG_TYPES.contains(&t))
+            })
+            .take(self.budget.max_related_logs)
+            .map(|h| {
+                let session = h.session_id.unwrap_or_default();
+                let date = h.created_at.get(..10).unwrap_or(&h.created_at);
+                Candidate {
+                    kind: "related_log",
+                    id: h.id,
+                    line: format!("- ({}) {}", date, h.content.trim()),
+                    label: session,
+                }
+            })
+            .collect())
+    }
+
+    /// Add a section's candidates while they fit, truncating oversized items.
+    fn pack(&self, out: &mut AssembledContext, heading: &str, candidates: Vec<Candidate>) {
+        let per_item = self.budget.per_item_tokens();
+        let heading_tokens = estimate_tokens(heading) + 1;
+        let mut section = String::new();
+
+        for candidate in candidates {
+            let mut line = candidate.line;
+            let mut truncated = false;
+            if estimate_tokens(&line) > per_item {
+                line = truncate_to_tokens(&line, per_item);
+                truncated = true;
+            }
+            let tokens = estimate_tokens(&line) + 1;
+            let overhead = if section.is_empty() {
+                heading_tokens
+            } else {
+                0
+            };
+            if out.used_tokens + overhead + tokens > self.budget.max_tokens {
+                out.skipped += 1;
+                continue;
+            }
+
+            if section.is_empty() {
+                section.push_str(heading);
+                section.push('\n');
+            }
+            section.push_str(&line);
+            section.push('\n');
+            out.used_tokens += overhead + tokens;
+            out.items.push(InjectedItem {
+                kind: candidate.kind.to_string(),
+                id: candidate.id,
+                label: candidate.label,
+                tokens,
+                truncated,
+            });
+        }
+
+        if !section.is_empty() {
+            if !out.text.is_empty() {
+                out.text.push('\n');
+            }
+            out.text.push_str(&section);
+        }
+    }
+}
+
+/// Distinctive words of a message, for an any-term keyword search.
+fn search_terms(text: &str) -> String {
+    let mut terms: Vec<String> = Vec::new();
+    for word in text.split(|c: char| !c.is_alphanumeric()) {
+        let long_enough = if word.is_ascii() {
+            word.len() >= 4
+        } else {
+            word.chars().count() >= 2
+        };
+        let word = word.to_lowercase();
+        if long_enough && !terms.contains(&word) {
+            terms.push(word);
+        }
+        if terms.len() >= MAX_QUERY_TERMS {
+            break;
+        }
+    }
+    terms.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opencrab_db::queries;
+    use std::sync::{Arc, Mutex};
+
+    fn setup() -> (MemoryManager, Arc<Mutex<rusqlite::Connection>>) {
+        let conn = Arc::new(Mutex::new(opencrab_db::init_memory().unwrap()));
+        (MemoryManager::new("agent-a", conn.clone()), conn)
+    }
+
+    fn impression(
+        conn: &Arc<Mutex<rusqlite::Connection>>,
+        session: &str,
+        target: &str,
+        notes: &str,
+    ) {
+        queries::upsert_impression(
+            &conn.lock().unwrap(),
+            &queries::ImpressionRow {
+                id: format!("imp-{}-{}", session, target),
+                agent_id: "agent-a".to_string(),
+                session_id: session.to_string(),
+                target_id: target.to_string(),
+                target_name: target.to_uppercase(),
+                personality: "direct".to_string(),
+                communication_style: String::new(),
+                recent_behavior: String::new(),
+                agreement: String::new(),
+                notes: notes.to_string(),
+                last_updated_turn: 1,
+            },
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_assembles_sections_in_priority_order() {
+        let (mm, conn) = setup();
+        impression(&conn, "s-old", "bob", "prefers data over anecdotes");
+        impression(&conn, "s-old", "eve", "not in this session");
+        mm.save_curated("m1", "misc", "Likes green tea").unwrap();
+        mm.save_curated("m2", "reflection", "Ask before assuming")
+            .unwrap();
+        mm.append_session_log(
+            "s-old",
+            "speech",
+            "The deployment pipeline kept failing on Fridays",
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        mm.append_session_log(
+            "s-now",
+            "speech",
+            "The deployment pipeline again?",
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        mm.append_session_log(
+            "s-old",
+            "system",
+            "deployment pipeline notice",
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let budget = ContextBudget::default();
+        let request = ContextRequest {
+            session_id: Some("s-now".to_string()),
+            participant_ids: vec!["bob".to_string()],
+            query: Some("What about the deployment pipeline?".to_string()),
+        };
+        let ctx = ContextAssembler::new(&mm, &budget)
+            .assemble(&request)
+            .await
+            .unwrap();
+
+        let people = ctx.text.find("## People").unwrap();
+        let remember = ctx.text.find("## What you remember").unwrap();
+        let related = ctx.text.find("## Related past").unwrap();
+        assert!(people < remember && remember < related);
+        assert!(ctx
+            .text
+            .contains("BOB — personality: direct; notes: prefers data"));
+        assert!(!ctx.text.contains("EVE"));
+        // Listed categories come before unlisted ones.
+        assert!(ctx.text.find("[reflection]").unwrap() < ctx.text.find("[misc]").unwrap());
+        // Only conversation from other sessions is retrieved.
+        assert!(ctx.text.contains("kept failing on Fridays"));
+        assert!(!ctx.text.contains("again?"));
+        assert!(!ctx.text.contains("notice"));
+
+        let kinds: Vec<&str> = ctx.items.iter().map(|i| i.kind.as_str()).collect();
+        assert_eq!(
+            kinds,
+            vec!["impression", "curated", "curated", "related_log"]
+        );
+        assert!(ctx.used_tokens > 0 && ctx.used_tokens <= budget.max_tokens);
+        assert_eq!(ctx.skipped, 0);
+    }
+
+    #[tokio::test]
+    async fn test_respects_token_budget() {
+        let (mm, _conn) = setup();
+        mm.save_curated("long", "facts", &"very long memory ".repeat(100))
+            .unwrap();
+        mm.save_curated("short", "facts", "short one").unwrap();
+
+        let budget = ContextBudget {
+            max_tokens: 40,
+            ..Default::default()
+        };
+        let ctx = ContextAssembler::new(&mm, &budget)
+            .assemble(&ContextRequest::default())
+            .await
+            .unwrap();
+        assert!(ctx.used_tokens <= 40);
+        assert!(ctx.items.iter().any(|i| i.truncated));
+        assert!(estimate_tokens(&ctx.text) <= 40);
+
+        let disabled = ContextBudget {
+            max_tokens: 0,
+            ..Default::default()
+        };
+        let ctx = ContextAssembler::new(&mm, &disabled)
+            .assemble(&ContextRequest::default())
+            .await
+            .unwrap();
+        assert!(ctx.is_empty() && ctx.text.is_empty());
+    }
+
+    #[test]
+    fn test_search_terms() {
+        assert_eq!(
+            search_terms("What about the Deployment pipeline? the pipeline!"),
+            "what about deployment pipeline"
+        );
+        assert_eq!(search_terms("デプロイ の 話"), "デプロイ");
+        assert!(search_terms("a an to").is_empty());
+    }
+}
@@ -0,0 +1,303 @@
+//! Consolidation of session logs into curated memories.
+//!
+//! A consolidation pass reads the conversation turns logged since the last
+//! checkpoint, asks the analysis model to extract facts, decisions,
+//! preferences and open questions, and plans how to merge them into the
+//! agent's curated memories. Extracted items that are near-duplicates of an
+//! existing memory (or of each other) only add provenance instead of creating
+//! a new entry.
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::context::ConversationTurn;
+use crate::memory::CuratedMemory;
+
+/// Curated memory categories filled by consolidation, keyed like the JSON
+/// the model is asked to return.
+pub const CONSOLIDATION_CATEGORIES: &[&str] = &["facts", "decisions", "preferences", "questions"];
+
+/// System prompt for the extraction request.
+pub const CONSOLIDATION_PROMPT: &str = "\
+You distil conversation logs into long-term memories for the agent whose logs these are.
+Each message is prefixed with its log ID as [#id].
+Extract only what is worth remembering in later conversations:
+- facts: stable facts about people, projects and the world
+- decisions: what was decided or agreed, and by whom
+- preferences: likes, dislikes and working preferences of participants
+- questions: open questions that were raised but not answered
+Write each item as one self-contained sentence in the language of the conversation.
+Skip greetings, small talk and anything already obvious from the item itself.
+Reply with a single JSON object only, for example:
+{\"facts\": [{\"content\": \"...\", \"sources\": [12, 15]}], \"decisions\": [], \"preferences\": [], \"questions\": []}";
+
+/// Settings for a consolidation pass.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConsolidationOptions {
+    /// Maximum number of session log entries read per pass.
+    pub max_logs: usize,
+    /// Similarity (0.0-1.0) at or above which two memories count as duplicates.
+    pub similarity_threshold: f32,
+}
+
+impl Default for ConsolidationOptions {
+    fn default() -> Self {
+        Self {
+            max_logs: 200,
+            similarity_threshold: 0.8,
+        }
+    }
+}
+
+/// Build the user message for an extraction request.
+pub fn consolidation_input(turns: &[ConversationTurn]) -> String {
+    turns
+        .iter()
+        .map(|t| format!("[#{}] {}", t.log_id, t.render()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A memory extracted by the model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExtractedMemory {
+    pub category: String,
+    pub content: String,
+    /// Session log IDs the memory was drawn from.
+    pub sources: Vec<i64>,
+}
+
+/// Parse the model's reply into extracted memories.
+///
+/// Items may be objects (`{"content", "sources"}`) or plain strings. Sources
+/// outside `turns` are dropped; items without valid sources are attributed to
+/// every turn in the batch. Returns `None` when the reply contains no JSON
+/// object, so the caller can tell a failed extraction from an empty one.
+pub fn parse_extraction(raw: &str, turns: &[ConversationTurn]) -> Option<Vec<ExtractedMemory>> {
+    let json = raw
+        .find('{')
+        .zip(raw.rfind('}'))
+        .filter(|(start, end)| start < end)
+        .and_then(|(start, end)| serde_json::from_str::<serde_json::Value>(&raw[start..=end]).ok())
+        .filter(|json| json.is_object())?;
+
+    let batch: Vec<i64> = turns.iter().map(|t| t.log_id).collect();
+    let mut out = Vec::new();
+    for category in CONSOLIDATION_CATEGORIES {
+        let Some(items) = json[category].as_array() else {
+            continue;
+        };
+        for item in items {
+            let (content, sources) = match item {
+                serde_json::Value::String(s) => (s.as_str(), Vec::new()),
+                _ => (
+                    item["content"].as_str().unwrap_or_default(),
+                    item["sources"]
+                        .as_array()
+                        .map(|ids| ids.iter().filter_map(|id| id.as_i64()).collect())
+                        .unwrap_or_default(),
+                ),
+            };
+            let content = content.trim();
+            if content.is_empty() {
+                continue;
+            }
+            let mut sources: Vec<i64> = sources
+                .into_iter()
+                .filter(|id| batch.contains(id))
+                .collect();
+            if sources.is_empty() {
+                sources = batch.clone();
+            }
+            sources.sort_unstable();
+            sources.dedup();
+            out.push(ExtractedMemory {
+                category: category.to_string(),
+                content: content.to_string(),
+                sources,
+            });
+        }
+    }
+    Some(out)
+}
+
+/// Character bigrams of the lowercased text without whitespace and punctuation.
+fn bigrams(text: &str) -> HashSet<(char, char)> {
+    let chars: Vec<char> = text
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if chars.len() == 1 {
+        return HashSet::from([(chars[0], chars[0])]);
+    }
+    chars.windows(2).map(|w| (w[0], w[1])).collect()
+}
+
+/// Jaccard similarity of character bigrams (works for Japanese and English alike).
+pub fn text_similarity(a: &str, b: &str) -> f32 {
+    let (a, b) = (bigrams(a), bigrams(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let shared = a.intersection(&b).count();
+    shared as f32 / (a.len() + b.len() - shared) as f32
+}
+
+/// What to do with one extracted memory.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ConsolidationAction {
+    /// Store a new curated memory.
+    Insert {
+        category: String,
+        content: String,
+        sources: Vec<i64>,
+    },
+    /// A near-duplicate exists: only record the additional sources.
+    Merge {
+        id: String,
+        content: String,
+        sources: Vec<i64>,
+    },
+}
+
+/// Plan how to merge extracted memories into the existing ones.
+///
+/// Duplicates are looked up within the same category, among existing memories
+/// and among the items inserted earlier in the same plan.
+pub fn plan_consolidation(
+    existing: &[CuratedMemory],
+    extracted: Vec<ExtractedMemory>,
+    similarity_threshold: f32,
+) -> Vec<ConsolidationAction> {
+    let mut actions: Vec<ConsolidationAction> = Vec::new();
+    for item in extracted {
+        let duplicate = existing
+            .iter()
+            .filter(|m| m.category == item.category)
+            .find(|m| text_similarity(&m.content, &item.content) >= similarity_threshold);
+        if let Some(memory) = duplicate {
+            actions.push(ConsolidationAction::Merge {
+                id: memory.id.clone(),
+                content: memory.content.clone(),
+                sources: item.sources,
+            });
+            continue;
+        }
+
+        let planned = actions.iter_mut().find_map(|action| match action {
+            ConsolidationAction::Insert {
+                category,
+                content,
+                sources,
+            } if *category == item.category
+                && text_similarity(content, &item.content) >= similarity_threshold =>
+            {
+                Some(sources)
+            }
+            _ => None,
+        });
+        match planned {
+            Some(sources) => {
+                sources.extend(item.sources);
+                sources.sort_unstable();
+                sources.dedup();
+            }
+            None => actions.push(ConsolidationAction::Insert {
+                category: item.category,
+                content: item.content,
+                sources: item.sources,
+            }),
+        }
+    }
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turns() -> Vec<ConversationTurn> {
+        vec![
+            ConversationTurn::message(10, "user-1", "Mika", "I only drink green tea."),
+            ConversationTurn::message(
+                11,
+                "agent-1",
+                "Alice",
+                "Noted. Let's ship the beta on Friday.",
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_parse_extraction_filters_sources() {
+        let raw = r#"Here you go:
+{"facts": ["Mika works remotely"],
+ "preferences": [{"content": "Mika only drinks green tea", "sources": [10, 99]}],
+ "decisions": [{"content": "  ", "sources": [11]}],
+ "questions": [], "other": [{"content": "ignored"}]}"#;
+        let extracted = parse_extraction(raw, &turns()).unwrap();
+        assert_eq!(extracted.len(), 2);
+        assert_eq!(extracted[0].category, "facts");
+        assert_eq!(extracted[0].sources, vec![10, 11]);
+        assert_eq!(extracted[1].category, "preferences");
+        assert_eq!(extracted[1].sources, vec![10]);
+
+        assert!(parse_extraction("no json here", &turns()).is_none());
+        assert!(parse_extraction(r#"{"facts": []}"#, &turns())
+            .unwrap()
+            .is_empty());
+        assert!(consolidation_input(&turns()).starts_with("[#10] [Mika]: I only drink green tea."));
+    }
+
+    #[test]
+    fn test_plan_deduplicates_against_existing_and_batch() {
+        let existing = vec![CuratedMemory {
+            id: "mem-1".to_string(),
+            category: "preferences".to_string(),
+            content: "Mika only drinks green tea.".to_string(),
+            importance: 0.7,
+            access_count: 0,
+            score: 0.7,
+        }];
+        let extracted = vec![
+            ExtractedMemory {
+                category: "preferences".to_string(),
+                content: "Mika only drinks green tea".to_string(),
+                sources: vec![10],
+            },
+            ExtractedMemory {
+                category: "decisions".to_string(),
+                content: "ベータ版は金曜日にリリースする".to_string(),
+                sources: vec![11],
+            },
+            ExtractedMemory {
+                category: "decisions".to_string(),
+                content: "ベータ版は金曜日にリリースする。".to_string(),
+                sources: vec![12],
+            },
+            // Same wording in another category is not a duplicate.
+            ExtractedMemory {
+                category: "facts".to_string(),
+                content: "Mika only drinks green tea".to_string(),
+                sources: vec![10],
+            },
+        ];
+        let actions = plan_consolidation(&existing, extracted, 0.8);
+        assert_eq!(actions.len(), 3);
+        assert!(
+            matches!(&actions[0], ConsolidationAction::Merge { id, sources, .. } if id == "mem-1" && sources == &vec![10])
+        );
+        assert!(
+            matches!(&actions[1], ConsolidationAction::Insert { category, sources, .. } if category == "decisions" && sources == &vec![11, 12])
+        );
+        assert!(
+            matches!(&actions[2], ConsolidationAction::Insert { category, .. } if category == "facts")
+        );
+
+        assert!(text_similarity("green tea", "black coffee") < 0.3);
+        assert_eq!(text_similarity("", "x"), 0.0);
+    }
+}
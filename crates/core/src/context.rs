@@ -0,0 +1,581 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{ChatMessage, ToolCall};
+
+/// Context window assumed when the provider does not report one.
+pub const DEFAULT_CONTEXT_WINDOW: usize = 8_192;
+
+/// Tokens added per message for role and speaker framing.
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+/// The history budget never drops below this, even with a huge system prompt.
+const MIN_HISTORY_BUDGET: usize = 256;
+
+/// Appended as the last "user" message when the history ends with the
+/// agent's own turn, so that the model is asked for a new reply.
+pub const CONTINUE_PROMPT: &str = "(Continue the conversation with your next message.)";
+
+/// System prompt used to fold older turns into the rolling summary.
+pub const SUMMARY_PROMPT: &str = "\
+You maintain a rolling summary of a multi-party conversation.
+Merge the previous summary (if any) and the new messages into one concise summary.
+Keep decisions, agreements, open questions, commitments and who proposed what.
+Drop greetings and small talk. Write in the language of the conversation.
+Reply with the summary only.";
+
+/// Rough token count of a text.
+///
+/// ASCII text is counted at about four characters per token; other scripts
+/// (Japanese, Chinese, ...) at one token per character. This overestimates
+/// for most tokenizers, which is the safe direction for budgeting.
+pub fn estimate_tokens(text: &str) -> usize {
+    let (ascii, other) = text.chars().fold((0usize, 0usize), |(ascii, other), c| {
+        if c.is_ascii() {
+            (ascii + 1, other)
+        } else {
+            (ascii, other + 1)
+        }
+    });
+    ascii.div_ceil(4) + other
+}
+
+/// Cut a text to roughly `max_tokens`, marking the cut with an ellipsis.
+pub fn truncate_to_tokens(text: &str, max_tokens: usize) -> String {
+    if estimate_tokens(text) <= max_tokens {
+        return text.to_string();
+    }
+    // Count in quarter tokens so that ASCII characters cost 1 and others 4.
+    let limit = max_tokens.saturating_sub(1) * 4;
+    let mut used = 0;
+    let mut out = String::new();
+    for c in text.chars() {
+        used += if c.is_ascii() { 1 } else { 4 };
+        if used > limit {
+            break;
+        }
+        out.push(c);
+    }
+    out.push('…');
+    out
+}
+
+/// What a logged turn contains.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TurnKind {
+    /// Something said in the conversation.
+    #[default]
+    Message,
+    /// The speaker requested tool calls (`content` is a readable description).
+    ToolCall { calls: Vec<ToolCall> },
+    /// The result of one of the speaker's tool calls.
+    ToolResult { tool_call_id: String },
+}
+
+/// One logged turn in a conversation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConversationTurn {
+    /// Session log ID (monotonic within a session).
+    pub log_id: i64,
+    /// Speaker ID (agent ID, user ID, "mentor", ...).
+    pub speaker: String,
+    /// Display name of the speaker.
+    pub speaker_name: String,
+    pub content: String,
+    #[serde(default)]
+    pub kind: TurnKind,
+}
+
+impl ConversationTurn {
+    /// A plain message turn.
+    pub fn message(
+        log_id: i64,
+        speaker: impl Into<String>,
+        speaker_name: impl Into<String>,
+        content: impl Into<String>,
+    ) -> Self {
+        Self {
+            log_id,
+            speaker: speaker.into(),
+            speaker_name: speaker_name.into(),
+            content: content.into(),
+            kind: TurnKind::Message,
+        }
+    }
+
+    /// Render as `[speaker name]: content`.
+    pub fn render(&self) -> String {
+        match self.kind {
+            TurnKind::Message => format!("[{}]: {}", self.speaker_name, self.content),
+            TurnKind::ToolCall { .. } => {
+                format!("[{}] (tool call): {}", self.speaker_name, self.content)
+            }
+            TurnKind::ToolResult { .. } => {
+                format!("[{}] (tool result): {}", self.speaker_name, self.content)
+            }
+        }
+    }
+
+    pub fn tokens(&self) -> usize {
+        estimate_tokens(&self.speaker_name)
+            + estimate_tokens(&self.content)
+            + MESSAGE_OVERHEAD_TOKENS
+    }
+
+    fn is_message(&self) -> bool {
+        self.kind == TurnKind::Message
+    }
+}
+
+/// A rolling summary of the turns up to and including `covers_until`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConversationSummary {
+    pub content: String,
+    /// Log ID of the last turn folded into this summary.
+    pub covers_until: i64,
+}
+
+impl ConversationSummary {
+    pub fn tokens(&self) -> usize {
+        estimate_tokens(&self.content) + MESSAGE_OVERHEAD_TOKENS
+    }
+}
+
+/// How a model's context window is split between the prompt and history.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextWindowConfig {
+    /// Total context window of the model, in tokens.
+    pub context_window: usize,
+    /// Tokens kept free for the reply and tool definitions.
+    pub reserved_tokens: usize,
+    /// Share of the history budget kept verbatim when older turns are folded.
+    pub recent_ratio: f64,
+    /// Turns kept verbatim when they fit, even beyond `recent_ratio`.
+    pub min_recent_turns: usize,
+}
+
+impl ContextWindowConfig {
+    /// Defaults for a model with the given context window.
+    pub fn for_window(context_window: usize) -> Self {
+        Self {
+            context_window,
+            reserved_tokens: (context_window / 4).clamp(256, 8_192),
+            recent_ratio: 0.5,
+            min_recent_turns: 4,
+        }
+    }
+
+    /// Tokens available for conversation history next to a system prompt.
+    pub fn history_budget(&self, system_prompt_tokens: usize) -> usize {
+        self.context_window
+            .saturating_sub(self.reserved_tokens + system_prompt_tokens)
+            .max(MIN_HISTORY_BUDGET)
+    }
+
+    /// Upper bound for the length of a rolling summary.
+    pub fn summary_max_tokens(&self, budget: usize) -> usize {
+        (budget / 4).clamp(128, 2_048)
+    }
+
+    /// Number of oldest turns to fold into the summary (0 if everything fits).
+    ///
+    /// When the history is over budget, the newest turns are kept verbatim up
+    /// to `recent_ratio` of the budget (at least `min_recent_turns` if they
+    /// fit, and always the newest turn); the rest is folded.
+    pub fn turns_to_fold(
+        &self,
+        summary: Option<&ConversationSummary>,
+        turns: &[ConversationTurn],
+        budget: usize,
+    ) -> usize {
+        let summary_tokens = summary.map(|s| s.tokens()).unwrap_or(0);
+        let total: usize = summary_tokens + turns.iter().map(|t| t.tokens()).sum::<usize>();
+        if total <= budget {
+            return 0;
+        }
+
+        let recent_budget = (budget as f64 * self.recent_ratio) as usize;
+        let mut kept = 0;
+        let mut used = 0;
+        for turn in turns.iter().rev() {
+            let tokens = turn.tokens();
+            let fits_recent = used + tokens <= recent_budget;
+            let fits_minimum = kept < self.min_recent_turns && used + tokens <= budget;
+            if kept > 0 && !fits_recent && !fits_minimum {
+                break;
+            }
+            used += tokens;
+            kept += 1;
+        }
+        turns.len() - kept
+    }
+}
+
+/// Split turns into chunks that each fit `budget` tokens, for summarising
+/// long backlogs step by step. A single oversized turn forms its own chunk.
+pub fn summary_chunks(turns: &[ConversationTurn], budget: usize) -> Vec<&[ConversationTurn]> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut used = 0;
+    for (i, turn) in turns.iter().enumerate() {
+        let tokens = turn.tokens();
+        if i > start && used + tokens > budget {
+            chunks.push(&turns[start..i]);
+            start = i;
+            used = 0;
+        }
+        used += tokens;
+    }
+    if start < turns.len() {
+        chunks.push(&turns[start..]);
+    }
+    chunks
+}
+
+/// Build the user message for a summarisation request.
+///
+/// Tool results are left out; the speaker's following message carries
+/// whatever they concluded from them.
+pub fn summary_input(previous: Option<&str>, turns: &[ConversationTurn]) -> String {
+    let previous = previous.unwrap_or("(none)");
+    let messages: Vec<String> = turns
+        .iter()
+        .filter(|t| !matches!(t.kind, TurnKind::ToolResult { .. }))
+        .map(|t| t.render())
+        .collect();
+    format!(
+        "Previous summary:\n{previous}\n\nNew messages:\n{}",
+        messages.join("\n")
+    )
+}
+
+/// The history actually sent to the model: an optional summary followed by
+/// the most recent turns.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConversationWindow {
+    pub summary: Option<String>,
+    pub turns: Vec<ConversationTurn>,
+    /// Older turns dropped without being summarised (e.g. summarisation failed).
+    pub omitted: usize,
+}
+
+impl ConversationWindow {
+    /// Fit a summary and turns into `budget` tokens.
+    ///
+    /// Oldest turns are dropped first; if the newest turn alone is still too
+    /// large, its content is truncated.
+    pub fn fit(
+        summary: Option<ConversationSummary>,
+        mut turns: Vec<ConversationTurn>,
+        budget: usize,
+    ) -> Self {
+        let summary_tokens = summary.as_ref().map(|s| s.tokens()).unwrap_or(0);
+        let mut used: usize = summary_tokens + turns.iter().map(|t| t.tokens()).sum::<usize>();
+        let mut omitted = 0;
+        while used > budget && turns.len() > 1 {
+            used -= turns.remove(0).tokens();
+            omitted += 1;
+        }
+        if used > budget {
+            if let Some(last) = turns.last_mut() {
+                let room = budget
+                    .saturating_sub(
+                        summary_tokens
+                            + estimate_tokens(&last.speaker_name)
+                            + MESSAGE_OVERHEAD_TOKENS,
+                    )
+                    .max(MIN_HISTORY_BUDGET / 4);
+                last.content = truncate_to_tokens(&last.content, room);
+            }
+        }
+        Self {
+            summary: summary.map(|s| s.content),
+            turns,
+            omitted,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.summary.is_none() && self.turns.is_empty()
+    }
+
+    /// Render as plain text, one `[speaker]: content` line per message.
+    /// Tool calls and results are not included.
+    pub fn render(&self) -> String {
+        if self.summary.is_none() && !self.turns.iter().any(|t| t.is_message()) {
+            return "No messages yet.".to_string();
+        }
+        let mut parts = Vec::new();
+        if let Some(summary) = &self.summary {
+            parts.push(format!("[Summary of earlier conversation]\n{summary}\n"));
+        }
+        if self.omitted > 0 {
+            parts.push(format!("[{} earlier messages omitted]", self.omitted));
+        }
+        parts.extend(
+            self.turns
+                .iter()
+                .filter(|t| t.is_message())
+                .map(|t| t.render()),
+        );
+        parts.join("\n")
+    }
+
+    /// Convert to chat messages as seen by `agent_id`.
+    ///
+    /// The agent's own messages become "assistant" messages, together with
+    /// its tool calls and their results; other participants' messages become
+    /// "user" messages whose `name` is the speaker (providers without a
+    /// `name` field put it into the text themselves). Other agents' tool
+    /// activity is private to them and left out, as are tool calls whose
+    /// call or results fell outside the window. The result always ends with
+    /// a "user" message.
+    pub fn to_messages(&self, agent_id: &str) -> Vec<ChatMessage> {
+        let mut messages = Vec::new();
+        if self.summary.is_some() || self.omitted > 0 {
+            let mut parts = Vec::new();
+            if let Some(summary) = &self.summary {
+                parts.push(format!("[Summary of earlier conversation]\n{summary}"));
+            }
+            if self.omitted > 0 {
+                parts.push(format!("[{} earlier messages omitted]", self.omitted));
+            }
+            messages.push(ChatMessage::new("user", parts.join("\n\n")));
+        }
+
+        let own = |turn: &&ConversationTurn| turn.speaker == agent_id;
+        let answered: HashSet<&str> = self
+            .turns
+            .iter()
+            .filter(own)
+            .filter_map(|t| match &t.kind {
+                TurnKind::ToolResult { tool_call_id } => Some(tool_call_id.as_str()),
+                _ => None,
+            })
+            .collect();
+        let mut open_calls: HashSet<&str> = HashSet::new();
+
+        for turn in &self.turns {
+            let is_own = own(&turn);
+            match &turn.kind {
+                TurnKind::Message if is_own => {
+                    messages.push(ChatMessage::new("assistant", turn.content.clone()));
+                }
+                TurnKind::Message => messages.push(ChatMessage {
+                    name: Some(message_name(&turn.speaker_name)),
+                    ..ChatMessage::new("user", turn.content.clone())
+                }),
+                // Only replay calls whose results are all present, and only
+                // results whose call was replayed: providers reject orphans.
+                TurnKind::ToolCall { calls }
+                    if is_own && calls.iter().all(|c| answered.contains(c.id.as_str())) =>
+                {
+                    open_calls.extend(calls.iter().map(|c| c.id.as_str()));
+                    messages.push(ChatMessage {
+                        tool_calls: calls.clone(),
+                        ..ChatMessage::new("assistant", "")
+                    });
+                }
+                TurnKind::ToolResult { tool_call_id }
+                    if is_own && open_calls.contains(tool_call_id.as_str()) =>
+                {
+                    messages.push(ChatMessage {
+                        tool_call_id: Some(tool_call_id.clone()),
+                        ..ChatMessage::new("tool", turn.content.clone())
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        if messages.last().is_none_or(|m| m.role != "user") {
+            messages.push(ChatMessage::new("user", CONTINUE_PROMPT));
+        }
+        messages
+    }
+}
+
+/// Participant name usable in the `name` field of chat APIs
+/// (no whitespace or `<|\\/>`, at most 64 characters).
+fn message_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| {
+            if c.is_whitespace() || "<|\\/>".contains(c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .take(64)
+        .collect();
+    if cleaned.is_empty() {
+        "user".to_string()
+    } else {
+        cleaned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn(log_id: i64, content: &str) -> ConversationTurn {
+        ConversationTurn::message(log_id, "a-1", "alice", content)
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+        assert_eq!(estimate_tokens("こんにちは"), 5);
+        let cut = truncate_to_tokens(&"word ".repeat(100), 10);
+        assert!(estimate_tokens(&cut) <= 10, "{cut}");
+        assert!(cut.ends_with('…'));
+        assert_eq!(truncate_to_tokens("short", 10), "short");
+    }
+
+    #[test]
+    fn test_turns_to_fold() {
+        let config = ContextWindowConfig {
+            context_window: 1_000,
+            reserved_tokens: 0,
+            recent_ratio: 0.5,
+            min_recent_turns: 2,
+        };
+        // Each turn is 25 (content) + 2 (speaker) + 4 (overhead) = 31 tokens.
+        let turns: Vec<_> = (1..=10).map(|i| turn(i, &"x".repeat(100))).collect();
+
+        assert_eq!(config.turns_to_fold(None, &turns, 1_000), 0);
+        // 124 tokens of recent budget keep 4 turns, the other 6 are folded.
+        assert_eq!(config.turns_to_fold(None, &turns, 248), 6);
+        // min_recent_turns wins over recent_ratio while the turns fit.
+        assert_eq!(config.turns_to_fold(None, &turns, 80), 8);
+        // The newest turn is always kept.
+        assert_eq!(config.turns_to_fold(None, &turns, 10), 9);
+
+        let summary = ConversationSummary {
+            content: "y".repeat(400),
+            covers_until: 0,
+        };
+        // The summary counts against the total but not against the recent share.
+        assert_eq!(config.turns_to_fold(Some(&summary), &turns, 320), 5);
+    }
+
+    #[test]
+    fn test_summary_chunks() {
+        let turns: Vec<_> = (1..=5).map(|i| turn(i, &"x".repeat(100))).collect();
+        let chunks = summary_chunks(&turns, 70);
+        assert_eq!(
+            chunks.iter().map(|c| c.len()).collect::<Vec<_>>(),
+            vec![2, 2, 1]
+        );
+        assert_eq!(summary_chunks(&turns, 10).len(), 5);
+        assert!(summary_chunks(&[], 10).is_empty());
+
+        let input = summary_input(Some("we chose Rust"), &turns[..1]);
+        assert!(input.contains("we chose Rust"));
+        assert!(input.contains("[alice]: xxx"));
+    }
+
+    #[test]
+    fn test_window_fit_and_render() {
+        let turns: Vec<_> = (1..=4).map(|i| turn(i, &format!("message {i}"))).collect();
+        let summary = ConversationSummary {
+            content: "Decided to use SQLite.".to_string(),
+            covers_until: 0,
+        };
+
+        let window = ConversationWindow::fit(Some(summary.clone()), turns.clone(), 1_000);
+        assert_eq!(window.turns.len(), 4);
+        let rendered = window.render();
+        assert!(rendered.starts_with("[Summary of earlier conversation]\nDecided to use SQLite."));
+        assert!(rendered.ends_with("[alice]: message 4"));
+
+        let window = ConversationWindow::fit(Some(summary), turns, 30);
+        assert_eq!(window.omitted, 2);
+        assert_eq!(window.turns[0].log_id, 3);
+        assert!(window.render().contains("[2 earlier messages omitted]"));
+
+        let huge = vec![turn(1, &"z".repeat(10_000))];
+        let window = ConversationWindow::fit(None, huge, 300);
+        assert!(window.turns[0].tokens() <= 300);
+
+        assert_eq!(ConversationWindow::default().render(), "No messages yet.");
+    }
+
+    #[test]
+    fn test_window_to_messages() {
+        let call = ToolCall {
+            id: "tc-1".to_string(),
+            name: "search_my_history".to_string(),
+            arguments: serde_json::json!({"query": "rust"}),
+        };
+        let tool_turn = |log_id, speaker: &str, kind| ConversationTurn {
+            kind,
+            ..ConversationTurn::message(log_id, speaker, speaker, "...")
+        };
+        let window = ConversationWindow {
+            summary: Some("Earlier: chose Rust.".to_string()),
+            turns: vec![
+                // Result whose call fell outside the window.
+                tool_turn(
+                    1,
+                    "a-1",
+                    TurnKind::ToolResult {
+                        tool_call_id: "tc-0".to_string(),
+                    },
+                ),
+                ConversationTurn::message(2, "u-1", "Bob Smith", "Why Rust?"),
+                tool_turn(
+                    3,
+                    "a-1",
+                    TurnKind::ToolCall {
+                        calls: vec![call.clone()],
+                    },
+                ),
+                tool_turn(
+                    4,
+                    "a-1",
+                    TurnKind::ToolResult {
+                        tool_call_id: "tc-1".to_string(),
+                    },
+                ),
+                ConversationTurn::message(5, "a-1", "alice", "Because of safety."),
+                // Another agent's tool activity is not shown.
+                tool_turn(
+                    6,
+                    "a-2",
+                    TurnKind::ToolCall {
+                        calls: vec![call.clone()],
+                    },
+                ),
+            ],
+            omitted: 0,
+        };
+
+        let messages = window.to_messages("a-1");
+        let roles: Vec<&str> = messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(
+            roles,
+            vec!["user", "user", "assistant", "tool", "assistant", "user"]
+        );
+        assert!(messages[0].content.contains("Earlier: chose Rust."));
+        assert_eq!(messages[1].name.as_deref(), Some("Bob_Smith"));
+        assert_eq!(messages[1].content, "Why Rust?");
+        assert_eq!(messages[2].tool_calls, vec![call]);
+        assert_eq!(messages[3].tool_call_id.as_deref(), Some("tc-1"));
+        assert_eq!(messages[4].content, "Because of safety.");
+        assert_eq!(messages[5].content, CONTINUE_PROMPT);
+
+        // Seen by another participant, alice's turn is a named user message.
+        let messages = window.to_messages("u-1");
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1].role, "assistant");
+        assert_eq!(messages[2].content, "Because of safety.");
+        assert_eq!(messages[2].name.as_deref(), Some("alice"));
+    }
+}
@@ -0,0 +1,169 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+/// Turns texts into vectors for semantic memory search.
+///
+/// The server bridges this to the LLM router's embedding API; tests and
+/// offline setups can use [`HashEmbedder`].
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    /// Identifier of the embedding model. Stored vectors are keyed by it, so
+    /// switching models re-embeds memories instead of mixing vector spaces.
+    fn model(&self) -> &str;
+
+    /// Embed the texts, returning one vector per text in the same order.
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>>;
+}
+
+/// Deterministic local embedder based on feature hashing.
+///
+/// Latin text contributes lowercase words; other scripts (Japanese, Chinese,
+/// ...) contribute character unigrams and bigrams, so texts without spaces
+/// still share features. It has no notion of meaning, but it is stable across
+/// runs and needs no network, which makes it suitable for tests.
+#[derive(Debug, Clone)]
+pub struct HashEmbedder {
+    dims: usize,
+    model: String,
+}
+
+impl HashEmbedder {
+    pub fn new(dims: usize) -> Self {
+        let dims = dims.max(1);
+        Self {
+            dims,
+            model: format!("hash-{dims}"),
+        }
+    }
+
+    /// Embed a single text (L2-normalised; all zeros for empty text).
+    pub fn embed_text(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0f32; self.dims];
+        for feature in features(text) {
+            let hash = fnv1a(feature.as_bytes());
+            let index = (hash % self.dims as u64) as usize;
+            let sign = if (hash >> 63) == 0 { 1.0 } else { -1.0 };
+            vector[index] += sign;
+        }
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|x| *x /= norm);
+        }
+        vector
+    }
+}
+
+impl Default for HashEmbedder {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+#[async_trait]
+impl Embedder for HashEmbedder {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Ok(texts.iter().map(|t| self.embed_text(t)).collect())
+    }
+}
+
+fn features(text: &str) -> Vec<String> {
+    let mut features = Vec::new();
+    let mut word = String::new();
+    let mut prev_cjk: Option<char> = None;
+
+    for c in text.to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() || (c.is_alphanumeric() && is_latin(c)) {
+            word.push(c);
+            prev_cjk = None;
+            continue;
+        }
+        if !word.is_empty() {
+            features.push(std::mem::take(&mut word));
+        }
+        if c.is_alphanumeric() {
+            features.push(c.to_string());
+            if let Some(prev) = prev_cjk {
+                features.push(format!("{prev}{c}"));
+            }
+            prev_cjk = Some(c);
+        } else {
+            prev_cjk = None;
+        }
+    }
+    if !word.is_empty() {
+        features.push(word);
+    }
+    features
+}
+
+fn is_latin(c: char) -> bool {
+    // Basic Latin through Latin Extended-B.
+    (c as u32) < 0x0250
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325u64, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Cosine similarity of two vectors (0.0 if either is zero or lengths differ).
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_embedder_is_deterministic_and_normalised() {
+        let embedder = HashEmbedder::new(64);
+        let a = embedder.embed_text("Rust ownership rules");
+        let b = embedder.embed_text("Rust ownership rules");
+        assert_eq!(a, b);
+        let norm = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-5);
+        assert!(embedder.embed_text("").iter().all(|x| *x == 0.0));
+        assert_eq!(embedder.model(), "hash-64");
+    }
+
+    #[test]
+    fn test_hash_embedder_similarity() {
+        let embedder = HashEmbedder::default();
+        let query = embedder.embed_text("猫が好き");
+        let related = embedder.embed_text("私は猫が大好きです");
+        let unrelated = embedder.embed_text("明日の天気予報");
+        assert!(
+            cosine_similarity(&query, &related) > cosine_similarity(&query, &unrelated),
+            "Japanese text should match on shared characters"
+        );
+
+        let query = embedder.embed_text("cats");
+        assert!(
+            cosine_similarity(&query, &embedder.embed_text("I like cats"))
+                > cosine_similarity(&query, &embedder.embed_text("dogs bark loudly"))
+        );
+    }
+
+    #[test]
+    fn test_cosine_similarity_edge_cases() {
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[1.0, 0.0]), 1.0);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+}
@@ -38,7 +38,7 @@ pub trait ActionExecutor: Send + Sync {
 // ---------------------------------------------------------------------------
 
 /// A simplified chat message for the engine's LLM interface.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ChatMessage {
     /// Role: "system", "user", "assistant", or "tool".
     pub role: String,
@@ -49,6 +49,22 @@ pub struct ChatMessage {
     /// Tool calls requested by the assistant (only for role = "assistant").
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub tool_calls: Vec<ToolCall>,
+    /// Name of the participant who wrote a "user" message in multi-party chats.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl ChatMessage {
+    /// A plain text message with the given role.
+    pub fn new(role: &str, content: impl Into<String>) -> Self {
+        Self {
+            role: role.to_string(),
+            content: content.into(),
+            tool_call_id: None,
+            tool_calls: vec![],
+            name: None,
+        }
+    }
 }
 
 /// A tool/function definition for LLM function calling.
@@ -63,7 +79,7 @@ pub struct ToolDefinition {
 }
 
 /// A tool call requested by the LLM.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ToolCall {
     /// Unique ID for this tool call (used to match results).
     pub id: String,
@@ -73,6 +89,7 @@ pub struct ToolCall {
     pub arguments: Value,
 }
 
+
 /// A simplified chat request for the engine.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatRequestSimple {
@@ -86,6 +103,10 @@ pub struct ChatRequestSimple {
     pub temperature: Option<f32>,
     /// Maximum tokens to generate.
     pub max_tokens: Option<u32>,
+    /// What the call is for (e.g. "tool_calling"). `None` leaves it to the
+    /// client, which uses the purpose of the whole run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purpose: Option<String>,
 }
 
 /// A simplified chat response from the LLM.
@@ -109,6 +130,15 @@ pub struct UsageInfo {
     pub total_tokens: u32,
 }
 
+/// Error an [`LlmClient`] returns when the prompt does not fit in the model's
+/// context window. The engine trims the conversation and tries again.
+#[derive(Debug, thiserror::Error)]
+#[error("context length exceeded: {0}")]
+pub struct ContextOverflow(pub String);
+
+/// Callback receiving streamed text fragments.
+pub type TextDeltaCallback<'a> = dyn Fn(&str) + Send + Sync + 'a;
+
 /// Trait for LLM chat completion.
 ///
 /// Defined in `opencrab-core` so the engine can call the LLM without
@@ -117,12 +147,112 @@ pub struct UsageInfo {
 pub trait LlmClient: Send + Sync {
     /// Send a chat request and receive a response.
     async fn chat(&self, request: ChatRequestSimple) -> Result<ChatResponseSimple>;
+
+    /// Send a chat request, calling `on_delta` with each text fragment as it
+    /// arrives, and return the assembled response.
+    ///
+    /// The default implementation falls back to [`LlmClient::chat`] and
+    /// reports the whole text as a single delta.
+    async fn chat_streaming(
+        &self,
+        request: ChatRequestSimple,
+        on_delta: &TextDeltaCallback<'_>,
+    ) -> Result<ChatResponseSimple> {
+        let response = self.chat(request).await?;
+        if let Some(text) = response.content.as_deref().filter(|t| !t.is_empty()) {
+            on_delta(text);
+        }
+        Ok(response)
+    }
 }
 
+// ---------------------------------------------------------------------------
+// Engine events
+// ---------------------------------------------------------------------------
+
+/// Progress events emitted by the engine while it runs.
+///
+/// Observers (e.g. the WebSocket gateway) receive these through the sink
+/// registered with [`SkillEngine::with_event_sink`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EngineEvent {
+    /// A fragment of generated text (streaming mode only).
+    TextDelta { text: String },
+    /// Token usage reported for one LLM call.
+    Usage {
+        prompt_tokens: u32,
+        completion_tokens: u32,
+        total_tokens: u32,
+    },
+    /// A tool call is about to be executed.
+    ToolCallStarted {
+        id: String,
+        name: String,
+        arguments: Value,
+    },
+    /// A tool call finished executing.
+    ToolCallFinished {
+        id: String,
+        name: String,
+        success: bool,
+        data: Value,
+        error: Option<String>,
+    },
+    /// The run completed (last event of [`SkillEngine::run_stream`]).
+    Finished { result: EngineResult },
+    /// The run failed (last event of [`SkillEngine::run_stream`]).
+    Failed { error: String },
+}
+
+/// Channel used to deliver [`EngineEvent`]s to an observer.
+pub type EngineEventSink = tokio::sync::mpsc::UnboundedSender<EngineEvent>;
+
 // ---------------------------------------------------------------------------
 // SkillEngine
 // ---------------------------------------------------------------------------
 
+/// How many times one call is retried with a trimmed context after a
+/// [`ContextOverflow`].
+const MAX_CONTEXT_TRIMS: usize = 3;
+
+/// Tool results shorter than this are never truncated.
+const MIN_TRUNCATED_TOOL_RESULT: usize = 1000;
+
+/// Shrink `messages` after a context overflow. Returns false when there is
+/// nothing left to trim.
+///
+/// The oldest half of the earlier conversation goes first (the system prompt
+/// and the latest user message at `first_new - 1` are kept, and no tool
+/// result is left without its call). Once that is gone, the longest tool
+/// result of this run is cut in half.
+fn trim_context(messages: &mut Vec<ChatMessage>, first_new: &mut usize) -> bool {
+    let earlier = first_new.saturating_sub(2);
+    if earlier > 0 {
+        let mut drop = earlier.div_ceil(2);
+        while drop < earlier && messages[1 + drop].role == "tool" {
+            drop += 1;
+        }
+        messages.drain(1..1 + drop);
+        *first_new -= drop;
+        return true;
+    }
+
+    let longest = messages
+        .iter_mut()
+        .skip(*first_new)
+        .filter(|m| m.role == "tool" && m.content.chars().count() > MIN_TRUNCATED_TOOL_RESULT)
+        .max_by_key(|m| m.content.len());
+    match longest {
+        Some(message) => {
+            let keep = message.content.chars().count() / 2;
+            message.content = message.content.chars().take(keep).collect::<String>() + "... (truncated)";
+            true
+        }
+        None => false,
+    }
+}
+
 /// The LLM-driven action loop engine.
 ///
 /// The SkillEngine orchestrates the cycle of:
@@ -141,8 +271,19 @@ pub struct SkillEngine {
     executor: Box<dyn ActionExecutor>,
     /// Maximum number of LLM call iterations before stopping.
     pub max_iterations: usize,
+    /// Optional observer for progress events.
+    events: Option<EngineEventSink>,
+    /// Whether LLM calls stream text deltas to the observer.
+    streaming: bool,
+    /// Model for the calls that follow tool results.
+    tool_calling_model: Option<String>,
+    /// Sampling temperature for every LLM call.
+    temperature: f32,
 }
 
+/// Temperature used unless [`SkillEngine::with_temperature`] sets one.
+pub const DEFAULT_TEMPERATURE: f32 = 0.7;
+
 impl SkillEngine {
     /// Create a new SkillEngine.
     pub fn new(
@@ -154,9 +295,91 @@ impl SkillEngine {
             llm,
             executor,
             max_iterations,
+            events: None,
+            streaming: false,
+            tool_calling_model: None,
+            temperature: DEFAULT_TEMPERATURE,
         }
     }
 
+    /// Sampling temperature, e.g. derived from the agent's openness
+    /// ([`crate::persona::PersonaOptions::behavior`]).
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    /// Use a separate model while the LLM is working through tool results.
+    ///
+    /// Calls made after tool results are tagged with the "tool_calling"
+    /// purpose and use this model, including the one that writes the final
+    /// answer once no more tools are requested (it is not asked again from the
+    /// default model). Turns without tool calls only use the run's default
+    /// model. A model override (`select_llm`) takes precedence over both.
+    pub fn with_tool_calling_model(mut self, model: Option<String>) -> Self {
+        self.tool_calling_model = model;
+        self
+    }
+
+    /// Stream LLM output as [`EngineEvent::TextDelta`]s to the event sink.
+    pub fn with_streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    /// Register a sink that receives [`EngineEvent`]s during runs.
+    pub fn with_event_sink(mut self, sink: EngineEventSink) -> Self {
+        self.events = Some(sink);
+        self
+    }
+
+    /// Run the action loop as a stream of [`EngineEvent`]s.
+    ///
+    /// Text deltas, tool call progress and usage are yielded as they happen.
+    /// The stream always ends with [`EngineEvent::Finished`] or
+    /// [`EngineEvent::Failed`].
+    pub fn run_stream<'a>(
+        &'a self,
+        system_context: &'a str,
+        user_message: &'a str,
+        model: &'a str,
+        model_override: Option<std::sync::Arc<std::sync::Mutex<Option<String>>>>,
+    ) -> futures::stream::BoxStream<'a, EngineEvent> {
+        use futures::{FutureExt, StreamExt};
+
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+
+        // The loop owns the sender; once it completes the sender is dropped
+        // and the receiver side of the stream ends after the final event.
+        let driver = async move {
+            let result = self
+                .run_loop(
+                    system_context,
+                    vec![ChatMessage::new("user", user_message)],
+                    model,
+                    model_override,
+                    Some(&tx),
+                    true,
+                )
+                .await;
+            let last = match result {
+                Ok(result) => EngineEvent::Finished { result },
+                Err(e) => EngineEvent::Failed {
+                    error: e.to_string(),
+                },
+            };
+            let _ = tx.send(last);
+        }
+        .into_stream()
+        .filter_map(|_| futures::future::ready(None));
+
+        let events = futures::stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|event| (event, rx))
+        });
+
+        futures::stream::select(driver, events).boxed()
+    }
+
     /// Run the action loop with the given system context and user message.
     ///
     /// Returns the final text response from the LLM after all tool calls
@@ -182,25 +405,66 @@ impl SkillEngine {
         default_model: &str,
         model_override: Option<std::sync::Arc<std::sync::Mutex<Option<String>>>>,
     ) -> Result<EngineResult> {
+        self.run_with_history(
+            system_context,
+            vec![ChatMessage::new("user", user_message)],
+            default_model,
+            model_override,
+        )
+        .await
+    }
+
+    /// Run the action loop on a multi-turn conversation.
+    ///
+    /// `history` follows the system prompt as-is: the agent's earlier turns
+    /// as "assistant" messages (with their tool calls and results) and other
+    /// participants' turns as "user" messages. It should end with a "user"
+    /// message.
+    pub async fn run_with_history(
+        &self,
+        system_context: &str,
+        history: Vec<ChatMessage>,
+        default_model: &str,
+        model_override: Option<std::sync::Arc<std::sync::Mutex<Option<String>>>>,
+    ) -> Result<EngineResult> {
+        self.run_loop(
+            system_context,
+            history,
+            default_model,
+            model_override,
+            self.events.as_ref(),
+            self.streaming,
+        )
+        .await
+    }
+
+    async fn run_loop(
+        &self,
+        system_context: &str,
+        history: Vec<ChatMessage>,
+        default_model: &str,
+        model_override: Option<std::sync::Arc<std::sync::Mutex<Option<String>>>>,
+        events: Option<&EngineEventSink>,
+        streaming: bool,
+    ) -> Result<EngineResult> {
+        let emit = |event: EngineEvent| {
+            if let Some(sink) = events {
+                // A dropped receiver only means nobody is watching any more.
+                let _ = sink.send(event);
+            }
+        };
+
         let tools = self.executor.list_tools();
 
-        let mut messages = vec![
-            ChatMessage {
-                role: "system".to_string(),
-                content: system_context.to_string(),
-                tool_call_id: None,
-                tool_calls: vec![],
-            },
-            ChatMessage {
-                role: "user".to_string(),
-                content: user_message.to_string(),
-                tool_call_id: None,
-                tool_calls: vec![],
-            },
-        ];
+        let mut messages = vec![ChatMessage::new("system", system_context)];
+        messages.extend(history);
+        // Messages added by this run (tool calls and their results).
+        let mut first_new = messages.len();
 
         let mut iterations = 0;
         let mut total_tool_calls = 0;
+        // Whether the previous call returned tool calls.
+        let mut after_tools = false;
 
         loop {
             iterations += 1;
@@ -216,35 +480,77 @@ impl SkillEngine {
                     iterations,
                     tool_calls_made: total_tool_calls,
                     stopped_by_limit: true,
+                    transcript: messages.split_off(first_new),
                 });
             }
 
             // Check for dynamic model override.
-            let model = model_override
+            let overridden = model_override
                 .as_ref()
-                .and_then(|o| o.lock().ok().and_then(|m| m.clone()))
-                .unwrap_or_else(|| default_model.to_string());
+                .and_then(|o| o.lock().ok().and_then(|m| m.clone()));
+            let model = match (overridden, after_tools, &self.tool_calling_model) {
+                (Some(model), _, _) => model,
+                (None, true, Some(tool_model)) => tool_model.clone(),
+                _ => default_model.to_string(),
+            };
+            let purpose = after_tools.then(|| "tool_calling".to_string());
 
-            tracing::debug!(iteration = iterations, model = %model, "SkillEngine LLM call");
+            tracing::debug!(iteration = iterations, model = %model, purpose = ?purpose, "SkillEngine LLM call");
 
-            let request = ChatRequestSimple {
+            let mut request = ChatRequestSimple {
                 model,
                 messages: messages.clone(),
                 tools: tools.clone(),
-                temperature: Some(0.7),
+                temperature: Some(self.temperature),
                 max_tokens: Some(4096),
+                purpose,
             };
 
-            let response = self.llm.chat(request).await?;
+            let mut trims = 0;
+            let response = loop {
+                let result = if streaming {
+                    let on_delta = |text: &str| {
+                        emit(EngineEvent::TextDelta {
+                            text: text.to_string(),
+                        })
+                    };
+                    self.llm.chat_streaming(request.clone(), &on_delta).await
+                } else {
+                    self.llm.chat(request.clone()).await
+                };
+                match result {
+                    Err(e)
+                        if e.downcast_ref::<ContextOverflow>().is_some()
+                            && trims < MAX_CONTEXT_TRIMS
+                            && trim_context(&mut messages, &mut first_new) =>
+                    {
+                        trims += 1;
+                        tracing::warn!(
+                            iteration = iterations,
+                            messages = messages.len(),
+                            error = %e,
+                            "Context overflow, retrying with a trimmed context"
+                        );
+                        request.messages = messages.clone();
+                    }
+                    result => break result?,
+                }
+            };
+
+            if let Some(usage) = &response.usage {
+                emit(EngineEvent::Usage {
+                    prompt_tokens: usage.prompt_tokens,
+                    completion_tokens: usage.completion_tokens,
+                    total_tokens: usage.total_tokens,
+                });
+            }
 
             // If there are tool calls, execute them and continue the loop.
             if !response.tool_calls.is_empty() {
                 // Add the assistant message with tool calls.
                 messages.push(ChatMessage {
-                    role: "assistant".to_string(),
-                    content: response.content.clone().unwrap_or_default(),
-                    tool_call_id: None,
                     tool_calls: response.tool_calls.clone(),
+                    ..ChatMessage::new("assistant", response.content.clone().unwrap_or_default())
                 });
 
                 for tool_call in &response.tool_calls {
@@ -256,19 +562,32 @@ impl SkillEngine {
                         "Executing tool call"
                     );
 
+                    emit(EngineEvent::ToolCallStarted {
+                        id: tool_call.id.clone(),
+                        name: tool_call.name.clone(),
+                        arguments: tool_call.arguments.clone(),
+                    });
+
                     let result = self.executor.execute(&tool_call.name, &tool_call.arguments).await;
 
+                    emit(EngineEvent::ToolCallFinished {
+                        id: tool_call.id.clone(),
+                        name: tool_call.name.clone(),
+                        success: result.success,
+                        data: result.data.clone(),
+                        error: result.error.clone(),
+                    });
+
                     let result_json = serde_json::to_string(&result)
                         .unwrap_or_else(|_| r#"{"error": "Failed to serialize result"}"#.to_string());
 
                     messages.push(ChatMessage {
-                        role: "tool".to_string(),
-                        content: result_json,
                         tool_call_id: Some(tool_call.id.clone()),
-                        tool_calls: vec![],
+                        ..ChatMessage::new("tool", result_json)
                     });
                 }
 
+                after_tools = true;
                 continue;
             }
 
@@ -282,6 +601,7 @@ impl SkillEngine {
                 iterations,
                 tool_calls_made: total_tool_calls,
                 stopped_by_limit: false,
+                transcript: messages.split_off(first_new),
             });
         }
     }
@@ -298,6 +618,10 @@ pub struct EngineResult {
     pub tool_calls_made: usize,
     /// Whether the engine stopped due to hitting the iteration limit.
     pub stopped_by_limit: bool,
+    /// Tool-call messages and tool results produced during the run, in order
+    /// (the final text response is not included).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transcript: Vec<ChatMessage>,
 }
 
 #[cfg(test)]
@@ -421,6 +745,126 @@ mod tests {
         assert!(!result.stopped_by_limit);
     }
 
+    #[tokio::test]
+    async fn test_tool_call_events() {
+        let llm = MockLlm::new(vec![
+            tool_call_response(vec![ToolCall {
+                id: "tc-1".to_string(),
+                name: "test_tool".to_string(),
+                arguments: serde_json::json!({"q": 1}),
+            }]),
+            text_response("done"),
+        ]);
+        let executor = MockExecutor::new().add_result(
+            "test_tool",
+            ActionResult {
+                success: true,
+                data: serde_json::json!({"result": "ok"}),
+                error: None,
+            },
+        );
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let engine =
+            SkillEngine::new(Box::new(llm), Box::new(executor), 10).with_event_sink(tx);
+
+        engine.run("system", "go", "test-model").await.unwrap();
+
+        match rx.try_recv().unwrap() {
+            EngineEvent::ToolCallStarted { id, name, arguments } => {
+                assert_eq!(id, "tc-1");
+                assert_eq!(name, "test_tool");
+                assert_eq!(arguments["q"], 1);
+            }
+            other => panic!("unexpected event: {other:?}"),
+        }
+        match rx.try_recv().unwrap() {
+            EngineEvent::ToolCallFinished { success, data, .. } => {
+                assert!(success);
+                assert_eq!(data["result"], "ok");
+            }
+            other => panic!("unexpected event: {other:?}"),
+        }
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_stream_events() {
+        use futures::StreamExt;
+
+        let mut final_response = text_response("streamed answer");
+        final_response.usage = Some(UsageInfo {
+            prompt_tokens: 12,
+            completion_tokens: 3,
+            total_tokens: 15,
+        });
+        let llm = MockLlm::new(vec![
+            tool_call_response(vec![ToolCall {
+                id: "tc-1".to_string(),
+                name: "test_tool".to_string(),
+                arguments: serde_json::json!({}),
+            }]),
+            final_response,
+        ]);
+        let executor = MockExecutor::new().add_result(
+            "test_tool",
+            ActionResult {
+                success: true,
+                data: serde_json::json!({}),
+                error: None,
+            },
+        );
+        let engine = SkillEngine::new(Box::new(llm), Box::new(executor), 10);
+
+        let events: Vec<EngineEvent> = engine
+            .run_stream("system", "go", "test-model", None)
+            .collect()
+            .await;
+
+        let kinds: Vec<&str> = events
+            .iter()
+            .map(|e| match e {
+                EngineEvent::TextDelta { .. } => "text_delta",
+                EngineEvent::Usage { .. } => "usage",
+                EngineEvent::ToolCallStarted { .. } => "tool_call_started",
+                EngineEvent::ToolCallFinished { .. } => "tool_call_finished",
+                EngineEvent::Finished { .. } => "finished",
+                EngineEvent::Failed { .. } => "failed",
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec!["tool_call_started", "tool_call_finished", "text_delta", "usage", "finished"]
+        );
+        match &events[2] {
+            EngineEvent::TextDelta { text } => assert_eq!(text, "streamed answer"),
+            other => panic!("unexpected event: {other:?}"),
+        }
+        match events.last().unwrap() {
+            EngineEvent::Finished { result } => {
+                assert_eq!(result.response, "streamed answer");
+                assert_eq!(result.tool_calls_made, 1);
+            }
+            other => panic!("unexpected event: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_stream_failure() {
+        use futures::StreamExt;
+
+        let engine = SkillEngine::new(
+            Box::new(MockLlm::new(vec![])),
+            Box::new(MockExecutor::new()),
+            10,
+        );
+        let events: Vec<EngineEvent> = engine
+            .run_stream("system", "go", "test-model", None)
+            .collect()
+            .await;
+        assert_eq!(events.len(), 1);
+        assert!(matches!(&events[0], EngineEvent::Failed { error } if error.contains("no more")));
+    }
+
     #[tokio::test]
     async fn test_max_iterations() {
         let llm = MockLlm::new(vec![
@@ -509,6 +953,71 @@ mod tests {
         assert!(!result.stopped_by_limit);
     }
 
+    #[tokio::test]
+    async fn test_run_with_history() {
+        use std::sync::{Arc, Mutex};
+
+        struct MessageCapturingLlm {
+            responses: Mutex<Vec<ChatResponseSimple>>,
+            captured: Arc<Mutex<Vec<Vec<ChatMessage>>>>,
+        }
+
+        #[async_trait]
+        impl LlmClient for MessageCapturingLlm {
+            async fn chat(&self, request: ChatRequestSimple) -> anyhow::Result<ChatResponseSimple> {
+                self.captured.lock().unwrap().push(request.messages);
+                Ok(self.responses.lock().unwrap().remove(0))
+            }
+        }
+
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let llm = MessageCapturingLlm {
+            responses: Mutex::new(vec![
+                tool_call_response(vec![ToolCall {
+                    id: "tc-1".to_string(),
+                    name: "test_tool".to_string(),
+                    arguments: serde_json::json!({"q": "x"}),
+                }]),
+                text_response("final"),
+            ]),
+            captured: captured.clone(),
+        };
+        let executor = MockExecutor::new().add_result(
+            "test_tool",
+            ActionResult {
+                success: true,
+                data: serde_json::json!({"ok": true}),
+                error: None,
+            },
+        );
+        let engine = SkillEngine::new(Box::new(llm), Box::new(executor), 10);
+
+        let history = vec![
+            ChatMessage {
+                name: Some("Bob".to_string()),
+                ..ChatMessage::new("user", "Hi Alice")
+            },
+            ChatMessage::new("assistant", "Hello Bob"),
+            ChatMessage::new("user", "What now?"),
+        ];
+        let result = engine
+            .run_with_history("system", history.clone(), "test-model", None)
+            .await
+            .unwrap();
+
+        let captured = captured.lock().unwrap();
+        assert_eq!(captured[0][0].role, "system");
+        assert_eq!(&captured[0][1..], &history[..]);
+        assert_eq!(captured[1].len(), 6);
+
+        assert_eq!(result.response, "final");
+        assert_eq!(result.transcript.len(), 2);
+        assert_eq!(result.transcript[0].role, "assistant");
+        assert_eq!(result.transcript[0].tool_calls[0].id, "tc-1");
+        assert_eq!(result.transcript[1].role, "tool");
+        assert_eq!(result.transcript[1].tool_call_id.as_deref(), Some("tc-1"));
+    }
+
     #[tokio::test]
     async fn test_model_override() {
         use std::sync::{Arc, Mutex};
@@ -580,4 +1089,170 @@ mod tests {
         // Second call should use the overridden model (race condition safe - set before tool call finishes).
         // Due to timing, it might be either; the important thing is the mechanism works.
     }
+
+    #[tokio::test]
+    async fn test_tool_calling_model_routing() {
+        use futures::StreamExt;
+        use std::sync::{Arc, Mutex};
+
+        // (model, purpose) of each request.
+        type Calls = Arc<Mutex<Vec<(String, Option<String>)>>>;
+
+        struct RoutingLlm {
+            responses: Mutex<Vec<ChatResponseSimple>>,
+            calls: Calls,
+        }
+
+        #[async_trait]
+        impl LlmClient for RoutingLlm {
+            async fn chat(&self, request: ChatRequestSimple) -> anyhow::Result<ChatResponseSimple> {
+                self.calls
+                    .lock()
+                    .unwrap()
+                    .push((request.model.clone(), request.purpose.clone()));
+                let mut responses = self.responses.lock().unwrap();
+                if responses.is_empty() {
+                    anyhow::bail!("no more mock responses");
+                }
+                Ok(responses.remove(0))
+            }
+        }
+
+        let tool_call = || {
+            tool_call_response(vec![ToolCall {
+                id: "tc-1".to_string(),
+                name: "test_tool".to_string(),
+                arguments: serde_json::json!({}),
+            }])
+        };
+        let calls: Calls = Arc::new(Mutex::new(Vec::new()));
+        let llm = RoutingLlm {
+            responses: Mutex::new(vec![
+                tool_call(),
+                tool_call(),
+                text_response("final answer"),
+            ]),
+            calls: calls.clone(),
+        };
+        let executor = MockExecutor::new().add_result(
+            "test_tool",
+            ActionResult {
+                success: true,
+                data: serde_json::json!({}),
+                error: None,
+            },
+        );
+        let engine = SkillEngine::new(Box::new(llm), Box::new(executor), 10)
+            .with_tool_calling_model(Some("tool-model".to_string()));
+
+        let events: Vec<EngineEvent> = engine
+            .run_stream("system", "go", "chat-model", None)
+            .collect()
+            .await;
+
+        let tool = Some("tool_calling".to_string());
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                ("chat-model".to_string(), None),
+                ("tool-model".to_string(), tool.clone()),
+                ("tool-model".to_string(), tool),
+            ]
+        );
+        // The tool model's answer is final: it is streamed and not asked again.
+        let streamed: Vec<&str> = events
+            .iter()
+            .filter_map(|e| match e {
+                EngineEvent::TextDelta { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(streamed, vec!["final answer"]);
+        match events.last() {
+            Some(EngineEvent::Finished { result }) => {
+                assert_eq!(result.response, "final answer");
+                assert_eq!(result.iterations, 3);
+                assert_eq!(result.tool_calls_made, 2);
+            }
+            other => panic!("unexpected last event: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_context_overflow_trims_history() {
+        use std::sync::Mutex;
+
+        /// Rejects requests with more than `limit` messages.
+        struct SmallWindowLlm {
+            limit: usize,
+            sizes: Mutex<Vec<usize>>,
+        }
+
+        #[async_trait]
+        impl LlmClient for SmallWindowLlm {
+            async fn chat(&self, request: ChatRequestSimple) -> anyhow::Result<ChatResponseSimple> {
+                self.sizes.lock().unwrap().push(request.messages.len());
+                if request.messages.len() > self.limit {
+                    return Err(ContextOverflow("too many messages".to_string()).into());
+                }
+                let last = request.messages.last().unwrap().content.clone();
+                Ok(text_response(&format!("answer to {last}")))
+            }
+        }
+
+        let history: Vec<ChatMessage> = (0..8)
+            .map(|i| ChatMessage::new(if i % 2 == 0 { "user" } else { "assistant" }, format!("turn {i}")))
+            .chain(std::iter::once(ChatMessage::new("user", "latest")))
+            .collect();
+        let llm = SmallWindowLlm { limit: 4, sizes: Mutex::new(Vec::new()) };
+        let engine = SkillEngine::new(Box::new(llm), Box::new(MockExecutor::new()), 5);
+        let result = engine.run_with_history("system", history.clone(), "m", None).await.unwrap();
+        assert_eq!(result.response, "answer to latest");
+
+        // Nothing is left to trim: the overflow is returned.
+        let llm = SmallWindowLlm { limit: 1, sizes: Mutex::new(Vec::new()) };
+        let engine = SkillEngine::new(Box::new(llm), Box::new(MockExecutor::new()), 5);
+        let err = engine.run_with_history("system", history, "m", None).await.unwrap_err();
+        assert!(err.downcast_ref::<ContextOverflow>().is_some());
+    }
+
+    #[test]
+    fn test_trim_context_keeps_tool_pairs() {
+        let call = ChatMessage {
+            tool_calls: vec![ToolCall {
+                id: "tc".to_string(),
+                name: "t".to_string(),
+                arguments: serde_json::json!({}),
+            }],
+            ..ChatMessage::new("assistant", "")
+        };
+        let mut messages = vec![
+            ChatMessage::new("system", "s"),
+            ChatMessage::new("user", "old"),
+            call.clone(),
+            ChatMessage::new("tool", "old result"),
+            ChatMessage::new("assistant", "old answer"),
+            ChatMessage::new("user", "latest"),
+            call,
+            ChatMessage::new("tool", "x".repeat(3000)),
+        ];
+        let mut first_new = 6;
+
+        // Half of the 4 earlier messages go, plus the orphaned tool result.
+        assert!(trim_context(&mut messages, &mut first_new));
+        let roles: Vec<&str> = messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["system", "assistant", "user", "assistant", "tool"]);
+        assert_eq!(first_new, 3);
+
+        assert!(trim_context(&mut messages, &mut first_new));
+        assert_eq!(first_new, 2);
+        assert_eq!(messages[1].content, "latest");
+
+        // Then the long tool result of this run is halved until it is short.
+        assert!(trim_context(&mut messages, &mut first_new));
+        assert!(messages[3].content.starts_with(&"x".repeat(1500)));
+        assert!(messages[3].content.ends_with("(truncated)"));
+        assert!(trim_context(&mut messages, &mut first_new));
+        assert!(!trim_context(&mut messages, &mut first_new));
+    }
 }
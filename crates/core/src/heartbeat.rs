@@ -1,4 +1,6 @@
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use tokio::sync::watch;
 use tracing;
 
@@ -23,7 +25,7 @@ impl Default for HeartbeatConfig {
 
 /// The decision made during a heartbeat tick.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type", rename_all = "snake_case")]
+#[serde(tag = "type", content = "message", rename_all = "snake_case")]
 pub enum HeartbeatDecision {
     /// The agent decided to say something.
     Speak(String),
@@ -33,6 +35,17 @@ pub enum HeartbeatDecision {
     Idle,
 }
 
+impl HeartbeatDecision {
+    /// Short label for the decision kind ("speak", "learn", "idle").
+    pub fn kind(&self) -> &'static str {
+        match self {
+            HeartbeatDecision::Speak(_) => "speak",
+            HeartbeatDecision::Learn => "learn",
+            HeartbeatDecision::Idle => "idle",
+        }
+    }
+}
+
 impl std::fmt::Display for HeartbeatDecision {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -49,6 +62,25 @@ impl std::fmt::Display for HeartbeatDecision {
 pub type HeartbeatCallback =
     Box<dyn Fn(&str, u64) -> HeartbeatDecision + Send + Sync + 'static>;
 
+/// Asynchronous handler invoked on each heartbeat tick.
+///
+/// Unlike [`HeartbeatCallback`], a handler may await (e.g. to consult an LLM
+/// and act on the decision) before the next tick is scheduled.
+#[async_trait]
+pub trait HeartbeatHandler: Send + Sync {
+    /// Decide (and carry out) what the agent does on this tick.
+    async fn on_tick(&self, agent_id: &str, tick: u64) -> HeartbeatDecision;
+}
+
+struct CallbackHandler(HeartbeatCallback);
+
+#[async_trait]
+impl HeartbeatHandler for CallbackHandler {
+    async fn on_tick(&self, agent_id: &str, tick: u64) -> HeartbeatDecision {
+        (self.0)(agent_id, tick)
+    }
+}
+
 /// Run the heartbeat loop for an agent.
 ///
 /// The loop fires at the configured interval (prime-numbered seconds by default)
@@ -63,6 +95,20 @@ pub async fn heartbeat_loop(
     agent_id: String,
     config: HeartbeatConfig,
     callback: HeartbeatCallback,
+    shutdown_rx: watch::Receiver<bool>,
+) {
+    run_heartbeat(agent_id, config, Arc::new(CallbackHandler(callback)), shutdown_rx).await
+}
+
+/// Run the heartbeat loop for an agent with an asynchronous handler.
+///
+/// Behaves like [`heartbeat_loop`], but awaits the handler on each tick. A
+/// shutdown signal received while a tick is in progress takes effect once the
+/// handler returns.
+pub async fn run_heartbeat(
+    agent_id: String,
+    config: HeartbeatConfig,
+    handler: Arc<dyn HeartbeatHandler>,
     mut shutdown_rx: watch::Receiver<bool>,
 ) {
     if !config.enabled {
@@ -84,7 +130,7 @@ pub async fn heartbeat_loop(
             _ = tokio::time::sleep(interval) => {
                 tick_count += 1;
 
-                let decision = callback(&agent_id, tick_count);
+                let decision = handler.on_tick(&agent_id, tick_count).await;
 
                 tracing::debug!(
                     agent_id = %agent_id,
@@ -127,3 +173,63 @@ pub async fn heartbeat_loop(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    struct CountingHandler(AtomicU64);
+
+    #[async_trait]
+    impl HeartbeatHandler for CountingHandler {
+        async fn on_tick(&self, _agent_id: &str, tick: u64) -> HeartbeatDecision {
+            self.0.store(tick, Ordering::SeqCst);
+            HeartbeatDecision::Idle
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_heartbeat_ticks_until_shutdown() {
+        let handler = Arc::new(CountingHandler(AtomicU64::new(0)));
+        let (tx, rx) = watch::channel(false);
+        let config = HeartbeatConfig {
+            interval_secs: 5,
+            enabled: true,
+        };
+
+        let task = tokio::spawn(run_heartbeat("a".to_string(), config, handler.clone(), rx));
+        tokio::time::sleep(tokio::time::Duration::from_secs(16)).await;
+        tx.send(true).unwrap();
+        task.await.unwrap();
+
+        assert_eq!(handler.0.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_heartbeat_returns_immediately() {
+        let (_tx, rx) = watch::channel(false);
+        heartbeat_loop(
+            "a".to_string(),
+            HeartbeatConfig::default(),
+            Box::new(|_, _| HeartbeatDecision::Idle),
+            rx,
+        )
+        .await;
+    }
+
+    #[test]
+    fn test_decision_serde() {
+        let v = serde_json::to_value(HeartbeatDecision::Speak("hi".into())).unwrap();
+        assert_eq!(v, serde_json::json!({"type": "speak", "message": "hi"}));
+        let v = serde_json::to_value(HeartbeatDecision::Idle).unwrap();
+        assert_eq!(v, serde_json::json!({"type": "idle"}));
+    }
+
+    #[test]
+    fn test_decision_kind() {
+        assert_eq!(HeartbeatDecision::Speak("hi".into()).kind(), "speak");
+        assert_eq!(HeartbeatDecision::Learn.kind(), "learn");
+        assert_eq!(HeartbeatDecision::Idle.kind(), "idle");
+    }
+}
@@ -76,7 +76,7 @@ impl Identity {
     pub fn build_context(&self) -> String {
         let mut ctx = String::new();
 
-        ctx.push_str(&format!("## Identity\n\n"));
+        ctx.push_str("## Identity\n\n");
         ctx.push_str(&format!("- Name: {}\n", self.name));
         ctx.push_str(&format!("- Role: {}\n", self.role));
 
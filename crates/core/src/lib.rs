@@ -4,33 +4,61 @@
 //! OpenCrab framework:
 //!
 //! - **Soul**: Personality traits, social style, and thinking preferences.
+//! - **Persona**: Rendering of soul traits into prompt instructions and runtime parameters.
+//! - **Prompt**: System prompt templates with variables and conditional sections.
 //! - **Identity**: Name, role, and organizational context.
 //! - **Memory**: Curated memories and session log management.
+//! - **Context**: Token budgeting and rolling summaries for conversation history.
+//! - **Assembler**: Budgeted injection of memories, impressions and related history into prompts.
+//! - **Consolidation**: Distilling session logs into de-duplicated curated memories.
+//! - **Embedding**: Embedder abstraction for semantic (hybrid) memory search.
 //! - **Skill**: Standard and acquired skill management.
+//! - **Skill loader**: Parsing of `*.skill.md` standard skill files.
 //! - **Workspace**: Sandboxed file operations with path traversal protection.
 //! - **Heartbeat**: Periodic agent activity loop.
+//! - **Session**: Phase transitions, speaker selection and end detection for facilitated sessions.
 //! - **Agent**: The combined agent struct.
 //! - **Engine**: LLM-driven action loop for executing skills.
 
 pub mod soul;
+pub mod persona;
+pub mod prompt;
 pub mod identity;
 pub mod memory;
+pub mod context;
+pub mod assembler;
+pub mod consolidation;
+pub mod embedding;
 pub mod skill;
+pub mod skill_loader;
 pub mod workspace;
 pub mod heartbeat;
+pub mod session;
 pub mod agent;
 pub mod engine;
 
 // Re-export primary types for convenience.
 pub use soul::{Soul, SocialStyle, Personality, ThinkingStyle};
+pub use persona::{BehaviorParams, PersonaLanguage, PersonaOptions, PersonaRenderer};
+pub use prompt::{PromptTemplate, PROMPT_VARIABLES};
 pub use identity::{Identity, AgentRole};
-pub use memory::MemoryManager;
+pub use memory::{HybridSearchConfig, Impression, MemoryHit, MemoryManager, MemoryPolicy, PruneReport};
+pub use context::{
+    ContextWindowConfig, ConversationSummary, ConversationTurn, ConversationWindow, TurnKind,
+    DEFAULT_CONTEXT_WINDOW,
+};
+pub use assembler::{AssembledContext, ContextAssembler, ContextBudget, ContextRequest, InjectedItem};
+pub use consolidation::{ConsolidationAction, ConsolidationOptions, ExtractedMemory, CONSOLIDATION_CATEGORIES};
+pub use embedding::{Embedder, HashEmbedder};
 pub use skill::{SkillManager, Skill, SkillSource};
+pub use skill_loader::{SkillFile, SkillSyncReport};
 pub use workspace::{Workspace, FileEntry};
-pub use heartbeat::{HeartbeatConfig, HeartbeatDecision};
-pub use agent::{Agent, AgentLlmConfig, AgentModels, ModelRef};
+pub use heartbeat::{HeartbeatConfig, HeartbeatDecision, HeartbeatHandler};
+pub use session::{FacilitatorDecision, SessionEndReason, SessionPhase, SessionProgress};
+pub use agent::{Agent, AgentLlmConfig, AgentModels, ModelRef, MODEL_PURPOSES};
 pub use engine::{
     SkillEngine, ActionExecutor, ActionResult, LlmClient,
     ChatRequestSimple, ChatResponseSimple, ChatMessage,
     ToolDefinition, ToolCall, UsageInfo, EngineResult,
+    EngineEvent, EngineEventSink, TextDeltaCallback, ContextOverflow, DEFAULT_TEMPERATURE,
 };
@@ -1,10 +1,20 @@
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::sync::{Arc, Mutex};
 use tracing;
 
 use opencrab_db::queries;
 
+use crate::embedding::{cosine_similarity, Embedder};
+
+/// Number of texts sent to the embedder per request.
+const EMBED_BATCH_SIZE: usize = 32;
+/// Maximum number of memories per category in [`MemoryManager::build_context`].
+const MAX_CONTEXT_PER_CATEGORY: usize = 10;
+
 /// Manages curated memories and session logs for an agent.
 ///
 /// The MemoryManager wraps a shared database connection and provides
@@ -13,6 +23,7 @@ use opencrab_db::queries;
 pub struct MemoryManager {
     agent_id: String,
     conn: Arc<Mutex<Connection>>,
+    policy: MemoryPolicy,
 }
 
 impl MemoryManager {
@@ -21,10 +32,23 @@ impl MemoryManager {
         Self {
             agent_id: agent_id.into(),
             conn,
+            policy: MemoryPolicy::default(),
         }
     }
 
-    /// Get all curated memories, optionally filtered by category.
+    /// Use a custom ranking and retention policy.
+    pub fn with_policy(mut self, policy: MemoryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// The ranking and retention policy in use.
+    pub fn policy(&self) -> &MemoryPolicy {
+        &self.policy
+    }
+
+    /// Get all curated memories, optionally filtered by category, ranked by
+    /// retention score (importance decayed by disuse, boosted by access).
     pub fn get_curated(&self, category: Option<&str>) -> Result<Vec<CuratedMemory>> {
         let conn = self.conn.lock().unwrap();
         let rows = if let Some(cat) = category {
@@ -32,15 +56,67 @@ impl MemoryManager {
         } else {
             queries::list_curated_memories(&conn, &self.agent_id)?
         };
+        let ids: Vec<String> = rows.iter().map(|r| r.id.clone()).collect();
+        let stats = queries::get_memory_stats(&conn, queries::EMBEDDING_SOURCE_CURATED, &ids)?;
+        let now = Utc::now();
 
-        Ok(rows
+        let mut memories: Vec<CuratedMemory> = rows
             .into_iter()
-            .map(|row| CuratedMemory {
-                id: row.id,
-                category: row.category,
-                content: row.content,
+            .map(|row| {
+                let stat = stats.get(&row.id);
+                CuratedMemory {
+                    importance: stat.map(|s| s.importance).unwrap_or(0.5),
+                    access_count: stat.map(|s| s.access_count).unwrap_or(0),
+                    score: stat.map(|s| self.policy.score(s, now)).unwrap_or(0.0),
+                    id: row.id,
+                    category: row.category,
+                    content: row.content,
+                }
             })
-            .collect())
+            .collect();
+        // Stable: equal scores keep the most recently updated first.
+        memories.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(memories)
+    }
+
+    /// Record that memories were used (e.g. injected into a prompt), which
+    /// slows their decay.
+    pub fn record_access(&self, source: &str, ids: &[String]) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let conn = self.conn.lock().unwrap();
+        queries::record_memory_access(&conn, source, ids)?;
+        Ok(())
+    }
+
+    /// Apply the retention rules: archive old session logs whose score fell
+    /// below the threshold, and delete logs archived long enough ago.
+    pub fn prune(&self, now: DateTime<Utc>) -> Result<PruneReport> {
+        let retention = &self.policy;
+        let mut report = PruneReport::default();
+        let conn = self.conn.lock().unwrap();
+
+        if let Some(days) = retention.archive_after_days {
+            let cutoff = (now - chrono::Duration::days(days as i64)).to_rfc3339();
+            let candidates =
+                queries::list_retention_candidates(&conn, &self.agent_id, &cutoff, &retention.keep_log_types)?;
+            let ids: Vec<i64> = candidates
+                .iter()
+                .filter(|c| retention.score(c, now) < retention.archive_below)
+                .filter_map(|c| c.id.parse().ok())
+                .collect();
+            report.archived = queries::archive_session_logs(&conn, &ids)?;
+        }
+        if let Some(days) = retention.delete_after_days {
+            let cutoff = (now - chrono::Duration::days(days as i64)).to_rfc3339();
+            report.deleted = queries::delete_archived_session_logs(&conn, &self.agent_id, &cutoff)?;
+        }
+
+        if !report.is_empty() {
+            tracing::info!(agent_id = %self.agent_id, archived = report.archived, deleted = report.deleted, "Pruned memories");
+        }
+        Ok(report)
     }
 
     /// Save or update a curated memory.
@@ -59,6 +135,32 @@ impl MemoryManager {
         Ok(())
     }
 
+    /// Latest impression of each target, preferring ones formed in `prefer_session`.
+    ///
+    /// Results follow the order of `target_ids`; targets without an impression are omitted.
+    pub fn get_impressions(
+        &self,
+        target_ids: &[String],
+        prefer_session: Option<&str>,
+    ) -> Result<Vec<Impression>> {
+        let conn = self.conn.lock().unwrap();
+        let rows = queries::get_latest_impressions(&conn, &self.agent_id, target_ids, prefer_session)?;
+        Ok(rows
+            .into_iter()
+            .map(|row| Impression {
+                id: row.id,
+                session_id: row.session_id,
+                target_id: row.target_id,
+                target_name: row.target_name,
+                personality: row.personality,
+                communication_style: row.communication_style,
+                recent_behavior: row.recent_behavior,
+                agreement: row.agreement,
+                notes: row.notes,
+            })
+            .collect())
+    }
+
     /// Append a log entry to the session log.
     pub fn append_session_log(
         &self,
@@ -109,22 +211,217 @@ impl MemoryManager {
             .collect())
     }
 
+    /// Embed session logs and curated memories that have no vector yet for
+    /// the embedder's model. Returns the number of memories embedded.
+    ///
+    /// Searches never embed stored memories themselves; call this when
+    /// memories are written or from a background job.
+    pub async fn index_embeddings(&self, embedder: &dyn Embedder, max_items: usize) -> Result<usize> {
+        let mut indexed = 0;
+        while indexed < max_items {
+            let batch = {
+                let conn = self.conn.lock().unwrap();
+                queries::list_unembedded_memories(
+                    &conn,
+                    &self.agent_id,
+                    embedder.model(),
+                    EMBED_BATCH_SIZE.min(max_items - indexed),
+                )?
+            };
+            if batch.is_empty() {
+                break;
+            }
+
+            let texts: Vec<String> = batch.iter().map(|c| c.content.clone()).collect();
+            let vectors = embedder.embed(&texts).await?;
+            if vectors.len() != batch.len() {
+                anyhow::bail!(
+                    "Embedder returned {} vectors for {} texts",
+                    vectors.len(),
+                    batch.len()
+                );
+            }
+
+            let conn = self.conn.lock().unwrap();
+            for (candidate, vector) in batch.iter().zip(&vectors) {
+                queries::upsert_embedding(
+                    &conn,
+                    &candidate.source,
+                    &candidate.source_id,
+                    &self.agent_id,
+                    embedder.model(),
+                    vector,
+                )?;
+            }
+            indexed += batch.len();
+        }
+        if indexed > 0 {
+            tracing::debug!(agent_id = %self.agent_id, model = %embedder.model(), indexed, "Indexed memory embeddings");
+        }
+        Ok(indexed)
+    }
+
+    /// Search session logs and curated memories with BM25 and vector similarity.
+    ///
+    /// Keyword hits come from FTS5 (session logs only); vector hits cover both
+    /// session logs and curated memories. The two rankings are merged with
+    /// weighted reciprocal rank fusion. Only memories already embedded by
+    /// [`MemoryManager::index_embeddings`] take part in the vector ranking.
+    /// Without an embedder, or if embedding the query fails, this degrades to
+    /// keyword-only ranking.
+    pub async fn hybrid_search(
+        &self,
+        query: &str,
+        limit: usize,
+        embedder: Option<&dyn Embedder>,
+        config: &HybridSearchConfig,
+    ) -> Result<Vec<MemoryHit>> {
+        let candidates = limit.max(1) * 4;
+        let keyword = {
+            let conn = self.conn.lock().unwrap();
+            if config.match_any {
+                queries::search_session_logs_any(&conn, &self.agent_id, query, candidates)?
+            } else {
+                queries::search_session_logs(&conn, &self.agent_id, query, candidates)?
+            }
+        };
+
+        let vector = match embedder {
+            Some(embedder) => match self.vector_search(query, candidates, embedder, config).await {
+                Ok(hits) => hits,
+                Err(e) => {
+                    tracing::warn!(agent_id = %self.agent_id, error = %e, "Vector search failed, using keyword ranking only");
+                    Vec::new()
+                }
+            },
+            None => Vec::new(),
+        };
+
+        let mut merged: HashMap<(String, String), MemoryHit> = HashMap::new();
+        for (rank, r) in keyword.into_iter().enumerate() {
+            let key = (queries::EMBEDDING_SOURCE_SESSION.to_string(), r.id.to_string());
+            merged.insert(
+                key,
+                MemoryHit {
+                    source: queries::EMBEDDING_SOURCE_SESSION.to_string(),
+                    id: r.id.to_string(),
+                    session_id: Some(r.session_id),
+                    log_type: Some(r.log_type),
+                    category: None,
+                    content: r.content,
+                    created_at: r.created_at,
+                    score: config.keyword_weight / (config.rrf_k + rank as f64 + 1.0),
+                    keyword_score: Some(r.score),
+                    vector_score: None,
+                    retention_score: None,
+                },
+            );
+        }
+        for (rank, (row, similarity)) in vector.into_iter().enumerate() {
+            let fused = config.vector_weight / (config.rrf_k + rank as f64 + 1.0);
+            let hit = merged
+                .entry((row.source.clone(), row.source_id.clone()))
+                .or_insert_with(|| MemoryHit {
+                    source: row.source,
+                    id: row.source_id,
+                    session_id: row.session_id,
+                    log_type: row.log_type,
+                    category: row.category,
+                    content: row.content,
+                    created_at: row.created_at,
+                    score: 0.0,
+                    keyword_score: None,
+                    vector_score: None,
+                    retention_score: None,
+                });
+            hit.score += fused;
+            hit.vector_score = Some(similarity as f64);
+        }
+
+        let mut hits: Vec<MemoryHit> = merged.into_values().collect();
+        self.apply_retention(&mut hits)?;
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| b.created_at.cmp(&a.created_at))
+        });
+        hits.truncate(limit);
+        Ok(hits)
+    }
+
+    /// Scale fused scores by the retention score so that important, recently
+    /// used memories win over stale ones of similar relevance.
+    fn apply_retention(&self, hits: &mut [MemoryHit]) -> Result<()> {
+        let weight = self.policy.retention_weight.clamp(0.0, 1.0);
+        let now = Utc::now();
+        let conn = self.conn.lock().unwrap();
+        for source in [queries::EMBEDDING_SOURCE_SESSION, queries::EMBEDDING_SOURCE_CURATED] {
+            let ids: Vec<String> = hits.iter().filter(|h| h.source == source).map(|h| h.id.clone()).collect();
+            let stats = queries::get_memory_stats(&conn, source, &ids)?;
+            for hit in hits.iter_mut().filter(|h| h.source == source) {
+                if let Some(stat) = stats.get(&hit.id) {
+                    let retention = self.policy.score(stat, now);
+                    hit.retention_score = Some(retention);
+                    hit.score *= (1.0 - weight) + weight * retention.min(1.0);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Rank embedded memories by cosine similarity to the query.
+    async fn vector_search(
+        &self,
+        query: &str,
+        limit: usize,
+        embedder: &dyn Embedder,
+        config: &HybridSearchConfig,
+    ) -> Result<Vec<(queries::EmbeddedMemoryRow, f32)>> {
+        let query_vector = embedder
+            .embed(&[query.to_string()])
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Embedder returned no vector for the query"))?;
+
+        let rows = {
+            let conn = self.conn.lock().unwrap();
+            queries::list_embedded_memories(&conn, &self.agent_id, embedder.model())?
+        };
+        let mut scored: Vec<(queries::EmbeddedMemoryRow, f32)> = rows
+            .into_iter()
+            .map(|row| {
+                let similarity = cosine_similarity(&query_vector, &row.vector);
+                (row, similarity)
+            })
+            .filter(|(_, similarity)| *similarity >= config.min_similarity)
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.truncate(limit);
+        Ok(scored)
+    }
+
     /// Build a context string summarizing the agent's curated memories for LLM prompts.
+    ///
+    /// Memories are grouped by category and ranked by retention score; only
+    /// the top entries of each category are included.
     pub fn build_context(&self) -> Result<String> {
         let memories = self.get_curated(None)?;
         if memories.is_empty() {
             return Ok(String::new());
         }
 
-        let mut ctx = String::from("## Curated Memories\n\n");
-        let mut current_category = String::new();
-
+        let mut by_category: BTreeMap<&str, Vec<&CuratedMemory>> = BTreeMap::new();
         for mem in &memories {
-            if mem.category != current_category {
-                ctx.push_str(&format!("### {}\n", mem.category));
-                current_category = mem.category.clone();
+            by_category.entry(mem.category.as_str()).or_default().push(mem);
+        }
+
+        let mut ctx = String::from("## Curated Memories\n\n");
+        for (category, entries) in by_category {
+            ctx.push_str(&format!("### {}\n", category));
+            for mem in entries.into_iter().take(MAX_CONTEXT_PER_CATEGORY) {
+                ctx.push_str(&format!("- {}\n", mem.content));
             }
-            ctx.push_str(&format!("- {}\n", mem.content));
         }
 
         Ok(ctx)
@@ -137,6 +434,99 @@ pub struct CuratedMemory {
     pub id: String,
     pub category: String,
     pub content: String,
+    /// Importance set when the memory was written (0.0-1.0).
+    pub importance: f64,
+    /// How often the memory was used.
+    pub access_count: i64,
+    /// Retention score at read time (see [`MemoryPolicy::score`]).
+    pub score: f64,
+}
+
+/// Ranking and retention rules for memories.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryPolicy {
+    /// Days without use after which a memory's weight halves.
+    pub half_life_days: f64,
+    /// Weight added per doubling of the access count.
+    pub access_boost: f64,
+    /// Share of the search score decided by retention (0.0 = relevance only).
+    pub retention_weight: f64,
+    /// Archive session logs older than this many days (None = never archive).
+    pub archive_after_days: Option<u32>,
+    /// Only logs whose retention score is below this are archived.
+    pub archive_below: f64,
+    /// Delete archived logs this many days after archiving (None = keep them).
+    pub delete_after_days: Option<u32>,
+    /// Log types that are never archived.
+    pub keep_log_types: Vec<String>,
+    /// Fusion weights for hybrid search.
+    pub search: HybridSearchConfig,
+}
+
+impl Default for MemoryPolicy {
+    fn default() -> Self {
+        Self {
+            half_life_days: 30.0,
+            access_boost: 0.2,
+            retention_weight: 0.3,
+            archive_after_days: Some(90),
+            archive_below: 0.2,
+            delete_after_days: None,
+            keep_log_types: vec!["summary".to_string()],
+            search: HybridSearchConfig::default(),
+        }
+    }
+}
+
+impl MemoryPolicy {
+    /// Retention score: importance, halved every `half_life_days` since the
+    /// memory was last used (or written), and boosted by how often it was used.
+    pub fn score(&self, stats: &queries::MemoryStatsRow, now: DateTime<Utc>) -> f64 {
+        let last_used = stats
+            .last_accessed_at
+            .as_deref()
+            .unwrap_or(&stats.created_at);
+        let age_days = DateTime::parse_from_rfc3339(last_used)
+            .map(|t| (now - t.with_timezone(&Utc)).num_seconds().max(0) as f64 / 86_400.0)
+            .unwrap_or(0.0);
+        let decay = if self.half_life_days > 0.0 {
+            0.5f64.powf(age_days / self.half_life_days)
+        } else {
+            1.0
+        };
+        let boost = 1.0 + self.access_boost * (1.0 + stats.access_count.max(0) as f64).log2();
+        stats.importance * decay * boost
+    }
+}
+
+/// What a pruning pass did.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PruneReport {
+    /// Session logs archived (removed from search, kept in history).
+    pub archived: usize,
+    /// Archived session logs deleted.
+    pub deleted: usize,
+}
+
+impl PruneReport {
+    pub fn is_empty(&self) -> bool {
+        self.archived == 0 && self.deleted == 0
+    }
+}
+
+/// What an agent thinks of another participant.
+#[derive(Debug, Clone)]
+pub struct Impression {
+    pub id: String,
+    /// Session in which the impression was last updated.
+    pub session_id: String,
+    pub target_id: String,
+    pub target_name: String,
+    pub personality: String,
+    pub communication_style: String,
+    pub recent_behavior: String,
+    pub agreement: String,
+    pub notes: String,
 }
 
 /// A search result from session log full-text search.
@@ -150,9 +540,59 @@ pub struct SearchResult {
     pub score: f64,
 }
 
+/// Weights for [`MemoryManager::hybrid_search`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HybridSearchConfig {
+    /// Weight of the BM25 ranking in the fused score.
+    pub keyword_weight: f64,
+    /// Weight of the vector ranking in the fused score.
+    pub vector_weight: f64,
+    /// Reciprocal rank fusion constant; larger values flatten rank differences.
+    pub rrf_k: f64,
+    /// Vector hits below this cosine similarity are ignored.
+    pub min_similarity: f32,
+    /// Match logs containing any query term instead of all of them.
+    pub match_any: bool,
+}
+
+impl Default for HybridSearchConfig {
+    fn default() -> Self {
+        Self {
+            keyword_weight: 1.0,
+            vector_weight: 1.0,
+            rrf_k: 60.0,
+            min_similarity: 0.2,
+            match_any: false,
+        }
+    }
+}
+
+/// A result from hybrid memory search.
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryHit {
+    /// `"session"` (session log) or `"curated"` (curated memory).
+    pub source: String,
+    /// Session log ID or curated memory ID.
+    pub id: String,
+    pub session_id: Option<String>,
+    pub log_type: Option<String>,
+    pub category: Option<String>,
+    pub content: String,
+    pub created_at: String,
+    /// Fused score (higher is better).
+    pub score: f64,
+    /// Raw BM25 score if the memory matched the keywords (lower is better).
+    pub keyword_score: Option<f64>,
+    /// Cosine similarity if the memory matched by vector.
+    pub vector_score: Option<f64>,
+    /// Retention score the fused score was weighted with.
+    pub retention_score: Option<f64>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::embedding::HashEmbedder;
 
     fn test_mm() -> MemoryManager {
         let conn = opencrab_db::init_memory().unwrap();
@@ -196,4 +636,142 @@ mod tests {
         assert!(ctx.contains("Curated Memories"));
         assert!(ctx.contains("Water is wet"));
     }
+
+    #[tokio::test]
+    async fn test_hybrid_search_without_embedder_uses_keywords() {
+        let mm = test_mm();
+        mm.append_session_log("s1", "speech", "Rust is great", None, None, None).unwrap();
+        mm.append_session_log("s1", "speech", "Python is fine", None, None, None).unwrap();
+
+        let hits = mm
+            .hybrid_search("Rust", 10, None, &HybridSearchConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].source, "session");
+        assert!(hits[0].keyword_score.is_some());
+        assert!(hits[0].vector_score.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_search_finds_japanese_and_curated() {
+        let mm = test_mm();
+        mm.append_session_log("s1", "speech", "私は猫が大好きです", None, None, None).unwrap();
+        mm.append_session_log("s1", "speech", "明日は雨の予報です", None, None, None).unwrap();
+        mm.save_curated("m1", "preferences", "猫の写真を集めている").unwrap();
+        let embedder = HashEmbedder::default();
+
+        // FTS5 tokenises the whole Japanese sentence as one token, so keywords miss it.
+        assert!(mm.search("猫", 10).unwrap().is_empty());
+
+        // Searching does not embed stored memories; only indexed ones are found by vector.
+        let hits = mm
+            .hybrid_search("猫", 10, Some(&embedder), &HybridSearchConfig::default())
+            .await
+            .unwrap();
+        assert!(hits.is_empty(), "{hits:?}");
+        assert_eq!(mm.index_embeddings(&embedder, 100).await.unwrap(), 3);
+
+        let hits = mm
+            .hybrid_search("猫", 10, Some(&embedder), &HybridSearchConfig::default())
+            .await
+            .unwrap();
+        let contents: Vec<&str> = hits.iter().map(|h| h.content.as_str()).collect();
+        assert!(contents.contains(&"私は猫が大好きです"), "{contents:?}");
+        assert!(contents.contains(&"猫の写真を集めている"), "{contents:?}");
+        assert!(!contents.contains(&"明日は雨の予報です"), "{contents:?}");
+        assert!(hits.iter().any(|h| h.source == "curated" && h.category.as_deref() == Some("preferences")));
+
+        // Everything is embedded once; there is nothing left to index.
+        assert_eq!(mm.index_embeddings(&embedder, 100).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_search_boosts_keyword_and_vector_matches() {
+        let mm = test_mm();
+        mm.append_session_log("s1", "speech", "rust borrow checker", None, None, None).unwrap();
+        mm.append_session_log("s1", "speech", "the borrow checker rejects this", None, None, None).unwrap();
+        let embedder = HashEmbedder::default();
+        mm.index_embeddings(&embedder, 100).await.unwrap();
+
+        let hits = mm
+            .hybrid_search("rust borrow checker", 10, Some(&embedder), &HybridSearchConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(hits[0].content, "rust borrow checker");
+        assert!(hits[0].keyword_score.is_some() && hits[0].vector_score.is_some());
+        assert!(hits[0].score > hits[1].score);
+    }
+
+    fn stats(importance: f64, access_count: i64, last_used: &str) -> queries::MemoryStatsRow {
+        queries::MemoryStatsRow {
+            source: "session".to_string(),
+            id: "1".to_string(),
+            log_type: Some("speech".to_string()),
+            importance,
+            access_count,
+            last_accessed_at: None,
+            created_at: last_used.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_policy_score_decays_and_boosts() {
+        let policy = MemoryPolicy::default();
+        let now = DateTime::parse_from_rfc3339("2026-03-31T00:00:00Z").unwrap().with_timezone(&Utc);
+
+        let fresh = policy.score(&stats(0.6, 0, "2026-03-31T00:00:00Z"), now);
+        let month_old = policy.score(&stats(0.6, 0, "2026-03-01T00:00:00Z"), now);
+        assert!((fresh - 0.6).abs() < 1e-9);
+        assert!((month_old - 0.3).abs() < 1e-9);
+
+        // Three accesses double the count twice: 1 + 0.2 * log2(4) = 1.4.
+        let used = policy.score(&stats(0.6, 3, "2026-03-31T00:00:00Z"), now);
+        assert!((used - 0.84).abs() < 1e-9);
+
+        // Access time wins over creation time.
+        let mut accessed = stats(0.6, 1, "2025-01-01T00:00:00Z");
+        accessed.last_accessed_at = Some("2026-03-31T00:00:00Z".to_string());
+        assert!(policy.score(&accessed, now) > fresh);
+    }
+
+    #[test]
+    fn test_curated_ranked_by_importance_and_access() {
+        let mm = test_mm();
+        mm.save_curated("m1", "facts", "Water is wet").unwrap();
+        mm.save_curated("m2", "decisions", "Ship on Friday").unwrap();
+        mm.save_curated("m3", "facts", "The sky is blue").unwrap();
+        mm.record_access("curated", &["m3".to_string()]).unwrap();
+
+        let memories = mm.get_curated(None).unwrap();
+        let ids: Vec<&str> = memories.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["m2", "m3", "m1"]);
+        assert_eq!(memories[1].access_count, 1);
+        assert!(memories[0].importance > memories[2].importance);
+    }
+
+    #[test]
+    fn test_prune_archives_low_value_logs() {
+        let policy = MemoryPolicy {
+            archive_after_days: Some(30),
+            archive_below: 0.2,
+            delete_after_days: Some(0),
+            ..Default::default()
+        };
+        let mm = test_mm().with_policy(policy);
+        mm.append_session_log("s1", "tool", "ran the tests", None, None, None).unwrap();
+        let speech = mm.append_session_log("s1", "speech", "Rust is great", None, None, None).unwrap();
+        let metadata = serde_json::json!({"covers_until": speech});
+        mm.append_session_log("s1", "summary", "We talked about Rust", None, None, Some(metadata)).unwrap();
+
+        // Nothing is old enough yet.
+        assert!(mm.prune(Utc::now()).unwrap().is_empty());
+
+        // After 60 days the tool log (0.2 -> 0.05) and the speech (0.5 -> 0.125)
+        // fall below the threshold; summaries are always kept. Both are covered
+        // by the summary, so they can be deleted.
+        let report = mm.prune(Utc::now() + chrono::Duration::days(60)).unwrap();
+        assert_eq!(report, PruneReport { archived: 2, deleted: 2 });
+        assert!(mm.search("Rust", 10).unwrap().iter().all(|r| r.log_type == "summary"));
+    }
 }
@@ -0,0 +1,288 @@
+//! Rendering a [`Soul`] into behavioural instructions and runtime parameters.
+//!
+//! The numeric traits of a soul are turned into short instructions for the
+//! system prompt (only traits that are clearly high or low are mentioned) and
+//! into sampling parameters: openness sets the temperature and extraversion
+//! sets how often the agent is picked to speak in autonomous sessions.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::soul::Soul;
+
+/// Keys of the trait phrases; each has a `_high` and a `_low` variant.
+pub const TRAIT_KEYS: &[&str] = &[
+    "openness",
+    "conscientiousness",
+    "extraversion",
+    "agreeableness",
+    "neuroticism",
+    "assertiveness",
+    "responsiveness",
+];
+
+/// Language of the built-in persona wording.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PersonaLanguage {
+    #[default]
+    #[serde(rename = "en")]
+    English,
+    #[serde(rename = "ja")]
+    Japanese,
+}
+
+const ENGLISH: &[(&str, &str)] = &[
+    ("heading", "## Persona: {name}"),
+    ("style", "Your social style is {style}."),
+    ("thinking", "Your thinking is mainly {primary}, backed by {secondary} reasoning."),
+    ("behaviour", "How you behave:"),
+    ("openness_high", "You are curious and imaginative: explore unconventional ideas and make unexpected connections."),
+    ("openness_low", "You are practical and conventional: prefer proven approaches and concrete facts over speculation."),
+    ("conscientiousness_high", "You are organised and thorough: structure your points and follow through on details."),
+    ("conscientiousness_low", "You are spontaneous and flexible: keep things loose and do not over-plan."),
+    ("extraversion_high", "You are outgoing and energetic: speak up often and drive the conversation."),
+    ("extraversion_low", "You are reserved: speak when you have something worth adding and keep it brief."),
+    ("agreeableness_high", "You are cooperative and warm: build on others' ideas and look for common ground."),
+    ("agreeableness_low", "You are critical and blunt: challenge weak arguments and say so when you disagree."),
+    ("neuroticism_high", "You are sensitive to risk: point out what could go wrong and voice your concerns."),
+    ("neuroticism_low", "You are calm and steady: stay composed and do not dwell on worst cases."),
+    ("assertiveness_high", "State your views directly and push for decisions."),
+    ("assertiveness_low", "Ask questions and invite others' views before stating your own."),
+    ("responsiveness_high", "Show your feelings and react warmly to what others say."),
+    ("responsiveness_low", "Keep an even, matter-of-fact tone."),
+];
+
+const JAPANESE: &[(&str, &str)] = &[
+    ("heading", "## ペルソナ: {name}"),
+    ("style", "あなたのソーシャルスタイルは「{style}」です。"),
+    ("thinking", "思考スタイルは主に「{primary}」、補助的に「{secondary}」です。"),
+    ("behaviour", "振る舞い:"),
+    ("openness_high", "好奇心が強く想像力豊か。型にはまらないアイデアを探り、意外な結びつきを見つける。"),
+    ("openness_low", "現実的で保守的。推測より実績のあるやり方と具体的な事実を重んじる。"),
+    ("conscientiousness_high", "几帳面で徹底している。論点を整理し、細部まで詰める。"),
+    ("conscientiousness_low", "臨機応変で柔軟。計画しすぎず、流れに任せる。"),
+    ("extraversion_high", "社交的でエネルギッシュ。積極的に発言し、会話を引っ張る。"),
+    ("extraversion_low", "控えめ。付け加える価値があるときに、簡潔に話す。"),
+    ("agreeableness_high", "協調的で温かい。他の人のアイデアに乗り、共通点を探す。"),
+    ("agreeableness_low", "批判的で率直。弱い論拠には異を唱え、反対ならそう言う。"),
+    ("neuroticism_high", "リスクに敏感。うまくいかない可能性を指摘し、懸念を口にする。"),
+    ("neuroticism_low", "落ち着いていて動じない。最悪の事態にとらわれない。"),
+    ("assertiveness_high", "意見をはっきり述べ、決定を促す。"),
+    ("assertiveness_low", "自分の意見より先に質問し、他の人の考えを聞く。"),
+    ("responsiveness_high", "感情を表に出し、相手の発言に温かく反応する。"),
+    ("responsiveness_low", "淡々とした事実ベースの口調を保つ。"),
+];
+
+/// Settings for [`PersonaRenderer`] and [`PersonaOptions::behavior`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersonaOptions {
+    pub language: PersonaLanguage,
+    /// Phrases replacing the built-in wording, keyed like `openness_high`,
+    /// `heading` (`{name}`), `style` (`{style}`), `thinking` (`{primary}`,
+    /// `{secondary}`) or `behaviour`. An empty phrase hides that line.
+    pub wording: HashMap<String, String>,
+    /// Traits at or below this value use their `_low` phrase.
+    pub low_threshold: f32,
+    /// Traits at or above this value use their `_high` phrase.
+    pub high_threshold: f32,
+    /// Temperature for openness 0.0 and 1.0 (linear in between).
+    pub temperature_range: (f32, f32),
+    /// Speaking weight for extraversion 0.0 and 1.0 (linear in between).
+    pub speak_weight_range: (f32, f32),
+}
+
+impl Default for PersonaOptions {
+    fn default() -> Self {
+        Self {
+            language: PersonaLanguage::default(),
+            wording: HashMap::new(),
+            low_threshold: 0.35,
+            high_threshold: 0.65,
+            temperature_range: (0.4, 1.0),
+            speak_weight_range: (0.5, 1.5),
+        }
+    }
+}
+
+/// Runtime parameters derived from a soul.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct BehaviorParams {
+    /// Sampling temperature for the agent's LLM calls.
+    pub temperature: f32,
+    /// Relative share of turns in autonomous sessions (1.0 = average).
+    pub speak_weight: f32,
+}
+
+impl PersonaOptions {
+    /// Map openness to temperature and extraversion to speaking weight.
+    pub fn behavior(&self, soul: &Soul) -> BehaviorParams {
+        BehaviorParams {
+            temperature: lerp(self.temperature_range, soul.personality.openness),
+            speak_weight: lerp(self.speak_weight_range, soul.personality.extraversion),
+        }
+    }
+}
+
+fn lerp((low, high): (f32, f32), value: f32) -> f32 {
+    low + (high - low) * value.clamp(0.0, 1.0)
+}
+
+/// Turns a soul's traits into a persona section for the system prompt.
+#[derive(Debug, Clone)]
+pub struct PersonaRenderer {
+    phrases: HashMap<String, String>,
+    low_threshold: f32,
+    high_threshold: f32,
+}
+
+impl PersonaRenderer {
+    pub fn new(options: &PersonaOptions) -> Self {
+        let builtin = match options.language {
+            PersonaLanguage::English => ENGLISH,
+            PersonaLanguage::Japanese => JAPANESE,
+        };
+        let mut phrases: HashMap<String, String> = builtin
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        phrases.extend(options.wording.clone());
+        Self {
+            phrases,
+            low_threshold: options.low_threshold,
+            high_threshold: options.high_threshold,
+        }
+    }
+
+    fn phrase(&self, key: &str) -> &str {
+        self.phrases.get(key).map(String::as_str).unwrap_or_default()
+    }
+
+    /// Behavioural instructions for the traits that are clearly high or low.
+    pub fn instructions(&self, soul: &Soul) -> Vec<String> {
+        let p = &soul.personality;
+        let s = &soul.social_style;
+        let values = [
+            p.openness,
+            p.conscientiousness,
+            p.extraversion,
+            p.agreeableness,
+            p.neuroticism,
+            s.assertiveness,
+            s.responsiveness,
+        ];
+        TRAIT_KEYS
+            .iter()
+            .zip(values)
+            .filter_map(|(key, value)| {
+                let level = if value >= self.high_threshold {
+                    "high"
+                } else if value <= self.low_threshold {
+                    "low"
+                } else {
+                    return None;
+                };
+                let phrase = self.phrase(&format!("{key}_{level}"));
+                (!phrase.is_empty()).then(|| phrase.to_string())
+            })
+            .collect()
+    }
+
+    /// Markdown persona section: heading, social and thinking style, then
+    /// one bullet per pronounced trait.
+    pub fn render(&self, soul: &Soul) -> String {
+        let mut lines = Vec::new();
+        let heading = self.phrase("heading").replace("{name}", &soul.persona_name);
+        if !soul.persona_name.is_empty() && !heading.is_empty() {
+            lines.push(heading);
+        }
+
+        let mut summary = Vec::new();
+        if !soul.social_style.style_name.is_empty() {
+            summary.push(self.phrase("style").replace("{style}", &soul.social_style.style_name));
+        }
+        let thinking = &soul.thinking_style;
+        if !thinking.primary.is_empty() {
+            summary.push(
+                self.phrase("thinking")
+                    .replace("{primary}", &thinking.primary)
+                    .replace("{secondary}", &thinking.secondary),
+            );
+        }
+        if !thinking.description.is_empty() {
+            summary.push(thinking.description.clone());
+        }
+        summary.retain(|s| !s.is_empty());
+        if !summary.is_empty() {
+            lines.push(summary.join(" "));
+        }
+
+        let instructions = self.instructions(soul);
+        if !instructions.is_empty() {
+            if !self.phrase("behaviour").is_empty() {
+                lines.push(self.phrase("behaviour").to_string());
+            }
+            lines.extend(instructions.into_iter().map(|i| format!("- {i}")));
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn soul() -> Soul {
+        let mut soul = Soul::new("Sage");
+        soul.personality.openness = 0.9;
+        soul.personality.extraversion = 0.1;
+        soul.social_style.assertiveness = 0.8;
+        soul.social_style.style_name = "Driver".to_string();
+        soul
+    }
+
+    #[test]
+    fn test_render_mentions_only_pronounced_traits() {
+        let text = PersonaRenderer::new(&PersonaOptions::default()).render(&soul());
+        assert!(text.starts_with("## Persona: Sage"));
+        assert!(text.contains("Your social style is Driver."));
+        assert!(text.contains("Your thinking is mainly Analytical, backed by Practical reasoning."));
+        assert!(text.contains("- You are curious and imaginative"));
+        assert!(text.contains("- You are reserved"));
+        assert!(text.contains("- State your views directly"));
+        assert!(!text.contains("organised"));
+        assert!(!text.contains("calm"));
+
+        let neutral = PersonaRenderer::new(&PersonaOptions::default()).render(&Soul::new("Plain"));
+        assert!(!neutral.contains("How you behave"));
+    }
+
+    #[test]
+    fn test_language_and_wording_overrides() {
+        let options = PersonaOptions {
+            language: PersonaLanguage::Japanese,
+            wording: HashMap::from([
+                ("openness_high".to_string(), "新しいものが好き。".to_string()),
+                ("extraversion_low".to_string(), String::new()),
+            ]),
+            ..Default::default()
+        };
+        let text = PersonaRenderer::new(&options).render(&soul());
+        assert!(text.starts_with("## ペルソナ: Sage"));
+        assert!(text.contains("- 新しいものが好き。"));
+        assert!(text.contains("- 意見をはっきり述べ"));
+        assert!(!text.contains("控えめ"));
+    }
+
+    #[test]
+    fn test_behavior_maps_openness_and_extraversion() {
+        let options = PersonaOptions::default();
+        let neutral = options.behavior(&Soul::new("x"));
+        assert!((neutral.temperature - 0.7).abs() < 1e-6);
+        assert!((neutral.speak_weight - 1.0).abs() < 1e-6);
+
+        let params = options.behavior(&soul());
+        assert!((params.temperature - 0.94).abs() < 1e-6);
+        assert!((params.speak_weight - 0.6).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,225 @@
+//! System prompt templates.
+//!
+//! Templates use a small mustache-like syntax:
+//!
+//! - `{{name}}` inserts a variable (unknown or unset variables render empty).
+//! - `{{#name}}...{{/name}}` renders its body only when `name` is non-empty.
+//! - `{{^name}}...{{/name}}` renders its body only when `name` is empty.
+//!
+//! Templates are parsed up front so that syntax errors and unknown variables
+//! are reported when a template is saved, not when an agent next speaks.
+
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{bail, Result};
+
+/// Variables available to system prompt templates, with descriptions.
+pub const PROMPT_VARIABLES: &[(&str, &str)] = &[
+    ("agent_id", "Agent ID"),
+    ("agent_name", "Identity name (falls back to the agent ID)"),
+    ("role", "Identity role"),
+    ("job_title", "Identity job title"),
+    ("organization", "Identity organization"),
+    ("persona_name", "Soul persona name"),
+    (
+        "persona",
+        "Soul traits rendered as behavioural instructions",
+    ),
+    ("custom_traits", "Soul custom traits (raw JSON)"),
+    ("skills", "Active skills as a bullet list"),
+    ("skill_names", "Comma-separated active skill names"),
+    (
+        "memories",
+        "Memories, impressions and related history within the context budget",
+    ),
+    ("session_id", "Current session ID"),
+    ("session_theme", "Current discussion topic"),
+    (
+        "gateway",
+        "Channel the agent is speaking through (rest, discord, session, heartbeat)",
+    ),
+    ("now", "Local date and time"),
+    ("date", "Local date"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Text(String),
+    Var(String),
+    Section {
+        name: String,
+        inverted: bool,
+        body: Vec<Node>,
+    },
+}
+
+/// A parsed system prompt template.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptTemplate {
+    nodes: Vec<Node>,
+}
+
+impl PromptTemplate {
+    /// Parse a template, failing on unbalanced or malformed tags.
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut stack: Vec<(String, bool, Vec<Node>)> = Vec::new();
+        let mut nodes: Vec<Node> = Vec::new();
+        let mut rest = source;
+
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                nodes.push(Node::Text(rest[..start].to_string()));
+            }
+            let after = &rest[start + 2..];
+            let Some(end) = after.find("}}") else {
+                bail!("Unclosed tag: '{{{{' without '}}}}'");
+            };
+            let tag = after[..end].trim();
+            rest = &after[end + 2..];
+
+            let (sigil, name) = match tag.chars().next() {
+                Some(c @ ('#' | '^' | '/')) => (Some(c), tag[1..].trim()),
+                _ => (None, tag),
+            };
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                bail!("Invalid tag: '{{{{{tag}}}}}'");
+            }
+
+            match sigil {
+                Some('#') | Some('^') => {
+                    stack.push((
+                        name.to_string(),
+                        sigil == Some('^'),
+                        std::mem::take(&mut nodes),
+                    ));
+                }
+                Some(_) => {
+                    let Some((open, inverted, parent)) = stack.pop() else {
+                        bail!("Unexpected closing tag: '{{{{/{name}}}}}'");
+                    };
+                    if open != name {
+                        bail!("Section '{open}' closed by '{{{{/{name}}}}}'");
+                    }
+                    let body = std::mem::replace(&mut nodes, parent);
+                    nodes.push(Node::Section {
+                        name: open,
+                        inverted,
+                        body,
+                    });
+                }
+                None => nodes.push(Node::Var(name.to_string())),
+            }
+        }
+        if !rest.is_empty() {
+            nodes.push(Node::Text(rest.to_string()));
+        }
+        if let Some((open, _, _)) = stack.last() {
+            bail!("Section '{open}' is not closed");
+        }
+        Ok(Self { nodes })
+    }
+
+    /// Names of all variables and sections used by the template.
+    pub fn variables(&self) -> BTreeSet<String> {
+        fn collect(nodes: &[Node], out: &mut BTreeSet<String>) {
+            for node in nodes {
+                match node {
+                    Node::Text(_) => {}
+                    Node::Var(name) => {
+                        out.insert(name.clone());
+                    }
+                    Node::Section { name, body, .. } => {
+                        out.insert(name.clone());
+                        collect(body, out);
+                    }
+                }
+            }
+        }
+        let mut out = BTreeSet::new();
+        collect(&self.nodes, &mut out);
+        out
+    }
+
+    /// Whether the template refers to `name`.
+    pub fn uses(&self, name: &str) -> bool {
+        self.variables().contains(name)
+    }
+
+    /// Variables used by the template that are not in [`PROMPT_VARIABLES`].
+    pub fn unknown_variables(&self) -> Vec<String> {
+        self.variables()
+            .into_iter()
+            .filter(|v| !PROMPT_VARIABLES.iter().any(|(name, _)| name == v))
+            .collect()
+    }
+
+    /// Render with the given variables.
+    pub fn render(&self, vars: &HashMap<String, String>) -> String {
+        fn walk(nodes: &[Node], vars: &HashMap<String, String>, out: &mut String) {
+            for node in nodes {
+                match node {
+                    Node::Text(text) => out.push_str(text),
+                    Node::Var(name) => {
+                        out.push_str(vars.get(name).map(String::as_str).unwrap_or(""))
+                    }
+                    Node::Section {
+                        name,
+                        inverted,
+                        body,
+                    } => {
+                        let present = vars.get(name).is_some_and(|v| !v.trim().is_empty());
+                        if present != *inverted {
+                            walk(body, vars, out);
+                        }
+                    }
+                }
+            }
+        }
+        let mut out = String::new();
+        walk(&self.nodes, vars, &mut out);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_render_variables_and_sections() {
+        let template = PromptTemplate::parse(
+            "あなたは{{ agent_name }}です。{{#skills}}\nスキル:\n{{skills}}{{/skills}}{{^memories}}\n(記憶なし){{/memories}}",
+        )
+        .unwrap();
+        assert_eq!(
+            template.render(&vars(&[("agent_name", "Alice"), ("skills", "- search")])),
+            "あなたはAliceです。\nスキル:\n- search\n(記憶なし)"
+        );
+        assert_eq!(
+            template.render(&vars(&[("agent_name", "Bob"), ("memories", "- tea")])),
+            "あなたはBobです。"
+        );
+        assert!(template.uses("memories"));
+        assert!(!template.uses("gateway"));
+        assert!(template.unknown_variables().is_empty());
+    }
+
+    #[test]
+    fn test_parse_errors_and_unknown_variables() {
+        assert!(PromptTemplate::parse("{{#skills}}open").is_err());
+        assert!(PromptTemplate::parse("{{/skills}}").is_err());
+        assert!(PromptTemplate::parse("{{#a}}{{/b}}").is_err());
+        assert!(PromptTemplate::parse("{{agent name}}").is_err());
+        assert!(PromptTemplate::parse("{{agent_name").is_err());
+
+        let template = PromptTemplate::parse("{{agent_name}} {{mood}}").unwrap();
+        assert_eq!(template.unknown_variables(), vec!["mood".to_string()]);
+    }
+}
@@ -0,0 +1,302 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Discussion phase of a facilitated session.
+///
+/// Phases only move forward: divergent (collect ideas) → convergent
+/// (narrow down and compare) → closing (summarise and wrap up).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionPhase {
+    Divergent,
+    Convergent,
+    Closing,
+}
+
+impl fmt::Display for SessionPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl SessionPhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SessionPhase::Divergent => "divergent",
+            SessionPhase::Convergent => "convergent",
+            SessionPhase::Closing => "closing",
+        }
+    }
+
+    /// Parse a phase name. Unknown values yield `None`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "divergent" => Some(SessionPhase::Divergent),
+            "convergent" => Some(SessionPhase::Convergent),
+            "closing" => Some(SessionPhase::Closing),
+            _ => None,
+        }
+    }
+
+    /// Short guidance shown to speakers for this phase.
+    pub fn guidance(&self) -> &'static str {
+        match self {
+            SessionPhase::Divergent => {
+                "Divergent phase: bring up new ideas and perspectives; do not judge yet."
+            }
+            SessionPhase::Convergent => {
+                "Convergent phase: compare the ideas raised so far and narrow them down."
+            }
+            SessionPhase::Closing => {
+                "Closing phase: summarise the conclusions. Call declare_done when you have nothing to add."
+            }
+        }
+    }
+
+    /// The minimum phase implied by session progress.
+    ///
+    /// With a turn limit, the first 40% of turns are divergent, up to 80% are
+    /// convergent and the rest is closing. Independently of the limit, once
+    /// half of the participants have declared done the discussion is at least
+    /// convergent.
+    pub fn scheduled(progress: &SessionProgress) -> Self {
+        let by_turns = match progress.max_turns {
+            Some(max) if max > 0 => {
+                let ratio = progress.turn_number as f64 / max as f64;
+                if ratio >= 0.8 {
+                    SessionPhase::Closing
+                } else if ratio >= 0.4 {
+                    SessionPhase::Convergent
+                } else {
+                    SessionPhase::Divergent
+                }
+            }
+            _ => SessionPhase::Divergent,
+        };
+        let by_done = if progress.participants > 0 && progress.done * 2 >= progress.participants {
+            SessionPhase::Convergent
+        } else {
+            SessionPhase::Divergent
+        };
+        by_turns.max(by_done)
+    }
+
+    /// Combine the current phase, the facilitator's proposal and the schedule.
+    /// The result never moves backwards.
+    pub fn resolve(current: Self, proposed: Option<Self>, progress: &SessionProgress) -> Self {
+        current
+            .max(proposed.unwrap_or(current))
+            .max(Self::scheduled(progress))
+    }
+}
+
+/// Counters used to decide phase transitions and session end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionProgress {
+    /// Turns completed so far.
+    pub turn_number: i32,
+    /// Turn limit, if any.
+    pub max_turns: Option<i32>,
+    /// Number of speaking participants (the facilitator is not counted).
+    pub participants: usize,
+    /// Number of speaking participants who declared done.
+    pub done: usize,
+}
+
+/// Why a session ended.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionEndReason {
+    /// `max_turns` was reached.
+    MaxTurns,
+    /// Every participant declared done.
+    AllDone,
+}
+
+impl SessionProgress {
+    /// Check whether the session should end.
+    pub fn end_reason(&self) -> Option<SessionEndReason> {
+        if self.max_turns.is_some_and(|max| self.turn_number >= max) {
+            return Some(SessionEndReason::MaxTurns);
+        }
+        if self.participants > 0 && self.done >= self.participants {
+            return Some(SessionEndReason::AllDone);
+        }
+        None
+    }
+}
+
+/// The facilitator's decision for the next turn.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FacilitatorDecision {
+    /// Agent ID or name of the next speaker, as written by the facilitator.
+    pub next_speaker: Option<String>,
+    /// Phase the facilitator wants to move to.
+    pub phase: Option<SessionPhase>,
+    /// Free-text reason.
+    pub reason: Option<String>,
+}
+
+impl FacilitatorDecision {
+    /// Parse the facilitator's reply.
+    ///
+    /// The first JSON object in the text is used; anything unparseable yields
+    /// an empty decision so that the caller can fall back to round-robin.
+    pub fn parse(raw: &str) -> Self {
+        let json = raw
+            .find('{')
+            .zip(raw.rfind('}'))
+            .filter(|(start, end)| start < end)
+            .and_then(|(start, end)| {
+                serde_json::from_str::<serde_json::Value>(&raw[start..=end]).ok()
+            });
+
+        let Some(v) = json else {
+            return Self::default();
+        };
+        Self {
+            next_speaker: v["next_speaker"]
+                .as_str()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(String::from),
+            phase: v["phase"].as_str().and_then(SessionPhase::parse),
+            reason: v["reason"].as_str().map(String::from),
+        }
+    }
+
+    /// Resolve `next_speaker` against `(agent_id, name)` candidates.
+    ///
+    /// Matches the ID exactly or the name case-insensitively.
+    pub fn speaker_in(&self, candidates: &[(String, String)]) -> Option<String> {
+        let wanted = self.next_speaker.as_deref()?;
+        candidates
+            .iter()
+            .find(|(id, name)| id == wanted || name.eq_ignore_ascii_case(wanted))
+            .map(|(id, _)| id.clone())
+    }
+}
+
+/// Pick the candidate with the largest unused share of turns.
+///
+/// Each candidate has a speaking weight (see
+/// [`crate::persona::BehaviorParams::speak_weight`]); over time a candidate
+/// speaks in proportion to its weight. Ties go to the candidate after
+/// `last_speaker` in order, so equal weights behave like round-robin.
+pub fn next_speaker_weighted(
+    candidates: &[(String, f32)],
+    spoken: &HashMap<String, usize>,
+    last_speaker: Option<&str>,
+) -> Option<String> {
+    if candidates.is_empty() {
+        return None;
+    }
+    let start = last_speaker
+        .and_then(|last| candidates.iter().position(|(id, _)| id == last))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let score = |(id, weight): &(String, f32)| {
+        (spoken.get(id).copied().unwrap_or(0) + 1) as f32 / weight.max(0.01)
+    };
+    (0..candidates.len())
+        .map(|offset| &candidates[(start + offset) % candidates.len()])
+        .fold(None::<&(String, f32)>, |best, c| match best {
+            Some(b) if score(b) <= score(c) => Some(b),
+            _ => Some(c),
+        })
+        .map(|(id, _)| id.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(turn_number: i32, max_turns: Option<i32>, participants: usize, done: usize) -> SessionProgress {
+        SessionProgress {
+            turn_number,
+            max_turns,
+            participants,
+            done,
+        }
+    }
+
+    #[test]
+    fn test_phase_schedule() {
+        assert_eq!(SessionPhase::scheduled(&progress(0, Some(10), 2, 0)), SessionPhase::Divergent);
+        assert_eq!(SessionPhase::scheduled(&progress(4, Some(10), 2, 0)), SessionPhase::Convergent);
+        assert_eq!(SessionPhase::scheduled(&progress(8, Some(10), 2, 0)), SessionPhase::Closing);
+        assert_eq!(SessionPhase::scheduled(&progress(1, None, 4, 2)), SessionPhase::Convergent);
+        assert_eq!(SessionPhase::scheduled(&progress(100, None, 4, 1)), SessionPhase::Divergent);
+    }
+
+    #[test]
+    fn test_phase_never_moves_backwards() {
+        let p = progress(0, Some(10), 2, 0);
+        assert_eq!(
+            SessionPhase::resolve(SessionPhase::Convergent, Some(SessionPhase::Divergent), &p),
+            SessionPhase::Convergent
+        );
+        assert_eq!(
+            SessionPhase::resolve(SessionPhase::Divergent, Some(SessionPhase::Closing), &p),
+            SessionPhase::Closing
+        );
+        assert_eq!(SessionPhase::resolve(SessionPhase::Divergent, None, &p), SessionPhase::Divergent);
+    }
+
+    #[test]
+    fn test_end_reason() {
+        assert_eq!(progress(10, Some(10), 2, 0).end_reason(), Some(SessionEndReason::MaxTurns));
+        assert_eq!(progress(3, Some(10), 2, 2).end_reason(), Some(SessionEndReason::AllDone));
+        assert_eq!(progress(3, None, 2, 1).end_reason(), None);
+        assert_eq!(progress(3, None, 0, 0).end_reason(), None);
+    }
+
+    #[test]
+    fn test_parse_facilitator_decision() {
+        let d = FacilitatorDecision::parse(
+            "Sure: {\"next_speaker\": \"Bob\", \"phase\": \"Convergent\", \"reason\": \"enough ideas\"}",
+        );
+        assert_eq!(d.next_speaker.as_deref(), Some("Bob"));
+        assert_eq!(d.phase, Some(SessionPhase::Convergent));
+        assert_eq!(d.reason.as_deref(), Some("enough ideas"));
+
+        let candidates = vec![
+            ("a-1".to_string(), "Alice".to_string()),
+            ("b-2".to_string(), "Bob".to_string()),
+        ];
+        assert_eq!(d.speaker_in(&candidates).as_deref(), Some("b-2"));
+
+        let unknown = FacilitatorDecision::parse("{\"next_speaker\": \"Carol\"}");
+        assert_eq!(unknown.speaker_in(&candidates), None);
+        assert_eq!(FacilitatorDecision::parse("no idea"), FacilitatorDecision::default());
+    }
+
+    #[test]
+    fn test_next_speaker_weighted() {
+        let equal: Vec<(String, f32)> = ["a", "b", "c"].iter().map(|id| (id.to_string(), 1.0)).collect();
+        let mut spoken = HashMap::new();
+        let mut last: Option<String> = None;
+        let mut order = Vec::new();
+        for _ in 0..4 {
+            let next = next_speaker_weighted(&equal, &spoken, last.as_deref()).unwrap();
+            *spoken.entry(next.clone()).or_insert(0) += 1;
+            order.push(next.clone());
+            last = Some(next);
+        }
+        assert_eq!(order, vec!["a", "b", "c", "a"]);
+
+        // A talkative agent gets three turns for every one of a quiet agent.
+        let weighted = vec![("loud".to_string(), 1.5), ("quiet".to_string(), 0.5)];
+        let mut spoken = HashMap::new();
+        let mut last: Option<String> = None;
+        for _ in 0..8 {
+            let next = next_speaker_weighted(&weighted, &spoken, last.as_deref()).unwrap();
+            *spoken.entry(next.clone()).or_insert(0) += 1;
+            last = Some(next);
+        }
+        assert_eq!(spoken["loud"], 6);
+        assert_eq!(spoken["quiet"], 2);
+        assert_eq!(next_speaker_weighted(&[], &spoken, None), None);
+    }
+}
@@ -7,6 +7,8 @@ use uuid::Uuid;
 
 use opencrab_db::queries;
 
+use crate::skill_loader::{self, SkillFile, SkillSyncReport, SkillVersionChange};
+
 /// The origin of a skill.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -74,6 +76,23 @@ impl SkillManager {
         Ok(rows.into_iter().map(Self::row_to_skill).collect())
     }
 
+    /// Collect the action names declared by this agent's active standard skills.
+    ///
+    /// Only standard skills are considered: acquired skills keep a free-text
+    /// situation pattern in the column standard skills use for their action
+    /// list. The result is deduplicated and sorted.
+    pub fn declared_actions(&self) -> Result<Vec<String>> {
+        let mut actions: Vec<String> = self
+            .get_active_skills()?
+            .into_iter()
+            .filter(|s| matches!(s.source, SkillSource::Standard { .. }))
+            .flat_map(|s| s.actions)
+            .collect();
+        actions.sort();
+        actions.dedup();
+        Ok(actions)
+    }
+
     /// Acquire a new skill at runtime.
     pub fn acquire_skill(
         &self,
@@ -96,6 +115,7 @@ impl SkillManager {
             source_type: "acquired".to_string(),
             source_context: Some(source_context.to_string()),
             file_path: None,
+            version: None,
             effectiveness: None,
             usage_count: 0,
             is_active: true,
@@ -126,6 +146,81 @@ impl SkillManager {
         })
     }
 
+    /// Synchronise standard skill files from `dir` into this agent's skills.
+    ///
+    /// New files are inserted with `source_type = 'standard'`. Existing standard
+    /// skills are only rewritten when the file's version differs from the stored
+    /// one, so usage counts and activation state survive reloads.
+    pub fn sync_standard_skills(&self, dir: impl AsRef<std::path::Path>) -> Result<SkillSyncReport> {
+        let files = skill_loader::load_skill_dir(dir)?;
+        self.sync_skill_files(&files)
+    }
+
+    /// Upsert already-parsed skill files as standard skills.
+    pub fn sync_skill_files(&self, files: &[SkillFile]) -> Result<SkillSyncReport> {
+        let conn = self.conn.lock().unwrap();
+        let mut report = SkillSyncReport::default();
+
+        for file in files {
+            let actions_json = serde_json::to_string(&file.actions)?;
+            match queries::get_standard_skill(&conn, &self.agent_id, &file.name)? {
+                None => {
+                    let row = queries::SkillRow {
+                        id: Uuid::new_v4().to_string(),
+                        agent_id: self.agent_id.clone(),
+                        name: file.name.clone(),
+                        description: file.description.clone(),
+                        situation_pattern: actions_json,
+                        guidance: file.guidance.clone(),
+                        source_type: "standard".to_string(),
+                        source_context: None,
+                        file_path: Some(file.file_path.clone()),
+                        version: Some(file.version.clone()),
+                        effectiveness: None,
+                        usage_count: 0,
+                        is_active: true,
+                    };
+                    queries::insert_skill(&conn, &row)?;
+                    report.added.push(file.name.clone());
+                }
+                Some(existing) if existing.version.as_deref() != Some(file.version.as_str()) => {
+                    let row = queries::SkillRow {
+                        description: file.description.clone(),
+                        situation_pattern: actions_json,
+                        guidance: file.guidance.clone(),
+                        file_path: Some(file.file_path.clone()),
+                        version: Some(file.version.clone()),
+                        ..existing.clone()
+                    };
+                    queries::update_skill_definition(&conn, &row)?;
+                    tracing::info!(
+                        agent_id = %self.agent_id,
+                        skill_name = %file.name,
+                        from = ?existing.version,
+                        to = %file.version,
+                        "Standard skill version changed"
+                    );
+                    report.updated.push(SkillVersionChange {
+                        name: file.name.clone(),
+                        from: existing.version,
+                        to: file.version.clone(),
+                    });
+                }
+                Some(_) => report.unchanged.push(file.name.clone()),
+            }
+        }
+
+        tracing::debug!(
+            agent_id = %self.agent_id,
+            added = report.added.len(),
+            updated = report.updated.len(),
+            unchanged = report.unchanged.len(),
+            "Synchronised standard skills"
+        );
+
+        Ok(report)
+    }
+
     /// Increment the usage count for a skill.
     pub fn increment_usage(&self, skill_id: &str) -> Result<()> {
         let conn = self.conn.lock().unwrap();
@@ -191,7 +286,7 @@ impl SkillManager {
             id: row.id,
             name: row.name,
             description: row.description,
-            version: "1.0.0".to_string(),
+            version: row.version.unwrap_or_else(|| "1.0.0".to_string()),
             actions,
             guidance: row.guidance,
             source,
@@ -249,6 +344,72 @@ mod tests {
         assert_eq!(found.usage_count, 1);
     }
 
+    #[test]
+    fn test_sync_standard_skills_detects_version_bump() {
+        let sm = test_sm();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chat.skill.md");
+        std::fs::write(&path, "---\nname: chat\ndescription: Chat\nversion: 1\nactions:\n  - send_speech\n---\nv1").unwrap();
+
+        let report = sm.sync_standard_skills(dir.path()).unwrap();
+        assert_eq!(report.added, vec!["chat"]);
+
+        let skill = sm.get_active_skills().unwrap().remove(0);
+        assert_eq!(skill.version, "1");
+        assert_eq!(skill.actions, vec!["send_speech"]);
+        assert!(matches!(skill.source, SkillSource::Standard { .. }));
+        sm.increment_usage(&skill.id).unwrap();
+
+        let report = sm.sync_standard_skills(dir.path()).unwrap();
+        assert_eq!(report.unchanged, vec!["chat"]);
+
+        std::fs::write(&path, "---\nname: chat\ndescription: Chat\nversion: 2\n---\nv2").unwrap();
+        let report = sm.sync_standard_skills(dir.path()).unwrap();
+        assert_eq!(report.updated.len(), 1);
+        assert_eq!(report.updated[0].from.as_deref(), Some("1"));
+        assert_eq!(report.updated[0].to, "2");
+
+        let skills = sm.get_active_skills().unwrap();
+        assert_eq!(skills.len(), 1);
+        assert_eq!(skills[0].id, skill.id);
+        assert_eq!(skills[0].guidance, "v2");
+        assert_eq!(skills[0].usage_count, 1);
+    }
+
+    #[test]
+    fn test_declared_actions_from_active_skills() {
+        let sm = test_sm();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("a.skill.md"),
+            "---\nname: a\nactions:\n  - ws_read\n  - send_speech\n---\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("b.skill.md"),
+            "---\nname: b\nactions:\n  - send_speech\n  - ws_delete\n---\n",
+        )
+        .unwrap();
+        sm.sync_standard_skills(dir.path()).unwrap();
+        sm.acquire_skill("free", "d", "g", "training", "ctx").unwrap();
+        assert_eq!(
+            sm.declared_actions().unwrap(),
+            vec!["send_speech", "ws_delete", "ws_read"]
+        );
+
+        let b = sm
+            .get_active_skills()
+            .unwrap()
+            .into_iter()
+            .find(|s| s.name == "b")
+            .unwrap();
+        {
+            let conn = sm.conn.lock().unwrap();
+            queries::set_skill_active(&conn, &b.id, false).unwrap();
+        }
+        assert_eq!(sm.declared_actions().unwrap(), vec!["send_speech", "ws_read"]);
+    }
+
     #[test]
     fn test_build_context() {
         let sm = test_sm();
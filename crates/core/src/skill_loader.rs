@@ -0,0 +1,212 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// File extension used by standard skill definitions.
+pub const SKILL_FILE_SUFFIX: &str = ".skill.md";
+
+/// A skill definition parsed from a `*.skill.md` file.
+///
+/// The file consists of a YAML front matter block delimited by `---` lines
+/// followed by a markdown body, which becomes the skill's guidance:
+///
+/// ```text
+/// ---
+/// name: autonomous
+/// description: "..."
+/// version: 1
+/// actions:
+///   - send_speech
+/// ---
+///
+/// # Manual
+/// ...
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkillFile {
+    /// Skill name (unique per agent among standard skills).
+    pub name: String,
+    /// Short description of the skill.
+    pub description: String,
+    /// Version string. Integer versions in the front matter are stringified.
+    pub version: String,
+    /// Action names this skill relies on.
+    pub actions: Vec<String>,
+    /// Markdown body, used as guidance for the LLM.
+    pub guidance: String,
+    /// Path the file was loaded from.
+    pub file_path: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FrontMatter {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    version: Option<serde_yaml::Value>,
+    #[serde(default)]
+    actions: Vec<String>,
+}
+
+/// Parse the contents of a skill file.
+pub fn parse_skill_file(content: &str, file_path: &str) -> Result<SkillFile> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+
+    let mut lines = content.lines();
+    if lines.next().map(str::trim_end) != Some("---") {
+        bail!("{}: missing front matter (expected leading '---')", file_path);
+    }
+
+    let mut yaml = String::new();
+    let mut closed = false;
+    for line in lines.by_ref() {
+        if line.trim_end() == "---" {
+            closed = true;
+            break;
+        }
+        yaml.push_str(line);
+        yaml.push('\n');
+    }
+    if !closed {
+        bail!("{}: unterminated front matter", file_path);
+    }
+
+    let body: Vec<&str> = lines.collect();
+    let guidance = body.join("\n").trim().to_string();
+
+    let fm: FrontMatter = serde_yaml::from_str(&yaml)
+        .with_context(|| format!("{}: invalid front matter", file_path))?;
+
+    if fm.name.trim().is_empty() {
+        bail!("{}: front matter 'name' is empty", file_path);
+    }
+
+    let version = match fm.version {
+        None | Some(serde_yaml::Value::Null) => "1".to_string(),
+        Some(serde_yaml::Value::String(s)) => s,
+        Some(serde_yaml::Value::Number(n)) => n.to_string(),
+        Some(other) => bail!("{}: unsupported version value: {:?}", file_path, other),
+    };
+
+    Ok(SkillFile {
+        name: fm.name.trim().to_string(),
+        description: fm.description,
+        version,
+        actions: fm.actions,
+        guidance,
+        file_path: file_path.to_string(),
+    })
+}
+
+/// Load every `*.skill.md` file in `dir`, sorted by file name.
+///
+/// A missing directory yields an empty list. Files that fail to parse are
+/// skipped with a warning so that one broken file does not block the rest.
+pub fn load_skill_dir(dir: impl AsRef<Path>) -> Result<Vec<SkillFile>> {
+    let dir = dir.as_ref();
+    if !dir.is_dir() {
+        tracing::warn!(dir = %dir.display(), "Skill directory not found, no standard skills loaded");
+        return Ok(Vec::new());
+    }
+
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read skill directory: {}", dir.display()))?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.is_file()
+                && p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.ends_with(SKILL_FILE_SUFFIX))
+        })
+        .collect();
+    paths.sort();
+
+    let mut skills = Vec::new();
+    for path in paths {
+        let path_str = path.to_string_lossy().to_string();
+        let parsed = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read skill file: {}", path_str))
+            .and_then(|content| parse_skill_file(&content, &path_str));
+        match parsed {
+            Ok(skill) => skills.push(skill),
+            Err(e) => tracing::warn!(file = %path_str, error = %e, "Skipping invalid skill file"),
+        }
+    }
+
+    Ok(skills)
+}
+
+/// Result of synchronising standard skill files into the database.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SkillSyncReport {
+    /// Skills inserted for the first time.
+    pub added: Vec<String>,
+    /// Skills whose version changed and were updated.
+    pub updated: Vec<SkillVersionChange>,
+    /// Skills already present at the same version.
+    pub unchanged: Vec<String>,
+}
+
+/// A version bump detected while synchronising a standard skill.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkillVersionChange {
+    pub name: String,
+    pub from: Option<String>,
+    pub to: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "---\nname: autonomous\ndescription: \"Autonomous mode\"\nversion: 2\nactions:\n  - send_speech\n  - declare_done\n---\n\n# Manual\n\nSpeak when useful.\n";
+
+    #[test]
+    fn test_parse_skill_file() {
+        let skill = parse_skill_file(SAMPLE, "skills/autonomous.skill.md").unwrap();
+        assert_eq!(skill.name, "autonomous");
+        assert_eq!(skill.description, "Autonomous mode");
+        assert_eq!(skill.version, "2");
+        assert_eq!(skill.actions, vec!["send_speech", "declare_done"]);
+        assert_eq!(skill.guidance, "# Manual\n\nSpeak when useful.");
+        assert_eq!(skill.file_path, "skills/autonomous.skill.md");
+    }
+
+    #[test]
+    fn test_parse_string_version_and_defaults() {
+        let skill = parse_skill_file("---\nname: x\nversion: \"1.2.0\"\n---\nbody", "x").unwrap();
+        assert_eq!(skill.version, "1.2.0");
+        assert!(skill.actions.is_empty());
+        assert_eq!(skill.guidance, "body");
+
+        let skill = parse_skill_file("---\nname: y\n---\n", "y").unwrap();
+        assert_eq!(skill.version, "1");
+    }
+
+    #[test]
+    fn test_parse_rejects_missing_front_matter() {
+        assert!(parse_skill_file("# no front matter", "a").is_err());
+        assert!(parse_skill_file("---\nname: a\n", "a").is_err());
+        assert!(parse_skill_file("---\ndescription: no name\n---\n", "a").is_err());
+    }
+
+    #[test]
+    fn test_load_skill_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("b.skill.md"), SAMPLE).unwrap();
+        std::fs::write(dir.path().join("a.skill.md"), "---\nname: alpha\n---\nA").unwrap();
+        std::fs::write(dir.path().join("broken.skill.md"), "no front matter").unwrap();
+        std::fs::write(dir.path().join("notes.md"), "---\nname: ignored\n---\n").unwrap();
+
+        let skills = load_skill_dir(dir.path()).unwrap();
+        let names: Vec<&str> = skills.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["alpha", "autonomous"]);
+    }
+
+    #[test]
+    fn test_load_missing_dir() {
+        let skills = load_skill_dir("/nonexistent/opencrab/skills").unwrap();
+        assert!(skills.is_empty());
+    }
+}
@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
 
+use opencrab_db::queries::SoulRow;
+
 /// Social style based on assertiveness and responsiveness dimensions.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SocialStyle {
     /// How assertive the agent is (0.0 = passive, 1.0 = very assertive).
     pub assertiveness: f32,
@@ -23,6 +26,7 @@ impl Default for SocialStyle {
 
 /// Big Five personality traits, each scored from 0.0 to 1.0.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Personality {
     pub openness: f32,
     pub conscientiousness: f32,
@@ -45,6 +49,7 @@ impl Default for Personality {
 
 /// Cognitive thinking style preference.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ThinkingStyle {
     /// Primary thinking mode (e.g., "Analytical", "Intuitive", "Practical").
     pub primary: String,
@@ -94,6 +99,23 @@ impl Soul {
         }
     }
 
+    /// Build a soul from its database row.
+    ///
+    /// Trait JSON that is missing fields falls back to the defaults for those
+    /// fields; JSON that does not parse at all falls back to default traits.
+    pub fn from_row(row: &SoulRow) -> Self {
+        Self {
+            persona_name: row.persona_name.clone(),
+            social_style: serde_json::from_str(&row.social_style_json).unwrap_or_default(),
+            personality: serde_json::from_str(&row.personality_json).unwrap_or_default(),
+            thinking_style: serde_json::from_str(&row.thinking_style_json).unwrap_or_default(),
+            custom_traits: row
+                .custom_traits_json
+                .as_deref()
+                .and_then(|s| serde_json::from_str(s).ok()),
+        }
+    }
+
     /// Build a context string describing this soul for LLM prompts.
     pub fn build_context(&self) -> String {
         let mut ctx = String::new();
@@ -134,3 +156,27 @@ impl Soul {
         ctx
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_row_fills_missing_fields() {
+        let row = SoulRow {
+            agent_id: "a".to_string(),
+            persona_name: "Sage".to_string(),
+            social_style_json: r#"{"style":"driver"}"#.to_string(),
+            personality_json: r#"{"openness":0.8}"#.to_string(),
+            thinking_style_json: "not json".to_string(),
+            custom_traits_json: Some(r#"{"motto":"why not"}"#.to_string()),
+        };
+        let soul = Soul::from_row(&row);
+        assert_eq!(soul.persona_name, "Sage");
+        assert_eq!(soul.personality.openness, 0.8);
+        assert_eq!(soul.personality.extraversion, 0.5);
+        assert_eq!(soul.social_style.style_name, "Balanced");
+        assert_eq!(soul.thinking_style.primary, "Analytical");
+        assert_eq!(soul.custom_traits.unwrap()["motto"], "why not");
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use anyhow::Result;
 use chrono::Utc;
 use rusqlite::{params, Connection};
@@ -209,7 +211,7 @@ pub fn get_identity(conn: &Connection, agent_id: &str) -> Result<Option<Identity
     }
 }
 
-/// Delete an agent and all related data (identity, soul, skills, curated memory, discord config).
+/// Delete an agent and all related data (identity, soul, skills, curated memory, LLM and discord config).
 pub fn delete_agent(conn: &Connection, agent_id: &str) -> Result<bool> {
     let deleted = conn.execute("DELETE FROM identity WHERE agent_id = ?1", params![agent_id])?;
     conn.execute("DELETE FROM soul WHERE agent_id = ?1", params![agent_id])?;
@@ -219,13 +221,37 @@ pub fn delete_agent(conn: &Connection, agent_id: &str) -> Result<bool> {
         "DELETE FROM memory_curated WHERE agent_id = ?1",
         params![agent_id],
     )?;
+    conn.execute(
+        "DELETE FROM memory_embeddings WHERE agent_id = ?1",
+        params![agent_id],
+    )?;
+    conn.execute(
+        "DELETE FROM agent_llm_config WHERE agent_id = ?1",
+        params![agent_id],
+    )?;
     conn.execute(
         "DELETE FROM agent_discord_config WHERE agent_id = ?1",
         params![agent_id],
     )?;
+    conn.execute(
+        "DELETE FROM consolidation_runs WHERE agent_id = ?1",
+        params![agent_id],
+    )?;
+    // 共通テンプレート（scope = '*'）は残す
+    conn.execute(
+        "DELETE FROM prompt_templates WHERE scope = ?1 AND scope != ?2",
+        params![agent_id, PROMPT_TEMPLATE_GLOBAL],
+    )?;
     Ok(deleted > 0)
 }
 
+/// 登録済みの全エージェントIDを取得する
+pub fn list_agent_ids(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT agent_id FROM identity ORDER BY agent_id")?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    Ok(rows.collect::<std::result::Result<_, _>>()?)
+}
+
 /// Find agents by partial ID prefix or name (case-insensitive).
 pub fn find_agents(
     conn: &Connection,
@@ -253,10 +279,31 @@ pub struct CuratedMemoryRow {
     pub content: String,
 }
 
+/// キュレーション記憶の書き込み時の重要度（カテゴリから決める。更新時は変えない）
+pub fn curated_importance(category: &str) -> f64 {
+    match category {
+        "decisions" => 0.8,
+        "preferences" => 0.7,
+        "facts" | "reflection" => 0.6,
+        _ => 0.5,
+    }
+}
+
+/// セッションログの書き込み時の重要度（`log_type` から決める）
+pub fn log_importance(log_type: &str) -> f64 {
+    match log_type {
+        "summary" => 0.8,
+        "speech" | "message" => 0.5,
+        "inner_voice" | "system" => 0.3,
+        LOG_TYPE_TOOL_CALL | LOG_TYPE_TOOL_RESULT => 0.2,
+        _ => 0.1,
+    }
+}
+
 pub fn upsert_curated_memory(conn: &Connection, memory: &CuratedMemoryRow) -> Result<()> {
     conn.execute(
-        "INSERT INTO memory_curated (id, agent_id, category, content, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5)
+        "INSERT INTO memory_curated (id, agent_id, category, content, importance, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
          ON CONFLICT(id) DO UPDATE SET
             content = excluded.content,
             updated_at = excluded.updated_at",
@@ -265,9 +312,15 @@ pub fn upsert_curated_memory(conn: &Connection, memory: &CuratedMemoryRow) -> Re
             memory.agent_id,
             memory.category,
             memory.content,
+            curated_importance(&memory.category),
             Utc::now().to_rfc3339(),
         ],
     )?;
+    // 内容が変わった可能性があるので古いベクトルは捨てる（次の検索時に再計算される）
+    conn.execute(
+        "DELETE FROM memory_embeddings WHERE source = 'curated' AND source_id = ?1",
+        params![memory.id],
+    )?;
     Ok(())
 }
 
@@ -314,10 +367,53 @@ pub fn list_curated_memories(
     Ok(rows.collect::<std::result::Result<_, _>>()?)
 }
 
+/// キュレーション記憶に出典（セッションログID）を追加する。既存の出典と合わせて重複なく保存する
+pub fn add_curated_memory_sources(conn: &Connection, memory_id: &str, log_ids: &[i64]) -> Result<()> {
+    let mut sources = get_curated_memory_sources(conn, memory_id)?;
+    sources.extend_from_slice(log_ids);
+    sources.sort_unstable();
+    sources.dedup();
+    conn.execute(
+        "UPDATE memory_curated SET source_log_ids_json = ?2 WHERE id = ?1",
+        params![memory_id, serde_json::to_string(&sources)?],
+    )?;
+    Ok(())
+}
+
+/// キュレーション記憶の出典（セッションログID）。記録がなければ空
+pub fn get_curated_memory_sources(conn: &Connection, memory_id: &str) -> Result<Vec<i64>> {
+    let json: Option<String> = conn
+        .query_row(
+            "SELECT source_log_ids_json FROM memory_curated WHERE id = ?1",
+            params![memory_id],
+            |row| row.get(0),
+        )
+        .or_else(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => Ok(None),
+            e => Err(e),
+        })?;
+    Ok(json
+        .and_then(|j| serde_json::from_str(&j).ok())
+        .unwrap_or_default())
+}
+
 // ============================================
 // MEMORY: Sessions
 // ============================================
 
+/// エージェントのツール呼び出しのログ種別（metadata_json に tool_calls を保存）
+pub const LOG_TYPE_TOOL_CALL: &str = "tool_call";
+/// ツール実行結果のログ種別（metadata_json に tool_call_id を保存）
+pub const LOG_TYPE_TOOL_RESULT: &str = "tool_result";
+/// システムプロンプトに注入した記憶の記録（デバッグ用）
+pub const LOG_TYPE_CONTEXT: &str = "context";
+/// 予算の通知（モデルの切り替え・拒否）の記録
+pub const LOG_TYPE_BUDGET: &str = "budget";
+
+/// 記録のためだけのログ種別。全文検索・埋め込みの対象にしない
+/// （注入した記憶の写しが検索で再び注入されるのを防ぐ）
+pub const UNINDEXED_LOG_TYPES: &[&str] = &[LOG_TYPE_CONTEXT, LOG_TYPE_BUDGET];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionLogRow {
     pub id: Option<i64>,
@@ -332,8 +428,8 @@ pub struct SessionLogRow {
 
 pub fn insert_session_log(conn: &Connection, log: &SessionLogRow) -> Result<i64> {
     conn.execute(
-        "INSERT INTO memory_sessions (agent_id, session_id, log_type, content, speaker_id, turn_number, metadata_json, created_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        "INSERT INTO memory_sessions (agent_id, session_id, log_type, content, speaker_id, turn_number, metadata_json, importance, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
         params![
             log.agent_id,
             log.session_id,
@@ -342,11 +438,15 @@ pub fn insert_session_log(conn: &Connection, log: &SessionLogRow) -> Result<i64>
             log.speaker_id,
             log.turn_number,
             log.metadata_json,
+            log_importance(&log.log_type),
             Utc::now().to_rfc3339(),
         ],
     )?;
 
     let row_id = conn.last_insert_rowid();
+    if UNINDEXED_LOG_TYPES.contains(&log.log_type.as_str()) {
+        return Ok(row_id);
+    }
 
     // FTSにも追加
     conn.execute(
@@ -373,18 +473,43 @@ pub fn search_session_logs(
     agent_id: &str,
     query: &str,
     limit: usize,
+) -> Result<Vec<SessionLogResult>> {
+    search_session_logs_fts(conn, agent_id, query, limit, " AND ")
+}
+
+/// いずれかの語を含むログを検索する（OR 検索）。
+/// 直近の発言から関連する過去ログを拾う用途向け。
+pub fn search_session_logs_any(
+    conn: &Connection,
+    agent_id: &str,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<SessionLogResult>> {
+    search_session_logs_fts(conn, agent_id, query, limit, " OR ")
+}
+
+fn search_session_logs_fts(
+    conn: &Connection,
+    agent_id: &str,
+    query: &str,
+    limit: usize,
+    operator: &str,
 ) -> Result<Vec<SessionLogResult>> {
     let tokens: Vec<String> = query
         .split_whitespace()
         .map(|t| format!("\"{}\"", t.replace('"', "\"\"")))
         .collect();
-    let fts_query = tokens.join(" AND ");
+    if tokens.is_empty() {
+        return Ok(Vec::new());
+    }
+    let fts_query = tokens.join(operator);
 
     let mut stmt = conn.prepare(
         "SELECT ms.id, ms.session_id, ms.log_type, ms.content, ms.created_at, bm25(memory_sessions_fts) as score
          FROM memory_sessions_fts fts
          JOIN memory_sessions ms ON fts.rowid = ms.id
          WHERE fts.agent_id = ?1 AND memory_sessions_fts MATCH ?2
+           AND ms.log_type NOT IN ('tool_call', 'tool_result', 'context', 'budget')
          ORDER BY score
          LIMIT ?3",
     )?;
@@ -430,6 +555,143 @@ pub fn list_session_logs_by_session(
     Ok(rows.collect::<std::result::Result<_, _>>()?)
 }
 
+// ============================================
+// MEMORY: Embeddings
+// ============================================
+
+/// 埋め込みの対象（セッションログ / キュレーション記憶）
+pub const EMBEDDING_SOURCE_SESSION: &str = "session";
+pub const EMBEDDING_SOURCE_CURATED: &str = "curated";
+
+/// ベクトルをBLOB（f32 リトルエンディアン）に変換する
+pub fn encode_vector(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+/// BLOBからベクトルを復元する
+pub fn decode_vector(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+pub fn upsert_embedding(
+    conn: &Connection,
+    source: &str,
+    source_id: &str,
+    agent_id: &str,
+    model: &str,
+    vector: &[f32],
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO memory_embeddings (source, source_id, agent_id, model, dims, vector, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+         ON CONFLICT(source, source_id, model) DO UPDATE SET
+            dims = excluded.dims,
+            vector = excluded.vector,
+            created_at = excluded.created_at",
+        params![
+            source,
+            source_id,
+            agent_id,
+            model,
+            vector.len() as i64,
+            encode_vector(vector),
+            Utc::now().to_rfc3339(),
+        ],
+    )?;
+    Ok(())
+}
+
+/// 埋め込みがまだ無い記憶
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingCandidate {
+    pub source: String,
+    pub source_id: String,
+    pub content: String,
+}
+
+/// 指定モデルの埋め込みがまだ無いセッションログ・キュレーション記憶を古い順に取得する
+pub fn list_unembedded_memories(
+    conn: &Connection,
+    agent_id: &str,
+    model: &str,
+    limit: usize,
+) -> Result<Vec<EmbeddingCandidate>> {
+    let mut stmt = conn.prepare(
+        "SELECT 'session', CAST(ms.id AS TEXT), ms.content, ms.created_at
+         FROM memory_sessions ms
+         WHERE ms.agent_id = ?1 AND ms.content != '' AND ms.archived_at IS NULL
+           AND ms.log_type NOT IN ('tool_call', 'tool_result', 'context', 'budget')
+           AND NOT EXISTS (SELECT 1 FROM memory_embeddings e
+                           WHERE e.source = 'session' AND e.source_id = CAST(ms.id AS TEXT) AND e.model = ?2)
+         UNION ALL
+         SELECT 'curated', mc.id, mc.content, mc.updated_at
+         FROM memory_curated mc
+         WHERE mc.agent_id = ?1 AND mc.content != ''
+           AND NOT EXISTS (SELECT 1 FROM memory_embeddings e
+                           WHERE e.source = 'curated' AND e.source_id = mc.id AND e.model = ?2)
+         ORDER BY 4
+         LIMIT ?3",
+    )?;
+    let rows = stmt.query_map(params![agent_id, model, limit as i64], |row| {
+        Ok(EmbeddingCandidate {
+            source: row.get(0)?,
+            source_id: row.get(1)?,
+            content: row.get(2)?,
+        })
+    })?;
+    Ok(rows.collect::<std::result::Result<_, _>>()?)
+}
+
+/// 埋め込み済みの記憶（ベクトル検索用）
+#[derive(Debug, Clone)]
+pub struct EmbeddedMemoryRow {
+    pub source: String,
+    pub source_id: String,
+    /// セッションログのみ
+    pub session_id: Option<String>,
+    /// セッションログのみ
+    pub log_type: Option<String>,
+    /// キュレーション記憶のみ
+    pub category: Option<String>,
+    pub content: String,
+    pub created_at: String,
+    pub vector: Vec<f32>,
+}
+
+/// エージェントの埋め込み済み記憶を全件取得する（総当たりのベクトル検索用）
+pub fn list_embedded_memories(
+    conn: &Connection,
+    agent_id: &str,
+    model: &str,
+) -> Result<Vec<EmbeddedMemoryRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT e.source, e.source_id, ms.session_id, ms.log_type, NULL, ms.content, ms.created_at, e.vector
+         FROM memory_embeddings e
+         JOIN memory_sessions ms ON e.source = 'session' AND e.source_id = CAST(ms.id AS TEXT)
+         WHERE e.agent_id = ?1 AND e.model = ?2 AND ms.log_type NOT IN ('context', 'budget')
+         UNION ALL
+         SELECT e.source, e.source_id, NULL, NULL, mc.category, mc.content, mc.updated_at, e.vector
+         FROM memory_embeddings e
+         JOIN memory_curated mc ON e.source = 'curated' AND e.source_id = mc.id
+         WHERE e.agent_id = ?1 AND e.model = ?2",
+    )?;
+    let rows = stmt.query_map(params![agent_id, model], |row| {
+        Ok(EmbeddedMemoryRow {
+            source: row.get(0)?,
+            source_id: row.get(1)?,
+            session_id: row.get(2)?,
+            log_type: row.get(3)?,
+            category: row.get(4)?,
+            content: row.get(5)?,
+            created_at: row.get(6)?,
+            vector: decode_vector(&row.get::<_, Vec<u8>>(7)?),
+        })
+    })?;
+    Ok(rows.collect::<std::result::Result<_, _>>()?)
+}
+
 // ============================================
 // Skills
 // ============================================
@@ -445,45 +707,87 @@ pub struct SkillRow {
     pub source_type: String,
     pub source_context: Option<String>,
     pub file_path: Option<String>,
+    /// スキルファイルのバージョン（標準スキルのみ）
+    #[serde(default)]
+    pub version: Option<String>,
     pub effectiveness: Option<f64>,
     pub usage_count: i32,
     pub is_active: bool,
 }
 
+fn row_to_skill_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<SkillRow> {
+    Ok(SkillRow {
+        id: row.get(0)?,
+        agent_id: row.get(1)?,
+        name: row.get(2)?,
+        description: row.get(3)?,
+        situation_pattern: row.get(4)?,
+        guidance: row.get(5)?,
+        source_type: row.get(6)?,
+        source_context: row.get(7)?,
+        file_path: row.get(8)?,
+        version: row.get(9)?,
+        effectiveness: row.get(10)?,
+        usage_count: row.get(11)?,
+        is_active: row.get(12)?,
+    })
+}
+
 pub fn list_skills(conn: &Connection, agent_id: &str, active_only: bool) -> Result<Vec<SkillRow>> {
     let sql = if active_only {
-        "SELECT id, agent_id, name, description, situation_pattern, guidance, source_type, source_context, file_path, effectiveness, usage_count, is_active
+        "SELECT id, agent_id, name, description, situation_pattern, guidance, source_type, source_context, file_path, version, effectiveness, usage_count, is_active
          FROM skills WHERE agent_id = ?1 AND is_active = 1 ORDER BY usage_count DESC"
     } else {
-        "SELECT id, agent_id, name, description, situation_pattern, guidance, source_type, source_context, file_path, effectiveness, usage_count, is_active
+        "SELECT id, agent_id, name, description, situation_pattern, guidance, source_type, source_context, file_path, version, effectiveness, usage_count, is_active
          FROM skills WHERE agent_id = ?1 ORDER BY usage_count DESC"
     };
 
     let mut stmt = conn.prepare(sql)?;
-    let rows = stmt.query_map(params![agent_id], |row| {
-        Ok(SkillRow {
-            id: row.get(0)?,
-            agent_id: row.get(1)?,
-            name: row.get(2)?,
-            description: row.get(3)?,
-            situation_pattern: row.get(4)?,
-            guidance: row.get(5)?,
-            source_type: row.get(6)?,
-            source_context: row.get(7)?,
-            file_path: row.get(8)?,
-            effectiveness: row.get(9)?,
-            usage_count: row.get(10)?,
-            is_active: row.get(11)?,
-        })
-    })?;
+    let rows = stmt.query_map(params![agent_id], row_to_skill_row)?;
 
     Ok(rows.collect::<std::result::Result<_, _>>()?)
 }
 
+/// 標準スキル（source_type='standard'）を名前で取得する
+pub fn get_standard_skill(conn: &Connection, agent_id: &str, name: &str) -> Result<Option<SkillRow>> {
+    let result = conn.query_row(
+        "SELECT id, agent_id, name, description, situation_pattern, guidance, source_type, source_context, file_path, version, effectiveness, usage_count, is_active
+         FROM skills WHERE agent_id = ?1 AND name = ?2 AND source_type = 'standard'",
+        params![agent_id, name],
+        row_to_skill_row,
+    );
+
+    match result {
+        Ok(row) => Ok(Some(row)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// スキルファイル由来の内容でスキルを更新する
+///
+/// usage_count / effectiveness / is_active は保持する。
+pub fn update_skill_definition(conn: &Connection, skill: &SkillRow) -> Result<()> {
+    conn.execute(
+        "UPDATE skills SET description = ?1, situation_pattern = ?2, guidance = ?3, file_path = ?4, version = ?5, updated_at = ?6
+         WHERE id = ?7",
+        params![
+            skill.description,
+            skill.situation_pattern,
+            skill.guidance,
+            skill.file_path,
+            skill.version,
+            Utc::now().to_rfc3339(),
+            skill.id,
+        ],
+    )?;
+    Ok(())
+}
+
 pub fn insert_skill(conn: &Connection, skill: &SkillRow) -> Result<()> {
     conn.execute(
-        "INSERT INTO skills (id, agent_id, name, description, situation_pattern, guidance, source_type, source_context, file_path, effectiveness, usage_count, is_active, created_at, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+        "INSERT INTO skills (id, agent_id, name, description, situation_pattern, guidance, source_type, source_context, file_path, version, effectiveness, usage_count, is_active, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
         params![
             skill.id,
             skill.agent_id,
@@ -494,6 +798,7 @@ pub fn insert_skill(conn: &Connection, skill: &SkillRow) -> Result<()> {
             skill.source_type,
             skill.source_context,
             skill.file_path,
+            skill.version,
             skill.effectiveness,
             skill.usage_count,
             skill.is_active,
@@ -599,6 +904,51 @@ pub fn get_impressions(
     Ok(rows.collect::<std::result::Result<_, _>>()?)
 }
 
+/// 指定した相手ごとに最新の印象を 1 件ずつ取得する。
+/// `prefer_session` の印象があればそれを優先し、なければ他セッションの最新を使う。
+pub fn get_latest_impressions(
+    conn: &Connection,
+    agent_id: &str,
+    target_ids: &[String],
+    prefer_session: Option<&str>,
+) -> Result<Vec<ImpressionRow>> {
+    if target_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare(
+        "SELECT id, agent_id, session_id, target_id, target_name, personality, communication_style, recent_behavior, agreement, notes, last_updated_turn
+         FROM impressions WHERE agent_id = ?1
+         ORDER BY (session_id = ?2) DESC, updated_at DESC",
+    )?;
+
+    let rows = stmt.query_map(params![agent_id, prefer_session.unwrap_or("")], |row| {
+        Ok(ImpressionRow {
+            id: row.get(0)?,
+            agent_id: row.get(1)?,
+            session_id: row.get(2)?,
+            target_id: row.get(3)?,
+            target_name: row.get(4)?,
+            personality: row.get(5)?,
+            communication_style: row.get(6)?,
+            recent_behavior: row.get(7)?,
+            agreement: row.get(8)?,
+            notes: row.get(9)?,
+            last_updated_turn: row.get(10)?,
+        })
+    })?;
+
+    let mut latest: Vec<ImpressionRow> = Vec::new();
+    for row in rows {
+        let row = row?;
+        if target_ids.contains(&row.target_id) && !latest.iter().any(|r| r.target_id == row.target_id) {
+            latest.push(row);
+        }
+    }
+    // 呼び出し側が渡した順序に揃える
+    latest.sort_by_key(|r| target_ids.iter().position(|t| *t == r.target_id));
+    Ok(latest)
+}
+
 // ============================================
 // LLM Metrics
 // ============================================
@@ -748,14 +1098,16 @@ pub fn list_model_experience_notes(
     Ok(rows.collect::<std::result::Result<_, _>>()?)
 }
 
+/// (model, purpose, self_evaluation, quality_score, tags, timestamp)
+pub type EvaluationEntry = (String, String, String, f64, Option<String>, Option<String>);
+
 /// Get recent evaluations with free-text feedback (self_evaluation) for a model.
 pub fn get_recent_evaluations(
     conn: &Connection,
     agent_id: &str,
     model_filter: Option<&str>,
     limit: usize,
-) -> Result<Vec<(String, String, String, f64, Option<String>, Option<String>)>> {
-    // Returns: (model, purpose, self_evaluation, quality_score, tags, timestamp)
+) -> Result<Vec<EvaluationEntry>> {
     let (sql, param_values): (&str, Vec<Box<dyn rusqlite::types::ToSql>>) = if let Some(model) = model_filter {
         (
             "SELECT model, purpose, COALESCE(self_evaluation, ''), COALESCE(quality_score, 0.0), tags, timestamp
@@ -835,6 +1187,27 @@ pub fn get_llm_metrics_summary(
     Ok(row)
 }
 
+/// 推定コスト（USD）の合計。`None` の条件は絞り込まない。
+///
+/// 予算チェック用（エージェント/日、セッション、全体/月）。
+pub fn get_llm_cost_total(
+    conn: &Connection,
+    agent_id: Option<&str>,
+    session_id: Option<&str>,
+    since: Option<&str>,
+) -> Result<f64> {
+    let total = conn.query_row(
+        "SELECT COALESCE(SUM(estimated_cost_usd), 0.0)
+         FROM llm_usage_metrics
+         WHERE (?1 IS NULL OR agent_id = ?1)
+           AND (?2 IS NULL OR session_id = ?2)
+           AND (?3 IS NULL OR timestamp >= ?3)",
+        params![agent_id, session_id, since],
+        |row| row.get(0),
+    )?;
+    Ok(total)
+}
+
 /// Per-model aggregated metrics for optimization analysis.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LlmModelStats {
@@ -1058,89 +1431,537 @@ pub fn update_session_metadata(
     Ok(())
 }
 
-// ============================================
-// Heartbeat Log
-// ============================================
+/// エージェントが参加中の進行中のセッション（`active` と自律実行中の `running`）を取得する
+pub fn list_active_sessions_for_agent(conn: &Connection, agent_id: &str) -> Result<Vec<SessionRow>> {
+    let sessions = list_sessions(conn)?;
+    Ok(sessions
+        .into_iter()
+        .filter(|s| matches!(s.status.as_str(), "active" | "running"))
+        .filter(|s| {
+            serde_json::from_str::<Vec<String>>(&s.participant_ids_json)
+                .map(|ids| ids.iter().any(|id| id == agent_id))
+                .unwrap_or(false)
+        })
+        .collect())
+}
 
-pub fn insert_heartbeat_log(
+/// セッションの進行状態（フェーズ・ターン番号）を更新する
+pub fn update_session_progress(
     conn: &Connection,
-    agent_id: &str,
-    decision: &str,
-    result_json: Option<&str>,
+    session_id: &str,
+    phase: &str,
+    turn_number: i32,
 ) -> Result<()> {
     conn.execute(
-        "INSERT INTO heartbeat_log (agent_id, decision, result_json, created_at)
-         VALUES (?1, ?2, ?3, ?4)",
-        params![agent_id, decision, result_json, Utc::now().to_rfc3339()],
+        "UPDATE sessions SET phase = ?1, turn_number = ?2, updated_at = ?3 WHERE id = ?4",
+        params![phase, turn_number, Utc::now().to_rfc3339(), session_id],
+    )?;
+    Ok(())
+}
+
+/// セッションのステータス（active, completed 等）を更新する
+pub fn update_session_status(conn: &Connection, session_id: &str, status: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE sessions SET status = ?1, updated_at = ?2 WHERE id = ?3",
+        params![status, Utc::now().to_rfc3339(), session_id],
     )?;
     Ok(())
 }
 
 // ============================================
-// Model Pricing
+// Agent Sessions
 // ============================================
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ModelPricingRow {
-    pub provider: String,
-    pub model: String,
-    pub input_price_per_1m: f64,
-    pub output_price_per_1m: f64,
-    pub context_window: Option<i32>,
+pub struct AgentSessionRow {
+    pub agent_id: String,
+    pub session_id: String,
+    pub last_speech_at: Option<String>,
+    pub done_declared: bool,
 }
 
-pub fn upsert_model_pricing(conn: &Connection, pricing: &ModelPricingRow) -> Result<()> {
+/// 発言時刻を記録する（参加状態の行がなければ作成する）
+pub fn record_agent_speech(conn: &Connection, agent_id: &str, session_id: &str) -> Result<()> {
     conn.execute(
-        "INSERT INTO model_pricing (provider, model, input_price_per_1m, output_price_per_1m, context_window, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-         ON CONFLICT(provider, model) DO UPDATE SET
-            input_price_per_1m = excluded.input_price_per_1m,
-            output_price_per_1m = excluded.output_price_per_1m,
-            context_window = excluded.context_window,
-            updated_at = excluded.updated_at",
-        params![
-            pricing.provider,
-            pricing.model,
-            pricing.input_price_per_1m,
-            pricing.output_price_per_1m,
-            pricing.context_window,
-            Utc::now().to_rfc3339(),
-        ],
+        "INSERT INTO agent_sessions (agent_id, session_id, last_speech_at, done_declared)
+         VALUES (?1, ?2, ?3, 0)
+         ON CONFLICT(agent_id, session_id) DO UPDATE SET last_speech_at = excluded.last_speech_at",
+        params![agent_id, session_id, Utc::now().to_rfc3339()],
     )?;
     Ok(())
 }
 
-pub fn get_model_pricing(
+/// 終了宣言の状態を設定し、セッションの `done_count` を再計算して返す
+pub fn set_done_declared(
     conn: &Connection,
-    provider: &str,
-    model: &str,
-) -> Result<Option<ModelPricingRow>> {
+    agent_id: &str,
+    session_id: &str,
+    done: bool,
+) -> Result<i32> {
+    conn.execute(
+        "INSERT INTO agent_sessions (agent_id, session_id, last_speech_at, done_declared)
+         VALUES (?1, ?2, NULL, ?3)
+         ON CONFLICT(agent_id, session_id) DO UPDATE SET done_declared = excluded.done_declared",
+        params![agent_id, session_id, done as i32],
+    )?;
+    let done_count: i32 = conn.query_row(
+        "SELECT COUNT(*) FROM agent_sessions WHERE session_id = ?1 AND done_declared = 1",
+        params![session_id],
+        |row| row.get(0),
+    )?;
+    conn.execute(
+        "UPDATE sessions SET done_count = ?1, updated_at = ?2 WHERE id = ?3",
+        params![done_count, Utc::now().to_rfc3339(), session_id],
+    )?;
+    Ok(done_count)
+}
+
+/// セッション内で持続するモデル切り替えを設定する（`None` で解除）
+pub fn set_agent_model_override(
+    conn: &Connection,
+    agent_id: &str,
+    session_id: &str,
+    model: Option<&str>,
+    duration: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO agent_sessions (agent_id, session_id, last_speech_at, done_declared, model_override, model_override_duration)
+         VALUES (?1, ?2, NULL, 0, ?3, ?4)
+         ON CONFLICT(agent_id, session_id) DO UPDATE SET
+            model_override = excluded.model_override,
+            model_override_duration = excluded.model_override_duration",
+        params![agent_id, session_id, model, duration],
+    )?;
+    Ok(())
+}
+
+/// セッション内で持続するモデル切り替えを取得する。`(model, duration)`
+pub fn get_agent_model_override(
+    conn: &Connection,
+    agent_id: &str,
+    session_id: &str,
+) -> Result<Option<(String, String)>> {
     let result = conn.query_row(
-        "SELECT provider, model, input_price_per_1m, output_price_per_1m, context_window
-         FROM model_pricing WHERE provider = ?1 AND model = ?2",
-        params![provider, model],
-        |row| {
-            Ok(ModelPricingRow {
-                provider: row.get(0)?,
-                model: row.get(1)?,
-                input_price_per_1m: row.get(2)?,
-                output_price_per_1m: row.get(3)?,
-                context_window: row.get(4)?,
-            })
-        },
+        "SELECT model_override, COALESCE(model_override_duration, '')
+         FROM agent_sessions
+         WHERE agent_id = ?1 AND session_id = ?2 AND model_override IS NOT NULL",
+        params![agent_id, session_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
     );
 
     match result {
-        Ok(p) => Ok(Some(p)),
+        Ok(pair) => Ok(Some(pair)),
         Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
         Err(e) => Err(e.into()),
     }
 }
 
-// ============================================
-// Discord Channel Config
-// ============================================
-
+pub fn list_agent_sessions(conn: &Connection, session_id: &str) -> Result<Vec<AgentSessionRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT agent_id, session_id, last_speech_at, done_declared
+         FROM agent_sessions WHERE session_id = ?1 ORDER BY agent_id",
+    )?;
+    let rows = stmt.query_map(params![session_id], |row| {
+        Ok(AgentSessionRow {
+            agent_id: row.get(0)?,
+            session_id: row.get(1)?,
+            last_speech_at: row.get(2)?,
+            done_declared: row.get::<_, i32>(3)? != 0,
+        })
+    })?;
+    Ok(rows.collect::<std::result::Result<_, _>>()?)
+}
+
+// ============================================
+// Heartbeat Log
+// ============================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeartbeatLogRow {
+    pub id: i64,
+    pub agent_id: String,
+    pub decision: String,
+    pub result_json: Option<String>,
+    pub created_at: String,
+}
+
+pub fn insert_heartbeat_log(
+    conn: &Connection,
+    agent_id: &str,
+    decision: &str,
+    result_json: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO heartbeat_log (agent_id, decision, result_json, created_at)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![agent_id, decision, result_json, Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+/// ハートビートログを新しい順に取得する
+pub fn list_heartbeat_logs(conn: &Connection, agent_id: &str, limit: usize) -> Result<Vec<HeartbeatLogRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, agent_id, decision, result_json, created_at
+         FROM heartbeat_log WHERE agent_id = ?1
+         ORDER BY id DESC LIMIT ?2",
+    )?;
+    let rows = stmt.query_map(params![agent_id, limit as i64], |row| {
+        Ok(HeartbeatLogRow {
+            id: row.get(0)?,
+            agent_id: row.get(1)?,
+            decision: row.get(2)?,
+            result_json: row.get(3)?,
+            created_at: row.get(4)?,
+        })
+    })?;
+    Ok(rows.collect::<std::result::Result<_, _>>()?)
+}
+
+// ============================================
+// Memory Consolidation
+// ============================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsolidationRunRow {
+    pub id: Option<i64>,
+    pub agent_id: String,
+    pub from_log_id: i64,
+    pub to_log_id: i64,
+    pub processed_logs: i64,
+    pub added: i64,
+    pub merged: i64,
+    pub result_json: Option<String>,
+    pub created_at: Option<String>,
+}
+
+/// 統合の実行結果を記録する（`to_log_id` が次回のチェックポイントになる）
+pub fn insert_consolidation_run(conn: &Connection, run: &ConsolidationRunRow) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO consolidation_runs
+            (agent_id, from_log_id, to_log_id, processed_logs, added, merged, result_json, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            run.agent_id,
+            run.from_log_id,
+            run.to_log_id,
+            run.processed_logs,
+            run.added,
+            run.merged,
+            run.result_json,
+            Utc::now().to_rfc3339(),
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// 統合の実行履歴を新しい順に取得する
+pub fn list_consolidation_runs(conn: &Connection, agent_id: &str, limit: usize) -> Result<Vec<ConsolidationRunRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, agent_id, from_log_id, to_log_id, processed_logs, added, merged, result_json, created_at
+         FROM consolidation_runs WHERE agent_id = ?1
+         ORDER BY id DESC LIMIT ?2",
+    )?;
+    let rows = stmt.query_map(params![agent_id, limit as i64], |row| {
+        Ok(ConsolidationRunRow {
+            id: row.get(0)?,
+            agent_id: row.get(1)?,
+            from_log_id: row.get(2)?,
+            to_log_id: row.get(3)?,
+            processed_logs: row.get(4)?,
+            added: row.get(5)?,
+            merged: row.get(6)?,
+            result_json: row.get(7)?,
+            created_at: row.get(8)?,
+        })
+    })?;
+    Ok(rows.collect::<std::result::Result<_, _>>()?)
+}
+
+/// 統合済みの最後のセッションログID（未実行なら 0）
+pub fn get_consolidation_checkpoint(conn: &Connection, agent_id: &str) -> Result<i64> {
+    Ok(conn.query_row(
+        "SELECT COALESCE(MAX(to_log_id), 0) FROM consolidation_runs WHERE agent_id = ?1",
+        params![agent_id],
+        |row| row.get(0),
+    )?)
+}
+
+/// `after_log_id` より後の、エージェントが参加したセッションの発言を古い順に取得する。
+///
+/// 参加 = セッションの参加者に含まれる、またはそのセッションにログを残している。
+pub fn list_logs_for_consolidation(
+    conn: &Connection,
+    agent_id: &str,
+    after_log_id: i64,
+    limit: usize,
+) -> Result<Vec<SessionLogRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, agent_id, session_id, log_type, content, speaker_id, turn_number, metadata_json
+         FROM memory_sessions
+         WHERE id > ?2 AND log_type IN ('speech', 'message') AND archived_at IS NULL
+           AND session_id IN (
+               SELECT session_id FROM memory_sessions WHERE agent_id = ?1
+               UNION
+               SELECT s.id FROM sessions s, json_each(s.participant_ids_json) p WHERE p.value = ?1
+           )
+         ORDER BY id ASC LIMIT ?3",
+    )?;
+    let rows = stmt.query_map(params![agent_id, after_log_id, limit as i64], |row| {
+        Ok(SessionLogRow {
+            id: row.get(0)?,
+            agent_id: row.get(1)?,
+            session_id: row.get(2)?,
+            log_type: row.get(3)?,
+            content: row.get(4)?,
+            speaker_id: row.get(5)?,
+            turn_number: row.get(6)?,
+            metadata_json: row.get(7)?,
+        })
+    })?;
+    Ok(rows.collect::<std::result::Result<_, _>>()?)
+}
+
+// ============================================
+// Memory Importance & Retention
+// ============================================
+
+/// 記憶（キュレーション記憶またはセッションログ）の重要度とアクセス記録
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryStatsRow {
+    /// [`EMBEDDING_SOURCE_SESSION`] または [`EMBEDDING_SOURCE_CURATED`]
+    pub source: String,
+    pub id: String,
+    pub log_type: Option<String>,
+    pub importance: f64,
+    pub access_count: i64,
+    pub last_accessed_at: Option<String>,
+    /// セッションログは作成日時、キュレーション記憶は更新日時
+    pub created_at: String,
+}
+
+fn memory_stats_from_row(row: &rusqlite::Row) -> rusqlite::Result<MemoryStatsRow> {
+    Ok(MemoryStatsRow {
+        source: row.get(0)?,
+        id: row.get(1)?,
+        log_type: row.get(2)?,
+        importance: row.get(3)?,
+        access_count: row.get(4)?,
+        last_accessed_at: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+/// 指定した記憶の重要度とアクセス記録（ID → 行）
+pub fn get_memory_stats(
+    conn: &Connection,
+    source: &str,
+    ids: &[String],
+) -> Result<HashMap<String, MemoryStatsRow>> {
+    let sql = if source == EMBEDDING_SOURCE_CURATED {
+        "SELECT 'curated', id, NULL, importance, access_count, last_accessed_at, updated_at
+         FROM memory_curated WHERE id = ?1"
+    } else {
+        "SELECT 'session', CAST(id AS TEXT), log_type, importance, access_count, last_accessed_at, created_at
+         FROM memory_sessions WHERE CAST(id AS TEXT) = ?1"
+    };
+    let mut stmt = conn.prepare(sql)?;
+    let mut stats = HashMap::new();
+    for id in ids {
+        match stmt.query_row(params![id], memory_stats_from_row) {
+            Ok(row) => {
+                stats.insert(id.clone(), row);
+            }
+            Err(rusqlite::Error::QueryReturnedNoRows) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(stats)
+}
+
+/// 記憶が使われたことを記録する（アクセス回数を増やし、最終アクセス日時を更新する）
+pub fn record_memory_access(conn: &Connection, source: &str, ids: &[String]) -> Result<usize> {
+    let sql = if source == EMBEDDING_SOURCE_CURATED {
+        "UPDATE memory_curated SET access_count = access_count + 1, last_accessed_at = ?2 WHERE id = ?1"
+    } else {
+        "UPDATE memory_sessions SET access_count = access_count + 1, last_accessed_at = ?2 WHERE id = CAST(?1 AS INTEGER)"
+    };
+    let now = Utc::now().to_rfc3339();
+    let mut updated = 0;
+    for id in ids {
+        updated += conn.execute(sql, params![id, now])?;
+    }
+    Ok(updated)
+}
+
+/// キュレーション記憶の重要度を `delta` だけ上げる（上限 1.0）。統合で同じ内容が再び抽出されたときに使う
+pub fn boost_curated_memory_importance(conn: &Connection, memory_id: &str, delta: f64) -> Result<()> {
+    conn.execute(
+        "UPDATE memory_curated SET importance = MIN(1.0, importance + ?2) WHERE id = ?1",
+        params![memory_id, delta],
+    )?;
+    Ok(())
+}
+
+/// `created_before` より前に作られた、アーカイブされていないエージェント自身のセッションログ。
+///
+/// `keep_log_types` の種類は含めない。同じセッションの他の参加者のログは対象にしない。
+pub fn list_retention_candidates(
+    conn: &Connection,
+    agent_id: &str,
+    created_before: &str,
+    keep_log_types: &[String],
+) -> Result<Vec<MemoryStatsRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT 'session', CAST(id AS TEXT), log_type, importance, access_count, last_accessed_at, created_at
+         FROM memory_sessions
+         WHERE agent_id = ?1 AND created_at < ?2 AND archived_at IS NULL
+         ORDER BY id ASC",
+    )?;
+    let rows = stmt.query_map(params![agent_id, created_before], memory_stats_from_row)?;
+    let rows: Vec<MemoryStatsRow> = rows.collect::<std::result::Result<_, _>>()?;
+    Ok(rows
+        .into_iter()
+        .filter(|r| !r.log_type.as_ref().is_some_and(|t| keep_log_types.contains(t)))
+        .collect())
+}
+
+/// セッションログをアーカイブする。全文検索・ベクトル検索・統合の対象から外れる（会話履歴には残る）
+pub fn archive_session_logs(conn: &Connection, ids: &[i64]) -> Result<usize> {
+    let now = Utc::now().to_rfc3339();
+    let mut archived = 0;
+    for id in ids {
+        let updated = conn.execute(
+            "UPDATE memory_sessions SET archived_at = ?2 WHERE id = ?1 AND archived_at IS NULL",
+            params![id, now],
+        )?;
+        if updated > 0 {
+            conn.execute("DELETE FROM memory_sessions_fts WHERE rowid = ?1", params![id])?;
+            conn.execute(
+                "DELETE FROM memory_embeddings WHERE source = 'session' AND source_id = CAST(?1 AS TEXT)",
+                params![id],
+            )?;
+            archived += updated;
+        }
+    }
+    Ok(archived)
+}
+
+/// `archived_before` より前にアーカイブされたエージェント自身のセッションログを削除する。
+///
+/// 会話履歴から消えるため、セッションの最新のローリング要約（`covers_until`）に含まれたログだけを削除する。
+pub fn delete_archived_session_logs(conn: &Connection, agent_id: &str, archived_before: &str) -> Result<usize> {
+    let ids: Vec<i64> = {
+        let mut stmt = conn.prepare(
+            "SELECT m.id FROM memory_sessions m
+             WHERE m.agent_id = ?1 AND m.archived_at IS NOT NULL AND m.archived_at < ?2
+               AND m.id <= COALESCE((
+                   SELECT json_extract(s.metadata_json, '$.covers_until') FROM memory_sessions s
+                   WHERE s.session_id = m.session_id AND s.log_type = 'summary'
+                   ORDER BY s.id DESC LIMIT 1
+               ), 0)",
+        )?;
+        let rows = stmt.query_map(params![agent_id, archived_before], |row| row.get(0))?;
+        rows.collect::<std::result::Result<_, _>>()?
+    };
+    for id in &ids {
+        conn.execute("DELETE FROM memory_sessions WHERE id = ?1", params![id])?;
+    }
+    Ok(ids.len())
+}
+
+// ============================================
+// Model Pricing
+// ============================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelPricingRow {
+    pub provider: String,
+    pub model: String,
+    pub input_price_per_1m: f64,
+    pub output_price_per_1m: f64,
+    pub context_window: Option<i32>,
+}
+
+pub fn upsert_model_pricing(conn: &Connection, pricing: &ModelPricingRow) -> Result<()> {
+    conn.execute(
+        "INSERT INTO model_pricing (provider, model, input_price_per_1m, output_price_per_1m, context_window, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(provider, model) DO UPDATE SET
+            input_price_per_1m = excluded.input_price_per_1m,
+            output_price_per_1m = excluded.output_price_per_1m,
+            context_window = excluded.context_window,
+            updated_at = excluded.updated_at",
+        params![
+            pricing.provider,
+            pricing.model,
+            pricing.input_price_per_1m,
+            pricing.output_price_per_1m,
+            pricing.context_window,
+            Utc::now().to_rfc3339(),
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn get_model_pricing(
+    conn: &Connection,
+    provider: &str,
+    model: &str,
+) -> Result<Option<ModelPricingRow>> {
+    let result = conn.query_row(
+        "SELECT provider, model, input_price_per_1m, output_price_per_1m, context_window
+         FROM model_pricing WHERE provider = ?1 AND model = ?2",
+        params![provider, model],
+        |row| {
+            Ok(ModelPricingRow {
+                provider: row.get(0)?,
+                model: row.get(1)?,
+                input_price_per_1m: row.get(2)?,
+                output_price_per_1m: row.get(3)?,
+                context_window: row.get(4)?,
+            })
+        },
+    );
+
+    match result {
+        Ok(p) => Ok(Some(p)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// 登録済みの価格を全件取得する（provider, model 順）
+pub fn list_model_pricing(conn: &Connection) -> Result<Vec<ModelPricingRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT provider, model, input_price_per_1m, output_price_per_1m, context_window
+         FROM model_pricing ORDER BY provider, model",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(ModelPricingRow {
+            provider: row.get(0)?,
+            model: row.get(1)?,
+            input_price_per_1m: row.get(2)?,
+            output_price_per_1m: row.get(3)?,
+            context_window: row.get(4)?,
+        })
+    })?;
+    Ok(rows.collect::<std::result::Result<_, _>>()?)
+}
+
+/// 価格を削除する。削除した場合 true を返す
+pub fn delete_model_pricing(conn: &Connection, provider: &str, model: &str) -> Result<bool> {
+    let deleted = conn.execute(
+        "DELETE FROM model_pricing WHERE provider = ?1 AND model = ?2",
+        params![provider, model],
+    )?;
+    Ok(deleted > 0)
+}
+
+// ============================================
+// Discord Channel Config
+// ============================================
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChannelConfigRow {
     pub channel_id: String,
@@ -1315,6 +2136,76 @@ pub fn list_enabled_agent_discord_configs(conn: &Connection) -> Result<Vec<Agent
     Ok(rows.collect::<std::result::Result<_, _>>()?)
 }
 
+// ============================================
+// Agent LLM Config
+// ============================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentLlmConfigRow {
+    pub agent_id: String,
+    pub default_provider: String,
+    pub default_model: String,
+    pub models_json: String,
+    pub allow_self_selection: bool,
+    pub selectable_models_json: String,
+}
+
+pub fn upsert_agent_llm_config(conn: &Connection, cfg: &AgentLlmConfigRow) -> Result<()> {
+    conn.execute(
+        "INSERT INTO agent_llm_config (agent_id, default_provider, default_model, models_json, allow_self_selection, selectable_models_json, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+         ON CONFLICT(agent_id) DO UPDATE SET
+            default_provider = excluded.default_provider,
+            default_model = excluded.default_model,
+            models_json = excluded.models_json,
+            allow_self_selection = excluded.allow_self_selection,
+            selectable_models_json = excluded.selectable_models_json,
+            updated_at = excluded.updated_at",
+        params![
+            cfg.agent_id,
+            cfg.default_provider,
+            cfg.default_model,
+            cfg.models_json,
+            cfg.allow_self_selection,
+            cfg.selectable_models_json,
+            Utc::now().to_rfc3339(),
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn get_agent_llm_config(conn: &Connection, agent_id: &str) -> Result<Option<AgentLlmConfigRow>> {
+    let result = conn.query_row(
+        "SELECT agent_id, default_provider, default_model, models_json, allow_self_selection, selectable_models_json
+         FROM agent_llm_config WHERE agent_id = ?1",
+        params![agent_id],
+        |row| {
+            Ok(AgentLlmConfigRow {
+                agent_id: row.get(0)?,
+                default_provider: row.get(1)?,
+                default_model: row.get(2)?,
+                models_json: row.get(3)?,
+                allow_self_selection: row.get(4)?,
+                selectable_models_json: row.get(5)?,
+            })
+        },
+    );
+
+    match result {
+        Ok(cfg) => Ok(Some(cfg)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn delete_agent_llm_config(conn: &Connection, agent_id: &str) -> Result<bool> {
+    let deleted = conn.execute(
+        "DELETE FROM agent_llm_config WHERE agent_id = ?1",
+        params![agent_id],
+    )?;
+    Ok(deleted > 0)
+}
+
 /// チャンネルが読み取り可能か判定する。設定なし=true（デフォルト許可）。
 pub fn is_channel_readable(conn: &Connection, channel_id: &str) -> bool {
     get_channel_config(conn, channel_id)
@@ -1333,6 +2224,120 @@ pub fn is_channel_writable(conn: &Connection, channel_id: &str) -> bool {
         .unwrap_or(true)
 }
 
+// ============================================
+// Prompt Templates
+// ============================================
+
+/// 全エージェント共通の既定テンプレートの scope
+pub const PROMPT_TEMPLATE_GLOBAL: &str = "*";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptTemplateRow {
+    pub id: String,
+    /// エージェントID または [`PROMPT_TEMPLATE_GLOBAL`]
+    pub scope: String,
+    pub version: i64,
+    pub content: String,
+    pub note: Option<String>,
+    pub active: bool,
+    pub created_at: String,
+}
+
+fn prompt_template_from_row(row: &rusqlite::Row) -> rusqlite::Result<PromptTemplateRow> {
+    Ok(PromptTemplateRow {
+        id: row.get(0)?,
+        scope: row.get(1)?,
+        version: row.get(2)?,
+        content: row.get(3)?,
+        note: row.get(4)?,
+        active: row.get::<_, i64>(5)? != 0,
+        created_at: row.get(6)?,
+    })
+}
+
+/// 新しいバージョンとして保存し、それを有効にする
+pub fn insert_prompt_template_version(
+    conn: &Connection,
+    scope: &str,
+    content: &str,
+    note: Option<&str>,
+) -> Result<PromptTemplateRow> {
+    let version: i64 = conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) + 1 FROM prompt_templates WHERE scope = ?1",
+        params![scope],
+        |row| row.get(0),
+    )?;
+    let row = PromptTemplateRow {
+        id: format!("{scope}:v{version}"),
+        scope: scope.to_string(),
+        version,
+        content: content.to_string(),
+        note: note.map(str::to_string),
+        active: true,
+        created_at: Utc::now().to_rfc3339(),
+    };
+    conn.execute(
+        "UPDATE prompt_templates SET active = 0 WHERE scope = ?1",
+        params![scope],
+    )?;
+    conn.execute(
+        "INSERT INTO prompt_templates (id, scope, version, content, note, active, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, 1, ?6)",
+        params![row.id, row.scope, row.version, row.content, row.note, row.created_at],
+    )?;
+    Ok(row)
+}
+
+/// バージョン履歴（新しい順）
+pub fn list_prompt_templates(conn: &Connection, scope: &str) -> Result<Vec<PromptTemplateRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, scope, version, content, note, active, created_at
+         FROM prompt_templates WHERE scope = ?1 ORDER BY version DESC",
+    )?;
+    let rows = stmt.query_map(params![scope], prompt_template_from_row)?;
+    Ok(rows.collect::<std::result::Result<_, _>>()?)
+}
+
+pub fn get_active_prompt_template(conn: &Connection, scope: &str) -> Result<Option<PromptTemplateRow>> {
+    let result = conn.query_row(
+        "SELECT id, scope, version, content, note, active, created_at
+         FROM prompt_templates WHERE scope = ?1 AND active = 1",
+        params![scope],
+        prompt_template_from_row,
+    );
+    match result {
+        Ok(row) => Ok(Some(row)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// 指定バージョンを有効にする（ロールバック）。バージョンがなければ false
+pub fn activate_prompt_template_version(conn: &Connection, scope: &str, version: i64) -> Result<bool> {
+    let exists: bool = conn.query_row(
+        "SELECT COUNT(*) FROM prompt_templates WHERE scope = ?1 AND version = ?2",
+        params![scope, version],
+        |row| row.get::<_, i64>(0),
+    )? > 0;
+    if !exists {
+        return Ok(false);
+    }
+    conn.execute(
+        "UPDATE prompt_templates SET active = (version = ?2) WHERE scope = ?1",
+        params![scope, version],
+    )?;
+    Ok(true)
+}
+
+/// scope のテンプレートを無効にする（履歴は残す）。有効なものがあれば true
+pub fn deactivate_prompt_templates(conn: &Connection, scope: &str) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE prompt_templates SET active = 0 WHERE scope = ?1 AND active = 1",
+        params![scope],
+    )?;
+    Ok(updated > 0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1568,6 +2573,104 @@ mod tests {
         assert!(results.is_empty());
     }
 
+    // 8a. test_fts_excludes_tool_logs
+    #[test]
+    fn test_fts_excludes_tool_logs() {
+        let conn = setup();
+
+        for (log_type, content) in [
+            (LOG_TYPE_TOOL_CALL, "search_my_history({\"query\":\"zebra\"})"),
+            (LOG_TYPE_TOOL_RESULT, "{\"results\":[\"zebra facts\"]}"),
+            ("speech", "Zebras have stripes."),
+            (LOG_TYPE_CONTEXT, "## Curated Memories\n- Zebras have stripes."),
+            (LOG_TYPE_BUDGET, "Budget exceeded while talking about zebras"),
+        ] {
+            let log = SessionLogRow {
+                id: None,
+                agent_id: "agent-1".to_string(),
+                session_id: "session-1".to_string(),
+                log_type: log_type.to_string(),
+                content: content.to_string(),
+                speaker_id: Some("agent-1".to_string()),
+                turn_number: None,
+                metadata_json: None,
+            };
+            insert_session_log(&conn, &log).unwrap();
+        }
+
+        let results = search_session_logs(&conn, "agent-1", "zebra", 10).unwrap();
+        assert_eq!(results.len(), 0);
+        let results = search_session_logs(&conn, "agent-1", "zebras", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].log_type, "speech");
+        let fts_rows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM memory_sessions_fts", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(fts_rows, 3);
+        let candidates = list_unembedded_memories(&conn, "agent-1", "m", 10).unwrap();
+        assert_eq!(candidates.len(), 1);
+    }
+
+    // 8b. test_memory_embeddings
+    #[test]
+    fn test_memory_embeddings() {
+        let conn = setup();
+
+        let log = SessionLogRow {
+            id: None,
+            agent_id: "agent-1".to_string(),
+            session_id: "session-1".to_string(),
+            log_type: "speech".to_string(),
+            content: "Cats are great.".to_string(),
+            speaker_id: Some("agent-1".to_string()),
+            turn_number: None,
+            metadata_json: None,
+        };
+        let log_id = insert_session_log(&conn, &log).unwrap();
+        upsert_curated_memory(
+            &conn,
+            &CuratedMemoryRow {
+                id: "mem-1".to_string(),
+                agent_id: "agent-1".to_string(),
+                category: "facts".to_string(),
+                content: "Likes cats".to_string(),
+            },
+        )
+        .unwrap();
+
+        let pending = list_unembedded_memories(&conn, "agent-1", "m", 10).unwrap();
+        assert_eq!(pending.len(), 2);
+
+        upsert_embedding(&conn, EMBEDDING_SOURCE_SESSION, &log_id.to_string(), "agent-1", "m", &[0.5, -1.0]).unwrap();
+        upsert_embedding(&conn, EMBEDDING_SOURCE_CURATED, "mem-1", "agent-1", "m", &[1.0, 0.0]).unwrap();
+        assert!(list_unembedded_memories(&conn, "agent-1", "m", 10).unwrap().is_empty());
+        // 別モデルでは未計算扱い
+        assert_eq!(list_unembedded_memories(&conn, "agent-1", "other", 10).unwrap().len(), 2);
+
+        let embedded = list_embedded_memories(&conn, "agent-1", "m").unwrap();
+        assert_eq!(embedded.len(), 2);
+        let session = embedded.iter().find(|e| e.source == "session").unwrap();
+        assert_eq!(session.vector, vec![0.5, -1.0]);
+        assert_eq!(session.session_id.as_deref(), Some("session-1"));
+        let curated = embedded.iter().find(|e| e.source == "curated").unwrap();
+        assert_eq!(curated.category.as_deref(), Some("facts"));
+
+        // キュレーション記憶を更新するとベクトルは再計算対象に戻る
+        upsert_curated_memory(
+            &conn,
+            &CuratedMemoryRow {
+                id: "mem-1".to_string(),
+                agent_id: "agent-1".to_string(),
+                category: "facts".to_string(),
+                content: "Loves cats".to_string(),
+            },
+        )
+        .unwrap();
+        let pending = list_unembedded_memories(&conn, "agent-1", "m", 10).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].source_id, "mem-1");
+    }
+
     // 9. test_skills_crud
     #[test]
     fn test_skills_crud() {
@@ -1583,6 +2686,7 @@ mod tests {
             source_type: "acquired".to_string(),
             source_context: Some("learned from session-1".to_string()),
             file_path: None,
+            version: None,
             effectiveness: None,
             usage_count: 0,
             is_active: true,
@@ -1614,6 +2718,7 @@ mod tests {
             source_type: "acquired".to_string(),
             source_context: None,
             file_path: None,
+            version: None,
             effectiveness: None,
             usage_count: 0,
             is_active: true,
@@ -1627,6 +2732,45 @@ mod tests {
         assert_eq!(skills[0].usage_count, 1);
     }
 
+    // 10b. test_standard_skill_lookup_and_update
+    #[test]
+    fn test_standard_skill_lookup_and_update() {
+        let conn = setup();
+
+        let skill = SkillRow {
+            id: "skill-std".to_string(),
+            agent_id: "agent-1".to_string(),
+            name: "autonomous".to_string(),
+            description: "v1".to_string(),
+            situation_pattern: "[\"send_speech\"]".to_string(),
+            guidance: "guide v1".to_string(),
+            source_type: "standard".to_string(),
+            source_context: None,
+            file_path: Some("skills/autonomous.skill.md".to_string()),
+            version: Some("1".to_string()),
+            effectiveness: None,
+            usage_count: 3,
+            is_active: false,
+        };
+        insert_skill(&conn, &skill).unwrap();
+
+        assert!(get_standard_skill(&conn, "agent-1", "other").unwrap().is_none());
+        assert!(get_standard_skill(&conn, "agent-2", "autonomous").unwrap().is_none());
+
+        let mut found = get_standard_skill(&conn, "agent-1", "autonomous").unwrap().unwrap();
+        assert_eq!(found.version.as_deref(), Some("1"));
+
+        found.version = Some("2".to_string());
+        found.guidance = "guide v2".to_string();
+        update_skill_definition(&conn, &found).unwrap();
+
+        let updated = get_standard_skill(&conn, "agent-1", "autonomous").unwrap().unwrap();
+        assert_eq!(updated.version.as_deref(), Some("2"));
+        assert_eq!(updated.guidance, "guide v2");
+        assert_eq!(updated.usage_count, 3);
+        assert!(!updated.is_active);
+    }
+
     // 11. test_impressions_upsert_and_get
     #[test]
     fn test_impressions_upsert_and_get() {
@@ -1662,6 +2806,243 @@ mod tests {
         assert_eq!(fetched.last_updated_turn, 5);
     }
 
+    #[test]
+    fn test_get_latest_impressions_prefers_session() {
+        let conn = setup();
+        let imp = |id: &str, session: &str, target: &str, notes: &str| ImpressionRow {
+            id: id.to_string(),
+            agent_id: "agent-1".to_string(),
+            session_id: session.to_string(),
+            target_id: target.to_string(),
+            target_name: target.to_string(),
+            personality: String::new(),
+            communication_style: String::new(),
+            recent_behavior: String::new(),
+            agreement: String::new(),
+            notes: notes.to_string(),
+            last_updated_turn: 1,
+        };
+        upsert_impression(&conn, &imp("i1", "session-1", "bob", "bob in s1")).unwrap();
+        upsert_impression(&conn, &imp("i2", "session-2", "bob", "bob in s2")).unwrap();
+        upsert_impression(&conn, &imp("i3", "session-2", "carol", "carol in s2")).unwrap();
+        upsert_impression(&conn, &imp("i4", "session-2", "dave", "not present")).unwrap();
+
+        let targets = vec!["carol".to_string(), "bob".to_string()];
+        let latest = get_latest_impressions(&conn, "agent-1", &targets, Some("session-1")).unwrap();
+        assert_eq!(latest.len(), 2);
+        assert_eq!(latest[0].notes, "carol in s2");
+        assert_eq!(latest[1].notes, "bob in s1");
+
+        assert!(get_latest_impressions(&conn, "agent-1", &[], None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_search_session_logs_any_matches_either_term() {
+        let conn = setup();
+        for (i, content) in ["deploy pipeline broke", "lunch was great", "unrelated"].iter().enumerate() {
+            insert_session_log(
+                &conn,
+                &SessionLogRow {
+                    id: None,
+                    agent_id: "agent-1".to_string(),
+                    session_id: "s1".to_string(),
+                    log_type: "speech".to_string(),
+                    content: content.to_string(),
+                    speaker_id: None,
+                    turn_number: Some(i as i32),
+                    metadata_json: None,
+                },
+            )
+            .unwrap();
+        }
+        assert!(search_session_logs(&conn, "agent-1", "pipeline lunch", 10).unwrap().is_empty());
+        let results = search_session_logs_any(&conn, "agent-1", "pipeline lunch", 10).unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(search_session_logs_any(&conn, "agent-1", "  ", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_consolidation_checkpoint_and_sources() {
+        let conn = setup();
+        let log = |agent_id: &str, session_id: &str, log_type: &str, content: &str| {
+            insert_session_log(
+                &conn,
+                &SessionLogRow {
+                    id: None,
+                    agent_id: agent_id.to_string(),
+                    session_id: session_id.to_string(),
+                    log_type: log_type.to_string(),
+                    content: content.to_string(),
+                    speaker_id: Some(agent_id.to_string()),
+                    turn_number: None,
+                    metadata_json: None,
+                },
+            )
+            .unwrap()
+        };
+        let first = log("user-1", "s1", "speech", "I like tea");
+        log("agent-1", "s1", "context", "Injected 0 memory items");
+        let second = log("agent-1", "s1", "speech", "Noted");
+        log("user-1", "s2", "speech", "Unrelated session");
+
+        let logs = list_logs_for_consolidation(&conn, "agent-1", 0, 10).unwrap();
+        assert_eq!(logs.iter().map(|l| l.id.unwrap()).collect::<Vec<_>>(), vec![first, second]);
+        assert_eq!(get_consolidation_checkpoint(&conn, "agent-1").unwrap(), 0);
+
+        insert_consolidation_run(
+            &conn,
+            &ConsolidationRunRow {
+                id: None,
+                agent_id: "agent-1".to_string(),
+                from_log_id: first,
+                to_log_id: second,
+                processed_logs: 2,
+                added: 1,
+                merged: 0,
+                result_json: None,
+                created_at: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(get_consolidation_checkpoint(&conn, "agent-1").unwrap(), second);
+        assert!(list_logs_for_consolidation(&conn, "agent-1", second, 10).unwrap().is_empty());
+        assert_eq!(list_consolidation_runs(&conn, "agent-1", 5).unwrap().len(), 1);
+
+        let memory = CuratedMemoryRow {
+            id: "mem-1".to_string(),
+            agent_id: "agent-1".to_string(),
+            category: "preferences".to_string(),
+            content: "user-1 likes tea".to_string(),
+        };
+        upsert_curated_memory(&conn, &memory).unwrap();
+        assert!(get_curated_memory_sources(&conn, "mem-1").unwrap().is_empty());
+        add_curated_memory_sources(&conn, "mem-1", &[second, first]).unwrap();
+        add_curated_memory_sources(&conn, "mem-1", &[first]).unwrap();
+        assert_eq!(get_curated_memory_sources(&conn, "mem-1").unwrap(), vec![first, second]);
+    }
+
+    #[test]
+    fn test_memory_importance_access_and_archive() {
+        let conn = setup();
+        let log = |log_type: &str, content: &str| {
+            insert_session_log(
+                &conn,
+                &SessionLogRow {
+                    id: None,
+                    agent_id: "agent-1".to_string(),
+                    session_id: "s1".to_string(),
+                    log_type: log_type.to_string(),
+                    content: content.to_string(),
+                    speaker_id: None,
+                    turn_number: None,
+                    metadata_json: None,
+                },
+            )
+            .unwrap()
+        };
+        let speech = log("speech", "Rust borrow checker");
+        let summary = log("summary", "Rust was discussed");
+        upsert_curated_memory(
+            &conn,
+            &CuratedMemoryRow {
+                id: "mem-1".to_string(),
+                agent_id: "agent-1".to_string(),
+                category: "decisions".to_string(),
+                content: "Use Rust".to_string(),
+            },
+        )
+        .unwrap();
+
+        // Importance is set at write time from the log type / category.
+        let ids = vec![speech.to_string(), summary.to_string()];
+        let stats = get_memory_stats(&conn, EMBEDDING_SOURCE_SESSION, &ids).unwrap();
+        assert_eq!(stats[&speech.to_string()].importance, 0.5);
+        assert_eq!(stats[&summary.to_string()].importance, 0.8);
+        let mem = vec!["mem-1".to_string()];
+        assert_eq!(get_memory_stats(&conn, EMBEDDING_SOURCE_CURATED, &mem).unwrap()["mem-1"].importance, 0.8);
+
+        boost_curated_memory_importance(&conn, "mem-1", 0.5).unwrap();
+        record_memory_access(&conn, EMBEDDING_SOURCE_CURATED, &mem).unwrap();
+        record_memory_access(&conn, EMBEDDING_SOURCE_SESSION, &ids[..1]).unwrap();
+        let curated = &get_memory_stats(&conn, EMBEDDING_SOURCE_CURATED, &mem).unwrap()["mem-1"];
+        assert_eq!((curated.importance, curated.access_count), (1.0, 1));
+        assert!(curated.last_accessed_at.is_some());
+        assert_eq!(get_memory_stats(&conn, EMBEDDING_SOURCE_SESSION, &ids).unwrap()[&ids[0]].access_count, 1);
+
+        let future = (Utc::now() + chrono::Duration::days(1)).to_rfc3339();
+        let candidates = list_retention_candidates(&conn, "agent-1", &future, &["summary".to_string()]).unwrap();
+        assert_eq!(candidates.iter().map(|c| c.id.clone()).collect::<Vec<_>>(), vec![ids[0].clone()]);
+
+        assert_eq!(archive_session_logs(&conn, &[speech]).unwrap(), 1);
+        assert_eq!(archive_session_logs(&conn, &[speech]).unwrap(), 0);
+        assert!(search_session_logs(&conn, "agent-1", "borrow", 10).unwrap().is_empty());
+        assert!(list_retention_candidates(&conn, "agent-1", &future, &[]).unwrap().iter().all(|c| c.id != ids[0]));
+        // Archived logs stay in the conversation history until deleted.
+        assert_eq!(list_session_logs_by_session(&conn, "s1").unwrap().len(), 2);
+
+        // Logs are only deleted once a rolling summary covers them.
+        assert_eq!(delete_archived_session_logs(&conn, "agent-1", &future).unwrap(), 0);
+        let row = |agent_id: &str, log_type: &str, content: &str, metadata_json: Option<String>| {
+            insert_session_log(
+                &conn,
+                &SessionLogRow {
+                    id: None,
+                    agent_id: agent_id.to_string(),
+                    session_id: "s1".to_string(),
+                    log_type: log_type.to_string(),
+                    content: content.to_string(),
+                    speaker_id: None,
+                    turn_number: None,
+                    metadata_json,
+                },
+            )
+            .unwrap()
+        };
+        let other = row("agent-2", "speech", "Go channels", None);
+        let later = row("agent-1", "speech", "Rust lifetimes", None);
+        row("system", "summary", "Rust and Go were discussed", Some(format!(r#"{{"covers_until": {other}}}"#)));
+
+        // Other participants' logs are left to their own retention rules.
+        let candidates = list_retention_candidates(&conn, "agent-1", &future, &[]).unwrap();
+        assert!(candidates.iter().all(|c| c.id != other.to_string()), "{candidates:?}");
+        assert_eq!(archive_session_logs(&conn, &[later]).unwrap(), 1);
+
+        // The speech is covered by the summary; the later one is not.
+        assert_eq!(delete_archived_session_logs(&conn, "agent-1", &future).unwrap(), 1);
+        let remaining: Vec<i64> = list_session_logs_by_session(&conn, "s1").unwrap().iter().filter_map(|l| l.id).collect();
+        assert!(!remaining.contains(&speech));
+        assert!(remaining.contains(&later));
+    }
+
+    #[test]
+    fn test_prompt_template_versions_and_rollback() {
+        let conn = setup();
+        assert!(get_active_prompt_template(&conn, "agent-1").unwrap().is_none());
+
+        let v1 = insert_prompt_template_version(&conn, "agent-1", "v1 {{agent_name}}", Some("first")).unwrap();
+        let v2 = insert_prompt_template_version(&conn, "agent-1", "v2 {{agent_name}}", None).unwrap();
+        insert_prompt_template_version(&conn, PROMPT_TEMPLATE_GLOBAL, "global", None).unwrap();
+        assert_eq!((v1.version, v2.version), (1, 2));
+        assert_eq!(v2.id, "agent-1:v2");
+
+        let active = get_active_prompt_template(&conn, "agent-1").unwrap().unwrap();
+        assert_eq!(active.content, "v2 {{agent_name}}");
+        let history = list_prompt_templates(&conn, "agent-1").unwrap();
+        assert_eq!(history.iter().map(|r| r.version).collect::<Vec<_>>(), vec![2, 1]);
+        assert_eq!(history.iter().filter(|r| r.active).count(), 1);
+
+        assert!(activate_prompt_template_version(&conn, "agent-1", 1).unwrap());
+        assert!(!activate_prompt_template_version(&conn, "agent-1", 9).unwrap());
+        let active = get_active_prompt_template(&conn, "agent-1").unwrap().unwrap();
+        assert_eq!(active.note.as_deref(), Some("first"));
+
+        assert!(deactivate_prompt_templates(&conn, "agent-1").unwrap());
+        assert!(get_active_prompt_template(&conn, "agent-1").unwrap().is_none());
+        assert_eq!(list_prompt_templates(&conn, "agent-1").unwrap().len(), 2);
+        // The global default is independent of agent scopes.
+        assert!(get_active_prompt_template(&conn, PROMPT_TEMPLATE_GLOBAL).unwrap().is_some());
+    }
+
     // 12. test_session_crud
     #[test]
     fn test_session_crud() {
@@ -1700,6 +3081,71 @@ mod tests {
         assert_eq!(all[0].id, "session-1");
     }
 
+    // 12b. test_session_progress_and_done_declared
+    #[test]
+    fn test_session_progress_and_done_declared() {
+        let conn = setup();
+        insert_session(
+            &conn,
+            &SessionRow {
+                id: "session-1".to_string(),
+                mode: "facilitated".to_string(),
+                theme: "Progress".to_string(),
+                phase: "divergent".to_string(),
+                turn_number: 0,
+                status: "active".to_string(),
+                participant_ids_json: r#"["agent-1","agent-2"]"#.to_string(),
+                facilitator_id: None,
+                done_count: 0,
+                max_turns: None,
+                metadata_json: None,
+            },
+        )
+        .unwrap();
+
+        update_session_progress(&conn, "session-1", "convergent", 3).unwrap();
+        record_agent_speech(&conn, "agent-1", "session-1").unwrap();
+        assert_eq!(set_done_declared(&conn, "agent-1", "session-1", true).unwrap(), 1);
+        assert_eq!(set_done_declared(&conn, "agent-2", "session-1", true).unwrap(), 2);
+        assert_eq!(set_done_declared(&conn, "agent-2", "session-1", false).unwrap(), 1);
+        update_session_status(&conn, "session-1", "completed").unwrap();
+
+        let session = get_session(&conn, "session-1").unwrap().unwrap();
+        assert_eq!(session.phase, "convergent");
+        assert_eq!(session.turn_number, 3);
+        assert_eq!(session.done_count, 1);
+        assert_eq!(session.status, "completed");
+
+        let rows = list_agent_sessions(&conn, "session-1").unwrap();
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0].done_declared);
+        assert!(rows[0].last_speech_at.is_some());
+        assert!(!rows[1].done_declared);
+        assert!(rows[1].last_speech_at.is_none());
+    }
+
+    // 12c. test_agent_model_override
+    #[test]
+    fn test_agent_model_override() {
+        let conn = setup();
+        assert_eq!(get_agent_model_override(&conn, "agent-1", "session-1").unwrap(), None);
+
+        record_agent_speech(&conn, "agent-1", "session-1").unwrap();
+        set_agent_model_override(&conn, "agent-1", "session-1", Some("openai:gpt-4o-mini"), Some("this_session")).unwrap();
+        assert_eq!(
+            get_agent_model_override(&conn, "agent-1", "session-1").unwrap(),
+            Some(("openai:gpt-4o-mini".to_string(), "this_session".to_string()))
+        );
+        // 他のセッションには影響しない
+        assert_eq!(get_agent_model_override(&conn, "agent-1", "session-2").unwrap(), None);
+        // 発言記録で上書きされない
+        record_agent_speech(&conn, "agent-1", "session-1").unwrap();
+        assert!(get_agent_model_override(&conn, "agent-1", "session-1").unwrap().is_some());
+
+        set_agent_model_override(&conn, "agent-1", "session-1", None, None).unwrap();
+        assert_eq!(get_agent_model_override(&conn, "agent-1", "session-1").unwrap(), None);
+    }
+
     // 13. test_llm_metrics_insert_and_summary
     #[test]
     fn test_llm_metrics_insert_and_summary() {
@@ -1940,6 +3386,60 @@ mod tests {
         assert!((gpt4o_anl.total_cost - 0.008).abs() < 1e-9);
     }
 
+    // 14d. test_llm_cost_total
+    #[test]
+    fn test_llm_cost_total() {
+        let conn = setup();
+
+        let base = LlmMetricsRow {
+            id: "c-1".to_string(),
+            agent_id: "agent-1".to_string(),
+            session_id: Some("s-1".to_string()),
+            timestamp: "2024-01-01T10:00:00+00:00".to_string(),
+            provider: "openai".to_string(),
+            model: "gpt-4o".to_string(),
+            purpose: "conversation".to_string(),
+            task_type: None,
+            complexity: None,
+            input_tokens: 100,
+            output_tokens: 50,
+            total_tokens: 150,
+            estimated_cost_usd: 0.5,
+            latency_ms: 100,
+            time_to_first_token_ms: None,
+        };
+        insert_llm_metrics(&conn, &base).unwrap();
+        insert_llm_metrics(
+            &conn,
+            &LlmMetricsRow {
+                id: "c-2".to_string(),
+                session_id: Some("s-2".to_string()),
+                timestamp: "2024-01-02T10:00:00+00:00".to_string(),
+                estimated_cost_usd: 0.25,
+                ..base.clone()
+            },
+        )
+        .unwrap();
+        insert_llm_metrics(
+            &conn,
+            &LlmMetricsRow {
+                id: "c-3".to_string(),
+                agent_id: "agent-2".to_string(),
+                session_id: None,
+                estimated_cost_usd: 1.0,
+                ..base.clone()
+            },
+        )
+        .unwrap();
+
+        let total = |agent, session, since| get_llm_cost_total(&conn, agent, session, since).unwrap();
+        assert!((total(None, None, None) - 1.75).abs() < 1e-9);
+        assert!((total(Some("agent-1"), None, None) - 0.75).abs() < 1e-9);
+        assert!((total(Some("agent-1"), None, Some("2024-01-02T00:00:00+00:00")) - 0.25).abs() < 1e-9);
+        assert!((total(None, Some("s-1"), None) - 0.5).abs() < 1e-9);
+        assert_eq!(total(Some("nobody"), None, None), 0.0);
+    }
+
     // 15. test_model_pricing_upsert_and_get
     #[test]
     fn test_model_pricing_upsert_and_get() {
@@ -1963,6 +3463,57 @@ mod tests {
         assert!((fetched.input_price_per_1m - 30.0).abs() < 1e-9);
         assert!((fetched.output_price_per_1m - 60.0).abs() < 1e-9);
         assert_eq!(fetched.context_window, Some(128000));
+
+        upsert_model_pricing(
+            &conn,
+            &ModelPricingRow {
+                provider: "openrouter".to_string(),
+                model: "deepseek/deepseek-chat".to_string(),
+                input_price_per_1m: 0.14,
+                output_price_per_1m: 0.28,
+                context_window: None,
+            },
+        )
+        .unwrap();
+        let all = list_model_pricing(&conn).unwrap();
+        let keys: Vec<(&str, &str)> = all.iter().map(|p| (p.provider.as_str(), p.model.as_str())).collect();
+        assert_eq!(keys, vec![("openai", "gpt-4"), ("openrouter", "deepseek/deepseek-chat")]);
+
+        assert!(delete_model_pricing(&conn, "openai", "gpt-4").unwrap());
+        assert!(!delete_model_pricing(&conn, "openai", "gpt-4").unwrap());
+        assert!(get_model_pricing(&conn, "openai", "gpt-4").unwrap().is_none());
+    }
+
+    // 15b. test_agent_llm_config_crud
+    #[test]
+    fn test_agent_llm_config_crud() {
+        let conn = setup();
+        assert!(get_agent_llm_config(&conn, "agent-1").unwrap().is_none());
+
+        let mut cfg = AgentLlmConfigRow {
+            agent_id: "agent-1".to_string(),
+            default_provider: "ollama".to_string(),
+            default_model: "llama3".to_string(),
+            models_json: r#"{"analysis":{"provider":"openai","model":"gpt-4o"}}"#.to_string(),
+            allow_self_selection: false,
+            selectable_models_json: "[]".to_string(),
+        };
+        upsert_agent_llm_config(&conn, &cfg).unwrap();
+        let fetched = get_agent_llm_config(&conn, "agent-1").unwrap().unwrap();
+        assert_eq!(fetched.default_provider, "ollama");
+        assert_eq!(fetched.models_json, cfg.models_json);
+        assert!(!fetched.allow_self_selection);
+
+        cfg.default_model = "llama3.1".to_string();
+        cfg.allow_self_selection = true;
+        upsert_agent_llm_config(&conn, &cfg).unwrap();
+        let fetched = get_agent_llm_config(&conn, "agent-1").unwrap().unwrap();
+        assert_eq!(fetched.default_model, "llama3.1");
+        assert!(fetched.allow_self_selection);
+
+        assert!(delete_agent_llm_config(&conn, "agent-1").unwrap());
+        assert!(!delete_agent_llm_config(&conn, "agent-1").unwrap());
+        assert!(get_agent_llm_config(&conn, "agent-1").unwrap().is_none());
     }
 
     // 16. test_heartbeat_log_insert
@@ -1979,6 +3530,64 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    // 16b. test_heartbeat_log_list
+    #[test]
+    fn test_heartbeat_log_list() {
+        let conn = setup();
+
+        insert_heartbeat_log(&conn, "agent-1", "idle", None).unwrap();
+        insert_heartbeat_log(&conn, "agent-1", "speak", Some(r#"{"sessions":1}"#)).unwrap();
+        insert_heartbeat_log(&conn, "agent-2", "learn", None).unwrap();
+
+        let logs = list_heartbeat_logs(&conn, "agent-1", 10).unwrap();
+        assert_eq!(logs.len(), 2);
+        assert_eq!(logs[0].decision, "speak");
+        assert_eq!(logs[1].decision, "idle");
+
+        let logs = list_heartbeat_logs(&conn, "agent-1", 1).unwrap();
+        assert_eq!(logs.len(), 1);
+    }
+
+    // 16c. test_list_active_sessions_for_agent
+    #[test]
+    fn test_list_active_sessions_for_agent() {
+        let conn = setup();
+
+        for (id, status, participants) in [
+            ("s1", "active", r#"["agent-1","agent-2"]"#),
+            ("s2", "completed", r#"["agent-1"]"#),
+            ("s3", "active", r#"["agent-10"]"#),
+            ("s4", "running", r#"["agent-1"]"#),
+            ("s5", "paused", r#"["agent-1"]"#),
+        ] {
+            insert_session(
+                &conn,
+                &SessionRow {
+                    id: id.to_string(),
+                    mode: "autonomous".to_string(),
+                    theme: "t".to_string(),
+                    phase: "divergent".to_string(),
+                    turn_number: 0,
+                    status: status.to_string(),
+                    participant_ids_json: participants.to_string(),
+                    facilitator_id: None,
+                    done_count: 0,
+                    max_turns: None,
+                    metadata_json: None,
+                },
+            )
+            .unwrap();
+        }
+
+        let mut ids: Vec<String> = list_active_sessions_for_agent(&conn, "agent-1")
+            .unwrap()
+            .into_iter()
+            .map(|s| s.id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec!["s1", "s4"]);
+    }
+
     // ── delete_agent ──
 
     #[test]
@@ -2021,6 +3630,23 @@ mod tests {
             },
         )
         .unwrap();
+        insert_prompt_template_version(&conn, "del-1", "Agent {{agent_id}}", None).unwrap();
+        insert_prompt_template_version(&conn, PROMPT_TEMPLATE_GLOBAL, "Global", None).unwrap();
+        insert_consolidation_run(
+            &conn,
+            &ConsolidationRunRow {
+                id: None,
+                agent_id: "del-1".into(),
+                from_log_id: 0,
+                to_log_id: 3,
+                processed_logs: 3,
+                added: 1,
+                merged: 0,
+                result_json: None,
+                created_at: None,
+            },
+        )
+        .unwrap();
 
         // Verify data exists
         assert!(get_identity(&conn, "del-1").unwrap().is_some());
@@ -2034,6 +3660,10 @@ mod tests {
         assert!(get_identity(&conn, "del-1").unwrap().is_none());
         assert!(get_soul(&conn, "del-1").unwrap().is_none());
         assert!(list_curated_memories(&conn, "del-1").unwrap().is_empty());
+        assert!(list_prompt_templates(&conn, "del-1").unwrap().is_empty());
+        assert!(list_consolidation_runs(&conn, "del-1", 10).unwrap().is_empty());
+        // 共通テンプレートは残る
+        assert_eq!(list_prompt_templates(&conn, PROMPT_TEMPLATE_GLOBAL).unwrap().len(), 1);
     }
 
     #[test]
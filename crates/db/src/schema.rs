@@ -10,13 +10,44 @@ pub fn initialize(conn: &Connection) -> rusqlite::Result<()> {
 /// 既存テーブルへのマイグレーション（カラム追加など）
 fn migrate(conn: &Connection) -> rusqlite::Result<()> {
     // sessions.metadata_json カラム追加（既存DBへの対応）
+    add_column_if_missing(conn, "sessions", "metadata_json", "TEXT")?;
+    // skills.version カラム追加（スキルファイルのバージョン追跡）
+    add_column_if_missing(conn, "skills", "version", "TEXT")?;
+    // agent_sessions.model_override* カラム追加（select_llm の持続的なモデル切り替え）
+    add_column_if_missing(conn, "agent_sessions", "model_override", "TEXT")?;
+    add_column_if_missing(conn, "agent_sessions", "model_override_duration", "TEXT")?;
+    // memory_curated.source_log_ids_json カラム追加（記憶の統合の出典）
+    add_column_if_missing(conn, "memory_curated", "source_log_ids_json", "TEXT")?;
+    // 記憶の重要度・アクセス記録・アーカイブ（減衰と忘却）
+    for table in ["memory_curated", "memory_sessions"] {
+        add_column_if_missing(conn, table, "importance", "REAL NOT NULL DEFAULT 0.5")?;
+        add_column_if_missing(conn, table, "access_count", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(conn, table, "last_accessed_at", "TEXT")?;
+    }
+    add_column_if_missing(conn, "memory_sessions", "archived_at", "TEXT")?;
+    Ok(())
+}
+
+/// 指定カラムが存在しなければ追加する
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
     let has_col: bool = conn
-        .prepare("SELECT COUNT(*) FROM pragma_table_info('sessions') WHERE name='metadata_json'")?
+        .prepare(&format!(
+            "SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name='{}'",
+            table, column
+        ))?
         .query_row([], |row| row.get::<_, i64>(0))
         .map(|c| c > 0)
         .unwrap_or(false);
     if !has_col {
-        conn.execute_batch("ALTER TABLE sessions ADD COLUMN metadata_json TEXT")?;
+        conn.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, definition
+        ))?;
     }
     Ok(())
 }
@@ -57,6 +88,12 @@ CREATE TABLE IF NOT EXISTS memory_curated (
     agent_id TEXT NOT NULL,
     category TEXT NOT NULL,
     content TEXT NOT NULL,
+    -- 統合（consolidation）で抽出した記憶の出典（memory_sessions.id の JSON配列）
+    source_log_ids_json TEXT,
+    -- 重要度（書き込み時にカテゴリから決める。0.0〜1.0）とアクセス記録（減衰の計算に使う）
+    importance REAL NOT NULL DEFAULT 0.5,
+    access_count INTEGER NOT NULL DEFAULT 0,
+    last_accessed_at TEXT,
     updated_at TEXT NOT NULL
 );
 CREATE INDEX IF NOT EXISTS idx_memory_curated_agent ON memory_curated(agent_id);
@@ -74,6 +111,11 @@ CREATE TABLE IF NOT EXISTS memory_sessions (
     speaker_id TEXT,
     turn_number INTEGER,
     metadata_json TEXT,
+    -- 重要度（書き込み時に log_type から決める）・アクセス記録・アーカイブ日時（検索対象から外れる）
+    importance REAL NOT NULL DEFAULT 0.5,
+    access_count INTEGER NOT NULL DEFAULT 0,
+    last_accessed_at TEXT,
+    archived_at TEXT,
     created_at TEXT NOT NULL
 );
 CREATE INDEX IF NOT EXISTS idx_memory_sessions_agent ON memory_sessions(agent_id);
@@ -89,6 +131,23 @@ CREATE VIRTUAL TABLE IF NOT EXISTS memory_sessions_fts USING fts5(
     log_type UNINDEXED
 );
 
+-- ============================================
+-- MEMORY: 埋め込みベクトル（ハイブリッド検索用）
+-- ============================================
+-- source: 'session'（memory_sessions.id）/ 'curated'（memory_curated.id）
+-- vector: f32 リトルエンディアンの連結
+CREATE TABLE IF NOT EXISTS memory_embeddings (
+    source TEXT NOT NULL,
+    source_id TEXT NOT NULL,
+    agent_id TEXT NOT NULL,
+    model TEXT NOT NULL,
+    dims INTEGER NOT NULL,
+    vector BLOB NOT NULL,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (source, source_id, model)
+);
+CREATE INDEX IF NOT EXISTS idx_memory_embeddings_agent ON memory_embeddings(agent_id, model);
+
 -- ============================================
 -- Skills: スキル管理
 -- ============================================
@@ -102,6 +161,7 @@ CREATE TABLE IF NOT EXISTS skills (
     source_type TEXT NOT NULL DEFAULT 'standard',
     source_context TEXT,
     file_path TEXT,
+    version TEXT,
     effectiveness REAL,
     usage_count INTEGER NOT NULL DEFAULT 0,
     is_active INTEGER NOT NULL DEFAULT 1,
@@ -212,6 +272,22 @@ CREATE TABLE IF NOT EXISTS heartbeat_log (
 );
 CREATE INDEX IF NOT EXISTS idx_heartbeat_agent ON heartbeat_log(agent_id);
 
+-- ============================================
+-- 記憶の統合（セッションログ → キュレーション記憶）
+-- ============================================
+CREATE TABLE IF NOT EXISTS consolidation_runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    agent_id TEXT NOT NULL,
+    from_log_id INTEGER NOT NULL,
+    to_log_id INTEGER NOT NULL,  -- チェックポイント（次回はこれより後のログを読む）
+    processed_logs INTEGER NOT NULL,
+    added INTEGER NOT NULL,
+    merged INTEGER NOT NULL,
+    result_json TEXT,
+    created_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_consolidation_runs_agent ON consolidation_runs(agent_id);
+
 -- ============================================
 -- セッション状態
 -- ============================================
@@ -234,11 +310,14 @@ CREATE TABLE IF NOT EXISTS sessions (
 -- ============================================
 -- エージェントのセッション参加状態
 -- ============================================
+-- model_override: select_llm で this_session / until_task_complete を指定したモデル
 CREATE TABLE IF NOT EXISTS agent_sessions (
     agent_id TEXT NOT NULL,
     session_id TEXT NOT NULL,
     last_speech_at TEXT,
     done_declared INTEGER NOT NULL DEFAULT 0,
+    model_override TEXT,
+    model_override_duration TEXT,
     PRIMARY KEY (agent_id, session_id)
 );
 
@@ -270,6 +349,21 @@ CREATE TABLE IF NOT EXISTS soul_presets (
 );
 CREATE INDEX IF NOT EXISTS idx_soul_presets_agent ON soul_presets(agent_id);
 
+-- ============================================
+-- エージェント別LLM設定
+-- ============================================
+-- models_json: 用途別モデル（thinking / conversation / analysis / tool_calling / embedding）
+-- selectable_models_json: 自己選択で使えるモデルの一覧
+CREATE TABLE IF NOT EXISTS agent_llm_config (
+    agent_id TEXT PRIMARY KEY,
+    default_provider TEXT NOT NULL,
+    default_model TEXT NOT NULL,
+    models_json TEXT NOT NULL DEFAULT '{}',
+    allow_self_selection INTEGER NOT NULL DEFAULT 0,
+    selectable_models_json TEXT NOT NULL DEFAULT '[]',
+    updated_at TEXT NOT NULL
+);
+
 -- ============================================
 -- エージェント別Discord Bot設定
 -- ============================================
@@ -280,4 +374,21 @@ CREATE TABLE IF NOT EXISTS agent_discord_config (
     enabled INTEGER NOT NULL DEFAULT 1,
     updated_at TEXT NOT NULL
 );
+
+-- ============================================
+-- システムプロンプトのテンプレート（バージョン履歴付き）
+-- ============================================
+-- scope: エージェントID、または '*'（全エージェント共通の既定）
+-- scope ごとに active = 1 の行は高々1つ
+CREATE TABLE IF NOT EXISTS prompt_templates (
+    id TEXT PRIMARY KEY,
+    scope TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    note TEXT,
+    active INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL,
+    UNIQUE(scope, version)
+);
+CREATE INDEX IF NOT EXISTS idx_prompt_templates_scope ON prompt_templates(scope, active);
 "#;
@@ -1,20 +1,342 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::{mpsc, Mutex};
+use tracing::{debug, warn};
 
-use crate::message::{IncomingMessage, OutgoingMessage};
+use crate::message::{
+    Channel, IncomingMessage, MessageContent, MessageSource, MessageTarget, OutgoingMessage,
+    Sender,
+};
 use crate::traits::Gateway;
 
-/// WebSocketゲートウェイ（スタブ）
+/// クライアント → サーバーのフレーム
+///
+/// すべてのフレームはJSONテキストで、`type` フィールドで種別を判別する。
+///
+/// ```json
+/// {"type": "subscribe", "session_id": "s-1"}
+/// {"type": "unsubscribe", "session_id": "s-1"}
+/// {"type": "send_message", "session_id": "s-1", "agent_id": "user", "content": "hi", "request_id": "r-1"}
+/// {"type": "ping"}
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientFrame {
+    /// セッションのイベント購読を開始する
+    Subscribe { session_id: String },
+    /// セッションのイベント購読を解除する
+    Unsubscribe { session_id: String },
+    /// セッションにメッセージを投稿する（`POST /api/sessions/{id}/messages` と同じ処理）
+    ///
+    /// `agent_id` は参加エージェントのID、または人間の送信者のID（`user` など）。
+    /// 参加していない登録済みエージェントは `error` フレームで拒否される。
+    SendMessage {
+        session_id: String,
+        agent_id: String,
+        content: String,
+        /// クライアント側の相関ID。`accepted` / `error` フレームにそのまま返す
+        #[serde(default)]
+        request_id: Option<String>,
+    },
+    /// 死活確認
+    Ping,
+}
+
+/// サーバー → クライアントのフレーム
+///
+/// ```json
+/// {"type": "subscribed", "session_id": "s-1"}
+/// {"type": "accepted", "session_id": "s-1", "message_id": "...", "request_id": "r-1"}
+/// {"type": "log", "session_id": "s-1", "log": {"id": 1, "log_type": "speech", ...}}
+/// {"type": "tool_event", "session_id": "s-1", "agent_id": "a-1", "event": {"type": "tool_call_started", ...}}
+/// {"type": "response", "session_id": "s-1", "content": "...", "reply_to": "...", "metadata": {...}}
+/// {"type": "error", "message": "...", "request_id": "r-1"}
+/// {"type": "pong"}
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerFrame {
+    /// 購読開始の確認
+    Subscribed { session_id: String },
+    /// 購読解除の確認
+    Unsubscribed { session_id: String },
+    /// 送信者を確認して `send_message` を受け付けた（処理は非同期に進む）
+    Accepted {
+        session_id: String,
+        message_id: String,
+        request_id: Option<String>,
+    },
+    /// セッションログが追加された（`SessionLogRow` のJSON）
+    Log {
+        session_id: String,
+        log: serde_json::Value,
+    },
+    /// エージェントのツール呼び出しの進捗（エンジンイベントのJSON）
+    ToolEvent {
+        session_id: String,
+        agent_id: String,
+        event: serde_json::Value,
+    },
+    /// エージェントの応答
+    Response {
+        session_id: Option<String>,
+        content: String,
+        reply_to: Option<String>,
+        metadata: HashMap<String, serde_json::Value>,
+    },
+    /// フレームの解釈や処理に失敗した
+    Error {
+        message: String,
+        request_id: Option<String>,
+    },
+    /// `ping` への応答
+    Pong,
+}
+
+/// 接続ごとの状態
+struct Connection {
+    tx: mpsc::UnboundedSender<ServerFrame>,
+    sessions: HashSet<String>,
+}
+
+/// WebSocketゲートウェイ
 ///
 /// WebSocketによるリアルタイム双方向通信を提供する。
-/// 現在は未実装のプレースホルダー。
+/// ソケットの読み書き自体はHTTPサーバー（Axumの `/ws` ルート）が担い、
+/// このゲートウェイは接続・購読の管理とフレームの振り分けを行う。
+///
+/// # 使い方
+///
+/// ```ignore
+/// let gateway = WebSocketGateway::new(32);
+///
+/// // ソケット側
+/// let (conn_id, mut frames) = gateway.open_connection();
+/// gateway.handle_text(&conn_id, r#"{"type":"subscribe","session_id":"s-1"}"#).await;
+/// // frames から ServerFrame を受け取りソケットへ書き出す
+///
+/// // Core側
+/// let incoming = gateway.next_message().await?;
+/// gateway.publish("s-1", ServerFrame::Log { .. });
+/// ```
 pub struct WebSocketGateway {
-    _port: u16,
+    tx_in: mpsc::Sender<IncomingMessage>,
+    rx_in: Mutex<mpsc::Receiver<IncomingMessage>>,
+    connections: std::sync::Mutex<HashMap<String, Connection>>,
+    connected: AtomicBool,
 }
 
 impl WebSocketGateway {
-    pub fn new(port: u16) -> Self {
-        Self { _port: port }
+    /// 新しいWebSocketGatewayを作成する
+    ///
+    /// `buffer_size` は受信メッセージキューの容量を指定する。
+    pub fn new(buffer_size: usize) -> Self {
+        let (tx_in, rx_in) = mpsc::channel(buffer_size);
+        Self {
+            tx_in,
+            rx_in: Mutex::new(rx_in),
+            connections: std::sync::Mutex::new(HashMap::new()),
+            connected: AtomicBool::new(true),
+        }
+    }
+
+    /// 新しい接続を登録し、接続IDと送信フレームの受信口を返す
+    pub fn open_connection(&self) -> (String, mpsc::UnboundedReceiver<ServerFrame>) {
+        let connection_id = uuid::Uuid::new_v4().to_string();
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.connections.lock().unwrap().insert(
+            connection_id.clone(),
+            Connection {
+                tx,
+                sessions: HashSet::new(),
+            },
+        );
+        debug!(connection_id = %connection_id, "WebSocket connection opened");
+        (connection_id, rx)
+    }
+
+    /// 接続を破棄する（購読もすべて解除される）
+    pub fn close_connection(&self, connection_id: &str) {
+        if self.connections.lock().unwrap().remove(connection_id).is_some() {
+            debug!(connection_id = %connection_id, "WebSocket connection closed");
+        }
+    }
+
+    /// 現在の接続数
+    pub fn connection_count(&self) -> usize {
+        self.connections.lock().unwrap().len()
+    }
+
+    /// 指定セッションを購読している接続数
+    pub fn subscriber_count(&self, session_id: &str) -> usize {
+        self.connections
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|c| c.sessions.contains(session_id))
+            .count()
+    }
+
+    /// 指定接続へフレームを送る。接続が存在しなければ `false`
+    pub fn send_to(&self, connection_id: &str, frame: ServerFrame) -> bool {
+        let connections = self.connections.lock().unwrap();
+        match connections.get(connection_id) {
+            Some(conn) => conn.tx.send(frame).is_ok(),
+            None => false,
+        }
+    }
+
+    /// セッションの購読者全員へフレームを配信し、配信できた接続数を返す
+    pub fn publish(&self, session_id: &str, frame: ServerFrame) -> usize {
+        let connections = self.connections.lock().unwrap();
+        connections
+            .values()
+            .filter(|c| c.sessions.contains(session_id))
+            .filter(|c| c.tx.send(frame.clone()).is_ok())
+            .count()
+    }
+
+    /// クライアントから届いたテキストフレームを処理する
+    ///
+    /// 解釈できないフレームには `error` フレームを返す。
+    pub async fn handle_text(&self, connection_id: &str, text: &str) {
+        match serde_json::from_str::<ClientFrame>(text) {
+            Ok(frame) => self.handle_frame(connection_id, frame).await,
+            Err(e) => {
+                self.send_to(
+                    connection_id,
+                    ServerFrame::Error {
+                        message: format!("Invalid frame: {e}"),
+                        request_id: None,
+                    },
+                );
+            }
+        }
+    }
+
+    /// クライアントフレームを処理する
+    pub async fn handle_frame(&self, connection_id: &str, frame: ClientFrame) {
+        let reply = match frame {
+            ClientFrame::Subscribe { session_id } => {
+                let mut connections = self.connections.lock().unwrap();
+                match connections.get_mut(connection_id) {
+                    Some(conn) => {
+                        conn.sessions.insert(session_id.clone());
+                        ServerFrame::Subscribed { session_id }
+                    }
+                    None => return,
+                }
+            }
+            ClientFrame::Unsubscribe { session_id } => {
+                let mut connections = self.connections.lock().unwrap();
+                match connections.get_mut(connection_id) {
+                    Some(conn) => {
+                        conn.sessions.remove(&session_id);
+                        ServerFrame::Unsubscribed { session_id }
+                    }
+                    None => return,
+                }
+            }
+            ClientFrame::SendMessage {
+                session_id,
+                agent_id,
+                content,
+                request_id,
+            } => {
+                let message = IncomingMessage::new(
+                    MessageSource::WebSocket {
+                        connection_id: connection_id.to_string(),
+                    },
+                    MessageContent::text(content),
+                    Sender::user(agent_id.clone(), agent_id),
+                )
+                .with_channel(Channel {
+                    id: session_id.clone(),
+                    name: session_id.clone(),
+                })
+                .with_metadata("session_id", serde_json::json!(session_id))
+                .with_metadata("request_id", serde_json::json!(request_id));
+
+                // `accepted` は受け取り側が送信者を確認してから `accept` で返す
+                match self.tx_in.send(message).await {
+                    Ok(()) => return,
+                    Err(e) => ServerFrame::Error {
+                        message: format!("Failed to submit message: {e}"),
+                        request_id,
+                    },
+                }
+            }
+            ClientFrame::Ping => ServerFrame::Pong,
+        };
+
+        self.send_to(connection_id, reply);
+    }
+
+    /// 投稿メッセージを受け付けたことを送信元の接続に `accepted` フレームで返す
+    pub fn accept(&self, message: &IncomingMessage) -> bool {
+        let MessageSource::WebSocket { connection_id } = &message.source else {
+            return false;
+        };
+        let frame = ServerFrame::Accepted {
+            session_id: message.channel.as_ref().map(|c| c.id.clone()).unwrap_or_default(),
+            message_id: message.id.clone(),
+            request_id: message
+                .metadata
+                .get("request_id")
+                .and_then(|v| v.as_str())
+                .map(String::from),
+        };
+        self.send_to(connection_id, frame)
+    }
+
+    /// クライアントから投稿された次のメッセージを待つ
+    ///
+    /// `Gateway::receive` と同じだが `&self` で呼べる（`Arc` 共有向け）。
+    pub async fn next_message(&self) -> Result<IncomingMessage> {
+        let mut rx = self.rx_in.lock().await;
+        rx.recv()
+            .await
+            .context("WebSocket gateway receive channel closed")
+    }
+
+    /// `OutgoingMessage` を `response` フレームとして配信する
+    pub fn deliver(&self, message: OutgoingMessage) -> Result<()> {
+        let content = match &message.content {
+            MessageContent::Text(text) => text.clone(),
+            other => serde_json::to_string(other)?,
+        };
+        let session_id = match &message.target {
+            MessageTarget::Channel { id } => Some(id.clone()),
+            _ => None,
+        };
+        let frame = ServerFrame::Response {
+            session_id: session_id.clone(),
+            content,
+            reply_to: message.reply_to.clone(),
+            metadata: message.metadata.clone(),
+        };
+
+        let delivered = match &message.target {
+            MessageTarget::Channel { id } => self.publish(id, frame),
+            MessageTarget::DirectMessage { user_id } => {
+                usize::from(self.send_to(user_id, frame))
+            }
+            MessageTarget::Broadcast => {
+                let connections = self.connections.lock().unwrap();
+                connections
+                    .values()
+                    .filter(|c| c.tx.send(frame.clone()).is_ok())
+                    .count()
+            }
+        };
+
+        if delivered == 0 {
+            debug!(target = ?message.target, "WebSocket response had no recipients");
+        }
+        Ok(())
     }
 }
 
@@ -25,18 +347,178 @@ impl Gateway for WebSocketGateway {
     }
 
     async fn receive(&mut self) -> Result<IncomingMessage> {
-        todo!("WebSocket gateway receive not yet implemented")
+        self.next_message().await
     }
 
-    async fn send(&self, _message: OutgoingMessage) -> Result<()> {
-        todo!("WebSocket gateway send not yet implemented")
+    async fn send(&self, message: OutgoingMessage) -> Result<()> {
+        if !self.connected.load(Ordering::SeqCst) {
+            warn!("WebSocket gateway send() called while disconnected; message dropped");
+            return Ok(());
+        }
+        self.deliver(message)
     }
 
+    /// ソケットの受け付けはAxumの `/ws` ルートが行うため、フラグを立てるだけ
     async fn connect(&mut self) -> Result<()> {
-        todo!("WebSocket gateway connect not yet implemented")
+        self.connected.store(true, Ordering::SeqCst);
+        debug!("WebSocket gateway connected");
+        Ok(())
     }
 
+    /// すべての接続を破棄する（送信口が閉じ、各ソケットの書き込みタスクが終了する）
     async fn disconnect(&mut self) -> Result<()> {
-        todo!("WebSocket gateway disconnect not yet implemented")
+        self.connected.store(false, Ordering::SeqCst);
+        self.connections.lock().unwrap().clear();
+        debug!("WebSocket gateway disconnected");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_json_format() {
+        let frame: ClientFrame =
+            serde_json::from_str(r#"{"type":"subscribe","session_id":"s-1"}"#).unwrap();
+        assert_eq!(
+            frame,
+            ClientFrame::Subscribe {
+                session_id: "s-1".to_string()
+            }
+        );
+
+        let frame: ClientFrame = serde_json::from_str(
+            r#"{"type":"send_message","session_id":"s-1","agent_id":"u","content":"hi"}"#,
+        )
+        .unwrap();
+        assert!(matches!(frame, ClientFrame::SendMessage { request_id: None, .. }));
+
+        let json = serde_json::to_value(ServerFrame::Pong).unwrap();
+        assert_eq!(json, serde_json::json!({"type": "pong"}));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_and_publish() {
+        let gateway = WebSocketGateway::new(8);
+        let (a, mut rx_a) = gateway.open_connection();
+        let (_b, mut rx_b) = gateway.open_connection();
+
+        gateway
+            .handle_text(&a, r#"{"type":"subscribe","session_id":"s-1"}"#)
+            .await;
+        assert!(matches!(rx_a.recv().await, Some(ServerFrame::Subscribed { .. })));
+        assert_eq!(gateway.subscriber_count("s-1"), 1);
+
+        let delivered = gateway.publish(
+            "s-1",
+            ServerFrame::Log {
+                session_id: "s-1".to_string(),
+                log: serde_json::json!({"content": "hello"}),
+            },
+        );
+        assert_eq!(delivered, 1);
+        match rx_a.recv().await {
+            Some(ServerFrame::Log { log, .. }) => assert_eq!(log["content"], "hello"),
+            other => panic!("unexpected frame: {other:?}"),
+        }
+        assert!(rx_b.try_recv().is_err());
+
+        gateway
+            .handle_frame(
+                &a,
+                ClientFrame::Unsubscribe {
+                    session_id: "s-1".to_string(),
+                },
+            )
+            .await;
+        assert!(matches!(rx_a.recv().await, Some(ServerFrame::Unsubscribed { .. })));
+        assert_eq!(gateway.subscriber_count("s-1"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_send_message_is_received() {
+        let mut gateway = WebSocketGateway::new(8);
+        let (conn, mut rx) = gateway.open_connection();
+
+        gateway
+            .handle_text(
+                &conn,
+                r#"{"type":"send_message","session_id":"s-1","agent_id":"user","content":"hi","request_id":"r-1"}"#,
+            )
+            .await;
+
+        // Nothing is acknowledged until the consumer accepts the message.
+        assert!(rx.try_recv().is_err());
+        let incoming = gateway.receive().await.unwrap();
+        assert!(gateway.accept(&incoming));
+        match rx.recv().await {
+            Some(ServerFrame::Accepted {
+                session_id,
+                message_id,
+                request_id,
+            }) => {
+                assert_eq!(session_id, "s-1");
+                assert_eq!(request_id.as_deref(), Some("r-1"));
+                assert_eq!(message_id, incoming.id);
+            }
+            other => panic!("unexpected frame: {other:?}"),
+        }
+        assert_eq!(incoming.content.as_text(), Some("hi"));
+        assert_eq!(incoming.sender.id, "user");
+        assert_eq!(incoming.metadata["session_id"], "s-1");
+        assert!(matches!(
+            incoming.source,
+            MessageSource::WebSocket { ref connection_id } if connection_id == &conn
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_frame_and_ping() {
+        let gateway = WebSocketGateway::new(8);
+        let (conn, mut rx) = gateway.open_connection();
+
+        gateway.handle_text(&conn, "not json").await;
+        assert!(matches!(rx.recv().await, Some(ServerFrame::Error { .. })));
+
+        gateway.handle_text(&conn, r#"{"type":"ping"}"#).await;
+        assert_eq!(rx.recv().await, Some(ServerFrame::Pong));
+    }
+
+    #[tokio::test]
+    async fn test_send_to_channel_and_disconnect() {
+        let mut gateway = WebSocketGateway::new(8);
+        let (conn, mut rx) = gateway.open_connection();
+        gateway
+            .handle_frame(
+                &conn,
+                ClientFrame::Subscribe {
+                    session_id: "s-1".to_string(),
+                },
+            )
+            .await;
+        rx.recv().await.unwrap();
+
+        let reply = OutgoingMessage::text_to_channel("answer", "s-1")
+            .with_metadata("agent_id", serde_json::json!("a-1"));
+        gateway.send(reply).await.unwrap();
+        match rx.recv().await {
+            Some(ServerFrame::Response {
+                session_id,
+                content,
+                metadata,
+                ..
+            }) => {
+                assert_eq!(session_id.as_deref(), Some("s-1"));
+                assert_eq!(content, "answer");
+                assert_eq!(metadata["agent_id"], "a-1");
+            }
+            other => panic!("unexpected frame: {other:?}"),
+        }
+
+        gateway.disconnect().await.unwrap();
+        assert_eq!(gateway.connection_count(), 0);
+        assert!(rx.recv().await.is_none());
     }
 }
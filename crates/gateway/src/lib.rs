@@ -11,7 +11,7 @@ pub use message::{
 };
 pub use adapters::rest::RestGateway;
 pub use adapters::cli::CliGateway;
-pub use adapters::websocket::WebSocketGateway;
+pub use adapters::websocket::{ClientFrame, ServerFrame, WebSocketGateway};
 
 #[cfg(feature = "discord")]
 pub use adapters::discord::DiscordGateway;
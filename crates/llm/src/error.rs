@@ -0,0 +1,354 @@
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use serde_json::Value;
+
+/// Typed errors returned by providers.
+///
+/// Providers wrap these in `anyhow::Error`; callers recover them with
+/// `err.downcast_ref::<LlmError>()` (or [`classify`]) to decide whether to
+/// retry, fall back to another model, or fix the request.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum LlmError {
+    /// The prompt does not fit in the model's context window.
+    #[error("{provider} context length exceeded for {model}: {message}")]
+    ContextOverflow {
+        provider: String,
+        model: String,
+        status: u16,
+        message: String,
+    },
+    /// Missing, invalid or insufficient credentials.
+    #[error("{provider} authentication failed ({status}): {message}")]
+    Auth {
+        provider: String,
+        model: String,
+        status: u16,
+        message: String,
+    },
+    /// Too many requests; `retry_after` comes from the `Retry-After` header.
+    #[error("{provider} rate limited ({status}): {message}")]
+    RateLimit {
+        provider: String,
+        model: String,
+        status: u16,
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    /// The input or output was blocked by the provider's content policy.
+    #[error("{provider} content filtered for {model}: {message}")]
+    ContentFilter {
+        provider: String,
+        model: String,
+        status: u16,
+        message: String,
+    },
+    /// The request never got an HTTP response (timeout, refused connection, ...).
+    #[error("{provider} network error: {message}")]
+    Network {
+        provider: String,
+        model: String,
+        message: String,
+    },
+    /// Any other non-success HTTP status.
+    #[error("{provider} API error ({status}): {message}")]
+    Api {
+        provider: String,
+        model: String,
+        status: u16,
+        message: String,
+        /// Delay requested by the `Retry-After` header.
+        retry_after: Option<Duration>,
+    },
+}
+
+impl LlmError {
+    /// Build an error from a failed response, reading the error message from
+    /// the body and the `Retry-After` header.
+    pub async fn from_response(provider: &str, model: &str, resp: reqwest::Response) -> Self {
+        let status = resp.status().as_u16();
+        let retry_after = retry_after(resp.headers());
+        let body = resp.text().await.unwrap_or_default();
+        Self::from_body(provider, model, status, retry_after, &body)
+    }
+
+    /// Classify an HTTP error body into a variant.
+    pub fn from_body(
+        provider: &str,
+        model: &str,
+        status: u16,
+        retry_after: Option<Duration>,
+        body: &str,
+    ) -> Self {
+        let (message, code) = error_message(body);
+        let provider = provider.to_string();
+        let model = model.to_string();
+        let haystack = format!("{} {}", code, message).to_lowercase();
+
+        if matches!(status, 401 | 403) {
+            Self::Auth { provider, model, status, message }
+        } else if status == 429 {
+            Self::RateLimit { provider, model, status, message, retry_after }
+        } else if status == 413 || CONTEXT_OVERFLOW_HINTS.iter().any(|h| haystack.contains(h)) {
+            Self::ContextOverflow { provider, model, status, message }
+        } else if CONTENT_FILTER_HINTS.iter().any(|h| haystack.contains(h)) {
+            Self::ContentFilter { provider, model, status, message }
+        } else {
+            Self::Api { provider, model, status, message, retry_after }
+        }
+    }
+
+    /// A transport failure before any HTTP response.
+    pub fn network(provider: &str, model: &str, err: reqwest::Error) -> Self {
+        Self::Network {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            message: err.to_string(),
+        }
+    }
+
+    pub fn provider(&self) -> &str {
+        match self {
+            Self::ContextOverflow { provider, .. }
+            | Self::Auth { provider, .. }
+            | Self::RateLimit { provider, .. }
+            | Self::ContentFilter { provider, .. }
+            | Self::Network { provider, .. }
+            | Self::Api { provider, .. } => provider,
+        }
+    }
+
+    pub fn model(&self) -> &str {
+        match self {
+            Self::ContextOverflow { model, .. }
+            | Self::Auth { model, .. }
+            | Self::RateLimit { model, .. }
+            | Self::ContentFilter { model, .. }
+            | Self::Network { model, .. }
+            | Self::Api { model, .. } => model,
+        }
+    }
+
+    /// HTTP status, when the provider answered.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::ContextOverflow { status, .. }
+            | Self::Auth { status, .. }
+            | Self::RateLimit { status, .. }
+            | Self::ContentFilter { status, .. }
+            | Self::Api { status, .. } => Some(*status),
+            Self::Network { .. } => None,
+        }
+    }
+
+    /// Whether the same request may succeed when sent again later.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::RateLimit { .. } | Self::Network { .. } => true,
+            Self::Api { status, .. } => matches!(status, 408 | 409 | 425 | 500..=599),
+            _ => false,
+        }
+    }
+
+    /// Whether another candidate model may succeed where this one failed.
+    ///
+    /// Context overflows and content filters are about the request itself:
+    /// the caller has to change it (trim the context, rephrase) rather than
+    /// shop it around.
+    pub fn should_fallback(&self) -> bool {
+        !matches!(self, Self::ContextOverflow { .. } | Self::ContentFilter { .. })
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimit { retry_after, .. } | Self::Api { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+/// Phrases providers use for prompts that exceed the context window
+/// (matched against the lowercased error code and message).
+const CONTEXT_OVERFLOW_HINTS: &[&str] = &[
+    "context_length_exceeded",
+    "context length",
+    "maximum context",
+    "context window",
+    "context size",
+    "prompt is too long",
+    "too many tokens",
+    "input token count",
+    "exceeds the maximum number of tokens",
+    "reduce the length",
+];
+
+/// Phrases providers use for content policy refusals.
+const CONTENT_FILTER_HINTS: &[&str] = &[
+    "content_filter",
+    "content_policy",
+    "content policy",
+    "content management policy",
+    "safety",
+    "moderation",
+    "flagged",
+];
+
+/// Extract a human-readable message and an error code from an error body.
+///
+/// Understands `{"error": {"message": .., "code"/"type"/"status": ..}}`
+/// (OpenAI, Anthropic, Google, OpenRouter), `{"error": ".."}` (Ollama,
+/// llama.cpp) and `{"message": ..}`; anything else is returned as
+/// (truncated) text.
+fn error_message(body: &str) -> (String, String) {
+    if let Ok(json) = serde_json::from_str::<Value>(body) {
+        let error = &json["error"];
+        let message = error["message"]
+            .as_str()
+            .or_else(|| error.as_str())
+            .or_else(|| json["message"].as_str());
+        let code = ["code", "type", "status"]
+            .iter()
+            .filter_map(|key| error[*key].as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if let Some(message) = message {
+            return (message.to_string(), code);
+        }
+    }
+    let body = body.trim();
+    let message = if body.is_empty() {
+        "unknown error".to_string()
+    } else {
+        body.chars().take(500).collect()
+    };
+    (message, String::new())
+}
+
+/// Parse `Retry-After` as delay-seconds or an HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<f64>() {
+        return (secs >= 0.0 && secs.is_finite()).then(|| Duration::from_secs_f64(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (at.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().ok()
+}
+
+/// How the router should treat a failed call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// Transient (rate limit, overload, network); retry after the delay, if any.
+    Retryable { retry_after: Option<Duration> },
+    /// Retrying the same request on the same provider will not help.
+    Fatal,
+}
+
+/// Classify an error returned by a provider.
+///
+/// Typed [`LlmError`]s decide for themselves; untyped transport errors
+/// (timeouts, refused connections) are retryable; anything else is fatal.
+pub fn classify(err: &anyhow::Error) -> ErrorClass {
+    if let Some(err) = err.downcast_ref::<LlmError>() {
+        return if err.is_retryable() {
+            ErrorClass::Retryable {
+                retry_after: err.retry_after(),
+            }
+        } else {
+            ErrorClass::Fatal
+        };
+    }
+    let transport = err.chain().any(|cause| {
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_timeout() || e.is_connect() || e.is_request() || e.is_body())
+    });
+    if transport {
+        ErrorClass::Retryable { retry_after: None }
+    } else {
+        ErrorClass::Fatal
+    }
+}
+
+/// Whether the router should try the next candidate after this error.
+pub fn should_fallback(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<LlmError>()
+        .is_none_or(LlmError::should_fallback)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn error(status: u16, body: &str) -> LlmError {
+        LlmError::from_body("OpenAI", "gpt-4o", status, None, body)
+    }
+
+    #[test]
+    fn test_from_body_variants() {
+        // OpenAI
+        let overflow = error(
+            400,
+            r#"{"error":{"message":"This model's maximum context length is 128000 tokens.","type":"invalid_request_error","code":"context_length_exceeded"}}"#,
+        );
+        assert!(matches!(overflow, LlmError::ContextOverflow { status: 400, .. }));
+        assert_eq!(overflow.model(), "gpt-4o");
+        assert!(!overflow.should_fallback() && !overflow.is_retryable());
+        let filtered = error(400, r#"{"error":{"message":"Flagged","code":"content_filter"}}"#);
+        assert!(matches!(filtered, LlmError::ContentFilter { .. }));
+        // Anthropic
+        let overflow = error(
+            400,
+            r#"{"type":"error","error":{"type":"invalid_request_error","message":"prompt is too long: 210000 tokens > 200000 maximum"}}"#,
+        );
+        assert!(matches!(overflow, LlmError::ContextOverflow { .. }));
+        assert!(matches!(error(529, r#"{"error":{"type":"overloaded_error","message":"Overloaded"}}"#), LlmError::Api { status: 529, .. }));
+        // Google
+        let auth = error(403, r#"{"error":{"code":403,"message":"API key not valid","status":"PERMISSION_DENIED"}}"#);
+        assert!(matches!(auth, LlmError::Auth { .. }));
+        assert!(auth.should_fallback() && !auth.is_retryable());
+        // Ollama / llama.cpp
+        let overflow = error(400, r#"{"error":"the request exceeds the available context size"}"#);
+        assert!(matches!(overflow, LlmError::ContextOverflow { .. }));
+        assert!(matches!(error(404, r#"{"error":"model 'x' not found"}"#), LlmError::Api { status: 404, .. }));
+
+        let limited = LlmError::from_body("OpenAI", "gpt-4o", 429, Some(Duration::from_secs(3)), "slow down");
+        assert!(matches!(limited, LlmError::RateLimit { .. }));
+        assert_eq!(limited.retry_after(), Some(Duration::from_secs(3)));
+        assert_eq!(limited.to_string(), "OpenAI rate limited (429): slow down");
+    }
+
+    #[test]
+    fn test_classify() {
+        let delay = Some(Duration::from_secs(3));
+        let limited: anyhow::Error = LlmError::from_body("OpenAI", "m", 429, delay, "").into();
+        assert_eq!(classify(&limited), ErrorClass::Retryable { retry_after: delay });
+        assert_eq!(classify(&error(503, "").into()), ErrorClass::Retryable { retry_after: None });
+        assert_eq!(classify(&error(400, "").into()), ErrorClass::Fatal);
+        assert_eq!(classify(&error(401, "").into()), ErrorClass::Fatal);
+        assert_eq!(classify(&anyhow::anyhow!("parse failure")), ErrorClass::Fatal);
+        // Context added on top of a typed error does not hide it.
+        let wrapped = anyhow::Error::from(error(502, "")).context("while routing");
+        assert_eq!(classify(&wrapped), ErrorClass::Retryable { retry_after: None });
+
+        assert!(should_fallback(&anyhow::anyhow!("parse failure")));
+        assert!(!should_fallback(&error(400, r#"{"error":"context window exceeded"}"#).into()));
+    }
+
+    #[test]
+    fn test_error_message_and_retry_after() {
+        assert_eq!(error_message(r#"{"error":{"message":"slow down"}}"#).0, "slow down");
+        assert_eq!(error_message(r#"{"error":"model not found"}"#).0, "model not found");
+        assert_eq!(error_message("<html>Bad Gateway</html>").0, "<html>Bad Gateway</html>");
+        assert_eq!(error_message("").0, "unknown error");
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("7"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+        let later = (chrono::Utc::now() + chrono::Duration::seconds(120)).to_rfc2822();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(&later).unwrap());
+        let parsed = retry_after(&headers).unwrap();
+        assert!(parsed > Duration::from_secs(100) && parsed <= Duration::from_secs(120));
+    }
+}
@@ -1,8 +1,11 @@
+pub mod error;
 pub mod message;
 pub mod metrics;
 pub mod pricing;
 pub mod providers;
+pub mod resilience;
 pub mod router;
+pub mod stream;
 pub mod traits;
 
 // Re-export primary types for convenience.
@@ -11,13 +14,16 @@ pub use message::{
     FunctionCall, FunctionCallBehavior, FunctionDefinition, ImageUrl, Message, MessageContent,
     Role, StreamChoice, ToolCall, Usage,
 };
+pub use error::{classify, should_fallback, ErrorClass, LlmError};
 pub use metrics::MetricsCollector;
-pub use pricing::{ModelPricing, PricingRegistry};
-pub use router::LlmRouter;
+pub use pricing::{parse_openrouter_models, ModelPricing, PricingRegistry};
+pub use resilience::{CircuitState, ProviderHealth, ResilienceConfig};
+pub use router::{LlmRouter, PurposeRoute};
+pub use stream::StreamAccumulator;
 pub use traits::{LlmProvider, ModelInfo};
 
 // Re-export providers.
 pub use providers::{
-    AnthropicProvider, GoogleProvider, LlamaCppProvider, OllamaProvider, OpenAiProvider,
-    OpenRouterProvider,
+    AnthropicProvider, AuthScheme, GoogleProvider, LlamaCppProvider, OllamaProvider,
+    OpenAiCompatibleProvider, OpenAiProvider, OpenRouterProvider, ReplayMode, ReplayProvider,
 };
@@ -132,6 +132,24 @@ impl Message {
             _ => None,
         }
     }
+
+    /// For APIs without a per-message `name` field: a user message with a
+    /// `name` gets the speaker prepended to its text as `[name]: text`.
+    /// Other messages are returned unchanged.
+    pub fn with_speaker_in_text(&self) -> Message {
+        let mut msg = self.clone();
+        let Some(name) = msg.name.take().filter(|_| msg.role == Role::User) else {
+            return self.clone();
+        };
+        match &mut msg.content {
+            Some(MessageContent::Text(text)) => *text = format!("[{name}]: {text}"),
+            Some(MessageContent::Multi(parts)) => {
+                parts.insert(0, ContentPart::Text { text: format!("[{name}]:") });
+            }
+            Some(MessageContent::Image { .. }) | None => {}
+        }
+        msg
+    }
 }
 
 /// Definition of a callable function/tool.
@@ -173,6 +191,12 @@ pub struct ChatRequest {
     /// Arbitrary metadata for provider-specific extensions.
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub metadata: HashMap<String, Value>,
+    /// What the call is for ("conversation", "tool_calling", ...).
+    ///
+    /// Not sent to providers. The router uses it to pick a model when `model`
+    /// is empty and to apply the purpose's fallback models.
+    #[serde(skip)]
+    pub purpose: Option<String>,
 }
 
 impl ChatRequest {
@@ -188,6 +212,7 @@ impl ChatRequest {
             stop: None,
             stream: None,
             metadata: HashMap::new(),
+            purpose: None,
         }
     }
 
@@ -202,6 +227,12 @@ impl ChatRequest {
         self.max_tokens = Some(max_tokens);
         self
     }
+
+    /// Set the purpose used for routing.
+    pub fn with_purpose(mut self, purpose: impl Into<String>) -> Self {
+        self.purpose = Some(purpose.into());
+        self
+    }
 }
 
 /// Token usage information.
@@ -261,6 +292,9 @@ pub struct ChatStreamDelta {
     pub id: String,
     pub model: String,
     pub choices: Vec<StreamChoice>,
+    /// Token usage, when the provider reports it in this chunk.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
 }
 
 /// A choice within a streaming chunk.
@@ -285,6 +319,66 @@ pub struct DeltaMessage {
     pub tool_calls: Option<Vec<ToolCall>>,
 }
 
+/// Request for text embeddings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingRequest {
+    pub model: String,
+    /// Texts to embed; the response keeps the same order.
+    pub input: Vec<String>,
+}
+
+impl EmbeddingRequest {
+    pub fn new(model: impl Into<String>, input: Vec<String>) -> Self {
+        Self {
+            model: model.into(),
+            input,
+        }
+    }
+}
+
+/// Response from an embedding request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingResponse {
+    pub model: String,
+    /// One vector per input text, in input order.
+    pub embeddings: Vec<Vec<f32>>,
+    #[serde(default)]
+    pub usage: Usage,
+}
+
+impl EmbeddingResponse {
+    /// Parse an OpenAI-style `{"data": [{"index", "embedding"}], "usage"}` body.
+    pub fn from_openai_json(body: &serde_json::Value, model: &str) -> anyhow::Result<Self> {
+        let data = body["data"]
+            .as_array()
+            .ok_or_else(|| anyhow::anyhow!("embedding response has no data"))?;
+        let mut indexed: Vec<(u64, Vec<f32>)> = data
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let index = item["index"].as_u64().unwrap_or(i as u64);
+                let vector = item["embedding"]
+                    .as_array()
+                    .map(|v| v.iter().filter_map(|x| x.as_f64()).map(|x| x as f32).collect())
+                    .unwrap_or_default();
+                (index, vector)
+            })
+            .collect();
+        indexed.sort_by_key(|(index, _)| *index);
+
+        let prompt_tokens = body["usage"]["prompt_tokens"].as_u64().unwrap_or(0) as u32;
+        Ok(Self {
+            model: body["model"].as_str().unwrap_or(model).to_string(),
+            embeddings: indexed.into_iter().map(|(_, v)| v).collect(),
+            usage: Usage {
+                prompt_tokens,
+                completion_tokens: 0,
+                total_tokens: prompt_tokens,
+            },
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -311,6 +405,25 @@ mod tests {
         assert_eq!(msg.text_content(), Some("hello"));
     }
 
+    #[test]
+    fn test_with_speaker_in_text() {
+        let msg = Message {
+            name: Some("Bob".to_string()),
+            ..Message::user("hello")
+        };
+        let prefixed = msg.with_speaker_in_text();
+        assert_eq!(prefixed.text_content(), Some("[Bob]: hello"));
+        assert_eq!(prefixed.name, None);
+
+        // Only named user messages change.
+        assert_eq!(Message::user("hi").with_speaker_in_text().text_content(), Some("hi"));
+        let tool = Message {
+            name: Some("search".to_string()),
+            ..Message::tool("id", "result")
+        };
+        assert_eq!(tool.with_speaker_in_text().text_content(), Some("result"));
+    }
+
     #[test]
     fn test_first_text() {
         let response = ChatResponse {
@@ -343,4 +456,20 @@ mod tests {
         assert_eq!(req.max_tokens, Some(100));
         assert_eq!(req.messages.len(), 1);
     }
+
+    #[test]
+    fn test_embedding_response_from_openai_json() {
+        let body = serde_json::json!({
+            "model": "text-embedding-3-small",
+            "data": [
+                {"index": 1, "embedding": [0.0, 1.0]},
+                {"index": 0, "embedding": [1.0, 0.0]}
+            ],
+            "usage": {"prompt_tokens": 7, "total_tokens": 7}
+        });
+        let resp = EmbeddingResponse::from_openai_json(&body, "fallback").unwrap();
+        assert_eq!(resp.model, "text-embedding-3-small");
+        assert_eq!(resp.embeddings, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+        assert_eq!(resp.usage.total_tokens, 7);
+    }
 }
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
 
 /// Pricing information for a single model (per 1M tokens).
@@ -10,6 +11,9 @@ pub struct ModelPricing {
     pub input_per_million: f64,
     /// Cost per 1M output tokens in USD.
     pub output_per_million: f64,
+    /// Context window in tokens, when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<u32>,
 }
 
 impl ModelPricing {
@@ -24,6 +28,7 @@ impl ModelPricing {
             model: model.into(),
             input_per_million,
             output_per_million,
+            context_window: None,
         }
     }
 
@@ -36,6 +41,9 @@ impl ModelPricing {
 }
 
 /// Registry of model pricing information.
+///
+/// A model named `*` prices every model of its provider that has no entry of
+/// its own (used for local providers).
 #[derive(Debug, Clone)]
 pub struct PricingRegistry {
     /// Map from "provider:model" to pricing.
@@ -43,24 +51,59 @@ pub struct PricingRegistry {
 }
 
 impl PricingRegistry {
+    /// A registry with the built-in prices of well-known models.
     pub fn new() -> Self {
-        let mut registry = Self {
-            prices: HashMap::new(),
-        };
+        let mut registry = Self::empty();
         registry.load_defaults();
         registry
     }
 
+    /// A registry without any prices.
+    pub fn empty() -> Self {
+        Self {
+            prices: HashMap::new(),
+        }
+    }
+
+    /// Built-in prices of well-known models.
+    pub fn defaults() -> Vec<ModelPricing> {
+        let mut prices: Vec<ModelPricing> = Self::new().prices.into_values().collect();
+        prices.sort_by(|a, b| (&a.provider, &a.model).cmp(&(&b.provider, &b.model)));
+        prices
+    }
+
     /// Register pricing for a model.
     pub fn register(&mut self, pricing: ModelPricing) {
         let key = format!("{}:{}", pricing.provider, pricing.model);
         self.prices.insert(key, pricing);
     }
 
-    /// Look up pricing for a given provider and model.
+    /// Remove the pricing registered for exactly this provider and model.
+    pub fn remove(&mut self, provider: &str, model: &str) -> Option<ModelPricing> {
+        self.prices.remove(&format!("{provider}:{model}"))
+    }
+
+    /// Look up pricing for a given provider and model, falling back to the
+    /// provider's `*` entry.
     pub fn get(&self, provider: &str, model: &str) -> Option<&ModelPricing> {
-        let key = format!("{provider}:{model}");
-        self.prices.get(&key)
+        self.prices
+            .get(&format!("{provider}:{model}"))
+            .or_else(|| self.prices.get(&format!("{provider}:*")))
+    }
+
+    /// All registered prices, sorted by provider and model.
+    pub fn list(&self) -> Vec<&ModelPricing> {
+        let mut prices: Vec<&ModelPricing> = self.prices.values().collect();
+        prices.sort_by(|a, b| (&a.provider, &a.model).cmp(&(&b.provider, &b.model)));
+        prices
+    }
+
+    pub fn len(&self) -> usize {
+        self.prices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prices.is_empty()
     }
 
     /// Calculate cost for a usage record.
@@ -106,6 +149,41 @@ impl PricingRegistry {
     }
 }
 
+/// Parse prices from an OpenRouter `/models` response.
+///
+/// OpenRouter quotes USD per token as strings (`"pricing": {"prompt":
+/// "0.00000014", "completion": "0.00000028"}`); they are converted to per-1M
+/// prices under the `openrouter` provider. Models with missing or negative
+/// (variable) prices are skipped.
+pub fn parse_openrouter_models(body: &Value) -> Vec<ModelPricing> {
+    let per_token = |v: &Value| -> Option<f64> {
+        match v {
+            Value::String(s) => s.trim().parse().ok(),
+            other => other.as_f64(),
+        }
+    };
+    body["data"]
+        .as_array()
+        .map(|models| {
+            models
+                .iter()
+                .filter_map(|m| {
+                    let id = m["id"].as_str()?;
+                    let input = per_token(&m["pricing"]["prompt"])?;
+                    let output = per_token(&m["pricing"]["completion"])?;
+                    if input < 0.0 || output < 0.0 {
+                        return None;
+                    }
+                    let mut pricing =
+                        ModelPricing::new("openrouter", id, input * 1_000_000.0, output * 1_000_000.0);
+                    pricing.context_window = m["context_length"].as_u64().map(|c| c as u32);
+                    Some(pricing)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 impl Default for PricingRegistry {
     fn default() -> Self {
         Self::new()
@@ -150,6 +228,49 @@ mod tests {
         assert!((pricing.output_per_million - 2.0).abs() < f64::EPSILON);
     }
 
+    #[test]
+    fn test_registry_wildcard_and_remove() {
+        let mut registry = PricingRegistry::new();
+        assert_eq!(registry.calculate_cost("ollama", "llama3.1:8b", 1000, 1000), Some(0.0));
+        assert_eq!(registry.calculate_cost("openai", "unknown-model", 1000, 1000), None);
+
+        registry.register(ModelPricing::new("ollama", "paid-model", 1.0, 1.0));
+        assert!(registry.calculate_cost("ollama", "paid-model", 1_000_000, 0).unwrap() > 0.99);
+        assert!(registry.remove("ollama", "paid-model").is_some());
+        assert_eq!(registry.calculate_cost("ollama", "paid-model", 1_000_000, 0), Some(0.0));
+
+        assert!(PricingRegistry::empty().is_empty());
+        assert_eq!(PricingRegistry::defaults().len(), registry.len());
+        let listed = registry.list();
+        assert!(listed.windows(2).all(|w| (&w[0].provider, &w[0].model) <= (&w[1].provider, &w[1].model)));
+    }
+
+    #[test]
+    fn test_parse_openrouter_models() {
+        let body = serde_json::json!({
+            "data": [
+                {
+                    "id": "deepseek/deepseek-chat",
+                    "context_length": 163840,
+                    "pricing": {"prompt": "0.00000014", "completion": "0.00000028", "image": "0"}
+                },
+                {"id": "openrouter/auto", "pricing": {"prompt": "-1", "completion": "-1"}},
+                {"id": "no-pricing/model"},
+                {"id": "free/model", "pricing": {"prompt": "0", "completion": "0"}}
+            ]
+        });
+        let prices = parse_openrouter_models(&body);
+        assert_eq!(prices.len(), 2);
+        let deepseek = &prices[0];
+        assert_eq!(deepseek.provider, "openrouter");
+        assert_eq!(deepseek.model, "deepseek/deepseek-chat");
+        assert!((deepseek.input_per_million - 0.14).abs() < 1e-9);
+        assert!((deepseek.output_per_million - 0.28).abs() < 1e-9);
+        assert_eq!(deepseek.context_window, Some(163_840));
+        assert_eq!(prices[1].model, "free/model");
+        assert!(parse_openrouter_models(&serde_json::json!({})).is_empty());
+    }
+
     #[test]
     fn test_registry_calculate_cost() {
         let registry = PricingRegistry::new();
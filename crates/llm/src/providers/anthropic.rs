@@ -1,12 +1,13 @@
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use futures::stream::BoxStream;
-use futures::StreamExt;
 use reqwest::Client;
 use serde_json::Value;
 use tracing::debug;
 
+use crate::error::LlmError;
 use crate::message::*;
+use crate::stream::{self, Framing, AnthropicStreamParser};
 use crate::traits::{LlmProvider, ModelInfo};
 
 const ANTHROPIC_API_URL: &str = "https://api.anthropic.com/v1";
@@ -57,7 +58,8 @@ impl AnthropicProvider {
                     }
                 }
                 Role::User => {
-                    let content = self.convert_content_to_anthropic(msg);
+                    // Anthropic has no `name` field, so the speaker goes into the text
+                    let content = self.convert_content_to_anthropic(&msg.with_speaker_in_text());
                     messages.push(serde_json::json!({
                         "role": "user",
                         "content": content,
@@ -289,17 +291,12 @@ impl LlmProvider for AnthropicProvider {
             .json(&body)
             .send()
             .await
-            .context("Anthropic API request failed")?;
-
-        let status = resp.status();
-        let resp_body: Value = resp.json().await.context("failed to parse Anthropic response")?;
+            .map_err(|e| LlmError::network("Anthropic", &request.model, e))?;
 
-        if !status.is_success() {
-            let error_msg = resp_body["error"]["message"]
-                .as_str()
-                .unwrap_or("unknown error");
-            anyhow::bail!("Anthropic API error ({}): {}", status, error_msg);
+        if !resp.status().is_success() {
+            return Err(LlmError::from_response("Anthropic", &request.model, resp).await.into());
         }
+        let resp_body: Value = resp.json().await.context("failed to parse Anthropic response")?;
 
         self.parse_response(resp_body)
     }
@@ -318,71 +315,15 @@ impl LlmProvider for AnthropicProvider {
             .json(&body)
             .send()
             .await
-            .context("Anthropic streaming request failed")?;
+            .map_err(|e| LlmError::network("Anthropic", &request.model, e))?;
 
         if !resp.status().is_success() {
-            let status = resp.status();
-            let err_body: Value = resp.json().await.unwrap_or_default();
-            let msg = err_body["error"]["message"]
-                .as_str()
-                .unwrap_or("unknown error");
-            anyhow::bail!("Anthropic API error ({}): {}", status, msg);
+            return Err(LlmError::from_response("Anthropic", &request.model, resp).await.into());
         }
 
-        let model = request.model.clone();
-        let stream = resp.bytes_stream().map(move |chunk| {
-            let chunk = chunk.context("stream chunk error")?;
-            let text = String::from_utf8_lossy(&chunk);
-            let model = model.clone();
-
-            let mut content_text = String::new();
-            let mut msg_id = String::new();
-
-            for line in text.lines() {
-                let line = line.trim();
-                if line.is_empty() {
-                    continue;
-                }
-                if let Some(data) = line.strip_prefix("data: ") {
-                    if let Ok(parsed) = serde_json::from_str::<Value>(data) {
-                        match parsed["type"].as_str() {
-                            Some("message_start") => {
-                                if let Some(id) = parsed["message"]["id"].as_str() {
-                                    msg_id = id.to_string();
-                                }
-                            }
-                            Some("content_block_delta") => {
-                                if let Some(text) = parsed["delta"]["text"].as_str() {
-                                    content_text.push_str(text);
-                                }
-                            }
-                            _ => {}
-                        }
-                    }
-                }
-            }
-
-            Ok(ChatStreamDelta {
-                id: msg_id,
-                model: model.clone(),
-                choices: vec![StreamChoice {
-                    index: 0,
-                    delta: DeltaMessage {
-                        role: None,
-                        content: if content_text.is_empty() {
-                            None
-                        } else {
-                            Some(content_text)
-                        },
-                        function_call: None,
-                        tool_calls: None,
-                    },
-                    finish_reason: None,
-                }],
-            })
-        });
-
-        Ok(Box::pin(stream))
+        let mut parser = AnthropicStreamParser::new(request.model.clone());
+        let payloads = stream::decode(resp.bytes_stream(), Framing::Sse);
+        Ok(stream::parse_with(payloads, move |payload| parser.parse(payload)))
     }
 
     fn supports_function_calling(&self) -> bool {
@@ -6,6 +6,7 @@ use reqwest::Client;
 use serde_json::Value;
 use tracing::debug;
 
+use crate::error::LlmError;
 use crate::message::*;
 use crate::traits::{LlmProvider, ModelInfo};
 
@@ -56,7 +57,8 @@ impl GoogleProvider {
                     }
                 }
                 Role::User => {
-                    let parts = self.convert_parts(msg);
+                    // Gemini has no `name` field, so the speaker goes into the text
+                    let parts = self.convert_parts(&msg.with_speaker_in_text());
                     contents.push(serde_json::json!({
                         "role": "user",
                         "parts": parts,
@@ -107,7 +109,7 @@ impl GoogleProvider {
         if let Some(ref stop) = request.stop {
             gen_config["stopSequences"] = serde_json::json!(stop);
         }
-        if gen_config.as_object().map_or(false, |o| !o.is_empty()) {
+        if gen_config.as_object().is_some_and(|o| !o.is_empty()) {
             body["generationConfig"] = gen_config;
         }
 
@@ -301,17 +303,12 @@ impl LlmProvider for GoogleProvider {
             .json(&body)
             .send()
             .await
-            .context("Gemini API request failed")?;
-
-        let status = resp.status();
-        let resp_body: Value = resp.json().await.context("failed to parse Gemini response")?;
+            .map_err(|e| LlmError::network("Gemini", &request.model, e))?;
 
-        if !status.is_success() {
-            let error_msg = resp_body["error"]["message"]
-                .as_str()
-                .unwrap_or("unknown error");
-            anyhow::bail!("Gemini API error ({}): {}", status, error_msg);
+        if !resp.status().is_success() {
+            return Err(LlmError::from_response("Gemini", &request.model, resp).await.into());
         }
+        let resp_body: Value = resp.json().await.context("failed to parse Gemini response")?;
 
         self.parse_response(resp_body, &request.model)
     }
@@ -332,15 +329,10 @@ impl LlmProvider for GoogleProvider {
             .json(&body)
             .send()
             .await
-            .context("Gemini streaming request failed")?;
+            .map_err(|e| LlmError::network("Gemini", &request.model, e))?;
 
         if !resp.status().is_success() {
-            let status = resp.status();
-            let err_body: Value = resp.json().await.unwrap_or_default();
-            let msg = err_body["error"]["message"]
-                .as_str()
-                .unwrap_or("unknown error");
-            anyhow::bail!("Gemini API error ({}): {}", status, msg);
+            return Err(LlmError::from_response("Gemini", &request.model, resp).await.into());
         }
 
         let model = request.model.clone();
@@ -353,7 +345,15 @@ impl LlmProvider for GoogleProvider {
             let trimmed = text.trim().trim_start_matches('[').trim_end_matches(']').trim_start_matches(',').trim();
 
             let mut content_text = String::new();
+            let mut usage = None;
             if let Ok(parsed) = serde_json::from_str::<Value>(trimmed) {
+                if let Some(meta) = parsed.get("usageMetadata") {
+                    usage = Some(Usage {
+                        prompt_tokens: meta["promptTokenCount"].as_u64().unwrap_or(0) as u32,
+                        completion_tokens: meta["candidatesTokenCount"].as_u64().unwrap_or(0) as u32,
+                        total_tokens: meta["totalTokenCount"].as_u64().unwrap_or(0) as u32,
+                    });
+                }
                 if let Some(parts) = parsed["candidates"][0]["content"]["parts"].as_array() {
                     for part in parts {
                         if let Some(t) = part["text"].as_str() {
@@ -380,6 +380,7 @@ impl LlmProvider for GoogleProvider {
                     },
                     finish_reason: None,
                 }],
+                usage,
             })
         });
 
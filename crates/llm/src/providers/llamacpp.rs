@@ -1,12 +1,13 @@
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use futures::stream::BoxStream;
-use futures::StreamExt;
 use reqwest::Client;
 use serde_json::Value;
 use tracing::debug;
 
+use crate::error::LlmError;
 use crate::message::*;
+use crate::stream::{self, Framing, OpenAiStreamParser};
 use crate::traits::{LlmProvider, ModelInfo};
 
 const LLAMACPP_DEFAULT_URL: &str = "http://localhost:8080";
@@ -228,12 +229,10 @@ impl LlmProvider for LlamaCppProvider {
             .json(&body)
             .send()
             .await
-            .context("llama.cpp API request failed")?;
+            .map_err(|e| LlmError::network("llama.cpp", &request.model, e))?;
 
-        let status = resp.status();
-        if !status.is_success() {
-            let err_text = resp.text().await.unwrap_or_default();
-            anyhow::bail!("llama.cpp API error ({}): {}", status, err_text);
+        if !resp.status().is_success() {
+            return Err(LlmError::from_response("llama.cpp", &request.model, resp).await.into());
         }
 
         let resp_body: Value = resp
@@ -243,6 +242,40 @@ impl LlmProvider for LlamaCppProvider {
         self.parse_response(resp_body)
     }
 
+    /// llama.cpp server (started with `--embedding`) exposes an OpenAI-compatible /v1/embeddings endpoint.
+    async fn embed(&self, request: EmbeddingRequest) -> Result<EmbeddingResponse> {
+        debug!(model = %request.model, count = request.input.len(), "llama.cpp embeddings");
+
+        let url = format!("{}/v1/embeddings", self.base_url);
+        let body = serde_json::json!({
+            "model": request.model,
+            "input": request.input,
+        });
+
+        let resp = self
+            .client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| LlmError::network("llama.cpp", &request.model, e))?;
+
+        if !resp.status().is_success() {
+            return Err(LlmError::from_response("llama.cpp", &request.model, resp).await.into());
+        }
+
+        let resp_body: Value = resp
+            .json()
+            .await
+            .context("failed to parse llama.cpp response")?;
+        EmbeddingResponse::from_openai_json(&resp_body, &request.model)
+    }
+
+    fn supports_embeddings(&self) -> bool {
+        true
+    }
+
     async fn chat_completion_stream(
         &self,
         request: ChatRequest,
@@ -259,72 +292,15 @@ impl LlmProvider for LlamaCppProvider {
             .json(&body)
             .send()
             .await
-            .context("llama.cpp streaming request failed")?;
+            .map_err(|e| LlmError::network("llama.cpp", &request.model, e))?;
 
         if !resp.status().is_success() {
-            let status = resp.status();
-            let err_text = resp.text().await.unwrap_or_default();
-            anyhow::bail!("llama.cpp API error ({}): {}", status, err_text);
+            return Err(LlmError::from_response("llama.cpp", &request.model, resp).await.into());
         }
 
-        let stream = resp.bytes_stream().map(move |chunk| {
-            let chunk = chunk.context("stream chunk error")?;
-            let text = String::from_utf8_lossy(&chunk);
-
-            let mut last_delta = None;
-            for line in text.lines() {
-                let line = line.trim();
-                if line.is_empty() || line == "data: [DONE]" {
-                    continue;
-                }
-                if let Some(data) = line.strip_prefix("data: ") {
-                    if let Ok(parsed) = serde_json::from_str::<Value>(data) {
-                        let id = parsed["id"].as_str().unwrap_or_default().to_string();
-                        let model = parsed["model"]
-                            .as_str()
-                            .unwrap_or("local")
-                            .to_string();
-
-                        let choices = parsed["choices"]
-                            .as_array()
-                            .map(|arr| {
-                                arr.iter()
-                                    .map(|c| {
-                                        let delta = &c["delta"];
-                                        StreamChoice {
-                                            index: c["index"].as_u64().unwrap_or(0) as u32,
-                                            delta: DeltaMessage {
-                                                role: delta
-                                                    .get("role")
-                                                    .and_then(|r| {
-                                                        serde_json::from_value(r.clone()).ok()
-                                                    }),
-                                                content: delta
-                                                    .get("content")
-                                                    .and_then(|v| v.as_str().map(String::from)),
-                                                function_call: None,
-                                                tool_calls: None,
-                                            },
-                                            finish_reason: c
-                                                .get("finish_reason")
-                                                .and_then(|fr| {
-                                                    serde_json::from_value(fr.clone()).ok()
-                                                }),
-                                        }
-                                    })
-                                    .collect()
-                            })
-                            .unwrap_or_default();
-
-                        last_delta = Some(ChatStreamDelta { id, model, choices });
-                    }
-                }
-            }
-
-            last_delta.ok_or_else(|| anyhow::anyhow!("no parseable SSE data in chunk"))
-        });
-
-        Ok(Box::pin(stream))
+        let mut parser = OpenAiStreamParser::new("llama.cpp", "local");
+        let payloads = stream::decode(resp.bytes_stream(), Framing::Sse);
+        Ok(stream::parse_with(payloads, move |payload| parser.parse(payload)))
     }
 
     fn supports_function_calling(&self) -> bool {
@@ -3,11 +3,15 @@ pub mod google;
 pub mod llamacpp;
 pub mod ollama;
 pub mod openai;
+pub mod openai_compatible;
 pub mod openrouter;
+pub mod replay;
 
 pub use anthropic::AnthropicProvider;
 pub use google::GoogleProvider;
 pub use llamacpp::LlamaCppProvider;
 pub use ollama::OllamaProvider;
 pub use openai::OpenAiProvider;
+pub use openai_compatible::{AuthScheme, OpenAiCompatibleProvider};
 pub use openrouter::OpenRouterProvider;
+pub use replay::{ReplayMode, ReplayProvider};
@@ -1,12 +1,13 @@
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use futures::stream::BoxStream;
-use futures::StreamExt;
 use reqwest::Client;
 use serde_json::Value;
 use tracing::debug;
 
+use crate::error::LlmError;
 use crate::message::*;
+use crate::stream::{self, Framing, OllamaStreamParser};
 use crate::traits::{LlmProvider, ModelInfo};
 
 const OLLAMA_DEFAULT_URL: &str = "http://localhost:11434";
@@ -45,6 +46,8 @@ impl OllamaProvider {
                     Role::Tool => "tool",
                 };
 
+                // Ollama ignores `name`, so the speaker goes into the text
+                let msg = msg.with_speaker_in_text();
                 let content = msg.text_content().unwrap_or("").to_string();
 
                 let mut m = serde_json::json!({
@@ -78,7 +81,7 @@ impl OllamaProvider {
         if let Some(ref stop) = request.stop {
             options["stop"] = serde_json::json!(stop);
         }
-        if options.as_object().map_or(false, |o| !o.is_empty()) {
+        if options.as_object().is_some_and(|o| !o.is_empty()) {
             body["options"] = options;
         }
 
@@ -215,18 +218,68 @@ impl LlmProvider for OllamaProvider {
             .json(&body)
             .send()
             .await
-            .context("Ollama API request failed")?;
+            .map_err(|e| LlmError::network("Ollama", &request.model, e))?;
 
-        let status = resp.status();
-        if !status.is_success() {
-            let err_text = resp.text().await.unwrap_or_default();
-            anyhow::bail!("Ollama API error ({}): {}", status, err_text);
+        if !resp.status().is_success() {
+            return Err(LlmError::from_response("Ollama", &request.model, resp).await.into());
         }
 
         let resp_body: Value = resp.json().await.context("failed to parse Ollama response")?;
         self.parse_response(resp_body)
     }
 
+    async fn embed(&self, request: EmbeddingRequest) -> Result<EmbeddingResponse> {
+        debug!(model = %request.model, count = request.input.len(), "Ollama embeddings");
+
+        let url = format!("{}/api/embed", self.base_url);
+        let body = serde_json::json!({
+            "model": request.model,
+            "input": request.input,
+        });
+
+        let resp = self
+            .client
+            .post(&url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| LlmError::network("Ollama", &request.model, e))?;
+
+        if !resp.status().is_success() {
+            return Err(LlmError::from_response("Ollama", &request.model, resp).await.into());
+        }
+
+        let resp_body: Value = resp.json().await.context("failed to parse Ollama response")?;
+        let embeddings = resp_body["embeddings"]
+            .as_array()
+            .ok_or_else(|| anyhow::anyhow!("Ollama embedding response has no embeddings"))?
+            .iter()
+            .map(|v| {
+                v.as_array()
+                    .map(|xs| xs.iter().filter_map(|x| x.as_f64()).map(|x| x as f32).collect())
+                    .unwrap_or_default()
+            })
+            .collect();
+        let prompt_tokens = resp_body["prompt_eval_count"].as_u64().unwrap_or(0) as u32;
+
+        Ok(EmbeddingResponse {
+            model: resp_body["model"]
+                .as_str()
+                .unwrap_or(&request.model)
+                .to_string(),
+            embeddings,
+            usage: Usage {
+                prompt_tokens,
+                completion_tokens: 0,
+                total_tokens: prompt_tokens,
+            },
+        })
+    }
+
+    fn supports_embeddings(&self) -> bool {
+        true
+    }
+
     async fn chat_completion_stream(
         &self,
         request: ChatRequest,
@@ -242,64 +295,15 @@ impl LlmProvider for OllamaProvider {
             .json(&body)
             .send()
             .await
-            .context("Ollama streaming request failed")?;
+            .map_err(|e| LlmError::network("Ollama", &request.model, e))?;
 
         if !resp.status().is_success() {
-            let status = resp.status();
-            let err_text = resp.text().await.unwrap_or_default();
-            anyhow::bail!("Ollama API error ({}): {}", status, err_text);
+            return Err(LlmError::from_response("Ollama", &request.model, resp).await.into());
         }
 
-        let model = request.model.clone();
-        let stream = resp.bytes_stream().map(move |chunk| {
-            let chunk = chunk.context("stream chunk error")?;
-            let text = String::from_utf8_lossy(&chunk);
-            let model = model.clone();
-
-            // Ollama streams newline-delimited JSON objects
-            let mut content_text = String::new();
-            let mut done = false;
-
-            for line in text.lines() {
-                let line = line.trim();
-                if line.is_empty() {
-                    continue;
-                }
-                if let Ok(parsed) = serde_json::from_str::<Value>(line) {
-                    if let Some(c) = parsed["message"]["content"].as_str() {
-                        content_text.push_str(c);
-                    }
-                    if parsed["done"].as_bool().unwrap_or(false) {
-                        done = true;
-                    }
-                }
-            }
-
-            Ok(ChatStreamDelta {
-                id: uuid::Uuid::new_v4().to_string(),
-                model,
-                choices: vec![StreamChoice {
-                    index: 0,
-                    delta: DeltaMessage {
-                        role: None,
-                        content: if content_text.is_empty() {
-                            None
-                        } else {
-                            Some(content_text)
-                        },
-                        function_call: None,
-                        tool_calls: None,
-                    },
-                    finish_reason: if done {
-                        Some(FinishReason::Stop)
-                    } else {
-                        None
-                    },
-                }],
-            })
-        });
-
-        Ok(Box::pin(stream))
+        let mut parser = OllamaStreamParser::new(request.model.clone());
+        let payloads = stream::decode(resp.bytes_stream(), Framing::Ndjson);
+        Ok(stream::parse_with(payloads, move |payload| parser.parse(payload)))
     }
 
     fn supports_function_calling(&self) -> bool {
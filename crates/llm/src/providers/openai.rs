@@ -1,12 +1,13 @@
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use futures::stream::BoxStream;
-use futures::StreamExt;
 use reqwest::Client;
 use serde_json::Value;
 use tracing::debug;
 
+use crate::error::LlmError;
 use crate::message::*;
+use crate::stream::{self, Framing, OpenAiStreamParser};
 use crate::traits::{LlmProvider, ModelInfo};
 
 /// OpenAI API provider.
@@ -51,199 +52,199 @@ impl OpenAiProvider {
 
         builder
     }
+}
 
-    /// Build the JSON body for a chat completion request.
-    fn build_request_body(&self, request: &ChatRequest) -> Value {
-        let mut body = serde_json::json!({
-            "model": request.model,
-            "messages": self.convert_messages(&request.messages),
-        });
+/// Build the JSON body for an OpenAI chat completion request.
+pub(crate) fn build_request_body(request: &ChatRequest) -> Value {
+    let mut body = serde_json::json!({
+        "model": request.model,
+        "messages": convert_messages(&request.messages),
+    });
 
-        if let Some(temp) = request.temperature {
-            body["temperature"] = serde_json::json!(temp);
-        }
-        if let Some(max) = request.max_tokens {
-            body["max_tokens"] = serde_json::json!(max);
-        }
-        if let Some(ref stop) = request.stop {
-            body["stop"] = serde_json::json!(stop);
-        }
-        if let Some(stream) = request.stream {
-            body["stream"] = serde_json::json!(stream);
-        }
-        if let Some(ref functions) = request.functions {
-            // Convert to OpenAI tools format
-            let tools: Vec<Value> = functions
-                .iter()
-                .map(|f| {
-                    serde_json::json!({
-                        "type": "function",
-                        "function": {
-                            "name": f.name,
-                            "description": f.description,
-                            "parameters": f.parameters,
-                        }
-                    })
+    if let Some(temp) = request.temperature {
+        body["temperature"] = serde_json::json!(temp);
+    }
+    if let Some(max) = request.max_tokens {
+        body["max_tokens"] = serde_json::json!(max);
+    }
+    if let Some(ref stop) = request.stop {
+        body["stop"] = serde_json::json!(stop);
+    }
+    if let Some(stream) = request.stream {
+        body["stream"] = serde_json::json!(stream);
+    }
+    if let Some(ref functions) = request.functions {
+        // Convert to OpenAI tools format
+        let tools: Vec<Value> = functions
+            .iter()
+            .map(|f| {
+                serde_json::json!({
+                    "type": "function",
+                    "function": {
+                        "name": f.name,
+                        "description": f.description,
+                        "parameters": f.parameters,
+                    }
                 })
-                .collect();
-            body["tools"] = serde_json::json!(tools);
-        }
-        if let Some(ref fc) = request.function_call {
-            match fc {
-                FunctionCallBehavior::Mode(mode) => {
-                    body["tool_choice"] = serde_json::json!(mode);
-                }
-                FunctionCallBehavior::Named { name } => {
-                    body["tool_choice"] = serde_json::json!({
-                        "type": "function",
-                        "function": { "name": name }
-                    });
-                }
+            })
+            .collect();
+        body["tools"] = serde_json::json!(tools);
+    }
+    if let Some(ref fc) = request.function_call {
+        match fc {
+            FunctionCallBehavior::Mode(mode) => {
+                body["tool_choice"] = serde_json::json!(mode);
+            }
+            FunctionCallBehavior::Named { name } => {
+                body["tool_choice"] = serde_json::json!({
+                    "type": "function",
+                    "function": { "name": name }
+                });
             }
         }
-
-        body
     }
 
-    fn convert_messages(&self, messages: &[Message]) -> Vec<Value> {
-        messages.iter().map(|m| self.convert_message(m)).collect()
-    }
+    body
+}
 
-    fn convert_message(&self, msg: &Message) -> Value {
-        let mut obj = serde_json::json!({
-            "role": msg.role,
-        });
+fn convert_messages(messages: &[Message]) -> Vec<Value> {
+    messages.iter().map(convert_message).collect()
+}
 
-        if let Some(ref content) = msg.content {
-            match content {
-                MessageContent::Text(text) => {
-                    obj["content"] = serde_json::json!(text);
-                }
-                MessageContent::Image { image_url, .. } => {
-                    obj["content"] = serde_json::json!([
-                        {
-                            "type": "image_url",
-                            "image_url": {
-                                "url": image_url.url,
-                            }
+fn convert_message(msg: &Message) -> Value {
+    let mut obj = serde_json::json!({
+        "role": msg.role,
+    });
+
+    if let Some(ref content) = msg.content {
+        match content {
+            MessageContent::Text(text) => {
+                obj["content"] = serde_json::json!(text);
+            }
+            MessageContent::Image { image_url, .. } => {
+                obj["content"] = serde_json::json!([
+                    {
+                        "type": "image_url",
+                        "image_url": {
+                            "url": image_url.url,
                         }
-                    ]);
-                }
-                MessageContent::Multi(parts) => {
-                    let parts_json: Vec<Value> = parts
-                        .iter()
-                        .map(|p| match p {
-                            ContentPart::Text { text } => {
-                                serde_json::json!({"type": "text", "text": text})
-                            }
-                            ContentPart::ImageUrl { image_url } => {
-                                serde_json::json!({
-                                    "type": "image_url",
-                                    "image_url": {"url": image_url.url}
-                                })
-                            }
-                        })
-                        .collect();
-                    obj["content"] = serde_json::json!(parts_json);
-                }
+                    }
+                ]);
+            }
+            MessageContent::Multi(parts) => {
+                let parts_json: Vec<Value> = parts
+                    .iter()
+                    .map(|p| match p {
+                        ContentPart::Text { text } => {
+                            serde_json::json!({"type": "text", "text": text})
+                        }
+                        ContentPart::ImageUrl { image_url } => {
+                            serde_json::json!({
+                                "type": "image_url",
+                                "image_url": {"url": image_url.url}
+                            })
+                        }
+                    })
+                    .collect();
+                obj["content"] = serde_json::json!(parts_json);
             }
         }
+    }
 
-        if let Some(ref name) = msg.name {
-            obj["name"] = serde_json::json!(name);
-        }
-        if let Some(ref tool_calls) = msg.tool_calls {
-            obj["tool_calls"] = serde_json::to_value(tool_calls).unwrap_or_default();
-        }
-        if let Some(ref tool_call_id) = msg.tool_call_id {
-            obj["tool_call_id"] = serde_json::json!(tool_call_id);
+    if let Some(ref name) = msg.name {
+        obj["name"] = serde_json::json!(name);
+    }
+    if let Some(ref tool_calls) = msg.tool_calls {
+        obj["tool_calls"] = serde_json::to_value(tool_calls).unwrap_or_default();
+    }
+    if let Some(ref tool_call_id) = msg.tool_call_id {
+        obj["tool_call_id"] = serde_json::json!(tool_call_id);
+    }
+
+    obj
+}
+
+/// Parse an OpenAI chat completion response into our unified format.
+pub(crate) fn parse_response(body: Value) -> Result<ChatResponse> {
+    let id = body["id"].as_str().unwrap_or_default().to_string();
+    let model = body["model"].as_str().unwrap_or_default().to_string();
+    let created = body["created"].as_i64().unwrap_or(0);
+
+    let usage = if let Some(u) = body.get("usage") {
+        Usage {
+            prompt_tokens: u["prompt_tokens"].as_u64().unwrap_or(0) as u32,
+            completion_tokens: u["completion_tokens"].as_u64().unwrap_or(0) as u32,
+            total_tokens: u["total_tokens"].as_u64().unwrap_or(0) as u32,
         }
+    } else {
+        Usage::default()
+    };
+
+    let choices = body["choices"]
+        .as_array()
+        .map(|arr| {
+            arr.iter()
+                .map(|c| {
+                    let msg = &c["message"];
+                    let role = match msg["role"].as_str().unwrap_or("assistant") {
+                        "system" => Role::System,
+                        "user" => Role::User,
+                        "assistant" => Role::Assistant,
+                        "tool" => Role::Tool,
+                        _ => Role::Assistant,
+                    };
+
+                    let content = msg.get("content").and_then(|v| {
+                        v.as_str().map(|s| MessageContent::Text(s.to_string()))
+                    });
 
-        obj
-    }
+                    let function_call = msg.get("function_call").and_then(|fc| {
+                        serde_json::from_value::<FunctionCall>(fc.clone()).ok()
+                    });
 
-    /// Parse the OpenAI API response into our unified format.
-    fn parse_response(&self, body: Value) -> Result<ChatResponse> {
-        let id = body["id"].as_str().unwrap_or_default().to_string();
-        let model = body["model"].as_str().unwrap_or_default().to_string();
-        let created = body["created"].as_i64().unwrap_or(0);
-
-        let usage = if let Some(u) = body.get("usage") {
-            Usage {
-                prompt_tokens: u["prompt_tokens"].as_u64().unwrap_or(0) as u32,
-                completion_tokens: u["completion_tokens"].as_u64().unwrap_or(0) as u32,
-                total_tokens: u["total_tokens"].as_u64().unwrap_or(0) as u32,
-            }
-        } else {
-            Usage::default()
-        };
+                    let tool_calls = msg.get("tool_calls").and_then(|tc| {
+                        serde_json::from_value::<Vec<ToolCall>>(tc.clone()).ok()
+                    });
 
-        let choices = body["choices"]
-            .as_array()
-            .map(|arr| {
-                arr.iter()
-                    .map(|c| {
-                        let msg = &c["message"];
-                        let role = match msg["role"].as_str().unwrap_or("assistant") {
-                            "system" => Role::System,
-                            "user" => Role::User,
-                            "assistant" => Role::Assistant,
-                            "tool" => Role::Tool,
-                            _ => Role::Assistant,
-                        };
-
-                        let content = msg.get("content").and_then(|v| {
-                            v.as_str().map(|s| MessageContent::Text(s.to_string()))
-                        });
-
-                        let function_call = msg.get("function_call").and_then(|fc| {
-                            serde_json::from_value::<FunctionCall>(fc.clone()).ok()
-                        });
-
-                        let tool_calls = msg.get("tool_calls").and_then(|tc| {
-                            serde_json::from_value::<Vec<ToolCall>>(tc.clone()).ok()
-                        });
-
-                        let tool_call_id = msg
-                            .get("tool_call_id")
-                            .and_then(|v| v.as_str().map(String::from));
-
-                        let finish_reason = c.get("finish_reason").and_then(|fr| {
-                            match fr.as_str()? {
-                                "stop" => Some(FinishReason::Stop),
-                                "length" => Some(FinishReason::Length),
-                                "function_call" => Some(FinishReason::FunctionCall),
-                                "tool_calls" => Some(FinishReason::ToolCalls),
-                                "content_filter" => Some(FinishReason::ContentFilter),
-                                _ => None,
-                            }
-                        });
-
-                        Choice {
-                            index: c["index"].as_u64().unwrap_or(0) as u32,
-                            message: Message {
-                                role,
-                                content,
-                                name: None,
-                                function_call,
-                                tool_calls,
-                                tool_call_id,
-                            },
-                            finish_reason,
+                    let tool_call_id = msg
+                        .get("tool_call_id")
+                        .and_then(|v| v.as_str().map(String::from));
+
+                    let finish_reason = c.get("finish_reason").and_then(|fr| {
+                        match fr.as_str()? {
+                            "stop" => Some(FinishReason::Stop),
+                            "length" => Some(FinishReason::Length),
+                            "function_call" => Some(FinishReason::FunctionCall),
+                            "tool_calls" => Some(FinishReason::ToolCalls),
+                            "content_filter" => Some(FinishReason::ContentFilter),
+                            _ => None,
                         }
-                    })
-                    .collect()
-            })
-            .unwrap_or_default();
+                    });
 
-        Ok(ChatResponse {
-            id,
-            model,
-            choices,
-            usage,
-            created,
+                    Choice {
+                        index: c["index"].as_u64().unwrap_or(0) as u32,
+                        message: Message {
+                            role,
+                            content,
+                            name: None,
+                            function_call,
+                            tool_calls,
+                            tool_call_id,
+                        },
+                        finish_reason,
+                    }
+                })
+                .collect()
         })
-    }
+        .unwrap_or_default();
+
+    Ok(ChatResponse {
+        id,
+        model,
+        choices,
+        usage,
+        created,
+    })
 }
 
 #[async_trait]
@@ -287,25 +288,46 @@ impl LlmProvider for OpenAiProvider {
     async fn chat_completion(&self, request: ChatRequest) -> Result<ChatResponse> {
         debug!(model = %request.model, "OpenAI chat completion");
 
-        let body = self.build_request_body(&request);
+        let body = build_request_body(&request);
         let resp = self
             .request_builder("chat/completions")
             .json(&body)
             .send()
             .await
-            .context("OpenAI API request failed")?;
+            .map_err(|e| LlmError::network("OpenAI", &request.model, e))?;
 
-        let status = resp.status();
+        if !resp.status().is_success() {
+            return Err(LlmError::from_response("OpenAI", &request.model, resp).await.into());
+        }
         let resp_body: Value = resp.json().await.context("failed to parse OpenAI response")?;
 
-        if !status.is_success() {
-            let error_msg = resp_body["error"]["message"]
-                .as_str()
-                .unwrap_or("unknown error");
-            anyhow::bail!("OpenAI API error ({}): {}", status, error_msg);
+        parse_response(resp_body)
+    }
+
+    async fn embed(&self, request: EmbeddingRequest) -> Result<EmbeddingResponse> {
+        debug!(model = %request.model, count = request.input.len(), "OpenAI embeddings");
+
+        let body = serde_json::json!({
+            "model": request.model,
+            "input": request.input,
+        });
+        let resp = self
+            .request_builder("embeddings")
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| LlmError::network("OpenAI", &request.model, e))?;
+
+        if !resp.status().is_success() {
+            return Err(LlmError::from_response("OpenAI", &request.model, resp).await.into());
         }
+        let resp_body: Value = resp.json().await.context("failed to parse OpenAI response")?;
 
-        self.parse_response(resp_body)
+        EmbeddingResponse::from_openai_json(&resp_body, &request.model)
+    }
+
+    fn supports_embeddings(&self) -> bool {
+        true
     }
 
     async fn chat_completion_stream(
@@ -314,7 +336,7 @@ impl LlmProvider for OpenAiProvider {
     ) -> Result<BoxStream<'static, Result<ChatStreamDelta>>> {
         debug!(model = %request.model, "OpenAI streaming chat completion");
 
-        let mut body = self.build_request_body(&request);
+        let mut body = build_request_body(&request);
         body["stream"] = serde_json::json!(true);
 
         let resp = self
@@ -322,81 +344,15 @@ impl LlmProvider for OpenAiProvider {
             .json(&body)
             .send()
             .await
-            .context("OpenAI streaming request failed")?;
+            .map_err(|e| LlmError::network("OpenAI", &request.model, e))?;
 
         if !resp.status().is_success() {
-            let status = resp.status();
-            let err_body: Value = resp.json().await.unwrap_or_default();
-            let msg = err_body["error"]["message"]
-                .as_str()
-                .unwrap_or("unknown error");
-            anyhow::bail!("OpenAI API error ({}): {}", status, msg);
+            return Err(LlmError::from_response("OpenAI", &request.model, resp).await.into());
         }
 
-        let stream = resp.bytes_stream().map(move |chunk| {
-            let chunk = chunk.context("stream chunk error")?;
-            let text = String::from_utf8_lossy(&chunk);
-
-            let mut last_delta = None;
-            for line in text.lines() {
-                let line = line.trim();
-                if line.is_empty() || line == "data: [DONE]" {
-                    continue;
-                }
-                if let Some(data) = line.strip_prefix("data: ") {
-                    if let Ok(parsed) = serde_json::from_str::<Value>(data) {
-                        let id = parsed["id"].as_str().unwrap_or_default().to_string();
-                        let model = parsed["model"].as_str().unwrap_or_default().to_string();
-                        let choices = parsed["choices"]
-                            .as_array()
-                            .map(|arr| {
-                                arr.iter()
-                                    .map(|c| {
-                                        let delta = &c["delta"];
-                                        StreamChoice {
-                                            index: c["index"].as_u64().unwrap_or(0) as u32,
-                                            delta: DeltaMessage {
-                                                role: delta
-                                                    .get("role")
-                                                    .and_then(|r| {
-                                                        serde_json::from_value(r.clone()).ok()
-                                                    }),
-                                                content: delta
-                                                    .get("content")
-                                                    .and_then(|v| {
-                                                        v.as_str().map(String::from)
-                                                    }),
-                                                function_call: delta
-                                                    .get("function_call")
-                                                    .and_then(|fc| {
-                                                        serde_json::from_value(fc.clone()).ok()
-                                                    }),
-                                                tool_calls: delta
-                                                    .get("tool_calls")
-                                                    .and_then(|tc| {
-                                                        serde_json::from_value(tc.clone()).ok()
-                                                    }),
-                                            },
-                                            finish_reason: c
-                                                .get("finish_reason")
-                                                .and_then(|fr| {
-                                                    serde_json::from_value(fr.clone()).ok()
-                                                }),
-                                        }
-                                    })
-                                    .collect()
-                            })
-                            .unwrap_or_default();
-
-                        last_delta = Some(ChatStreamDelta { id, model, choices });
-                    }
-                }
-            }
-
-            last_delta.ok_or_else(|| anyhow::anyhow!("no parseable SSE data in chunk"))
-        });
-
-        Ok(Box::pin(stream))
+        let mut parser = OpenAiStreamParser::new("OpenAI", request.model.clone());
+        let payloads = stream::decode(resp.bytes_stream(), Framing::Sse);
+        Ok(stream::parse_with(payloads, move |payload| parser.parse(payload)))
     }
 
     fn supports_function_calling(&self) -> bool {
@@ -0,0 +1,372 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use reqwest::Client;
+use serde_json::Value;
+use tracing::debug;
+
+use crate::error::LlmError;
+use crate::message::*;
+use crate::providers::openai::{build_request_body, parse_response};
+use crate::stream::{self, Framing, OpenAiStreamParser};
+use crate::traits::{LlmProvider, ModelInfo};
+
+/// How the API key is sent to an OpenAI-compatible server.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum AuthScheme {
+    /// `Authorization: Bearer <key>`.
+    #[default]
+    Bearer,
+    /// The raw key in a custom header (e.g. `api-key` for Azure-style gateways).
+    Header(String),
+    /// No authentication header.
+    None,
+}
+
+/// Generic provider for servers that speak the OpenAI chat completions API
+/// (vLLM, LM Studio, LocalAI, ...).
+///
+/// Unlike the built-in providers it is registered under a configurable name,
+/// so several servers can be used side by side.
+#[derive(Debug, Clone)]
+pub struct OpenAiCompatibleProvider {
+    name: String,
+    client: Client,
+    base_url: String,
+    api_key: String,
+    auth: AuthScheme,
+    headers: Vec<(String, String)>,
+    /// Served models. Empty means "ask the server's `/models` endpoint".
+    models: Vec<String>,
+    context_window: u32,
+    function_calling: bool,
+    vision: bool,
+}
+
+impl OpenAiCompatibleProvider {
+    /// `base_url` is the API root including the version, e.g. `http://localhost:8000/v1`.
+    pub fn new(name: impl Into<String>, base_url: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            client: Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            api_key: String::new(),
+            auth: AuthScheme::default(),
+            headers: Vec::new(),
+            models: Vec::new(),
+            context_window: 0,
+            function_calling: true,
+            vision: false,
+        }
+    }
+
+    /// Key sent according to the auth scheme. An empty key sends no auth header.
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = api_key.into();
+        self
+    }
+
+    pub fn with_auth(mut self, auth: AuthScheme) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    /// Extra header sent with every request.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Fixed model list, for servers without a `/models` endpoint.
+    pub fn with_models(mut self, models: Vec<String>) -> Self {
+        self.models = models;
+        self
+    }
+
+    /// Context window reported for the served models (0 = unknown).
+    pub fn with_context_window(mut self, tokens: u32) -> Self {
+        self.context_window = tokens;
+        self
+    }
+
+    pub fn with_function_calling(mut self, supported: bool) -> Self {
+        self.function_calling = supported;
+        self
+    }
+
+    pub fn with_vision(mut self, supported: bool) -> Self {
+        self.vision = supported;
+        self
+    }
+
+    /// Attach auth and custom headers.
+    fn authorize(&self, mut builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        if !self.api_key.is_empty() {
+            builder = match &self.auth {
+                AuthScheme::Bearer => {
+                    builder.header("Authorization", format!("Bearer {}", self.api_key))
+                }
+                AuthScheme::Header(name) => builder.header(name.as_str(), &self.api_key),
+                AuthScheme::None => builder,
+            };
+        }
+        for (name, value) in &self.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        builder
+    }
+
+    fn post(&self, endpoint: &str) -> reqwest::RequestBuilder {
+        let url = format!("{}/{}", self.base_url, endpoint);
+        self.authorize(self.client.post(&url))
+            .header("Content-Type", "application/json")
+    }
+
+    /// OpenAI request body, without tools when the server cannot call functions.
+    fn request_body(&self, request: &ChatRequest, stream: bool) -> Value {
+        let mut body = build_request_body(request);
+        if stream {
+            body["stream"] = serde_json::json!(true);
+        }
+        if !self.function_calling {
+            if let Some(obj) = body.as_object_mut() {
+                obj.remove("tools");
+                obj.remove("tool_choice");
+            }
+        }
+        body
+    }
+
+    fn model_info(&self, id: String) -> ModelInfo {
+        ModelInfo {
+            name: id.clone(),
+            id,
+            context_window: self.context_window,
+            supports_function_calling: self.function_calling,
+            supports_vision: self.vision,
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiCompatibleProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn available_models(&self) -> Result<Vec<ModelInfo>> {
+        if !self.models.is_empty() {
+            return Ok(self
+                .models
+                .iter()
+                .cloned()
+                .map(|id| self.model_info(id))
+                .collect());
+        }
+
+        let url = format!("{}/models", self.base_url);
+        let resp = self
+            .authorize(self.client.get(&url))
+            .send()
+            .await
+            .with_context(|| format!("failed to list {} models", self.name))?;
+        let body: Value = resp.json().await.context("failed to parse model list")?;
+        Ok(body["data"]
+            .as_array()
+            .map(|arr| {
+                arr.iter()
+                    .filter_map(|m| m["id"].as_str())
+                    .map(|id| self.model_info(id.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn chat_completion(&self, request: ChatRequest) -> Result<ChatResponse> {
+        debug!(provider = %self.name, model = %request.model, "OpenAI-compatible chat completion");
+
+        let body = self.request_body(&request, false);
+        let resp = self
+            .post("chat/completions")
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| LlmError::network(&self.name, &request.model, e))?;
+
+        if !resp.status().is_success() {
+            return Err(LlmError::from_response(&self.name, &request.model, resp)
+                .await
+                .into());
+        }
+        let resp_body: Value = resp
+            .json()
+            .await
+            .with_context(|| format!("failed to parse {} response", self.name))?;
+
+        parse_response(resp_body)
+    }
+
+    async fn chat_completion_stream(
+        &self,
+        request: ChatRequest,
+    ) -> Result<BoxStream<'static, Result<ChatStreamDelta>>> {
+        debug!(provider = %self.name, model = %request.model, "OpenAI-compatible streaming chat completion");
+
+        let body = self.request_body(&request, true);
+
+        let resp = self
+            .post("chat/completions")
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| LlmError::network(&self.name, &request.model, e))?;
+
+        if !resp.status().is_success() {
+            return Err(LlmError::from_response(&self.name, &request.model, resp)
+                .await
+                .into());
+        }
+
+        let mut parser = OpenAiStreamParser::new(self.name.clone(), request.model.clone());
+        let payloads = stream::decode(resp.bytes_stream(), Framing::Sse);
+        Ok(stream::parse_with(payloads, move |payload| {
+            parser.parse(payload)
+        }))
+    }
+
+    async fn embed(&self, request: EmbeddingRequest) -> Result<EmbeddingResponse> {
+        debug!(provider = %self.name, model = %request.model, count = request.input.len(), "OpenAI-compatible embeddings");
+
+        let body = serde_json::json!({
+            "model": request.model,
+            "input": request.input,
+        });
+        let resp = self
+            .post("embeddings")
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| LlmError::network(&self.name, &request.model, e))?;
+
+        if !resp.status().is_success() {
+            return Err(LlmError::from_response(&self.name, &request.model, resp)
+                .await
+                .into());
+        }
+        let resp_body: Value = resp
+            .json()
+            .await
+            .with_context(|| format!("failed to parse {} response", self.name))?;
+
+        EmbeddingResponse::from_openai_json(&resp_body, &request.model)
+    }
+
+    fn supports_embeddings(&self) -> bool {
+        true
+    }
+
+    fn supports_function_calling(&self) -> bool {
+        self.function_calling
+    }
+
+    fn supports_vision(&self) -> bool {
+        self.vision
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        let url = format!("{}/models", self.base_url);
+        let resp = self.authorize(self.client.get(&url)).send().await?;
+        Ok(resp.status().is_success())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Serve one HTTP request with `body` and return the raw request text.
+    async fn serve_once(body: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut raw = Vec::new();
+            let mut buf = [0u8; 4096];
+            // Read the headers, then as much body as Content-Length announces.
+            loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                raw.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&raw).to_string();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text
+                        .to_lowercase()
+                        .lines()
+                        .find_map(|l| {
+                            l.strip_prefix("content-length:")?
+                                .trim()
+                                .parse::<usize>()
+                                .ok()
+                        })
+                        .unwrap_or(0);
+                    if raw.len() >= end + 4 + length {
+                        break;
+                    }
+                }
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&raw).to_string()
+        });
+        (base_url, handle)
+    }
+
+    #[tokio::test]
+    async fn test_custom_auth_headers_and_capabilities() {
+        let (base_url, server) = serve_once(
+            r#"{"id":"x","model":"qwen","choices":[{"index":0,"message":{"role":"assistant","content":"hi"},"finish_reason":"stop"}],"usage":{"prompt_tokens":4,"completion_tokens":1,"total_tokens":5}}"#,
+        )
+        .await;
+        let provider = OpenAiCompatibleProvider::new("vllm-a", base_url)
+            .with_api_key("secret")
+            .with_auth(AuthScheme::Header("api-key".to_string()))
+            .with_header("X-Tenant", "lab")
+            .with_function_calling(false);
+
+        let mut request = ChatRequest::new("qwen", vec![Message::user("hello")]);
+        request.functions = Some(vec![FunctionDefinition {
+            name: "search".to_string(),
+            description: Some("Search".to_string()),
+            parameters: serde_json::json!({"type": "object"}),
+        }]);
+        let response = provider.chat_completion(request).await.unwrap();
+        assert_eq!(response.choices[0].message.text_content(), Some("hi"));
+        assert_eq!(response.usage.total_tokens, 5);
+
+        let raw = server.await.unwrap().to_lowercase();
+        assert!(raw.starts_with("post /v1/chat/completions"));
+        assert!(raw.contains("api-key: secret"));
+        assert!(raw.contains("x-tenant: lab"));
+        assert!(!raw.contains("authorization:"));
+        // Tools are not sent to a server without function calling.
+        assert!(!raw.contains("\"tools\""));
+    }
+
+    #[tokio::test]
+    async fn test_configured_model_list() {
+        let provider = OpenAiCompatibleProvider::new("lmstudio", "http://127.0.0.1:1/v1")
+            .with_models(vec!["llama-3-8b".to_string()])
+            .with_context_window(8192)
+            .with_vision(true);
+        assert_eq!(provider.name(), "lmstudio");
+        let models = provider.available_models().await.unwrap();
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].id, "llama-3-8b");
+        assert_eq!(models[0].context_window, 8192);
+        assert!(models[0].supports_vision && provider.supports_vision());
+    }
+}
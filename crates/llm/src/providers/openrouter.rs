@@ -1,12 +1,13 @@
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use futures::stream::BoxStream;
-use futures::StreamExt;
 use reqwest::Client;
 use serde_json::Value;
 use tracing::debug;
 
+use crate::error::LlmError;
 use crate::message::*;
+use crate::stream::{self, Framing, OpenAiStreamParser};
 use crate::traits::{LlmProvider, ModelInfo};
 
 const OPENROUTER_API_URL: &str = "https://openrouter.ai/api/v1";
@@ -72,6 +73,28 @@ impl OpenRouterProvider {
         builder
     }
 
+    /// Fetch the raw `/models` listing (model metadata and pricing).
+    async fn fetch_models(&self) -> Result<Value> {
+        let url = format!("{}/models", self.base_url);
+        let mut builder = self.client.get(&url);
+
+        if let Some(ref referer) = self.referer {
+            builder = builder.header("HTTP-Referer", referer);
+        }
+        if let Some(ref title) = self.title {
+            builder = builder.header("X-Title", title);
+        }
+
+        let resp = builder
+            .send()
+            .await
+            .context("failed to list OpenRouter models")?;
+
+        resp.json()
+            .await
+            .context("failed to parse OpenRouter model list")
+    }
+
     /// Build the request body (OpenAI-compatible format).
     fn build_request_body(&self, request: &ChatRequest) -> Value {
         let messages: Vec<Value> = request
@@ -268,25 +291,7 @@ impl LlmProvider for OpenRouterProvider {
     }
 
     async fn available_models(&self) -> Result<Vec<ModelInfo>> {
-        let url = format!("{}/models", self.base_url);
-        let mut builder = self.client.get(&url);
-
-        if let Some(ref referer) = self.referer {
-            builder = builder.header("HTTP-Referer", referer);
-        }
-        if let Some(ref title) = self.title {
-            builder = builder.header("X-Title", title);
-        }
-
-        let resp = builder
-            .send()
-            .await
-            .context("failed to list OpenRouter models")?;
-
-        let body: Value = resp
-            .json()
-            .await
-            .context("failed to parse OpenRouter model list")?;
+        let body = self.fetch_models().await?;
 
         let models = body["data"]
             .as_array()
@@ -311,6 +316,11 @@ impl LlmProvider for OpenRouterProvider {
         Ok(models)
     }
 
+    async fn model_pricing(&self) -> Result<Vec<crate::pricing::ModelPricing>> {
+        let body = self.fetch_models().await?;
+        Ok(crate::pricing::parse_openrouter_models(&body))
+    }
+
     async fn chat_completion(&self, request: ChatRequest) -> Result<ChatResponse> {
         debug!(model = %request.model, "OpenRouter chat completion");
 
@@ -320,21 +330,16 @@ impl LlmProvider for OpenRouterProvider {
             .json(&body)
             .send()
             .await
-            .context("OpenRouter API request failed")?;
+            .map_err(|e| LlmError::network("OpenRouter", &request.model, e))?;
 
-        let status = resp.status();
+        if !resp.status().is_success() {
+            return Err(LlmError::from_response("OpenRouter", &request.model, resp).await.into());
+        }
         let resp_body: Value = resp
             .json()
             .await
             .context("failed to parse OpenRouter response")?;
 
-        if !status.is_success() {
-            let error_msg = resp_body["error"]["message"]
-                .as_str()
-                .unwrap_or("unknown error");
-            anyhow::bail!("OpenRouter API error ({}): {}", status, error_msg);
-        }
-
         self.parse_response(resp_body)
     }
 
@@ -352,79 +357,15 @@ impl LlmProvider for OpenRouterProvider {
             .json(&body)
             .send()
             .await
-            .context("OpenRouter streaming request failed")?;
+            .map_err(|e| LlmError::network("OpenRouter", &request.model, e))?;
 
         if !resp.status().is_success() {
-            let status = resp.status();
-            let err_body: Value = resp.json().await.unwrap_or_default();
-            let msg = err_body["error"]["message"]
-                .as_str()
-                .unwrap_or("unknown error");
-            anyhow::bail!("OpenRouter API error ({}): {}", status, msg);
+            return Err(LlmError::from_response("OpenRouter", &request.model, resp).await.into());
         }
 
-        let stream = resp.bytes_stream().map(move |chunk| {
-            let chunk = chunk.context("stream chunk error")?;
-            let text = String::from_utf8_lossy(&chunk);
-
-            let mut last_delta = None;
-            for line in text.lines() {
-                let line = line.trim();
-                if line.is_empty() || line == "data: [DONE]" {
-                    continue;
-                }
-                if let Some(data) = line.strip_prefix("data: ") {
-                    if let Ok(parsed) = serde_json::from_str::<Value>(data) {
-                        let id = parsed["id"].as_str().unwrap_or_default().to_string();
-                        let model = parsed["model"].as_str().unwrap_or_default().to_string();
-                        let choices = parsed["choices"]
-                            .as_array()
-                            .map(|arr| {
-                                arr.iter()
-                                    .map(|c| {
-                                        let delta = &c["delta"];
-                                        StreamChoice {
-                                            index: c["index"].as_u64().unwrap_or(0) as u32,
-                                            delta: DeltaMessage {
-                                                role: delta
-                                                    .get("role")
-                                                    .and_then(|r| {
-                                                        serde_json::from_value(r.clone()).ok()
-                                                    }),
-                                                content: delta
-                                                    .get("content")
-                                                    .and_then(|v| v.as_str().map(String::from)),
-                                                function_call: delta
-                                                    .get("function_call")
-                                                    .and_then(|fc| {
-                                                        serde_json::from_value(fc.clone()).ok()
-                                                    }),
-                                                tool_calls: delta
-                                                    .get("tool_calls")
-                                                    .and_then(|tc| {
-                                                        serde_json::from_value(tc.clone()).ok()
-                                                    }),
-                                            },
-                                            finish_reason: c
-                                                .get("finish_reason")
-                                                .and_then(|fr| {
-                                                    serde_json::from_value(fr.clone()).ok()
-                                                }),
-                                        }
-                                    })
-                                    .collect()
-                            })
-                            .unwrap_or_default();
-
-                        last_delta = Some(ChatStreamDelta { id, model, choices });
-                    }
-                }
-            }
-
-            last_delta.ok_or_else(|| anyhow::anyhow!("no parseable SSE data in chunk"))
-        });
-
-        Ok(Box::pin(stream))
+        let mut parser = OpenAiStreamParser::new("OpenRouter", request.model.clone());
+        let payloads = stream::decode(resp.bytes_stream(), Framing::Sse);
+        Ok(stream::parse_with(payloads, move |payload| parser.parse(payload)))
     }
 
     fn supports_function_calling(&self) -> bool {
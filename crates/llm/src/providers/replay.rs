@@ -0,0 +1,540 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, info};
+
+use crate::message::*;
+use crate::traits::{LlmProvider, ModelInfo};
+
+/// What `ReplayProvider` does with a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReplayMode {
+    /// Answer from the fixture file. Requests without a recording go to the
+    /// wrapped provider (and are recorded) unless the provider is strict.
+    #[default]
+    Replay,
+    /// Always call the wrapped provider and overwrite the recording.
+    Record,
+}
+
+/// One recorded request/response pair.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Fixture {
+    /// The normalised request, kept for reviewing fixture diffs.
+    request: Value,
+    response: Value,
+}
+
+/// Fixture file contents, keyed by request hash. A `BTreeMap` keeps the file
+/// order stable so re-recording produces small diffs.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FixtureFile {
+    #[serde(default)]
+    entries: BTreeMap<String, Fixture>,
+}
+
+/// Provider that records request/response pairs of a wrapped provider to a
+/// JSON fixture file and replays them without network access.
+///
+/// Requests are keyed by a hash of the request with transport-only fields
+/// (`stream`) removed, so a streaming call replays a non-streaming recording.
+/// Function definitions are sorted by name, since callers may list them in
+/// any order.
+/// Date-times in the request text (such as the current time in a system
+/// prompt or `created_at` in tool results) are masked before hashing, so a
+/// recording still matches when it is replayed later.
+/// Streaming replays yield the whole response as a single delta.
+pub struct ReplayProvider {
+    name: String,
+    path: PathBuf,
+    mode: ReplayMode,
+    strict: bool,
+    inner: Option<Arc<dyn LlmProvider>>,
+    fixtures: Mutex<FixtureFile>,
+}
+
+impl ReplayProvider {
+    /// Open the fixture file at `path`; a missing file starts empty.
+    pub fn new(name: impl Into<String>, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let fixtures = if path.exists() {
+            let text = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read fixtures {}", path.display()))?;
+            serde_json::from_str(&text)
+                .with_context(|| format!("Invalid fixture file {}", path.display()))?
+        } else {
+            FixtureFile::default()
+        };
+        Ok(Self {
+            name: name.into(),
+            path,
+            mode: ReplayMode::default(),
+            strict: false,
+            inner: None,
+            fixtures: Mutex::new(fixtures),
+        })
+    }
+
+    /// Wrap a real provider, used for recording.
+    pub fn with_inner(mut self, inner: Arc<dyn LlmProvider>) -> Self {
+        self.inner = Some(inner);
+        self
+    }
+
+    pub fn with_mode(mut self, mode: ReplayMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// In strict mode a request without a recording fails instead of
+    /// reaching the wrapped provider.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Number of recorded requests.
+    pub fn len(&self) -> usize {
+        self.fixtures.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Fixture key of a chat request.
+    pub fn chat_key(request: &ChatRequest) -> String {
+        fixture_key("chat", &normalize_chat(request))
+    }
+
+    fn lookup<T: serde::de::DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        let fixtures = self.fixtures.lock().unwrap();
+        let Some(fixture) = fixtures.entries.get(key) else {
+            return Ok(None);
+        };
+        let response = serde_json::from_value(fixture.response.clone()).with_context(|| {
+            format!("Invalid recorded response {key} in {}", self.path.display())
+        })?;
+        Ok(Some(response))
+    }
+
+    fn record<T: Serialize>(&self, key: String, request: Value, response: &T) -> Result<()> {
+        let mut fixtures = self.fixtures.lock().unwrap();
+        fixtures.entries.insert(
+            key,
+            Fixture {
+                request,
+                response: serde_json::to_value(response)?,
+            },
+        );
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = serde_json::to_string_pretty(&*fixtures)?;
+        std::fs::write(&self.path, text)
+            .with_context(|| format!("Failed to write fixtures {}", self.path.display()))
+    }
+
+    /// The wrapped provider to call for `key`, or an error when the request
+    /// must be answered from the fixtures.
+    fn passthrough(&self, key: &str) -> Result<&Arc<dyn LlmProvider>> {
+        if self.mode == ReplayMode::Replay && self.strict {
+            anyhow::bail!(
+                "No recorded response for request {key} in {} (strict replay)",
+                self.path.display()
+            );
+        }
+        self.inner.as_ref().with_context(|| {
+            format!(
+                "No recorded response for request {key} in {} and no provider to record from",
+                self.path.display()
+            )
+        })
+    }
+}
+
+/// The request as JSON without fields that do not change the answer, with
+/// function definitions sorted and date-times masked.
+fn normalize_chat(request: &ChatRequest) -> Value {
+    let mut value = serde_json::to_value(request).unwrap_or(Value::Null);
+    if let Some(map) = value.as_object_mut() {
+        map.remove("stream");
+        if let Some(Value::Array(functions)) = map.get_mut("functions") {
+            functions.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+        }
+    }
+    mask_datetimes(&mut value);
+    value
+}
+
+/// Placeholder for a masked date-time.
+const DATETIME_MASK: &str = "<datetime>";
+
+/// Replace date-times in every string of `value` with [`DATETIME_MASK`].
+fn mask_datetimes(value: &mut Value) {
+    match value {
+        Value::String(text) => {
+            if let Some(masked) = mask_datetimes_in(text) {
+                *text = masked;
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(mask_datetimes),
+        Value::Object(map) => map.values_mut().for_each(mask_datetimes),
+        _ => {}
+    }
+}
+
+/// `text` with `YYYY-MM-DD[ T]HH:MM[:SS[.fff]]` and a following time zone
+/// (`Z`, `+09:00`, ` UTC`, ` +0900`) masked, or `None` if there is none.
+fn mask_datetimes_in(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut out = String::new();
+    let (mut copied, mut i) = (0, 0);
+    while i < bytes.len() {
+        match datetime_len(&bytes[i..]) {
+            Some(len) => {
+                out.push_str(&text[copied..i]);
+                out.push_str(DATETIME_MASK);
+                i += len;
+                copied = i;
+            }
+            None => i += 1,
+        }
+    }
+    if copied == 0 {
+        return None;
+    }
+    out.push_str(&text[copied..]);
+    Some(out)
+}
+
+/// Length of the date-time at the start of `s`, if any.
+fn datetime_len(s: &[u8]) -> Option<usize> {
+    let digits =
+        |at: usize, n: usize| s.len() >= at + n && s[at..at + n].iter().all(u8::is_ascii_digit);
+    let byte = |at: usize| s.get(at).copied();
+
+    // YYYY-MM-DD[ T]HH:MM
+    let date = digits(0, 4)
+        && byte(4) == Some(b'-')
+        && digits(5, 2)
+        && byte(7) == Some(b'-')
+        && digits(8, 2);
+    if !date
+        || !matches!(byte(10), Some(b' ' | b'T'))
+        || !digits(11, 2)
+        || byte(13) != Some(b':')
+        || !digits(14, 2)
+    {
+        return None;
+    }
+    let mut len = 16;
+    if byte(len) == Some(b':') && digits(len + 1, 2) {
+        len += 3;
+        if byte(len) == Some(b'.') && digits(len + 1, 1) {
+            len += 1;
+            while digits(len, 1) {
+                len += 1;
+            }
+        }
+    }
+
+    // Time zone: `Z`, an offset, or a space and an offset or abbreviation.
+    let zone_at = if byte(len) == Some(b' ') {
+        len + 1
+    } else {
+        len
+    };
+    match byte(zone_at) {
+        Some(b'Z') if zone_at == len => len += 1,
+        Some(b'+' | b'-') if digits(zone_at + 1, 2) => {
+            len = zone_at + 3;
+            if byte(len) == Some(b':') && digits(len + 1, 2) {
+                len += 3;
+            } else if digits(len, 2) {
+                len += 2;
+            }
+        }
+        Some(b'A'..=b'Z') if zone_at > len => {
+            let end = (zone_at..s.len())
+                .find(|&at| !s[at].is_ascii_uppercase())
+                .unwrap_or(s.len());
+            if (2..=5).contains(&(end - zone_at))
+                && !byte(end).is_some_and(|b| b.is_ascii_alphanumeric())
+            {
+                len = end;
+            }
+        }
+        _ => {}
+    }
+    Some(len)
+}
+
+/// `kind-<hash>` with a 64-bit FNV-1a hash of the canonical JSON. serde_json
+/// sorts object keys, so the hash does not depend on field order.
+fn fixture_key(kind: &str, request: &Value) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in request.to_string().bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{kind}-{hash:016x}")
+}
+
+#[async_trait]
+impl LlmProvider for ReplayProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn available_models(&self) -> Result<Vec<ModelInfo>> {
+        if let (ReplayMode::Record, Some(inner)) = (self.mode, &self.inner) {
+            return inner.available_models().await;
+        }
+        // Offline: report the models that appear in the recordings.
+        let fixtures = self.fixtures.lock().unwrap();
+        let mut models: Vec<&str> = fixtures
+            .entries
+            .values()
+            .filter_map(|f| f.request["model"].as_str())
+            .collect();
+        models.sort_unstable();
+        models.dedup();
+        Ok(models
+            .into_iter()
+            .map(|id| ModelInfo {
+                id: id.to_string(),
+                name: id.to_string(),
+                context_window: 0,
+                supports_function_calling: self.supports_function_calling(),
+                supports_vision: self.supports_vision(),
+            })
+            .collect())
+    }
+
+    async fn chat_completion(&self, request: ChatRequest) -> Result<ChatResponse> {
+        let normalized = normalize_chat(&request);
+        let key = fixture_key("chat", &normalized);
+        if self.mode == ReplayMode::Replay {
+            if let Some(response) = self.lookup(&key)? {
+                debug!(provider = %self.name, key = %key, "Replaying recorded chat completion");
+                return Ok(response);
+            }
+        }
+
+        let inner = self.passthrough(&key)?;
+        let response = inner.chat_completion(request).await?;
+        self.record(key.clone(), normalized, &response)?;
+        info!(provider = %self.name, key = %key, "Recorded chat completion");
+        Ok(response)
+    }
+
+    async fn embed(&self, request: EmbeddingRequest) -> Result<EmbeddingResponse> {
+        let normalized = serde_json::to_value(&request)?;
+        let key = fixture_key("embed", &normalized);
+        if self.mode == ReplayMode::Replay {
+            if let Some(response) = self.lookup(&key)? {
+                return Ok(response);
+            }
+        }
+
+        let inner = self.passthrough(&key)?;
+        let response = inner.embed(request).await?;
+        self.record(key, normalized, &response)?;
+        Ok(response)
+    }
+
+    fn supports_embeddings(&self) -> bool {
+        self.inner.as_ref().is_none_or(|p| p.supports_embeddings())
+    }
+
+    fn supports_function_calling(&self) -> bool {
+        self.inner
+            .as_ref()
+            .is_none_or(|p| p.supports_function_calling())
+    }
+
+    fn supports_vision(&self) -> bool {
+        self.inner.as_ref().is_none_or(|p| p.supports_vision())
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        match (self.mode, &self.inner) {
+            (ReplayMode::Record, Some(inner)) => inner.health_check().await,
+            _ => Ok(true),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Answers every request with the last user message reversed.
+    struct EchoProvider {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl LlmProvider for EchoProvider {
+        fn name(&self) -> &str {
+            "echo"
+        }
+        async fn available_models(&self) -> Result<Vec<ModelInfo>> {
+            Ok(Vec::new())
+        }
+        async fn chat_completion(&self, request: ChatRequest) -> Result<ChatResponse> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let text: String = request
+                .messages
+                .last()
+                .and_then(|m| m.text_content())
+                .unwrap_or_default()
+                .chars()
+                .rev()
+                .collect();
+            Ok(ChatResponse {
+                id: "r1".to_string(),
+                model: request.model,
+                choices: vec![Choice {
+                    index: 0,
+                    message: Message::assistant(text),
+                    finish_reason: Some(FinishReason::Stop),
+                }],
+                usage: Usage {
+                    prompt_tokens: 3,
+                    completion_tokens: 2,
+                    total_tokens: 5,
+                },
+                created: 0,
+            })
+        }
+    }
+
+    fn fixture_path() -> PathBuf {
+        std::env::temp_dir().join(format!("opencrab-replay-{}.json", uuid::Uuid::new_v4()))
+    }
+
+    fn request(text: &str) -> ChatRequest {
+        ChatRequest::new(
+            "gpt-4o",
+            vec![Message::system("Be brief."), Message::user(text)],
+        )
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay_offline() {
+        let path = fixture_path();
+        let echo = Arc::new(EchoProvider {
+            calls: AtomicUsize::new(0),
+        });
+        let recorder = ReplayProvider::new("openai", &path)
+            .unwrap()
+            .with_inner(echo.clone())
+            .with_mode(ReplayMode::Record);
+        let recorded = recorder.chat_completion(request("hello")).await.unwrap();
+        assert_eq!(recorded.choices[0].message.text_content(), Some("olleh"));
+        assert_eq!(echo.calls.load(Ordering::SeqCst), 1);
+
+        // A fresh provider without a wrapped provider answers from the file,
+        // including streaming calls and requests that only differ in `stream`.
+        let replay = ReplayProvider::new("openai", &path)
+            .unwrap()
+            .with_strict(true);
+        assert_eq!(replay.len(), 1);
+        let mut streamed = request("hello");
+        streamed.stream = Some(true);
+        let replayed = replay.chat_completion(streamed).await.unwrap();
+        assert_eq!(replayed.choices[0].message.text_content(), Some("olleh"));
+        assert_eq!(replayed.usage.total_tokens, 5);
+        let models = replay.available_models().await.unwrap();
+        assert_eq!(models[0].id, "gpt-4o");
+
+        let err = replay
+            .chat_completion(request("unknown"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("strict replay"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replay_records_missing_requests_when_not_strict() {
+        let path = fixture_path();
+        let echo = Arc::new(EchoProvider {
+            calls: AtomicUsize::new(0),
+        });
+        let provider = ReplayProvider::new("openai", &path)
+            .unwrap()
+            .with_inner(echo.clone());
+        provider.chat_completion(request("abc")).await.unwrap();
+        provider.chat_completion(request("abc")).await.unwrap();
+        assert_eq!(echo.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(provider.len(), 1);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.contains(&ReplayProvider::chat_key(&request("abc"))));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_chat_key_is_normalised() {
+        let mut a = request("hi");
+        a.metadata.insert("b".to_string(), serde_json::json!(1));
+        a.metadata.insert("a".to_string(), serde_json::json!(2));
+        let mut b = request("hi");
+        b.metadata.insert("a".to_string(), serde_json::json!(2));
+        b.metadata.insert("b".to_string(), serde_json::json!(1));
+        b.stream = Some(true);
+        b.purpose = Some("conversation".to_string());
+        let function = |name: &str| FunctionDefinition {
+            name: name.to_string(),
+            description: None,
+            parameters: serde_json::json!({"type": "object"}),
+        };
+        a.functions = Some(vec![function("ws_read"), function("send_speech")]);
+        b.functions = Some(vec![function("send_speech"), function("ws_read")]);
+        assert_eq!(ReplayProvider::chat_key(&a), ReplayProvider::chat_key(&b));
+        assert_ne!(
+            ReplayProvider::chat_key(&a),
+            ReplayProvider::chat_key(&request("ho"))
+        );
+    }
+
+    #[test]
+    fn test_chat_key_ignores_datetimes() {
+        let at = |now: &str| request(&format!("Current date and time: {now}\nTopic: tea"));
+        let key = ReplayProvider::chat_key(&at("2026-10-17 05:32:36 +09:00"));
+        assert_eq!(
+            key,
+            ReplayProvider::chat_key(&at("2027-01-02 23:59:01 UTC"))
+        );
+        assert_eq!(
+            key,
+            ReplayProvider::chat_key(&at("2026-10-17T05:32:36.123456Z"))
+        );
+        assert_ne!(
+            key,
+            ReplayProvider::chat_key(&request("Current date and time: soon\nTopic: tea"))
+        );
+
+        assert_eq!(
+            mask_datetimes_in(
+                r#"{"created_at":"2026-10-17T05:32:36+00:00"} at 2026-10-17 05:32 UTCs"#
+            )
+            .as_deref(),
+            Some(r#"{"created_at":"<datetime>"} at <datetime> UTCs"#)
+        );
+        assert_eq!(mask_datetimes_in("on 2026-10-17 only"), None);
+    }
+}
@@ -0,0 +1,348 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// Retry and circuit breaker settings shared by all providers of a router.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResilienceConfig {
+    /// Retries per candidate after a retryable failure (0 = try once).
+    pub max_retries: u32,
+    /// Backoff before the first retry; doubled on every further retry.
+    pub base_delay: Duration,
+    /// Upper bound for a single wait. A `Retry-After` longer than this skips
+    /// the provider instead of sleeping.
+    pub max_delay: Duration,
+    /// Consecutive retryable failures that open a provider's circuit.
+    pub failure_threshold: u32,
+    /// How long an open circuit rejects calls before letting one probe through.
+    pub open_duration: Duration,
+}
+
+impl Default for ResilienceConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ResilienceConfig {
+    /// Delay before retry number `attempt` (0-based): exponential backoff with
+    /// "equal jitter" (a random point in the upper half of the window).
+    /// A `Retry-After` from the provider is honoured as a lower bound.
+    pub fn backoff(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        let window = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        let half = window / 2;
+        let jittered = half + half.mul_f64(random_unit());
+        retry_after.map_or(jittered, |after| after.max(jittered))
+    }
+}
+
+/// A random number in `[0, 1)`.
+fn random_unit() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Circuit breaker state of one provider.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Calls go through.
+    Closed,
+    /// Too many failures: calls are rejected until `open_duration` has passed.
+    Open,
+    /// One probe call is allowed; its result closes or re-opens the circuit.
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct Health {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probe_in_flight: bool,
+    /// Incremented for every probe, so a stale [`Admission`] cannot release a newer one.
+    probe_id: u64,
+    /// Set from `Retry-After` on rate limits: skip the provider until then.
+    blocked_until: Option<Instant>,
+    last_error: Option<String>,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            state: CircuitState::Closed,
+            consecutive_failures: 0,
+            opened_at: None,
+            probe_in_flight: false,
+            probe_id: 0,
+            blocked_until: None,
+            last_error: None,
+        }
+    }
+}
+
+/// Snapshot of a provider's health, as reported by `LlmRouter::health_check_all`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProviderHealth {
+    /// Result of the provider's own health check (`None` when not probed).
+    pub reachable: Option<bool>,
+    pub circuit: CircuitState,
+    pub consecutive_failures: u32,
+    /// Seconds until an open circuit lets a probe through, or until a
+    /// rate limit expires.
+    pub retry_in_secs: Option<f64>,
+    pub last_error: Option<String>,
+}
+
+/// Per-provider circuit breakers.
+#[derive(Debug, Default)]
+pub(crate) struct CircuitBreakers {
+    health: Mutex<HashMap<String, Health>>,
+}
+
+/// Permission to make one call, returned by [`CircuitBreakers::allow`].
+///
+/// Keep it alive until the call's outcome has been recorded. If a half-open
+/// probe is dropped first (e.g. the caller's future was cancelled), the probe
+/// slot is released so the circuit does not stay half-open forever.
+#[must_use]
+pub(crate) struct Admission<'a> {
+    breakers: &'a CircuitBreakers,
+    provider: String,
+    probe: Option<u64>,
+}
+
+impl Drop for Admission<'_> {
+    fn drop(&mut self) {
+        let Some(probe_id) = self.probe else {
+            return;
+        };
+        let mut map = self.breakers.health.lock().unwrap();
+        if let Some(health) = map.get_mut(&self.provider) {
+            // Outcomes recorded for this probe have already left the half-open state.
+            if health.state == CircuitState::HalfOpen && health.probe_in_flight && health.probe_id == probe_id {
+                health.probe_in_flight = false;
+            }
+        }
+    }
+}
+
+impl CircuitBreakers {
+    /// Whether a call to `provider` may be made now. Moves an open circuit to
+    /// half-open once its timeout has passed, letting exactly one probe through.
+    pub(crate) fn allow(&self, provider: &str, config: &ResilienceConfig) -> Option<Admission<'_>> {
+        let mut map = self.health.lock().unwrap();
+        let health = map.entry(provider.to_string()).or_default();
+        let now = Instant::now();
+        if health.blocked_until.is_some_and(|until| now < until) {
+            return None;
+        }
+        let probe = match health.state {
+            CircuitState::Closed => false,
+            CircuitState::Open => {
+                let ready = health
+                    .opened_at
+                    .is_none_or(|at| now.duration_since(at) >= config.open_duration);
+                if !ready {
+                    return None;
+                }
+                health.state = CircuitState::HalfOpen;
+                true
+            }
+            CircuitState::HalfOpen if health.probe_in_flight => return None,
+            CircuitState::HalfOpen => true,
+        };
+        let probe = probe.then(|| {
+            health.probe_in_flight = true;
+            health.probe_id += 1;
+            health.probe_id
+        });
+        Some(Admission {
+            breakers: self,
+            provider: provider.to_string(),
+            probe,
+        })
+    }
+
+    pub(crate) fn record_success(&self, provider: &str) {
+        let mut map = self.health.lock().unwrap();
+        let health = map.entry(provider.to_string()).or_default();
+        *health = Health {
+            last_error: health.last_error.take(),
+            probe_id: health.probe_id,
+            ..Health::default()
+        };
+    }
+
+    /// Record a retryable failure. Opens the circuit after `failure_threshold`
+    /// consecutive failures, or immediately when a half-open probe fails.
+    /// Returns true when this failure opened the circuit.
+    pub(crate) fn record_failure(
+        &self,
+        provider: &str,
+        error: &str,
+        retry_after: Option<Duration>,
+        config: &ResilienceConfig,
+    ) -> bool {
+        let mut map = self.health.lock().unwrap();
+        let health = map.entry(provider.to_string()).or_default();
+        let now = Instant::now();
+        health.consecutive_failures += 1;
+        health.last_error = Some(error.to_string());
+        health.probe_in_flight = false;
+        if let Some(after) = retry_after {
+            health.blocked_until = Some(now + after);
+        }
+        let open = health.state == CircuitState::HalfOpen
+            || (health.state == CircuitState::Closed
+                && health.consecutive_failures >= config.failure_threshold);
+        if open {
+            health.state = CircuitState::Open;
+            health.opened_at = Some(now);
+        }
+        open
+    }
+
+    /// Record a fatal failure: it says nothing about the provider's health,
+    /// but a half-open probe that got an answer proves the provider is up.
+    pub(crate) fn record_fatal(&self, provider: &str, error: &str) {
+        let mut map = self.health.lock().unwrap();
+        let health = map.entry(provider.to_string()).or_default();
+        health.last_error = Some(error.to_string());
+        if health.state == CircuitState::HalfOpen {
+            health.state = CircuitState::Closed;
+            health.consecutive_failures = 0;
+            health.probe_in_flight = false;
+        }
+    }
+
+    pub(crate) fn snapshot(&self, provider: &str, config: &ResilienceConfig) -> ProviderHealth {
+        let map = self.health.lock().unwrap();
+        let Some(health) = map.get(provider) else {
+            return ProviderHealth {
+                reachable: None,
+                circuit: CircuitState::Closed,
+                consecutive_failures: 0,
+                retry_in_secs: None,
+                last_error: None,
+            };
+        };
+        let now = Instant::now();
+        let reopen = health
+            .opened_at
+            .filter(|_| health.state == CircuitState::Open)
+            .map(|at| (at + config.open_duration).saturating_duration_since(now));
+        let unblock = health
+            .blocked_until
+            .map(|until| until.saturating_duration_since(now))
+            .filter(|d| !d.is_zero());
+        ProviderHealth {
+            reachable: None,
+            circuit: health.state,
+            consecutive_failures: health.consecutive_failures,
+            retry_in_secs: reopen.max(unblock).map(|d| d.as_secs_f64()),
+            last_error: health.last_error.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ResilienceConfig {
+        ResilienceConfig {
+            max_retries: 0,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(400),
+            failure_threshold: 2,
+            open_duration: Duration::from_millis(20),
+        }
+    }
+
+    #[test]
+    fn test_backoff_bounds() {
+        let config = config();
+        for _ in 0..20 {
+            let first = config.backoff(0, None);
+            assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+            let capped = config.backoff(10, None);
+            assert!(capped >= Duration::from_millis(200) && capped <= Duration::from_millis(400));
+        }
+        let after = config.backoff(0, Some(Duration::from_secs(2)));
+        assert_eq!(after, Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_circuit_opens_and_half_opens() {
+        let config = config();
+        let breakers = CircuitBreakers::default();
+        assert!(breakers.allow("a", &config).is_some());
+        assert!(!breakers.record_failure("a", "503", None, &config));
+        assert!(breakers.record_failure("a", "503", None, &config));
+        assert!(breakers.allow("a", &config).is_none());
+        assert_eq!(breakers.snapshot("a", &config).circuit, CircuitState::Open);
+
+        std::thread::sleep(Duration::from_millis(25));
+        // Exactly one probe goes through.
+        let probe = breakers.allow("a", &config);
+        assert!(probe.is_some());
+        assert!(breakers.allow("a", &config).is_none());
+        // A failed probe re-opens the circuit at once.
+        assert!(breakers.record_failure("a", "503", None, &config));
+        drop(probe);
+        assert!(breakers.allow("a", &config).is_none());
+
+        std::thread::sleep(Duration::from_millis(25));
+        let probe = breakers.allow("a", &config);
+        assert!(probe.is_some());
+        breakers.record_success("a");
+        drop(probe);
+        let health = breakers.snapshot("a", &config);
+        assert_eq!(health.circuit, CircuitState::Closed);
+        assert_eq!(health.consecutive_failures, 0);
+        assert_eq!(health.last_error.as_deref(), Some("503"));
+    }
+
+    #[test]
+    fn test_retry_after_blocks_provider() {
+        let config = config();
+        let breakers = CircuitBreakers::default();
+        breakers.record_failure("a", "429", Some(Duration::from_millis(20)), &config);
+        assert!(breakers.allow("a", &config).is_none());
+        assert!(breakers.snapshot("a", &config).retry_in_secs.is_some());
+        std::thread::sleep(Duration::from_millis(25));
+        assert!(breakers.allow("a", &config).is_some());
+    }
+
+    #[test]
+    fn test_dropped_probe_releases_half_open_circuit() {
+        let config = config();
+        let breakers = CircuitBreakers::default();
+        breakers.record_failure("a", "503", None, &config);
+        breakers.record_failure("a", "503", None, &config);
+        std::thread::sleep(Duration::from_millis(25));
+
+        let stale = breakers.allow("a", &config).unwrap();
+        assert!(breakers.allow("a", &config).is_none());
+        // The probe is abandoned without an outcome: the next call may probe.
+        drop(stale);
+        let probe = breakers.allow("a", &config).unwrap();
+        assert_eq!(breakers.snapshot("a", &config).circuit, CircuitState::HalfOpen);
+        assert!(breakers.allow("a", &config).is_none());
+        drop(probe);
+    }
+}
@@ -1,13 +1,19 @@
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use futures::stream::BoxStream;
 use tracing::{debug, info, warn};
 
-use crate::message::{ChatRequest, ChatResponse, ChatStreamDelta};
+use crate::error::{classify, should_fallback, ErrorClass};
+use crate::message::{
+    ChatRequest, ChatResponse, ChatStreamDelta, EmbeddingRequest, EmbeddingResponse,
+};
 use crate::metrics::MetricsCollector;
-use crate::traits::LlmProvider;
+use crate::resilience::{CircuitBreakers, ProviderHealth, ResilienceConfig};
+use crate::traits::{LlmProvider, ModelInfo};
 
 /// LLM Router for dynamic provider switching with fallback chains.
 ///
@@ -16,13 +22,37 @@ use crate::traits::LlmProvider;
 /// - Default provider selection
 /// - Fallback chains (try providers in order until one succeeds)
 /// - Model aliasing (map user-facing names to provider-specific models)
+/// - Purpose routes (a model plus fallback models per purpose)
+/// - Retries with backoff and a circuit breaker per provider
 pub struct LlmRouter {
     providers: HashMap<String, Arc<dyn LlmProvider>>,
     default_provider: Option<String>,
     fallback_chain: Vec<String>,
     /// Maps alias names to "provider:model" strings.
     model_mapping: HashMap<String, String>,
+    /// Model and fallbacks per purpose ("conversation", "tool_calling", ...).
+    purpose_routes: HashMap<String, PurposeRoute>,
     metrics: Option<MetricsCollector>,
+    /// Model lists fetched from providers, keyed by provider name.
+    model_info_cache: Mutex<HashMap<String, CachedModels>>,
+    /// How long to wait before listing a provider's models again after a failure.
+    model_list_retry: Duration,
+    resilience: ResilienceConfig,
+    breakers: CircuitBreakers,
+}
+
+/// A provider's model list, or the time fetching it last failed.
+#[derive(Debug, Clone)]
+enum CachedModels {
+    Listed(Vec<ModelInfo>),
+    Failed(Instant),
+}
+
+/// Model selection for one purpose.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PurposeRoute {
+    pub model: String,
+    pub fallbacks: Vec<String>,
 }
 
 impl LlmRouter {
@@ -32,7 +62,12 @@ impl LlmRouter {
             default_provider: None,
             fallback_chain: Vec::new(),
             model_mapping: HashMap::new(),
+            purpose_routes: HashMap::new(),
             metrics: None,
+            model_info_cache: Mutex::new(HashMap::new()),
+            model_list_retry: Duration::from_secs(60),
+            resilience: ResilienceConfig::default(),
+            breakers: CircuitBreakers::default(),
         }
     }
 
@@ -59,6 +94,33 @@ impl LlmRouter {
         self.model_mapping.insert(alias.into(), target.into());
     }
 
+    /// Route a purpose to a model, with fallback models tried in order when it
+    /// fails. Models may be aliases or "provider:model" strings.
+    pub fn set_purpose_route(
+        &mut self,
+        purpose: impl Into<String>,
+        model: impl Into<String>,
+        fallbacks: Vec<String>,
+    ) {
+        self.purpose_routes.insert(
+            purpose.into(),
+            PurposeRoute {
+                model: model.into(),
+                fallbacks,
+            },
+        );
+    }
+
+    /// The model configured for a purpose, if any.
+    pub fn purpose_model(&self, purpose: &str) -> Option<&str> {
+        self.purpose_routes.get(purpose).map(|r| r.model.as_str())
+    }
+
+    /// Set the retry and circuit breaker settings.
+    pub fn set_resilience(&mut self, config: ResilienceConfig) {
+        self.resilience = config;
+    }
+
     /// Attach a metrics collector to the router.
     pub fn set_metrics(&mut self, metrics: MetricsCollector) {
         self.metrics = Some(metrics);
@@ -116,26 +178,130 @@ impl LlmRouter {
         Ok((parts[0].to_string(), parts[1].to_string()))
     }
 
-    /// Route a chat completion request to the appropriate provider.
+    /// Build the ordered list of `(provider, model)` pairs to try.
     ///
-    /// Resolution order:
-    /// 1. Resolve the model (alias -> provider:model)
-    /// 2. Send to that provider
-    /// 3. On failure, try the fallback chain
-    pub async fn chat_completion(&self, mut request: ChatRequest) -> Result<ChatResponse> {
-        let (provider_name, model_name) = self.resolve_model(&request.model)?;
-        request.model = model_name;
+    /// 1. The requested model, or the purpose's model when `model` is empty
+    /// 2. The purpose's fallback models
+    /// 3. The provider fallback chain (same model name on other providers)
+    fn candidates(&self, request: &ChatRequest) -> Result<Vec<(String, String)>> {
+        let route = request
+            .purpose
+            .as_deref()
+            .and_then(|p| self.purpose_routes.get(p));
+        let primary = if request.model.is_empty() {
+            match route {
+                Some(route) => route.model.as_str(),
+                None => anyhow::bail!(
+                    "No model given and no model configured for purpose '{}'",
+                    request.purpose.as_deref().unwrap_or("")
+                ),
+            }
+        } else {
+            request.model.as_str()
+        };
+        let (provider_name, model_name) = self.resolve_model(primary)?;
+
+        let mut candidates = vec![(provider_name, model_name.clone())];
+        let mut push = |pair: (String, String)| {
+            if !candidates.contains(&pair) {
+                candidates.push(pair);
+            }
+        };
+        for fallback in route.map(|r| r.fallbacks.as_slice()).unwrap_or_default() {
+            match self.resolve_model(fallback) {
+                Ok(pair) => push(pair),
+                Err(e) => warn!(model = %fallback, error = %e, "Skipping unresolvable purpose fallback"),
+            }
+        }
+        for fallback_name in &self.fallback_chain {
+            push((fallback_name.clone(), model_name.clone()));
+        }
+        Ok(candidates)
+    }
 
-        debug!(provider = %provider_name, model = %request.model, "Routing chat completion");
+    /// Call one provider, retrying retryable failures with backoff.
+    ///
+    /// Fatal errors and failures that open the circuit are returned at once,
+    /// as is a `Retry-After` longer than `max_delay` (the next candidate is
+    /// tried instead of waiting). A provider whose circuit is open is not
+    /// called at all.
+    async fn call_with_retry<T, F, Fut>(&self, provider_name: &str, mut call: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let config = &self.resilience;
+        let mut attempt = 0;
+        loop {
+            let Some(_admission) = self.breakers.allow(provider_name, config) else {
+                anyhow::bail!("Provider '{provider_name}' is unavailable (circuit open or rate limited)");
+            };
+            let error = match call().await {
+                Ok(value) => {
+                    self.breakers.record_success(provider_name);
+                    return Ok(value);
+                }
+                Err(e) => e,
+            };
+            let ErrorClass::Retryable { retry_after } = classify(&error) else {
+                self.breakers.record_fatal(provider_name, &error.to_string());
+                return Err(error);
+            };
+            let opened = self
+                .breakers
+                .record_failure(provider_name, &error.to_string(), retry_after, config);
+            if opened {
+                warn!(provider = %provider_name, "Circuit opened after repeated failures");
+                return Err(error);
+            }
+            if attempt >= config.max_retries {
+                return Err(error);
+            }
+            let delay = config.backoff(attempt, retry_after);
+            if delay > config.max_delay {
+                return Err(error);
+            }
+            debug!(provider = %provider_name, attempt, ?delay, error = %error, "Retrying after backoff");
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Route a chat completion request to the appropriate provider.
+    ///
+    /// Candidates are tried in the order given by [`Self::candidates`] until
+    /// one succeeds; each candidate is retried per [`ResilienceConfig`].
+    /// Errors that another model would not fix (context overflow, content
+    /// filter) are returned at once. The returned error keeps the last
+    /// [`crate::LlmError`] as its source.
+    pub async fn chat_completion(&self, request: ChatRequest) -> Result<ChatResponse> {
+        let candidates = self.candidates(&request)?;
+        let purpose = request.purpose.as_deref().unwrap_or("-");
+        let mut last_error = None;
+
+        for (i, (provider_name, model_name)) in candidates.iter().enumerate() {
+            let Some(provider) = self.providers.get(provider_name) else {
+                if i == 0 {
+                    warn!(provider = %provider_name, "Provider not found, trying fallbacks");
+                }
+                continue;
+            };
+            let mut request = request.clone();
+            request.model = model_name.clone();
+            debug!(provider = %provider_name, model = %model_name, purpose, "Routing chat completion");
 
-        // Try the resolved provider first
-        if let Some(provider) = self.providers.get(&provider_name) {
             let start = std::time::Instant::now();
-            match provider.chat_completion(request.clone()).await {
+            let result = self
+                .call_with_retry(provider_name, || provider.chat_completion(request.clone()))
+                .await;
+            match result {
                 Ok(response) => {
+                    if i > 0 {
+                        info!(provider = %provider_name, model = %model_name, "Fallback succeeded");
+                    }
                     if let Some(ref metrics) = self.metrics {
                         metrics.record_success(
-                            &provider_name,
+                            provider_name,
                             &response.model,
                             response.usage.prompt_tokens,
                             response.usage.completion_tokens,
@@ -145,141 +311,165 @@ impl LlmRouter {
                     return Ok(response);
                 }
                 Err(e) => {
-                    warn!(
-                        provider = %provider_name,
-                        error = %e,
-                        "Primary provider failed, trying fallback chain"
-                    );
                     if let Some(ref metrics) = self.metrics {
                         metrics.record_failure(
-                            &provider_name,
-                            &request.model,
+                            provider_name,
+                            model_name,
                             start.elapsed().as_millis() as u64,
                             &e.to_string(),
                         );
                     }
-                }
-            }
-        } else {
-            warn!(provider = %provider_name, "Provider not found, trying fallback chain");
-        }
-
-        // Try fallback chain
-        for fallback_name in &self.fallback_chain {
-            if fallback_name == &provider_name {
-                continue; // Skip the provider we already tried
-            }
-
-            if let Some(provider) = self.providers.get(fallback_name) {
-                debug!(provider = %fallback_name, "Trying fallback provider");
-                let start = std::time::Instant::now();
-                match provider.chat_completion(request.clone()).await {
-                    Ok(response) => {
-                        info!(provider = %fallback_name, "Fallback provider succeeded");
-                        if let Some(ref metrics) = self.metrics {
-                            metrics.record_success(
-                                fallback_name,
-                                &response.model,
-                                response.usage.prompt_tokens,
-                                response.usage.completion_tokens,
-                                start.elapsed().as_millis() as u64,
-                            );
-                        }
-                        return Ok(response);
-                    }
-                    Err(e) => {
-                        warn!(
-                            provider = %fallback_name,
-                            error = %e,
-                            "Fallback provider failed"
-                        );
-                        if let Some(ref metrics) = self.metrics {
-                            metrics.record_failure(
-                                fallback_name,
-                                &request.model,
-                                start.elapsed().as_millis() as u64,
-                                &e.to_string(),
-                            );
-                        }
+                    if !should_fallback(&e) {
+                        return Err(e);
                     }
+                    warn!(
+                        provider = %provider_name,
+                        model = %model_name,
+                        error = %e,
+                        "Chat completion failed, trying next candidate"
+                    );
+                    last_error = Some(e);
                 }
             }
         }
 
-        anyhow::bail!(
-            "All providers failed for model '{}'. Tried: {} + fallback chain {:?}",
-            request.model,
-            provider_name,
-            self.fallback_chain
-        )
+        Err(all_failed(last_error, &candidates))
     }
 
     /// Route a streaming chat completion request.
+    ///
+    /// Retries and the circuit breaker apply to opening the stream; errors
+    /// inside an opened stream are passed to the caller.
     pub async fn chat_completion_stream(
         &self,
-        mut request: ChatRequest,
+        request: ChatRequest,
     ) -> Result<BoxStream<'static, Result<ChatStreamDelta>>> {
-        let (provider_name, model_name) = self.resolve_model(&request.model)?;
-        request.model = model_name;
+        let candidates = self.candidates(&request)?;
+        let mut last_error = None;
 
-        debug!(provider = %provider_name, model = %request.model, "Routing streaming chat completion");
-
-        // Try resolved provider first
-        if let Some(provider) = self.providers.get(&provider_name) {
-            match provider.chat_completion_stream(request.clone()).await {
+        for (provider_name, model_name) in &candidates {
+            let Some(provider) = self.providers.get(provider_name) else {
+                continue;
+            };
+            let mut request = request.clone();
+            request.model = model_name.clone();
+            debug!(provider = %provider_name, model = %model_name, "Routing streaming chat completion");
+
+            let result = self
+                .call_with_retry(provider_name, || provider.chat_completion_stream(request.clone()))
+                .await;
+            match result {
                 Ok(stream) => return Ok(stream),
+                Err(e) if !should_fallback(&e) => return Err(e),
                 Err(e) => {
                     warn!(
                         provider = %provider_name,
+                        model = %model_name,
                         error = %e,
-                        "Primary provider stream failed, trying fallback chain"
+                        "Stream failed, trying next candidate"
                     );
+                    last_error = Some(e);
                 }
             }
         }
 
-        // Try fallback chain
-        for fallback_name in &self.fallback_chain {
-            if fallback_name == &provider_name {
-                continue;
-            }
+        Err(all_failed(last_error, &candidates))
+    }
 
-            if let Some(provider) = self.providers.get(fallback_name) {
-                match provider.chat_completion_stream(request.clone()).await {
-                    Ok(stream) => {
-                        info!(provider = %fallback_name, "Fallback provider stream succeeded");
-                        return Ok(stream);
-                    }
+    /// Route an embedding request to the provider of the resolved model.
+    ///
+    /// There is no fallback: vectors from different models are not comparable,
+    /// so a failure is returned to the caller as-is.
+    pub async fn embed(&self, mut request: EmbeddingRequest) -> Result<EmbeddingResponse> {
+        let (provider_name, model_name) = self.resolve_model(&request.model)?;
+        request.model = model_name;
+
+        debug!(provider = %provider_name, model = %request.model, "Routing embedding request");
+
+        let provider = self
+            .providers
+            .get(&provider_name)
+            .ok_or_else(|| anyhow::anyhow!("Provider not found: {provider_name}"))?;
+        provider.embed(request).await
+    }
+
+    /// Look up model metadata (context window, capabilities).
+    ///
+    /// Each provider's model list is fetched once and cached. A failed fetch
+    /// returns `None` and is retried after `model_list_retry`, so a provider
+    /// that was briefly unreachable does not stay without metadata.
+    pub async fn model_info(&self, model_or_alias: &str) -> Option<ModelInfo> {
+        let (provider_name, model_name) = self.resolve_model(model_or_alias).ok()?;
+        let cached = self
+            .model_info_cache
+            .lock()
+            .unwrap()
+            .get(&provider_name)
+            .cloned();
+        let models = match cached {
+            Some(CachedModels::Listed(models)) => models,
+            Some(CachedModels::Failed(at)) if at.elapsed() < self.model_list_retry => return None,
+            _ => {
+                let provider = self.providers.get(&provider_name)?;
+                let (entry, models) = match provider.available_models().await {
+                    Ok(models) => (CachedModels::Listed(models.clone()), models),
                     Err(e) => {
-                        warn!(
-                            provider = %fallback_name,
-                            error = %e,
-                            "Fallback provider stream failed"
-                        );
+                        warn!(provider = %provider_name, error = %e, "Failed to list models");
+                        (CachedModels::Failed(Instant::now()), Vec::new())
                     }
-                }
+                };
+                self.model_info_cache
+                    .lock()
+                    .unwrap()
+                    .insert(provider_name.clone(), entry);
+                models
             }
-        }
+        };
+        models.into_iter().find(|m| m.id == model_name)
+    }
+
+    /// Context window (in tokens) of a model, if the provider reports it.
+    pub async fn context_window(&self, model_or_alias: &str) -> Option<u32> {
+        self.model_info(model_or_alias)
+            .await
+            .map(|m| m.context_window)
+            .filter(|w| *w > 0)
+    }
 
-        anyhow::bail!(
-            "All providers failed for streaming model '{}'. Tried: {} + fallback chain {:?}",
-            request.model,
-            provider_name,
-            self.fallback_chain
-        )
+    /// Circuit breaker state of a provider, without contacting it.
+    pub fn provider_health(&self, name: &str) -> ProviderHealth {
+        self.breakers.snapshot(name, &self.resilience)
     }
 
-    /// Run health checks on all registered providers.
-    pub async fn health_check_all(&self) -> HashMap<String, bool> {
+    /// Run health checks on all registered providers and report them with
+    /// each provider's circuit breaker state.
+    pub async fn health_check_all(&self) -> HashMap<String, ProviderHealth> {
         let mut results = HashMap::new();
         for (name, provider) in &self.providers {
-            let healthy = provider.health_check().await.unwrap_or(false);
-            results.insert(name.clone(), healthy);
+            let reachable = provider.health_check().await.unwrap_or(false);
+            let health = ProviderHealth {
+                reachable: Some(reachable),
+                ..self.provider_health(name)
+            };
+            results.insert(name.clone(), health);
         }
         results
     }
 }
 
+/// The error after every candidate failed, wrapping the last failure (if any
+/// provider was called) so its [`crate::LlmError`] can still be downcast.
+fn all_failed(last_error: Option<anyhow::Error>, candidates: &[(String, String)]) -> anyhow::Error {
+    let message = format!(
+        "All providers failed for model '{}'. Tried: {:?}",
+        candidates[0].1, candidates
+    );
+    match last_error {
+        Some(e) => e.context(message),
+        None => anyhow::anyhow!(message),
+    }
+}
+
 impl Default for LlmRouter {
     fn default() -> Self {
         Self::new()
@@ -293,6 +483,8 @@ impl std::fmt::Debug for LlmRouter {
             .field("default_provider", &self.default_provider)
             .field("fallback_chain", &self.fallback_chain)
             .field("model_mapping", &self.model_mapping)
+            .field("purpose_routes", &self.purpose_routes)
+            .field("resilience", &self.resilience)
             .finish()
     }
 }
@@ -301,8 +493,10 @@ impl std::fmt::Debug for LlmRouter {
 mod tests {
     use super::*;
     use crate::message::*;
+    use crate::resilience::CircuitState;
     use crate::traits::LlmProvider;
     use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
 
     struct MockProvider {
         provider_name: String,
@@ -324,7 +518,16 @@ mod tests {
             &self.provider_name
         }
         async fn available_models(&self) -> anyhow::Result<Vec<crate::traits::ModelInfo>> {
-            Ok(vec![])
+            if self.should_fail.load(Ordering::SeqCst) {
+                anyhow::bail!("mock failure");
+            }
+            Ok(vec![crate::traits::ModelInfo {
+                id: "gpt-4o".to_string(),
+                name: "GPT-4o".to_string(),
+                context_window: 128_000,
+                supports_function_calling: true,
+                supports_vision: true,
+            }])
         }
         async fn chat_completion(&self, request: ChatRequest) -> anyhow::Result<ChatResponse> {
             if self.should_fail.load(Ordering::SeqCst) {
@@ -348,6 +551,197 @@ mod tests {
         }
     }
 
+    /// Provider that plays back scripted HTTP statuses (200 = success).
+    struct ScriptedProvider {
+        provider_name: String,
+        script: Mutex<std::collections::VecDeque<(u16, Option<Duration>)>>,
+        calls: std::sync::atomic::AtomicUsize,
+        /// Delay before every answer.
+        latency: Mutex<Duration>,
+    }
+
+    impl ScriptedProvider {
+        fn new(name: &str, script: &[(u16, Option<Duration>)]) -> Arc<Self> {
+            Arc::new(Self {
+                provider_name: name.to_string(),
+                script: Mutex::new(script.iter().copied().collect()),
+                calls: Default::default(),
+                latency: Mutex::new(Duration::ZERO),
+            })
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl LlmProvider for ScriptedProvider {
+        fn name(&self) -> &str {
+            &self.provider_name
+        }
+        async fn available_models(&self) -> anyhow::Result<Vec<crate::traits::ModelInfo>> {
+            Ok(Vec::new())
+        }
+        async fn chat_completion(&self, request: ChatRequest) -> anyhow::Result<ChatResponse> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let latency = *self.latency.lock().unwrap();
+            tokio::time::sleep(latency).await;
+            let (status, retry_after) = self.script.lock().unwrap().pop_front().unwrap_or((200, None));
+            if status != 200 {
+                let error = crate::error::LlmError::from_body(
+                    &self.provider_name,
+                    &request.model,
+                    status,
+                    retry_after,
+                    "scripted",
+                );
+                return Err(error.into());
+            }
+            Ok(ChatResponse {
+                id: "resp-1".to_string(),
+                model: format!("{}:{}", self.provider_name, request.model),
+                choices: vec![Choice {
+                    index: 0,
+                    message: Message::assistant("ok"),
+                    finish_reason: Some(FinishReason::Stop),
+                }],
+                usage: Usage::default(),
+                created: 0,
+            })
+        }
+    }
+
+    fn resilient_router(primary: Arc<ScriptedProvider>, backup: Arc<ScriptedProvider>) -> LlmRouter {
+        let mut router = LlmRouter::new();
+        router.add_provider(primary);
+        router.add_provider(backup);
+        router.set_default_provider("primary");
+        router.set_fallback_chain(vec!["backup".to_string()]);
+        router.set_resilience(ResilienceConfig {
+            max_retries: 2,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(50),
+            failure_threshold: 3,
+            open_duration: Duration::from_millis(30),
+        });
+        router
+    }
+
+    fn hello() -> ChatRequest {
+        ChatRequest::new("m", vec![Message::user("hello")])
+    }
+
+    #[tokio::test]
+    async fn test_retry_then_succeed_on_same_provider() {
+        let primary = ScriptedProvider::new("primary", &[(503, None), (429, Some(Duration::from_millis(5)))]);
+        let backup = ScriptedProvider::new("backup", &[]);
+        let router = resilient_router(primary.clone(), backup.clone());
+
+        let response = router.chat_completion(hello()).await.unwrap();
+        assert_eq!(response.model, "primary:m");
+        assert_eq!(primary.calls(), 3);
+        assert_eq!(backup.calls(), 0);
+        assert_eq!(router.provider_health("primary").circuit, CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_fatal_error_falls_back_without_retry() {
+        let primary = ScriptedProvider::new("primary", &[(400, None)]);
+        let backup = ScriptedProvider::new("backup", &[]);
+        let router = resilient_router(primary.clone(), backup.clone());
+
+        let response = router.chat_completion(hello()).await.unwrap();
+        assert_eq!(response.model, "backup:m");
+        assert_eq!(primary.calls(), 1);
+        assert_eq!(router.provider_health("primary").consecutive_failures, 0);
+    }
+
+    #[tokio::test]
+    async fn test_long_retry_after_skips_to_fallback() {
+        let primary = ScriptedProvider::new("primary", &[(429, Some(Duration::from_secs(60)))]);
+        let backup = ScriptedProvider::new("backup", &[]);
+        let router = resilient_router(primary.clone(), backup.clone());
+
+        let started = std::time::Instant::now();
+        assert_eq!(router.chat_completion(hello()).await.unwrap().model, "backup:m");
+        assert!(started.elapsed() < Duration::from_secs(1));
+        // The rate-limited provider is skipped until Retry-After expires.
+        assert_eq!(router.chat_completion(hello()).await.unwrap().model, "backup:m");
+        assert_eq!(primary.calls(), 1);
+        assert!(router.provider_health("primary").retry_in_secs.unwrap() > 50.0);
+    }
+
+    #[tokio::test]
+    async fn test_request_errors_skip_fallback() {
+        // 413 is a context overflow: another candidate would not fit it either.
+        let primary = ScriptedProvider::new("primary", &[(413, None), (401, None)]);
+        let backup = ScriptedProvider::new("backup", &[(500, None); 3]);
+        let router = resilient_router(primary.clone(), backup.clone());
+
+        let err = router.chat_completion(hello()).await.unwrap_err();
+        let typed = err.downcast_ref::<crate::error::LlmError>().unwrap();
+        assert!(matches!(typed, crate::error::LlmError::ContextOverflow { .. }));
+        assert_eq!(backup.calls(), 0);
+
+        // Auth failures fall back; the last typed error survives the summary.
+        let err = router.chat_completion(hello()).await.unwrap_err();
+        assert!(err.to_string().starts_with("All providers failed"));
+        let typed = err.downcast_ref::<crate::error::LlmError>().unwrap();
+        assert_eq!((typed.provider(), typed.status()), ("backup", Some(500)));
+        assert_eq!(backup.calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_opens_and_recovers() {
+        let primary = ScriptedProvider::new("primary", &[(503, None); 3]);
+        let backup = ScriptedProvider::new("backup", &[]);
+        let router = resilient_router(primary.clone(), backup.clone());
+
+        // Three consecutive failures (1 try + 2 retries) open the circuit.
+        assert_eq!(router.chat_completion(hello()).await.unwrap().model, "backup:m");
+        assert_eq!(primary.calls(), 3);
+        let health = router.provider_health("primary");
+        assert_eq!(health.circuit, CircuitState::Open);
+        assert_eq!(health.last_error.as_deref(), Some("primary API error (503): scripted"));
+
+        // While open, the provider is not called.
+        assert_eq!(router.chat_completion(hello()).await.unwrap().model, "backup:m");
+        assert_eq!(primary.calls(), 3);
+
+        // After the timeout one probe goes through and closes the circuit.
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert_eq!(router.chat_completion(hello()).await.unwrap().model, "primary:m");
+        assert_eq!(primary.calls(), 4);
+        assert_eq!(router.provider_health("primary").circuit, CircuitState::Closed);
+
+        let report = router.health_check_all().await;
+        assert_eq!(report["primary"].reachable, Some(true));
+        assert_eq!(report["backup"].circuit, CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_probe_does_not_wedge_circuit() {
+        let primary = ScriptedProvider::new("primary", &[(503, None); 3]);
+        let backup = ScriptedProvider::new("backup", &[]);
+        let router = resilient_router(primary.clone(), backup.clone());
+        router.chat_completion(hello()).await.unwrap();
+        assert_eq!(router.provider_health("primary").circuit, CircuitState::Open);
+
+        // The probe is cancelled while waiting on the provider.
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        *primary.latency.lock().unwrap() = Duration::from_secs(5);
+        let cancelled = tokio::time::timeout(Duration::from_millis(20), router.chat_completion(hello())).await;
+        assert!(cancelled.is_err());
+        assert_eq!(primary.calls(), 4);
+        assert_eq!(router.provider_health("primary").circuit, CircuitState::HalfOpen);
+
+        // The next call probes again instead of being rejected forever.
+        *primary.latency.lock().unwrap() = Duration::ZERO;
+        assert_eq!(router.chat_completion(hello()).await.unwrap().model, "primary:m");
+        assert_eq!(router.provider_health("primary").circuit, CircuitState::Closed);
+    }
+
     #[test]
     fn test_resolve_model_default() {
         let mut router = LlmRouter::new();
@@ -381,6 +775,36 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_context_window_lookup() {
+        let mut router = LlmRouter::new();
+        router.add_provider(Arc::new(MockProvider::new("openai", false)));
+        router.set_default_provider("openai");
+        router.add_model_mapping("smart", "openai:gpt-4o");
+
+        assert_eq!(router.context_window("gpt-4o").await, Some(128_000));
+        assert_eq!(router.context_window("smart").await, Some(128_000));
+        assert_eq!(router.context_window("openai:unknown").await, None);
+        assert_eq!(router.context_window("missing:gpt-4o").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_failed_model_list_is_retried() {
+        let provider = Arc::new(MockProvider::new("openai", true));
+        let mut router = LlmRouter::new();
+        router.add_provider(provider.clone());
+        router.set_default_provider("openai");
+
+        assert_eq!(router.context_window("gpt-4o").await, None);
+
+        // Within the retry interval the failure is not re-fetched.
+        provider.should_fail.store(false, Ordering::SeqCst);
+        assert_eq!(router.context_window("gpt-4o").await, None);
+
+        router.model_list_retry = Duration::ZERO;
+        assert_eq!(router.context_window("gpt-4o").await, Some(128_000));
+    }
+
     #[tokio::test]
     async fn test_provider_success() {
         let mut router = LlmRouter::new();
@@ -434,6 +858,37 @@ mod tests {
         assert!(response.is_err());
     }
 
+    #[tokio::test]
+    async fn test_purpose_route_and_fallbacks() {
+        let mut router = LlmRouter::new();
+        router.add_provider(Arc::new(MockProvider::new("primary", true)));
+        router.add_provider(Arc::new(MockProvider::new("backup", false)));
+        router.set_default_provider("primary");
+        router.add_model_mapping("cheap", "backup:small");
+        router.set_purpose_route("tool_calling", "primary:big", vec!["cheap".to_string()]);
+
+        assert_eq!(router.purpose_model("tool_calling"), Some("primary:big"));
+        assert_eq!(router.purpose_model("conversation"), None);
+
+        // Empty model: the purpose's model is used, then its fallbacks.
+        let request = ChatRequest::new("", vec![Message::user("hello")]).with_purpose("tool_calling");
+        let response = router.chat_completion(request).await.unwrap();
+        assert_eq!(response.model, "small");
+
+        // An explicit model still falls back to the purpose's fallbacks.
+        let request =
+            ChatRequest::new("primary:other", vec![Message::user("hello")]).with_purpose("tool_calling");
+        assert_eq!(router.chat_completion(request).await.unwrap().model, "small");
+
+        // Without a purpose there is nothing to fall back to.
+        let request = ChatRequest::new("primary:other", vec![Message::user("hello")]);
+        assert!(router.chat_completion(request).await.is_err());
+
+        // Empty model with an unrouted purpose is an error.
+        let request = ChatRequest::new("", vec![Message::user("hello")]).with_purpose("analysis");
+        assert!(router.chat_completion(request).await.is_err());
+    }
+
     #[test]
     fn test_provider_names() {
         let mut router = LlmRouter::new();
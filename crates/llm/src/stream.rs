@@ -0,0 +1,781 @@
+//! Streaming response decoding shared by all providers.
+//!
+//! Byte chunks from the HTTP body are split into payloads (`data:` events for
+//! SSE, lines for NDJSON) with buffering across chunk boundaries, then a
+//! provider-specific parser turns each payload into a `ChatStreamDelta`.
+//! `StreamAccumulator` rebuilds the complete `ChatResponse` from the deltas.
+
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::{Context, Result};
+use futures::stream::{self, BoxStream, Stream};
+use futures::StreamExt;
+use serde_json::Value;
+
+use crate::error::LlmError;
+use crate::message::*;
+
+/// Splits a byte stream into lines. Bytes are buffered until a `\n` arrives,
+/// so a line (or a multi-byte UTF-8 character) split across chunks is decoded
+/// only once it is complete. A trailing `\r` is stripped.
+#[derive(Debug, Default)]
+pub struct LineDecoder {
+    buf: Vec<u8>,
+}
+
+impl LineDecoder {
+    /// Feed one chunk and return the lines it completed.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buf.extend_from_slice(chunk);
+        let mut lines = Vec::new();
+        let mut start = 0;
+        while let Some(pos) = self.buf[start..].iter().position(|&b| b == b'\n') {
+            lines.push(decode_line(&self.buf[start..start + pos]));
+            start += pos + 1;
+        }
+        self.buf.drain(..start);
+        lines
+    }
+
+    /// The last line when the stream ended without a newline.
+    pub fn finish(&mut self) -> Option<String> {
+        if self.buf.is_empty() {
+            return None;
+        }
+        let line = decode_line(&self.buf);
+        self.buf.clear();
+        Some(line)
+    }
+}
+
+fn decode_line(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// How a streaming body delimits payloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// Server-sent events: `data:` lines, terminated by a blank line.
+    Sse,
+    /// One JSON document per line.
+    Ndjson,
+}
+
+/// Turns byte chunks into complete payloads.
+#[derive(Debug)]
+pub struct PayloadDecoder {
+    framing: Framing,
+    lines: LineDecoder,
+    /// `data:` lines of the SSE event being read.
+    data: Vec<String>,
+}
+
+impl PayloadDecoder {
+    pub fn new(framing: Framing) -> Self {
+        Self {
+            framing,
+            lines: LineDecoder::default(),
+            data: Vec::new(),
+        }
+    }
+
+    /// Feed one chunk and return the payloads it completed.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        let mut payloads = Vec::new();
+        for line in self.lines.push(chunk) {
+            self.line(line, &mut payloads);
+        }
+        payloads
+    }
+
+    /// Flush what is left when the body ends: a last line without a newline
+    /// and an SSE event without its closing blank line.
+    pub fn finish(&mut self) -> Vec<String> {
+        let mut payloads = Vec::new();
+        if let Some(line) = self.lines.finish() {
+            self.line(line, &mut payloads);
+        }
+        if !self.data.is_empty() {
+            payloads.push(self.data.drain(..).collect::<Vec<_>>().join("\n"));
+        }
+        payloads
+    }
+
+    fn line(&mut self, line: String, payloads: &mut Vec<String>) {
+        match self.framing {
+            Framing::Ndjson => {
+                if !line.trim().is_empty() {
+                    payloads.push(line);
+                }
+            }
+            Framing::Sse => {
+                if line.is_empty() {
+                    if !self.data.is_empty() {
+                        payloads.push(self.data.drain(..).collect::<Vec<_>>().join("\n"));
+                    }
+                    return;
+                }
+                // Comments (`: keep-alive`) and other fields (`event:`, `id:`) carry no data.
+                let (field, value) = line.split_once(':').unwrap_or((&line, ""));
+                if field == "data" {
+                    self.data
+                        .push(value.strip_prefix(' ').unwrap_or(value).to_string());
+                }
+            }
+        }
+    }
+}
+
+/// Decode a streaming HTTP body into payloads.
+pub fn decode<S, B, E>(bytes: S, framing: Framing) -> BoxStream<'static, Result<String>>
+where
+    S: Stream<Item = std::result::Result<B, E>> + Send + 'static,
+    B: AsRef<[u8]> + Send + 'static,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let state = (
+        bytes.boxed(),
+        PayloadDecoder::new(framing),
+        VecDeque::new(),
+        false,
+    );
+    stream::unfold(
+        state,
+        |(mut bytes, mut decoder, mut pending, mut done)| async move {
+            loop {
+                if let Some(payload) = pending.pop_front() {
+                    return Some((Ok(payload), (bytes, decoder, pending, done)));
+                }
+                if done {
+                    return None;
+                }
+                match bytes.next().await {
+                    Some(Ok(chunk)) => pending.extend(decoder.push(chunk.as_ref())),
+                    Some(Err(e)) => {
+                        done = true;
+                        let err = Err(e).context("stream chunk error");
+                        return Some((err, (bytes, decoder, pending, done)));
+                    }
+                    None => {
+                        done = true;
+                        pending.extend(decoder.finish());
+                    }
+                }
+            }
+        },
+    )
+    .boxed()
+}
+
+/// Run a stateful payload parser over decoded payloads. Payloads the parser
+/// maps to `None` (keep-alives, `[DONE]`, bookkeeping events) are dropped.
+pub fn parse_with<P>(
+    payloads: BoxStream<'static, Result<String>>,
+    mut parse: P,
+) -> BoxStream<'static, Result<ChatStreamDelta>>
+where
+    P: FnMut(&str) -> Result<Option<ChatStreamDelta>> + Send + 'static,
+{
+    payloads
+        .filter_map(move |payload| {
+            futures::future::ready(payload.and_then(|p| parse(&p)).transpose())
+        })
+        .boxed()
+}
+
+fn parse_json(provider: &str, payload: &str) -> Result<Value> {
+    serde_json::from_str(payload)
+        .with_context(|| format!("{provider} sent an invalid stream payload: {payload}"))
+}
+
+/// Classify an error payload sent inside an opened stream like an HTTP error
+/// body. The status is the payload's numeric `code` when it is an HTTP status
+/// (OpenRouter mirrors the upstream status there), otherwise the 200 the
+/// stream was opened with.
+fn stream_error(provider: &str, model: &str, error: &Value) -> anyhow::Error {
+    let status = error["code"]
+        .as_u64()
+        .filter(|code| (400..600).contains(code))
+        .unwrap_or(200) as u16;
+    let body = serde_json::json!({ "error": error }).to_string();
+    LlmError::from_body(provider, model, status, None, &body).into()
+}
+
+fn text_delta(id: String, model: String, content: Option<String>) -> ChatStreamDelta {
+    ChatStreamDelta {
+        id,
+        model,
+        choices: vec![StreamChoice {
+            index: 0,
+            delta: DeltaMessage {
+                role: None,
+                content,
+                function_call: None,
+                tool_calls: None,
+            },
+            finish_reason: None,
+        }],
+        usage: None,
+    }
+}
+
+fn tool_fragment(id: String, name: &str, arguments: &str) -> ToolCall {
+    ToolCall {
+        id,
+        call_type: "function".to_string(),
+        function: FunctionCall {
+            name: name.to_string(),
+            arguments: arguments.to_string(),
+        },
+    }
+}
+
+fn usage(prompt: u64, completion: u64) -> Usage {
+    Usage {
+        prompt_tokens: prompt as u32,
+        completion_tokens: completion as u32,
+        total_tokens: (prompt + completion) as u32,
+    }
+}
+
+/// Parser for OpenAI-style chat completion chunks (OpenAI, OpenRouter, llama.cpp).
+///
+/// Tool call fragments after the first carry only their `index`; the parser
+/// fills in the id seen for that index so every fragment can be merged.
+#[derive(Debug)]
+pub struct OpenAiStreamParser {
+    provider: String,
+    model: String,
+    tool_ids: HashMap<u64, String>,
+}
+
+impl OpenAiStreamParser {
+    /// `model` is used when a chunk does not name one.
+    pub fn new(provider: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            provider: provider.into(),
+            model: model.into(),
+            tool_ids: HashMap::new(),
+        }
+    }
+
+    pub fn parse(&mut self, payload: &str) -> Result<Option<ChatStreamDelta>> {
+        if payload.trim() == "[DONE]" {
+            return Ok(None);
+        }
+        let parsed = parse_json(&self.provider, payload)?;
+        if let Some(error) = parsed.get("error") {
+            return Err(stream_error(&self.provider, &self.model, error));
+        }
+
+        let choices: Vec<StreamChoice> = parsed["choices"]
+            .as_array()
+            .map(|arr| arr.iter().map(|c| self.choice(c)).collect())
+            .unwrap_or_default();
+        let usage = parsed
+            .get("usage")
+            .filter(|u| u.is_object())
+            .and_then(|u| serde_json::from_value::<Usage>(u.clone()).ok());
+        if choices.is_empty() && usage.is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(ChatStreamDelta {
+            id: parsed["id"].as_str().unwrap_or_default().to_string(),
+            model: parsed["model"].as_str().unwrap_or(&self.model).to_string(),
+            choices,
+            usage,
+        }))
+    }
+
+    fn choice(&mut self, choice: &Value) -> StreamChoice {
+        let delta = &choice["delta"];
+        let tool_calls = delta["tool_calls"].as_array().map(|calls| {
+            calls
+                .iter()
+                .map(|tc| {
+                    let index = tc["index"].as_u64().unwrap_or(0);
+                    let id = match tc["id"].as_str().filter(|id| !id.is_empty()) {
+                        Some(id) => {
+                            self.tool_ids.insert(index, id.to_string());
+                            id.to_string()
+                        }
+                        None => self.tool_ids.get(&index).cloned().unwrap_or_default(),
+                    };
+                    let function = &tc["function"];
+                    tool_fragment(
+                        id,
+                        function["name"].as_str().unwrap_or_default(),
+                        function["arguments"].as_str().unwrap_or_default(),
+                    )
+                })
+                .collect()
+        });
+
+        StreamChoice {
+            index: choice["index"].as_u64().unwrap_or(0) as u32,
+            delta: DeltaMessage {
+                role: delta
+                    .get("role")
+                    .and_then(|r| serde_json::from_value(r.clone()).ok()),
+                content: delta["content"].as_str().map(String::from),
+                function_call: delta
+                    .get("function_call")
+                    .and_then(|fc| serde_json::from_value(fc.clone()).ok()),
+                tool_calls,
+            },
+            finish_reason: choice
+                .get("finish_reason")
+                .and_then(|fr| serde_json::from_value(fr.clone()).ok()),
+        }
+    }
+}
+
+/// Parser for Anthropic Messages API events.
+#[derive(Debug)]
+pub struct AnthropicStreamParser {
+    model: String,
+    id: String,
+    /// Content block index -> tool use id, for `input_json_delta` fragments.
+    tool_ids: HashMap<u64, String>,
+}
+
+impl AnthropicStreamParser {
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            id: String::new(),
+            tool_ids: HashMap::new(),
+        }
+    }
+
+    pub fn parse(&mut self, payload: &str) -> Result<Option<ChatStreamDelta>> {
+        let parsed = parse_json("Anthropic", payload)?;
+        let mut delta = text_delta(self.id.clone(), self.model.clone(), None);
+        match parsed["type"].as_str() {
+            Some("message_start") => {
+                let message = &parsed["message"];
+                if let Some(id) = message["id"].as_str() {
+                    self.id = id.to_string();
+                    delta.id = self.id.clone();
+                }
+                if let Some(model) = message["model"].as_str() {
+                    self.model = model.to_string();
+                    delta.model = self.model.clone();
+                }
+                let u = &message["usage"];
+                delta.usage = Some(usage(
+                    u["input_tokens"].as_u64().unwrap_or(0),
+                    u["output_tokens"].as_u64().unwrap_or(0),
+                ));
+            }
+            Some("content_block_start") => {
+                let block = &parsed["content_block"];
+                if block["type"].as_str() != Some("tool_use") {
+                    return Ok(None);
+                }
+                let id = block["id"].as_str().unwrap_or_default().to_string();
+                let index = parsed["index"].as_u64().unwrap_or(0);
+                self.tool_ids.insert(index, id.clone());
+                let name = block["name"].as_str().unwrap_or_default();
+                delta.choices[0].delta.tool_calls = Some(vec![tool_fragment(id, name, "")]);
+            }
+            Some("content_block_delta") => {
+                let inner = &parsed["delta"];
+                match inner["type"].as_str() {
+                    Some("text_delta") => {
+                        delta.choices[0].delta.content = inner["text"].as_str().map(String::from);
+                    }
+                    Some("input_json_delta") => {
+                        let index = parsed["index"].as_u64().unwrap_or(0);
+                        let id = self.tool_ids.get(&index).cloned().unwrap_or_default();
+                        let json = inner["partial_json"].as_str().unwrap_or_default();
+                        delta.choices[0].delta.tool_calls = Some(vec![tool_fragment(id, "", json)]);
+                    }
+                    _ => return Ok(None),
+                }
+            }
+            Some("message_delta") => {
+                delta.choices[0].finish_reason = match parsed["delta"]["stop_reason"].as_str() {
+                    Some("end_turn") | Some("stop_sequence") => Some(FinishReason::Stop),
+                    Some("max_tokens") => Some(FinishReason::Length),
+                    Some("tool_use") => Some(FinishReason::ToolCalls),
+                    _ => None,
+                };
+                let u = &parsed["usage"];
+                if u.is_object() {
+                    delta.usage = Some(usage(
+                        u["input_tokens"].as_u64().unwrap_or(0),
+                        u["output_tokens"].as_u64().unwrap_or(0),
+                    ));
+                }
+            }
+            Some("error") => {
+                return Err(stream_error("Anthropic", &self.model, &parsed["error"]));
+            }
+            // ping, content_block_stop, message_stop
+            _ => return Ok(None),
+        }
+        Ok(Some(delta))
+    }
+}
+
+/// Parser for Ollama's NDJSON chat stream.
+#[derive(Debug)]
+pub struct OllamaStreamParser {
+    model: String,
+    id: String,
+    saw_tool_calls: bool,
+}
+
+impl OllamaStreamParser {
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            id: uuid::Uuid::new_v4().to_string(),
+            saw_tool_calls: false,
+        }
+    }
+
+    pub fn parse(&mut self, payload: &str) -> Result<Option<ChatStreamDelta>> {
+        let parsed = parse_json("Ollama", payload)?;
+        if let Some(error) = parsed.get("error") {
+            return Err(stream_error("Ollama", &self.model, error));
+        }
+
+        let model = parsed["model"].as_str().unwrap_or(&self.model).to_string();
+        let message = &parsed["message"];
+        let content = message["content"]
+            .as_str()
+            .filter(|c| !c.is_empty())
+            .map(String::from);
+        let mut delta = text_delta(self.id.clone(), model, content);
+
+        // Ollama sends each tool call complete, with the arguments as an object.
+        if let Some(calls) = message["tool_calls"].as_array().filter(|c| !c.is_empty()) {
+            self.saw_tool_calls = true;
+            let calls = calls
+                .iter()
+                .map(|tc| {
+                    let function = &tc["function"];
+                    let arguments = match &function["arguments"] {
+                        Value::String(s) => s.clone(),
+                        Value::Null => "{}".to_string(),
+                        other => other.to_string(),
+                    };
+                    let id = tc["id"]
+                        .as_str()
+                        .map(String::from)
+                        .unwrap_or_else(|| format!("call_{}", uuid::Uuid::new_v4().simple()));
+                    tool_fragment(
+                        id,
+                        function["name"].as_str().unwrap_or_default(),
+                        &arguments,
+                    )
+                })
+                .collect();
+            delta.choices[0].delta.tool_calls = Some(calls);
+        }
+
+        if parsed["done"].as_bool().unwrap_or(false) {
+            delta.choices[0].finish_reason = Some(match parsed["done_reason"].as_str() {
+                Some("length") => FinishReason::Length,
+                _ if self.saw_tool_calls => FinishReason::ToolCalls,
+                _ => FinishReason::Stop,
+            });
+            delta.usage = Some(usage(
+                parsed["prompt_eval_count"].as_u64().unwrap_or(0),
+                parsed["eval_count"].as_u64().unwrap_or(0),
+            ));
+        }
+        Ok(Some(delta))
+    }
+}
+
+/// Rebuilds a complete `ChatResponse` from streamed deltas: concatenates the
+/// text, merges tool call fragments by id and merges usage reported across
+/// several chunks.
+#[derive(Debug, Default)]
+pub struct StreamAccumulator {
+    id: String,
+    model: String,
+    content: String,
+    tool_calls: Vec<ToolCall>,
+    finish_reason: Option<FinishReason>,
+    usage: Option<Usage>,
+}
+
+impl StreamAccumulator {
+    /// Merge one delta. Returns the text fragment it carried, if any.
+    pub fn push(&mut self, delta: ChatStreamDelta) -> Option<String> {
+        if self.id.is_empty() {
+            self.id = delta.id;
+        }
+        if self.model.is_empty() {
+            self.model = delta.model;
+        }
+        if let Some(u) = delta.usage {
+            // Providers may split usage across chunks (e.g. input first, output last).
+            let merged = self.usage.get_or_insert_with(Usage::default);
+            merged.prompt_tokens = merged.prompt_tokens.max(u.prompt_tokens);
+            merged.completion_tokens = merged.completion_tokens.max(u.completion_tokens);
+            merged.total_tokens = merged
+                .total_tokens
+                .max(u.total_tokens)
+                .max(merged.prompt_tokens + merged.completion_tokens);
+        }
+
+        let choice = delta.choices.into_iter().next()?;
+        if choice.finish_reason.is_some() {
+            self.finish_reason = choice.finish_reason;
+        }
+        for tc in choice.delta.tool_calls.unwrap_or_default() {
+            // Continuation fragments carry no id, or the id of their call.
+            let existing = if tc.id.is_empty() {
+                self.tool_calls.last_mut()
+            } else {
+                self.tool_calls.iter_mut().find(|call| call.id == tc.id)
+            };
+            match existing {
+                Some(call) => {
+                    call.function.name.push_str(&tc.function.name);
+                    call.function.arguments.push_str(&tc.function.arguments);
+                }
+                None => self.tool_calls.push(tc),
+            }
+        }
+
+        let text = choice.delta.content.filter(|t| !t.is_empty())?;
+        self.content.push_str(&text);
+        Some(text)
+    }
+
+    /// Whether any delta reported token usage.
+    pub fn has_usage(&self) -> bool {
+        self.usage.is_some()
+    }
+
+    pub fn finish(self) -> ChatResponse {
+        let mut tool_calls = self.tool_calls;
+        for call in &mut tool_calls {
+            if call.function.arguments.trim().is_empty() {
+                call.function.arguments = "{}".to_string();
+            }
+        }
+        let finish_reason = self
+            .finish_reason
+            .or_else(|| (!tool_calls.is_empty()).then_some(FinishReason::ToolCalls));
+
+        ChatResponse {
+            id: self.id,
+            model: self.model,
+            choices: vec![Choice {
+                index: 0,
+                message: Message {
+                    role: Role::Assistant,
+                    content: (!self.content.is_empty())
+                        .then_some(MessageContent::Text(self.content)),
+                    name: None,
+                    function_call: None,
+                    tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+                    tool_call_id: None,
+                },
+                finish_reason,
+            }],
+            usage: self.usage.unwrap_or_default(),
+            created: chrono::Utc::now().timestamp(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `body` to a decoder in chunks of `size` bytes.
+    fn payloads(framing: Framing, body: &[u8], size: usize) -> Vec<String> {
+        let mut decoder = PayloadDecoder::new(framing);
+        let mut out: Vec<String> = body.chunks(size).flat_map(|c| decoder.push(c)).collect();
+        out.extend(decoder.finish());
+        out
+    }
+
+    #[test]
+    fn test_sse_payloads_survive_any_chunk_boundary() {
+        let body = ": keep-alive\r\n\r\nevent: message\r\ndata: {\"text\":\"héllo 🦀\"}\r\n\r\ndata:first\ndata: second\n\ndata: [DONE]";
+        for size in 1..=body.len() {
+            assert_eq!(
+                payloads(Framing::Sse, body.as_bytes(), size),
+                vec!["{\"text\":\"héllo 🦀\"}", "first\nsecond", "[DONE]"],
+                "chunk size {size}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_ndjson_split_inside_multibyte_char() {
+        let body = "{\"a\":\"日本\"}\n\n{\"b\":2}".as_bytes();
+        // Split right after the first byte of '日'.
+        let split = body.iter().position(|&b| b >= 0x80).unwrap() + 1;
+        let mut decoder = PayloadDecoder::new(Framing::Ndjson);
+        assert!(decoder.push(&body[..split]).is_empty());
+        assert_eq!(decoder.push(&body[split..]), vec!["{\"a\":\"日本\"}"]);
+        assert_eq!(decoder.finish(), vec!["{\"b\":2}"]);
+    }
+
+    #[tokio::test]
+    async fn test_decode_reports_chunk_errors() {
+        let chunks: Vec<std::result::Result<Vec<u8>, std::io::Error>> = vec![
+            Ok(b"data: a\n".to_vec()),
+            Ok(b"\ndata: b".to_vec()),
+            Err(std::io::Error::other("connection reset")),
+        ];
+        let results: Vec<Result<String>> =
+            decode(stream::iter(chunks), Framing::Sse).collect().await;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap(), "a");
+        assert!(results[1]
+            .as_ref()
+            .unwrap_err()
+            .to_string()
+            .contains("stream chunk error"));
+    }
+
+    #[tokio::test]
+    async fn test_openai_tool_calls_reassembled_from_byte_chunks() {
+        let events = [
+            r#"{"id":"c1","model":"gpt-4o","choices":[{"index":0,"delta":{"role":"assistant","content":"Let me "}}]}"#,
+            r#"{"id":"c1","model":"gpt-4o","choices":[{"index":0,"delta":{"content":"check."}}]}"#,
+            r#"{"id":"c1","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"id":"call_a","type":"function","function":{"name":"search","arguments":""}}]}}]}"#,
+            r#"{"id":"c1","choices":[{"index":0,"delta":{"tool_calls":[{"index":1,"id":"call_b","type":"function","function":{"name":"learn","arguments":"{}"}}]}}]}"#,
+            r#"{"id":"c1","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"q\":"}}]}}]}"#,
+            r#"{"id":"c1","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"rust\"}"}}]}}]}"#,
+            r#"{"id":"c1","choices":[{"index":0,"delta":{},"finish_reason":"tool_calls"}]}"#,
+            r#"{"id":"c1","choices":[],"usage":{"prompt_tokens":12,"completion_tokens":8,"total_tokens":20}}"#,
+            "[DONE]",
+        ];
+        let body: String = events.iter().map(|e| format!("data: {e}\n\n")).collect();
+        // Three-byte chunks split events, JSON strings and the `data:` prefix.
+        let chunks: Vec<std::result::Result<Vec<u8>, std::io::Error>> =
+            body.as_bytes().chunks(3).map(|c| Ok(c.to_vec())).collect();
+
+        let mut parser = OpenAiStreamParser::new("OpenAI", "gpt-4o");
+        let mut deltas = parse_with(decode(stream::iter(chunks), Framing::Sse), move |p| {
+            parser.parse(p)
+        });
+        let mut acc = StreamAccumulator::default();
+        let mut text = String::new();
+        while let Some(delta) = deltas.next().await {
+            if let Some(t) = acc.push(delta.unwrap()) {
+                text.push_str(&t);
+            }
+        }
+
+        assert_eq!(text, "Let me check.");
+        assert!(acc.has_usage());
+        let response = acc.finish();
+        assert_eq!(response.model, "gpt-4o");
+        assert_eq!(response.usage.total_tokens, 20);
+        let choice = &response.choices[0];
+        assert_eq!(choice.finish_reason, Some(FinishReason::ToolCalls));
+        let calls = choice.message.tool_calls.as_ref().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].id, "call_a");
+        assert_eq!(calls[0].function.arguments, r#"{"q":"rust"}"#);
+        assert_eq!(calls[1].function.name, "learn");
+    }
+
+    #[test]
+    fn test_openai_stream_error_payload() {
+        let mut parser = OpenAiStreamParser::new("OpenRouter", "x/model");
+        assert!(parser.parse("[DONE]").unwrap().is_none());
+        let err = parser
+            .parse(r#"{"error":{"message":"upstream overloaded","code":502}}"#)
+            .unwrap_err();
+        assert!(err.to_string().contains("upstream overloaded"));
+        let typed = err.downcast_ref::<LlmError>().unwrap();
+        assert_eq!(typed.status(), Some(502));
+        assert!(typed.is_retryable());
+
+        let err = parser
+            .parse(r#"{"error":{"message":"This model's maximum context length is 8192 tokens","code":"context_length_exceeded"}}"#)
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<LlmError>(),
+            Some(LlmError::ContextOverflow { model, .. }) if model == "x/model"
+        ));
+        assert!(parser.parse("{not json").is_err());
+    }
+
+    #[test]
+    fn test_anthropic_events_with_tool_use() {
+        let events = [
+            r#"{"type":"message_start","message":{"id":"msg_1","model":"claude-sonnet-4-5","usage":{"input_tokens":25,"output_tokens":1}}}"#,
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+            r#"{"type":"ping"}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Searching"}}"#,
+            r#"{"type":"content_block_stop","index":0}"#,
+            r#"{"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_1","name":"search","input":{}}}"#,
+            r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"q\": \"ru"}}"#,
+            r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"st\"}"}}"#,
+            r#"{"type":"content_block_start","index":2,"content_block":{"type":"tool_use","id":"toolu_2","name":"noop","input":{}}}"#,
+            r#"{"type":"message_delta","delta":{"stop_reason":"tool_use"},"usage":{"output_tokens":40}}"#,
+            r#"{"type":"message_stop"}"#,
+        ];
+        let mut parser = AnthropicStreamParser::new("claude");
+        let mut acc = StreamAccumulator::default();
+        for event in events {
+            if let Some(delta) = parser.parse(event).unwrap() {
+                acc.push(delta);
+            }
+        }
+
+        let response = acc.finish();
+        assert_eq!(response.id, "msg_1");
+        assert_eq!(response.model, "claude-sonnet-4-5");
+        assert_eq!(response.usage.prompt_tokens, 25);
+        assert_eq!(response.usage.completion_tokens, 40);
+        assert_eq!(response.usage.total_tokens, 65);
+        let choice = &response.choices[0];
+        assert_eq!(choice.finish_reason, Some(FinishReason::ToolCalls));
+        assert_eq!(choice.message.text_content(), Some("Searching"));
+        let calls = choice.message.tool_calls.as_ref().unwrap();
+        assert_eq!(calls[0].id, "toolu_1");
+        assert_eq!(calls[0].function.arguments, r#"{"q": "rust"}"#);
+        // A tool with no input deltas still gets valid JSON arguments.
+        assert_eq!(calls[1].function.arguments, "{}");
+
+        let err = parser
+            .parse(r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#)
+            .unwrap_err();
+        assert!(err.to_string().contains("Overloaded"));
+    }
+
+    #[test]
+    fn test_ollama_ndjson_with_tool_calls() {
+        let body = concat!(
+            r#"{"model":"llama3","message":{"role":"assistant","content":"Hi"},"done":false}"#,
+            "\n",
+            r#"{"model":"llama3","message":{"role":"assistant","content":"","tool_calls":[{"function":{"name":"search","arguments":{"q":"rust"}}}]},"done":false}"#,
+            "\n",
+            r#"{"model":"llama3","message":{"role":"assistant","content":""},"done":true,"done_reason":"stop","prompt_eval_count":9,"eval_count":4}"#,
+        );
+        let mut parser = OllamaStreamParser::new("llama3");
+        let mut acc = StreamAccumulator::default();
+        for payload in payloads(Framing::Ndjson, body.as_bytes(), 7) {
+            acc.push(parser.parse(&payload).unwrap().unwrap());
+        }
+
+        let response = acc.finish();
+        assert_eq!(response.usage.total_tokens, 13);
+        let choice = &response.choices[0];
+        assert_eq!(choice.message.text_content(), Some("Hi"));
+        assert_eq!(choice.finish_reason, Some(FinishReason::ToolCalls));
+        let calls = choice.message.tool_calls.as_ref().unwrap();
+        assert_eq!(calls[0].function.name, "search");
+        assert_eq!(calls[0].function.arguments, r#"{"q":"rust"}"#);
+
+        assert!(parser.parse(r#"{"error":"model not found"}"#).is_err());
+    }
+}
@@ -2,7 +2,9 @@ use anyhow::Result;
 use async_trait::async_trait;
 use futures::stream::BoxStream;
 
-use crate::message::{ChatRequest, ChatResponse, ChatStreamDelta};
+use crate::message::{
+    ChatRequest, ChatResponse, ChatStreamDelta, EmbeddingRequest, EmbeddingResponse,
+};
 
 /// Information about an available model.
 #[derive(Debug, Clone)]
@@ -59,11 +61,28 @@ pub trait LlmProvider: Send + Sync {
                     finish_reason: c.finish_reason,
                 })
                 .collect(),
+            usage: Some(response.usage),
         };
 
         Ok(Box::pin(futures::stream::once(async { Ok(delta) })))
     }
 
+    /// Compute embeddings for the given texts.
+    ///
+    /// Providers without an embedding endpoint return an error.
+    async fn embed(&self, request: EmbeddingRequest) -> Result<EmbeddingResponse> {
+        anyhow::bail!(
+            "Provider '{}' does not support embeddings (model '{}')",
+            self.name(),
+            request.model
+        )
+    }
+
+    /// Whether this provider supports embeddings.
+    fn supports_embeddings(&self) -> bool {
+        false
+    }
+
     /// Whether this provider supports function calling / tools.
     fn supports_function_calling(&self) -> bool {
         false
@@ -74,6 +93,13 @@ pub trait LlmProvider: Send + Sync {
         false
     }
 
+    /// Prices of this provider's models, when its API publishes them.
+    ///
+    /// The default implementation returns an empty list.
+    async fn model_pricing(&self) -> Result<Vec<crate::pricing::ModelPricing>> {
+        Ok(Vec::new())
+    }
+
     /// Check if the provider's API endpoint is reachable.
     async fn health_check(&self) -> Result<bool> {
         Ok(true)
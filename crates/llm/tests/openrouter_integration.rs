@@ -42,6 +42,19 @@ async fn test_available_models() {
     assert!(has_gpt4, "Model list should include a GPT-4 variant");
 }
 
+#[tokio::test]
+#[ignore]
+async fn test_model_pricing() {
+    let p = provider();
+    let prices = p.model_pricing().await.unwrap();
+    let deepseek = prices
+        .iter()
+        .find(|m| m.model == "deepseek/deepseek-chat")
+        .expect("deepseek/deepseek-chat should be priced");
+    assert_eq!(deepseek.provider, "openrouter");
+    assert!(deepseek.input_per_million > 0.0);
+}
+
 #[tokio::test]
 #[ignore]
 async fn test_chat_completion_simple() {
@@ -115,6 +128,7 @@ async fn test_chat_completion_tool_calling() {
         stop: None,
         stream: Some(false),
         metadata: Default::default(),
+        purpose: None,
     };
 
     let response = p.chat_completion(request).await.unwrap();
@@ -87,6 +87,12 @@ pub async fn create_agent(
         custom_traits_json: None,
     };
     opencrab_db::queries::upsert_soul(&conn, &soul).unwrap();
+    drop(conn);
+
+    // 起動時の同期より後に作られたエージェントにも標準スキルを付与する
+    if let Err(e) = crate::skill_sync::sync_agent_skills(&state.db, &state.skills_dir, &agent_id) {
+        tracing::warn!(agent_id = %agent_id, error = %e, "Failed to synchronise standard skills");
+    }
 
     Json(serde_json::json!({
         "id": agent_id,
@@ -240,6 +246,127 @@ pub async fn apply_soul_preset(
     Json(serde_json::json!({ "ok": true }))
 }
 
+// ============================================
+// LLM per-agent config
+// ============================================
+
+/// 用途ごとに実際に使われるモデル。埋め込みは未設定なら null
+fn resolved_models(
+    state: &AppState,
+    config: Option<&opencrab_core::AgentLlmConfig>,
+) -> serde_json::Map<String, serde_json::Value> {
+    opencrab_core::MODEL_PURPOSES
+        .iter()
+        .map(|purpose| {
+            let model = match (config, *purpose) {
+                (Some(c), "embedding") => c
+                    .models
+                    .embedding
+                    .as_ref()
+                    .map(|m| m.to_string())
+                    .or_else(|| state.embedding_model.clone()),
+                (None, "embedding") => state.embedding_model.clone(),
+                (Some(c), _) => Some(c.model_for(purpose).to_string()),
+                (None, _) => Some(
+                    state
+                        .llm_router
+                        .purpose_model(purpose)
+                        .map(String::from)
+                        .unwrap_or_else(|| state.default_model.clone()),
+                ),
+            };
+            (purpose.to_string(), serde_json::json!(model))
+        })
+        .collect()
+}
+
+pub async fn get_llm_config(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Json<serde_json::Value> {
+    let config = {
+        let conn = state.db.lock().unwrap();
+        opencrab_core::AgentLlmConfig::load(&conn, &id)
+    };
+    match config {
+        Ok(config) => Json(serde_json::json!({
+            "configured": config.is_some(),
+            "resolved": resolved_models(&state, config.as_ref()),
+            "config": config,
+        })),
+        Err(e) => Json(serde_json::json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+pub async fn update_llm_config(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(config): Json<opencrab_core::AgentLlmConfig>,
+) -> Json<serde_json::Value> {
+    let exists = {
+        let conn = state.db.lock().unwrap();
+        opencrab_db::queries::get_identity(&conn, &id).unwrap().is_some()
+    };
+    if !exists {
+        return Json(serde_json::json!({"ok": false, "error": "Agent not found"}));
+    }
+
+    let models = std::iter::once(config.default_model_ref())
+        .chain(
+            opencrab_core::MODEL_PURPOSES
+                .iter()
+                .filter_map(|p| config.models.for_purpose(p).cloned()),
+        )
+        .chain(config.selectable_models.iter().cloned())
+        .collect::<Vec<_>>();
+    if let Some(bad) = models
+        .iter()
+        .find(|m| m.provider.trim().is_empty() || m.model.trim().is_empty())
+    {
+        return Json(serde_json::json!({
+            "ok": false,
+            "error": format!("Provider and model must not be empty: '{bad}'"),
+        }));
+    }
+
+    // 未登録のプロバイダは保存はするが警告を返す（後から設定を追加する場合があるため）
+    let providers = state.llm_router.provider_names();
+    let mut warnings: Vec<String> = models
+        .iter()
+        .filter(|m| !providers.contains(&m.provider.as_str()))
+        .map(|m| format!("Provider '{}' is not configured ({m})", m.provider))
+        .collect();
+    warnings.sort();
+    warnings.dedup();
+
+    let saved = {
+        let conn = state.db.lock().unwrap();
+        config.save(&conn, &id)
+    };
+    if let Err(e) = saved {
+        return Json(serde_json::json!({"ok": false, "error": e.to_string()}));
+    }
+
+    Json(serde_json::json!({
+        "ok": true,
+        "resolved": resolved_models(&state, Some(&config)),
+        "config": config,
+        "warnings": warnings,
+    }))
+}
+
+/// エージェント別LLM設定を削除する（以降はサーバーの既定モデルを使う）
+pub async fn delete_llm_config(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Json<serde_json::Value> {
+    let conn = state.db.lock().unwrap();
+    match opencrab_db::queries::delete_agent_llm_config(&conn, &id) {
+        Ok(deleted) => Json(serde_json::json!({"ok": true, "deleted": deleted})),
+        Err(e) => Json(serde_json::json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
 // ============================================
 // Discord per-agent config
 // ============================================
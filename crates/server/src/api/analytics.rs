@@ -9,6 +9,8 @@ use crate::AppState;
 #[derive(Debug, Deserialize)]
 pub struct AnalyticsQuery {
     pub period: Option<String>,
+    /// 指定時はセッションの予算も返す
+    pub session_id: Option<String>,
 }
 
 fn period_to_since(period: &str) -> String {
@@ -30,6 +32,7 @@ pub async fn get_metrics_summary(
     let period = query.period.as_deref().unwrap_or("week");
     let since = period_to_since(period);
     let conn = state.db.lock().unwrap();
+    let budget = budget_json(&state, &conn, &id, query.session_id.as_deref());
     match opencrab_db::queries::get_llm_metrics_summary(&conn, &id, &since) {
         Ok(summary) => Json(serde_json::json!({
             "count": summary.count,
@@ -37,11 +40,32 @@ pub async fn get_metrics_summary(
             "total_cost": summary.total_cost.unwrap_or(0.0),
             "avg_latency": summary.avg_latency.unwrap_or(0.0),
             "avg_quality": summary.avg_quality.unwrap_or(0.0),
+            "budget": budget,
         })),
         Err(e) => Json(serde_json::json!({"error": e.to_string()})),
     }
 }
 
+/// 予算の設定と上限ごとの利用状況
+fn budget_json(
+    state: &AppState,
+    conn: &rusqlite::Connection,
+    agent_id: &str,
+    session_id: Option<&str>,
+) -> serde_json::Value {
+    let config = &state.budget;
+    let limits = match crate::budget::budget_status(conn, config, agent_id, session_id) {
+        Ok(statuses) => serde_json::json!(statuses),
+        Err(e) => serde_json::json!({"error": e.to_string()}),
+    };
+    serde_json::json!({
+        "enabled": config.is_enabled(),
+        "downgrade_model": config.downgrade_model,
+        "warn_ratio": config.warn_ratio,
+        "limits": limits,
+    })
+}
+
 pub async fn get_metrics_detail(
     State(state): State<AppState>,
     Path(id): Path<String>,
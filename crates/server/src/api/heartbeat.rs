@@ -0,0 +1,67 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use serde::Deserialize;
+
+use crate::AppState;
+
+#[derive(Debug, Deserialize, Default)]
+pub struct StartHeartbeatRequest {
+    pub interval_secs: Option<u64>,
+}
+
+pub async fn start_heartbeat(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    body: Option<Json<StartHeartbeatRequest>>,
+) -> Json<serde_json::Value> {
+    let exists = {
+        let conn = state.db.lock().unwrap();
+        opencrab_db::queries::get_identity(&conn, &id).unwrap().is_some()
+    };
+    if !exists {
+        return Json(serde_json::json!({"ok": false, "error": "Agent not found"}));
+    }
+
+    let req = body.map(|Json(r)| r).unwrap_or_default();
+    let status = state
+        .heartbeat
+        .start(state.clone(), &id, req.interval_secs)
+        .await;
+    Json(serde_json::json!({"ok": true, "status": status}))
+}
+
+pub async fn stop_heartbeat(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Json<serde_json::Value> {
+    let stopped = state.heartbeat.stop(&id).await;
+    Json(serde_json::json!({"ok": true, "stopped": stopped}))
+}
+
+pub async fn get_heartbeat_status(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Json<serde_json::Value> {
+    let status = state.heartbeat.status(&id).await;
+    let recent = {
+        let conn = state.db.lock().unwrap();
+        opencrab_db::queries::list_heartbeat_logs(&conn, &id, 20).unwrap_or_default()
+    };
+    Json(serde_json::json!({
+        "status": status,
+        "recent": recent,
+    }))
+}
+
+/// ハートビートを1回だけ即時実行する
+pub async fn tick_heartbeat(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Json<serde_json::Value> {
+    match crate::heartbeat::tick_once(&state, &id).await {
+        Ok(outcome) => Json(serde_json::json!({"ok": true, "outcome": outcome})),
+        Err(e) => Json(serde_json::json!({"ok": false, "error": e.to_string()})),
+    }
+}
@@ -0,0 +1,16 @@
+use axum::{extract::State, Json};
+
+use crate::AppState;
+
+/// プロバイダーごとの疎通確認とサーキットブレーカーの状態
+pub async fn get_llm_health(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let providers = state.llm_router.health_check_all().await;
+    let healthy = providers
+        .values()
+        .all(|h| h.reachable != Some(false) && h.circuit == opencrab_llm::CircuitState::Closed);
+    Json(serde_json::json!({
+        "ok": true,
+        "healthy": healthy,
+        "providers": providers,
+    }))
+}
@@ -1,9 +1,10 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     Json,
 };
 use serde::Deserialize;
 
+use crate::process;
 use crate::AppState;
 
 pub async fn list_curated_memory(
@@ -21,15 +22,23 @@ pub struct SearchMemoryRequest {
     pub limit: Option<usize>,
 }
 
+/// キーワード（BM25）と埋め込みベクトルのハイブリッド検索。
+///
+/// 埋め込みモデルが未設定、または埋め込みに失敗した場合はキーワード検索のみ。
 pub async fn search_memory(
     State(state): State<AppState>,
     Path(id): Path<String>,
     Json(req): Json<SearchMemoryRequest>,
 ) -> Json<serde_json::Value> {
     let limit = req.limit.unwrap_or(10);
-    let conn = state.db.lock().unwrap();
+    let embedder = process::embedder(&state, &id);
+    let memory = opencrab_core::MemoryManager::new(&id, state.db.clone())
+        .with_policy(state.memory_policy.clone());
 
-    match opencrab_db::queries::search_session_logs(&conn, &id, &req.query, limit) {
+    match memory
+        .hybrid_search(&req.query, limit, embedder.as_deref(), &state.memory_policy.search)
+        .await
+    {
         Ok(results) => Json(serde_json::json!({
             "query": req.query,
             "count": results.len(),
@@ -40,3 +49,49 @@ pub async fn search_memory(
         })),
     }
 }
+
+#[derive(Debug, Deserialize)]
+pub struct MemoryContextQuery {
+    pub session_id: Option<String>,
+    /// 関連ログの検索語（省略時はセッションの最新の発言）
+    pub query: Option<String>,
+}
+
+/// システムプロンプトに注入される記憶ブロックのプレビュー（ログには記録しない）。
+pub async fn preview_memory_context(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<MemoryContextQuery>,
+) -> Json<serde_json::Value> {
+    match process::assemble_memory_context(&state, &id, query.session_id.as_deref(), query.query).await {
+        Ok(context) => Json(serde_json::json!(context)),
+        Err(e) => Json(serde_json::json!({
+            "error": e.to_string(),
+        })),
+    }
+}
+
+/// チェックポイント以降のセッションログをキュレーション記憶に統合する
+pub async fn consolidate_memory(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Json<serde_json::Value> {
+    match crate::consolidation::consolidate_once(&state, &id).await {
+        Ok(outcome) => Json(serde_json::json!({"ok": true, "outcome": outcome})),
+        Err(e) => Json(serde_json::json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+/// 統合の実行履歴（新しい順）と現在のチェックポイント
+pub async fn list_consolidation_runs(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Json<serde_json::Value> {
+    let conn = state.db.lock().unwrap();
+    let checkpoint = opencrab_db::queries::get_consolidation_checkpoint(&conn, &id).unwrap_or_default();
+    let runs = opencrab_db::queries::list_consolidation_runs(&conn, &id, 20).unwrap_or_default();
+    Json(serde_json::json!({
+        "checkpoint": checkpoint,
+        "runs": runs,
+    }))
+}
@@ -1,5 +1,9 @@
 pub mod agents;
 pub mod analytics;
+pub mod heartbeat;
+pub mod llm;
+pub mod pricing;
+pub mod prompt;
 pub mod sessions;
 pub mod skills;
 pub mod memory;
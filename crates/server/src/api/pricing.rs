@@ -0,0 +1,166 @@
+use std::collections::HashSet;
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use opencrab_llm::pricing::ModelPricing;
+use serde::Deserialize;
+
+use crate::AppState;
+
+/// DBに保存されている料金の (provider, model)
+fn db_keys(conn: &rusqlite::Connection) -> anyhow::Result<HashSet<(String, String)>> {
+    Ok(opencrab_db::queries::list_model_pricing(conn)?
+        .into_iter()
+        .map(|row| (row.provider, row.model))
+        .collect())
+}
+
+fn pricing_json(pricing: &ModelPricing, from_db: bool) -> serde_json::Value {
+    serde_json::json!({
+        "provider": pricing.provider,
+        "model": pricing.model,
+        "input_per_million": pricing.input_per_million,
+        "output_per_million": pricing.output_per_million,
+        "context_window": pricing.context_window,
+        "source": if from_db { "db" } else { "default" },
+    })
+}
+
+/// 変更をDBに書いたあと共有の料金表を読み直す
+fn reload(state: &AppState, conn: &rusqlite::Connection) -> Result<(), String> {
+    crate::pricing::reload(conn, &state.pricing).map_err(|e| e.to_string())
+}
+
+/// 料金表の一覧（組み込み + DB）
+pub async fn list_pricing(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let conn = state.db.lock().unwrap();
+    let keys = match db_keys(&conn) {
+        Ok(keys) => keys,
+        Err(e) => return Json(serde_json::json!({"ok": false, "error": e.to_string()})),
+    };
+    let registry = state.pricing.read().unwrap();
+    let prices: Vec<serde_json::Value> = registry
+        .list()
+        .into_iter()
+        .map(|p| pricing_json(p, keys.contains(&(p.provider.clone(), p.model.clone()))))
+        .collect();
+    Json(serde_json::json!({"ok": true, "count": prices.len(), "prices": prices}))
+}
+
+/// 1モデルの料金（`provider:*` へのフォールバックを含む）
+pub async fn get_pricing(
+    State(state): State<AppState>,
+    Path((provider, model)): Path<(String, String)>,
+) -> Json<serde_json::Value> {
+    let conn = state.db.lock().unwrap();
+    let registry = state.pricing.read().unwrap();
+    let Some(pricing) = registry.get(&provider, &model) else {
+        return Json(serde_json::json!({"ok": false, "error": "Pricing not found"}));
+    };
+    let from_db = opencrab_db::queries::get_model_pricing(&conn, &pricing.provider, &pricing.model)
+        .ok()
+        .flatten()
+        .is_some();
+    Json(serde_json::json!({"ok": true, "pricing": pricing_json(pricing, from_db)}))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdatePricingRequest {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+    pub context_window: Option<u32>,
+}
+
+/// 1モデルの料金を登録・更新する（組み込みの料金も上書きできる）
+pub async fn update_pricing(
+    State(state): State<AppState>,
+    Path((provider, model)): Path<(String, String)>,
+    Json(req): Json<UpdatePricingRequest>,
+) -> Json<serde_json::Value> {
+    let prices = [req.input_per_million, req.output_per_million];
+    if prices.iter().any(|p| !p.is_finite() || *p < 0.0) {
+        return Json(serde_json::json!({
+            "ok": false,
+            "error": "Prices must be non-negative numbers",
+        }));
+    }
+
+    let mut pricing = ModelPricing::new(provider, model, req.input_per_million, req.output_per_million);
+    pricing.context_window = req.context_window;
+
+    let conn = state.db.lock().unwrap();
+    if let Err(e) = opencrab_db::queries::upsert_model_pricing(&conn, &crate::pricing::to_row(&pricing)) {
+        return Json(serde_json::json!({"ok": false, "error": e.to_string()}));
+    }
+    if let Err(e) = reload(&state, &conn) {
+        return Json(serde_json::json!({"ok": false, "error": e}));
+    }
+    Json(serde_json::json!({"ok": true, "pricing": pricing_json(&pricing, true)}))
+}
+
+/// DBの料金を削除する（組み込みの料金があればそれに戻る）
+pub async fn delete_pricing(
+    State(state): State<AppState>,
+    Path((provider, model)): Path<(String, String)>,
+) -> Json<serde_json::Value> {
+    let conn = state.db.lock().unwrap();
+    let deleted = match opencrab_db::queries::delete_model_pricing(&conn, &provider, &model) {
+        Ok(deleted) => deleted,
+        Err(e) => return Json(serde_json::json!({"ok": false, "error": e.to_string()})),
+    };
+    if let Err(e) = reload(&state, &conn) {
+        return Json(serde_json::json!({"ok": false, "error": e}));
+    }
+    Json(serde_json::json!({"ok": true, "deleted": deleted}))
+}
+
+/// 料金のインポート元。`provider` はプロバイダのAPIから取得し、
+/// `models` は OpenRouter の `/models` レスポンスと同じ形式のJSONを読む
+#[derive(Debug, Deserialize)]
+pub struct ImportPricingRequest {
+    pub provider: Option<String>,
+    pub models: Option<serde_json::Value>,
+}
+
+pub async fn import_pricing(
+    State(state): State<AppState>,
+    Json(req): Json<ImportPricingRequest>,
+) -> Json<serde_json::Value> {
+    let prices = match (req.provider, req.models) {
+        (_, Some(models)) => opencrab_llm::parse_openrouter_models(&models),
+        (Some(name), None) => {
+            let Some(provider) = state.llm_router.get_provider(&name) else {
+                return Json(serde_json::json!({
+                    "ok": false,
+                    "error": format!("Provider '{name}' is not configured"),
+                }));
+            };
+            match provider.model_pricing().await {
+                // ルーターに登録した名前で保存する（コスト計算はその名前で引くため）
+                Ok(prices) => prices
+                    .into_iter()
+                    .map(|p| ModelPricing { provider: name.clone(), ..p })
+                    .collect(),
+                Err(e) => return Json(serde_json::json!({"ok": false, "error": e.to_string()})),
+            }
+        }
+        (None, None) => {
+            return Json(serde_json::json!({
+                "ok": false,
+                "error": "Either 'provider' or 'models' is required",
+            }))
+        }
+    };
+
+    let conn = state.db.lock().unwrap();
+    let imported = match crate::pricing::import(&conn, &prices) {
+        Ok(imported) => imported,
+        Err(e) => return Json(serde_json::json!({"ok": false, "error": e.to_string()})),
+    };
+    if let Err(e) = reload(&state, &conn) {
+        return Json(serde_json::json!({"ok": false, "error": e}));
+    }
+    Json(serde_json::json!({"ok": true, "imported": imported}))
+}
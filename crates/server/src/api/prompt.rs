@@ -0,0 +1,264 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use opencrab_core::{PromptTemplate, PROMPT_VARIABLES};
+use opencrab_db::queries::PROMPT_TEMPLATE_GLOBAL;
+use serde::Deserialize;
+
+use crate::process::{self, PromptInput};
+use crate::AppState;
+
+fn variables_json() -> serde_json::Value {
+    PROMPT_VARIABLES
+        .iter()
+        .map(|(name, description)| serde_json::json!({"name": name, "description": description}))
+        .collect()
+}
+
+/// テンプレートを解析し、未知の変数があればエラーにする
+fn validate(content: &str) -> Result<PromptTemplate, String> {
+    let template = PromptTemplate::parse(content).map_err(|e| e.to_string())?;
+    let unknown = template.unknown_variables();
+    if !unknown.is_empty() {
+        return Err(format!("Unknown variables: {}", unknown.join(", ")));
+    }
+    Ok(template)
+}
+
+/// エージェント別の操作の対象を確認する。
+///
+/// 共通テンプレートの scope（`*`）はエージェントIDとして扱わず、未登録のエージェントも拒否する。
+fn check_agent(state: &AppState, id: &str) -> Result<(), serde_json::Value> {
+    if id == PROMPT_TEMPLATE_GLOBAL {
+        return Err(
+            serde_json::json!({"ok": false, "error": "Use /api/prompt for the global template"}),
+        );
+    }
+    let conn = state.db.lock().unwrap();
+    match opencrab_db::queries::get_identity(&conn, id) {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(serde_json::json!({"ok": false, "error": "Agent not found"})),
+        Err(e) => Err(serde_json::json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+/// scope のバージョン履歴と有効なバージョン
+fn template_status(state: &AppState, scope: &str) -> serde_json::Value {
+    let conn = state.db.lock().unwrap();
+    let versions = match opencrab_db::queries::list_prompt_templates(&conn, scope) {
+        Ok(versions) => versions,
+        Err(e) => return serde_json::json!({"ok": false, "error": e.to_string()}),
+    };
+    let active = versions.iter().find(|v| v.active).map(|v| v.version);
+    serde_json::json!({
+        "ok": true,
+        "scope": scope,
+        "active_version": active,
+        "versions": versions,
+        "variables": variables_json(),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SavePromptRequest {
+    pub content: String,
+    pub note: Option<String>,
+}
+
+fn save_template(state: &AppState, scope: &str, req: &SavePromptRequest) -> serde_json::Value {
+    if let Err(e) = validate(&req.content) {
+        return serde_json::json!({"ok": false, "error": e});
+    }
+    let conn = state.db.lock().unwrap();
+    match opencrab_db::queries::insert_prompt_template_version(
+        &conn,
+        scope,
+        &req.content,
+        req.note.as_deref(),
+    ) {
+        Ok(row) => serde_json::json!({"ok": true, "template": row}),
+        Err(e) => serde_json::json!({"ok": false, "error": e.to_string()}),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RollbackPromptRequest {
+    pub version: i64,
+}
+
+fn rollback_template(state: &AppState, scope: &str, version: i64) -> serde_json::Value {
+    let conn = state.db.lock().unwrap();
+    match opencrab_db::queries::activate_prompt_template_version(&conn, scope, version) {
+        Ok(true) => serde_json::json!({"ok": true, "active_version": version}),
+        Ok(false) => {
+            serde_json::json!({"ok": false, "error": format!("Version {version} not found")})
+        }
+        Err(e) => serde_json::json!({"ok": false, "error": e.to_string()}),
+    }
+}
+
+fn clear_template(state: &AppState, scope: &str) -> serde_json::Value {
+    let conn = state.db.lock().unwrap();
+    match opencrab_db::queries::deactivate_prompt_templates(&conn, scope) {
+        Ok(deactivated) => serde_json::json!({"ok": true, "deactivated": deactivated}),
+        Err(e) => serde_json::json!({"ok": false, "error": e.to_string()}),
+    }
+}
+
+/// エージェントのテンプレート: 有効なバージョン・履歴と、実際に使われるテンプレート
+pub async fn get_prompt(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Json<serde_json::Value> {
+    if let Err(e) = check_agent(&state, &id) {
+        return Json(e);
+    }
+    let mut status = template_status(&state, &id);
+    if status["ok"] == true {
+        let conn = state.db.lock().unwrap();
+        let resolved = process::resolve_prompt_template(&conn, &id);
+        let content = match resolved.source {
+            "builtin" => Some(process::DEFAULT_PROMPT_TEMPLATE.to_string()),
+            source => {
+                let scope = if source == "agent" {
+                    id.as_str()
+                } else {
+                    PROMPT_TEMPLATE_GLOBAL
+                };
+                opencrab_db::queries::get_active_prompt_template(&conn, scope)
+                    .ok()
+                    .flatten()
+                    .map(|row| row.content)
+            }
+        };
+        status["effective"] = serde_json::json!({
+            "source": resolved.source,
+            "version": resolved.version,
+            "content": content,
+        });
+    }
+    Json(status)
+}
+
+/// エージェントのテンプレートを新しいバージョンとして保存し、有効にする
+pub async fn update_prompt(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<SavePromptRequest>,
+) -> Json<serde_json::Value> {
+    if let Err(e) = check_agent(&state, &id) {
+        return Json(e);
+    }
+    Json(save_template(&state, &id, &req))
+}
+
+/// エージェントのテンプレートを無効にする（共通 → 組み込みに戻る。履歴は残る）
+pub async fn delete_prompt(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Json<serde_json::Value> {
+    if let Err(e) = check_agent(&state, &id) {
+        return Json(e);
+    }
+    Json(clear_template(&state, &id))
+}
+
+/// エージェントのテンプレートを過去のバージョンに戻す
+pub async fn rollback_prompt(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<RollbackPromptRequest>,
+) -> Json<serde_json::Value> {
+    if let Err(e) = check_agent(&state, &id) {
+        return Json(e);
+    }
+    Json(rollback_template(&state, &id, req.version))
+}
+
+/// 共通テンプレートの状態
+pub async fn get_global_prompt(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let mut status = template_status(&state, PROMPT_TEMPLATE_GLOBAL);
+    if status["ok"] == true {
+        status["builtin"] = serde_json::json!(process::DEFAULT_PROMPT_TEMPLATE);
+    }
+    Json(status)
+}
+
+/// 共通テンプレートを新しいバージョンとして保存し、有効にする
+pub async fn update_global_prompt(
+    State(state): State<AppState>,
+    Json(req): Json<SavePromptRequest>,
+) -> Json<serde_json::Value> {
+    Json(save_template(&state, PROMPT_TEMPLATE_GLOBAL, &req))
+}
+
+/// 共通テンプレートを無効にする（組み込みに戻る）
+pub async fn delete_global_prompt(State(state): State<AppState>) -> Json<serde_json::Value> {
+    Json(clear_template(&state, PROMPT_TEMPLATE_GLOBAL))
+}
+
+/// 共通テンプレートを過去のバージョンに戻す
+pub async fn rollback_global_prompt(
+    State(state): State<AppState>,
+    Json(req): Json<RollbackPromptRequest>,
+) -> Json<serde_json::Value> {
+    Json(rollback_template(
+        &state,
+        PROMPT_TEMPLATE_GLOBAL,
+        req.version,
+    ))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PreviewPromptRequest {
+    /// セッション（テーマ・参加者・記憶の検索語に使う）
+    pub session_id: Option<String>,
+    /// `{{gateway}}` の値（省略時は "rest"）
+    pub gateway: Option<String>,
+    /// 保存前の下書き。省略時は実際に使われるテンプレート
+    pub content: Option<String>,
+}
+
+/// エージェントが受け取るシステムプロンプトをそのまま組み立てる（ログには記録しない）。
+pub async fn preview_prompt(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<PreviewPromptRequest>,
+) -> Json<serde_json::Value> {
+    if let Err(e) = check_agent(&state, &id) {
+        return Json(e);
+    }
+    let draft = match req.content.as_deref().map(validate).transpose() {
+        Ok(draft) => draft,
+        Err(e) => return Json(serde_json::json!({"ok": false, "error": e})),
+    };
+    let theme = match req.session_id.as_deref() {
+        Some(session_id) => {
+            let conn = state.db.lock().unwrap();
+            match opencrab_db::queries::get_session(&conn, session_id) {
+                Ok(Some(session)) => session.theme,
+                Ok(None) => {
+                    return Json(serde_json::json!({"ok": false, "error": "Session not found"}))
+                }
+                Err(e) => return Json(serde_json::json!({"ok": false, "error": e.to_string()})),
+            }
+        }
+        None => "(no active discussion)".to_string(),
+    };
+
+    let input = PromptInput {
+        session_id: req.session_id.as_deref(),
+        session_theme: &theme,
+        gateway: req.gateway.as_deref().unwrap_or("rest"),
+        with_memories: true,
+    };
+    let rendered = process::render_system_prompt(&state, &id, &input, draft).await;
+    Json(serde_json::json!({
+        "ok": true,
+        "source": rendered.source,
+        "version": rendered.version,
+        "prompt": rendered.prompt,
+        "memory": rendered.context,
+    }))
+}
@@ -1,7 +1,15 @@
+use std::convert::Infallible;
+
 use axum::{
     extract::{Path, State},
+    http::{header, HeaderMap},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     Json,
 };
+use futures::Stream;
 use serde::Deserialize;
 
 use crate::AppState;
@@ -36,8 +44,7 @@ pub async fn send_mentor_instruction(
         turn_number: None,
         metadata_json: None,
     };
-    let conn = state.db.lock().unwrap();
-    let log_id = opencrab_db::queries::insert_session_log(&conn, &log).unwrap();
+    let log_id = process::record_session_log(&state, &log).unwrap();
     Json(serde_json::json!({"id": log_id}))
 }
 
@@ -55,6 +62,8 @@ pub struct CreateSessionRequest {
     pub mode: Option<String>,
     pub participant_ids: Vec<String>,
     pub max_turns: Option<i32>,
+    /// ファシリテーター（`mode = "facilitated"` で次の発言者を選ぶエージェント）
+    pub facilitator_id: Option<String>,
 }
 
 pub async fn create_session(
@@ -70,7 +79,7 @@ pub async fn create_session(
         turn_number: 0,
         status: "active".to_string(),
         participant_ids_json: serde_json::to_string(&req.participant_ids).unwrap(),
-        facilitator_id: None,
+        facilitator_id: req.facilitator_id,
         done_count: 0,
         max_turns: req.max_turns,
         metadata_json: None,
@@ -93,133 +102,202 @@ pub async fn get_session(
     Json(serde_json::to_value(session).unwrap())
 }
 
+/// `POST /api/sessions/{id}/turn` — ファシリテーター付きセッションを1ターン進める。
+pub async fn advance_turn(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Json<serde_json::Value> {
+    if state.session_runner.status(&state, &id).await.running {
+        return Json(serde_json::json!({"ok": false, "error": "Session is running in the background"}));
+    }
+    match crate::orchestrator::run_facilitated_turn(&state, &id, None).await {
+        Ok(turn) => Json(serde_json::json!({"ok": true, "turn": turn})),
+        Err(e) => Json(serde_json::json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct RunSessionRequest {
+    /// ターン間隔（ミリ秒）。省略時は設定値
+    pub interval_ms: Option<u64>,
+}
+
+/// `POST /api/sessions/{id}/run` — 自律実行を開始（一時停止中なら再開）する。
+pub async fn run_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    body: Option<Json<RunSessionRequest>>,
+) -> Json<serde_json::Value> {
+    let req = body.map(|Json(r)| r).unwrap_or_default();
+    match state
+        .session_runner
+        .run(state.clone(), &id, req.interval_ms)
+        .await
+    {
+        Ok(status) => Json(serde_json::json!({"ok": true, "status": status})),
+        Err(e) => Json(serde_json::json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+/// `POST /api/sessions/{id}/pause`
+pub async fn pause_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Json<serde_json::Value> {
+    match state.session_runner.pause(&state, &id).await {
+        Ok(paused) => Json(serde_json::json!({"ok": true, "paused": paused})),
+        Err(e) => Json(serde_json::json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+/// `POST /api/sessions/{id}/stop`
+pub async fn stop_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Json<serde_json::Value> {
+    match state.session_runner.stop(&state, &id).await {
+        Ok(stopped) => Json(serde_json::json!({"ok": true, "stopped": stopped})),
+        Err(e) => Json(serde_json::json!({"ok": false, "error": e.to_string()})),
+    }
+}
+
+/// `GET /api/sessions/{id}/run` — 自律実行の稼働状況
+pub async fn get_run_status(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Json<serde_json::Value> {
+    let status = state.session_runner.status(&state, &id).await;
+    Json(serde_json::json!({"status": status}))
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SendMessageRequest {
     pub agent_id: String,
     pub content: String,
+    /// `true` ならSSEで進捗を返す（`Accept: text/event-stream` でも可）
+    #[serde(default)]
+    pub stream: bool,
 }
 
 pub async fn send_message(
     State(state): State<AppState>,
     Path(id): Path<String>,
+    headers: HeaderMap,
     Json(req): Json<SendMessageRequest>,
-) -> Json<serde_json::Value> {
-    // 1. Log the sender's message to DB.
-    let log = opencrab_db::queries::SessionLogRow {
-        id: None,
-        agent_id: req.agent_id.clone(),
-        session_id: id.clone(),
-        log_type: "speech".to_string(),
-        content: req.content.clone(),
-        speaker_id: Some(req.agent_id.clone()),
-        turn_number: None,
-        metadata_json: None,
-    };
+) -> Response {
+    let wants_sse = req.stream
+        || headers
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.contains("text/event-stream"));
+    if wants_sse {
+        return send_message_sse(state, id, req).into_response();
+    }
 
-    let log_id = {
-        let conn = state.db.lock().unwrap();
-        opencrab_db::queries::insert_session_log(&conn, &log).unwrap()
-    };
+    let outcome =
+        process::handle_session_message(&state, &id, &req.agent_id, &req.content, "rest", None)
+            .await;
+    Json(message_outcome_json(&id, outcome)).into_response()
+}
 
-    // 2. Check if LLM providers are available. If none, fall back to legacy behavior.
-    if state.llm_router.provider_names().is_empty() {
-        return Json(serde_json::json!({
+fn message_outcome_json(
+    session_id: &str,
+    outcome: anyhow::Result<process::SessionMessageOutcome>,
+) -> serde_json::Value {
+    match outcome {
+        Ok(process::SessionMessageOutcome {
+            log_id,
+            replies: None,
+        }) => serde_json::json!({
+            "id": log_id,
+            "session_id": session_id,
+        }),
+        Ok(process::SessionMessageOutcome {
+            log_id,
+            replies: Some(replies),
+        }) => serde_json::json!({
             "id": log_id,
-            "session_id": id,
-        }));
+            "session_id": session_id,
+            "responses": replies,
+        }),
+        Err(e) => serde_json::json!({"ok": false, "error": e.to_string()}),
     }
+}
 
-    // 3. Get session and participant IDs.
-    let (participant_ids, session_theme) = {
-        let conn = state.db.lock().unwrap();
-        let session = opencrab_db::queries::get_session(&conn, &id)
-            .unwrap()
-            .unwrap();
-        let ids: Vec<String> =
-            serde_json::from_str(&session.participant_ids_json).unwrap_or_default();
-        (ids, session.theme)
-    };
-
-    // 4. For each participant (except the sender), run SkillEngine.
-    let mut responses = Vec::new();
-
-    for agent_id in &participant_ids {
-        if agent_id == &req.agent_id {
-            continue;
-        }
-
-        // Build agent context from DB.
-        let (system_prompt, agent_name) = {
-            let conn = state.db.lock().unwrap();
-            process::build_agent_context(&conn, agent_id, &session_theme)
-        };
+/// SSEモード: 処理の進捗をイベントとして逐次返す。
+///
+/// イベント: `accepted` → (`agent_start` → `text_delta` / `tool_call_started` /
+/// `tool_call_finished` / `usage` → `response`)×参加者 → `done`（失敗時は `error`）。
+/// `done` のデータは非SSEモードのレスポンスと同じ。
+fn send_message_sse(
+    state: AppState,
+    session_id: String,
+    req: SendMessageRequest,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<Event>();
 
-        // Build conversation history from session logs.
-        let conversation = {
-            let conn = state.db.lock().unwrap();
-            process::build_conversation_string(&conn, &id)
+    tokio::spawn(async move {
+        let (event_tx, mut event_rx) = tokio::sync::mpsc::unbounded_channel();
+        let relay = {
+            let tx = tx.clone();
+            let session_id = session_id.clone();
+            tokio::spawn(async move {
+                while let Some(event) = event_rx.recv().await {
+                    let _ = tx.send(session_event_to_sse(&session_id, event));
+                }
+            })
         };
 
-        // Run agent through the shared pipeline.
-        let result = process::run_agent_response(
+        let outcome = process::handle_session_message(
             &state,
-            agent_id,
-            &agent_name,
-            &id,
-            &system_prompt,
-            &conversation,
+            &session_id,
+            &req.agent_id,
+            &req.content,
             "rest",
-            None,
+            Some(&event_tx),
         )
         .await;
+        drop(event_tx);
+        relay.await.ok();
 
-        match result {
-            Ok(engine_result) => {
-                // Log the agent's response to DB.
-                let response_log = opencrab_db::queries::SessionLogRow {
-                    id: None,
-                    agent_id: agent_id.clone(),
-                    session_id: id.clone(),
-                    log_type: "speech".to_string(),
-                    content: engine_result.response.clone(),
-                    speaker_id: Some(agent_id.clone()),
-                    turn_number: None,
-                    metadata_json: Some(
-                        serde_json::json!({
-                            "iterations": engine_result.iterations,
-                            "tool_calls_made": engine_result.tool_calls_made,
-                        })
-                        .to_string(),
-                    ),
-                };
-                {
-                    let conn = state.db.lock().unwrap();
-                    opencrab_db::queries::insert_session_log(&conn, &response_log).ok();
-                }
+        let last = match outcome {
+            Ok(outcome) => sse_event("done", &message_outcome_json(&session_id, Ok(outcome))),
+            Err(e) => sse_event("error", &serde_json::json!({"error": e.to_string()})),
+        };
+        let _ = tx.send(last);
+    });
 
-                responses.push(serde_json::json!({
-                    "agent_id": agent_id,
-                    "agent_name": agent_name,
-                    "content": engine_result.response,
-                    "tool_calls_made": engine_result.tool_calls_made,
-                }));
-            }
-            Err(e) => {
-                tracing::error!(agent_id = %agent_id, error = %e, "SkillEngine failed");
-                responses.push(serde_json::json!({
-                    "agent_id": agent_id,
-                    "agent_name": agent_name,
-                    "content": format!("(Error: {})", e),
-                    "tool_calls_made": 0,
-                }));
-            }
+    let stream = futures::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|event| (Ok(event), rx))
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+fn session_event_to_sse(session_id: &str, event: process::SessionEvent) -> Event {
+    match event {
+        process::SessionEvent::Accepted { log_id } => sse_event(
+            "accepted",
+            &serde_json::json!({"id": log_id, "session_id": session_id}),
+        ),
+        process::SessionEvent::AgentStarted {
+            agent_id,
+            agent_name,
+        } => sse_event(
+            "agent_start",
+            &serde_json::json!({"agent_id": agent_id, "agent_name": agent_name}),
+        ),
+        process::SessionEvent::Engine { agent_id, event } => {
+            let mut data = serde_json::to_value(&event).unwrap_or_default();
+            let name = data["type"].as_str().unwrap_or("engine").to_string();
+            data["agent_id"] = serde_json::json!(agent_id);
+            sse_event(&name, &data)
+        }
+        process::SessionEvent::Reply(reply) => {
+            sse_event("response", &serde_json::to_value(&reply).unwrap_or_default())
         }
     }
-
-    Json(serde_json::json!({
-        "id": log_id,
-        "session_id": id,
-        "responses": responses,
-    }))
 }
 
+fn sse_event(name: &str, data: &serde_json::Value) -> Event {
+    Event::default().event(name).data(data.to_string())
+}
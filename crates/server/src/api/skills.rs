@@ -39,6 +39,7 @@ pub async fn add_skill(
         source_type: "manual".to_string(),
         source_context: None,
         file_path: None,
+        version: None,
         effectiveness: None,
         usage_count: 0,
         is_active: true,
@@ -66,3 +67,57 @@ pub async fn toggle_skill(
     opencrab_db::queries::set_skill_active(&conn, &skill_id, req.active).unwrap();
     Json(serde_json::json!({"toggled": true}))
 }
+
+/// 標準スキルファイルを再読み込みしてエージェントに同期する
+pub async fn reload_skills(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Json<serde_json::Value> {
+    let exists = {
+        let conn = state.db.lock().unwrap();
+        opencrab_db::queries::get_identity(&conn, &id).unwrap().is_some()
+    };
+    if !exists {
+        return Json(serde_json::json!({"ok": false, "error": "Agent not found"}));
+    }
+    match crate::skill_sync::sync_agent_skills(&state.db, &state.skills_dir, &id) {
+        Ok(report) => Json(serde_json::json!({
+            "ok": true,
+            "added": report.added,
+            "updated": report.updated,
+            "unchanged": report.unchanged,
+        })),
+        Err(e) => Json(serde_json::json!({
+            "ok": false,
+            "error": e.to_string(),
+        })),
+    }
+}
+
+/// エージェントに公開されるツール（許可リスト適用後）を返す
+pub async fn list_agent_tools(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Json<serde_json::Value> {
+    let allowlist =
+        match crate::process::agent_tool_allowlist(&state.db, &id, &state.base_actions, false) {
+            Ok(allowlist) => allowlist,
+            Err(e) => return Json(serde_json::json!({"ok": false, "error": e.to_string()})),
+        };
+
+    let dispatcher = opencrab_actions::ActionDispatcher::new();
+    let mut tools: Vec<serde_json::Value> = dispatcher
+        .get_definitions(&[])
+        .into_iter()
+        .filter(|d| allowlist.contains(&d.name))
+        .map(|d| serde_json::json!({"name": d.name, "description": d.description}))
+        .collect();
+    tools.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+
+    Json(serde_json::json!({
+        "ok": true,
+        "base_actions": state.base_actions,
+        "allowed_actions": allowlist,
+        "tools": tools,
+    }))
+}
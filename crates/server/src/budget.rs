@@ -0,0 +1,292 @@
+//! LLM利用料の予算チェック。
+//!
+//! `[llm.budget]` の上限（エージェント/日、セッション、全体/月）を `llm_usage_metrics` の
+//! 推定コストの合計と比べる。`LlmRouterAdapter` が呼び出しごとに `BudgetGuard` で確認し、
+//! 超過していれば `downgrade_model` に切り替えるか、呼び出しを拒否する。
+//! 警告・切り替え・拒否はセッションログ（`log_type = 'budget'`）に残す。
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::config::BudgetConfig;
+use crate::process;
+use crate::AppState;
+
+/// 予算の通知を保存するセッションログの `log_type`（会話履歴には含めない）
+pub const BUDGET_LOG_TYPE: &str = opencrab_db::queries::LOG_TYPE_BUDGET;
+
+/// 1つの上限に対する利用状況
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BudgetStatus {
+    /// `agent_daily` / `session` / `global_monthly`
+    pub scope: &'static str,
+    pub limit_usd: f64,
+    pub spent_usd: f64,
+    /// 集計の開始時刻（RFC3339）。セッションの上限は期間なし
+    pub since: Option<String>,
+    /// 上限に達している
+    pub exceeded: bool,
+    /// `warn_ratio` に達している
+    pub warning: bool,
+}
+
+impl BudgetStatus {
+    fn new(
+        scope: &'static str,
+        limit_usd: f64,
+        spent_usd: f64,
+        since: Option<String>,
+        warn_ratio: f64,
+    ) -> Self {
+        Self {
+            scope,
+            limit_usd,
+            spent_usd,
+            since,
+            exceeded: spent_usd >= limit_usd,
+            warning: spent_usd >= limit_usd * warn_ratio,
+        }
+    }
+
+    /// 例: `agent_daily $0.5100 / $0.5000`
+    pub fn describe(&self) -> String {
+        format!(
+            "{} ${:.4} / ${:.4}",
+            self.scope, self.spent_usd, self.limit_usd
+        )
+    }
+}
+
+/// UTCの当日0時
+fn day_start(now: DateTime<Utc>) -> DateTime<Utc> {
+    Utc.from_utc_datetime(&now.date_naive().and_hms_opt(0, 0, 0).unwrap_or_default())
+}
+
+/// UTCの当月1日0時
+fn month_start(now: DateTime<Utc>) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0)
+        .single()
+        .unwrap_or_else(|| day_start(now))
+}
+
+/// 設定されている上限ごとの利用状況。セッション外（`session_id` が None）ではセッションの上限を省く。
+pub fn budget_status(
+    conn: &rusqlite::Connection,
+    config: &BudgetConfig,
+    agent_id: &str,
+    session_id: Option<&str>,
+) -> anyhow::Result<Vec<BudgetStatus>> {
+    let now = Utc::now();
+    let mut statuses = Vec::new();
+
+    if let Some(limit) = config.agent_daily_usd {
+        let since = day_start(now).to_rfc3339();
+        let spent =
+            opencrab_db::queries::get_llm_cost_total(conn, Some(agent_id), None, Some(&since))?;
+        statuses.push(BudgetStatus::new(
+            "agent_daily",
+            limit,
+            spent,
+            Some(since),
+            config.warn_ratio,
+        ));
+    }
+    if let (Some(limit), Some(session_id)) = (config.session_usd, session_id) {
+        let spent = opencrab_db::queries::get_llm_cost_total(conn, None, Some(session_id), None)?;
+        statuses.push(BudgetStatus::new(
+            "session",
+            limit,
+            spent,
+            None,
+            config.warn_ratio,
+        ));
+    }
+    if let Some(limit) = config.global_monthly_usd {
+        let since = month_start(now).to_rfc3339();
+        let spent = opencrab_db::queries::get_llm_cost_total(conn, None, None, Some(&since))?;
+        statuses.push(BudgetStatus::new(
+            "global_monthly",
+            limit,
+            spent,
+            Some(since),
+            config.warn_ratio,
+        ));
+    }
+    Ok(statuses)
+}
+
+/// 利用状況に対する判断
+#[derive(Debug, Clone, PartialEq)]
+pub enum BudgetDecision {
+    Allow,
+    /// 超過。このモデルに切り替えて続ける
+    Downgrade(String),
+    /// 超過。呼び出しを拒否する
+    Refuse,
+}
+
+/// 上限を1つでも超えていれば、切り替え先があれば切り替え、なければ拒否する
+pub fn decide(config: &BudgetConfig, statuses: &[BudgetStatus]) -> BudgetDecision {
+    if !statuses.iter().any(|s| s.exceeded) {
+        return BudgetDecision::Allow;
+    }
+    match &config.downgrade_model {
+        Some(model) => BudgetDecision::Downgrade(model.clone()),
+        None => BudgetDecision::Refuse,
+    }
+}
+
+/// 1回の実行（エージェント × セッション）の予算チェック
+pub struct BudgetGuard {
+    state: AppState,
+    agent_id: String,
+    session_id: Option<String>,
+    /// この実行で記録済みの通知（同じ通知を呼び出しごとに残さない）
+    notified: Mutex<HashSet<String>>,
+}
+
+impl BudgetGuard {
+    /// 上限が1つも設定されていなければ None
+    pub fn new(state: &AppState, agent_id: &str, session_id: Option<&str>) -> Option<Self> {
+        state.budget.is_enabled().then(|| Self {
+            state: state.clone(),
+            agent_id: agent_id.to_string(),
+            session_id: session_id.map(String::from),
+            notified: Mutex::new(HashSet::new()),
+        })
+    }
+
+    fn status(&self) -> Vec<BudgetStatus> {
+        let conn = self.state.db.lock().unwrap();
+        budget_status(
+            &conn,
+            &self.state.budget,
+            &self.agent_id,
+            self.session_id.as_deref(),
+        )
+        .unwrap_or_else(|e| {
+            warn!(agent_id = %self.agent_id, error = %e, "Failed to read LLM budget status");
+            Vec::new()
+        })
+    }
+
+    /// 呼び出し前のチェック。使うモデルを返す。
+    ///
+    /// 超過していて切り替え先がなければエラーにする（エンジンのエラーとして応答に出る）。
+    pub fn check(&self, model: &str) -> anyhow::Result<String> {
+        let statuses = self.status();
+        let exceeded: Vec<&BudgetStatus> = statuses.iter().filter(|s| s.exceeded).collect();
+        let summary = exceeded
+            .iter()
+            .map(|s| s.describe())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        match decide(&self.state.budget, &statuses) {
+            BudgetDecision::Allow => Ok(model.to_string()),
+            BudgetDecision::Downgrade(cheaper) => {
+                self.notify(
+                    "downgrade",
+                    format!("LLM budget exceeded ({summary}): switched from {model} to {cheaper}"),
+                    &exceeded,
+                );
+                Ok(cheaper)
+            }
+            BudgetDecision::Refuse => {
+                let message = format!("LLM budget exceeded ({summary}): call refused");
+                self.notify("refuse", message.clone(), &exceeded);
+                anyhow::bail!(message)
+            }
+        }
+    }
+
+    /// 呼び出し後のチェック。この呼び出しで `warn_ratio` を超えた上限を警告として残す。
+    pub fn after_call(&self, cost_usd: f64) {
+        if cost_usd <= 0.0 {
+            return;
+        }
+        let warn_ratio = self.state.budget.warn_ratio;
+        for status in self.status() {
+            let threshold = status.limit_usd * warn_ratio;
+            if status.spent_usd >= threshold && status.spent_usd - cost_usd < threshold {
+                self.notify(
+                    &format!("warning:{}", status.scope),
+                    format!(
+                        "LLM budget warning: {} ({:.0}% used)",
+                        status.describe(),
+                        status.spent_usd / status.limit_usd * 100.0
+                    ),
+                    &[&status],
+                );
+            }
+        }
+    }
+
+    fn notify(&self, key: &str, content: String, statuses: &[&BudgetStatus]) {
+        warn!(agent_id = %self.agent_id, action = key, "{content}");
+        if !self.notified.lock().unwrap().insert(key.to_string()) {
+            return;
+        }
+        let Some(session_id) = &self.session_id else {
+            return;
+        };
+        let log = opencrab_db::queries::SessionLogRow {
+            id: None,
+            agent_id: self.agent_id.clone(),
+            session_id: session_id.clone(),
+            log_type: BUDGET_LOG_TYPE.to_string(),
+            content,
+            speaker_id: None,
+            turn_number: None,
+            metadata_json: Some(
+                serde_json::json!({
+                    "action": key.split(':').next().unwrap_or(key),
+                    "budgets": statuses,
+                })
+                .to_string(),
+            ),
+        };
+        if let Err(e) = process::record_session_log(&self.state, &log) {
+            warn!(agent_id = %self.agent_id, error = %e, "Failed to record budget log");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decide_and_periods() {
+        let mut config = BudgetConfig {
+            agent_daily_usd: Some(1.0),
+            ..Default::default()
+        };
+        let under = BudgetStatus::new("agent_daily", 1.0, 0.85, None, config.warn_ratio);
+        assert!(under.warning && !under.exceeded);
+        assert_eq!(
+            decide(&config, std::slice::from_ref(&under)),
+            BudgetDecision::Allow
+        );
+
+        let over = BudgetStatus::new("agent_daily", 1.0, 1.0, None, config.warn_ratio);
+        assert!(over.exceeded);
+        assert_eq!(
+            decide(&config, &[under, over.clone()]),
+            BudgetDecision::Refuse
+        );
+        config.downgrade_model = Some("cheap".to_string());
+        assert_eq!(
+            decide(&config, &[over]),
+            BudgetDecision::Downgrade("cheap".to_string())
+        );
+
+        let now = Utc.with_ymd_and_hms(2026, 3, 15, 13, 45, 0).unwrap();
+        assert_eq!(day_start(now).to_rfc3339(), "2026-03-15T00:00:00+00:00");
+        assert_eq!(month_start(now).to_rfc3339(), "2026-03-01T00:00:00+00:00");
+    }
+}
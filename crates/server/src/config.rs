@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{Context, Result};
 use serde::Deserialize;
@@ -19,6 +20,10 @@ pub struct AppConfig {
     pub gateway: GatewayConfig,
     #[serde(default)]
     pub database: DatabaseConfig,
+    #[serde(default)]
+    pub skills: SkillsConfig,
+    #[serde(default)]
+    pub agent: AgentConfig,
 }
 
 #[derive(Debug, Deserialize)]
@@ -33,6 +38,18 @@ pub struct LlmConfig {
     pub fallback: FallbackConfig,
     #[serde(default)]
     pub aliases: HashMap<String, AliasConfig>,
+    /// 用途別モデル設定（`[llm.models]`）。`embedding` は記憶のベクトル検索に使う
+    #[serde(default)]
+    pub models: HashMap<String, PurposeModelConfig>,
+    /// エージェント自身によるモデル選択（`[llm.self_selection]`）
+    #[serde(default)]
+    pub self_selection: SelfSelectionConfig,
+    /// LLM利用料の上限（`[llm.budget]`）
+    #[serde(default)]
+    pub budget: BudgetConfig,
+    /// リトライとサーキットブレーカー（`[llm.resilience]`）
+    #[serde(default)]
+    pub resilience: ResilienceConfig,
 }
 
 impl Default for LlmConfig {
@@ -43,10 +60,21 @@ impl Default for LlmConfig {
             providers: HashMap::new(),
             fallback: FallbackConfig::default(),
             aliases: HashMap::new(),
+            models: HashMap::new(),
+            self_selection: SelfSelectionConfig::default(),
+            budget: BudgetConfig::default(),
+            resilience: ResilienceConfig::default(),
         }
     }
 }
 
+impl LlmConfig {
+    /// 埋め込みモデル（`[llm.models] embedding`）を `provider:model` 形式で返す
+    pub fn embedding_model(&self) -> Option<String> {
+        self.models.get("embedding").map(PurposeModelConfig::target)
+    }
+}
+
 fn default_provider() -> String {
     "openai".to_string()
 }
@@ -56,6 +84,9 @@ fn default_model() -> String {
 
 #[derive(Debug, Deserialize, Default, Clone)]
 pub struct ProviderConfig {
+    /// プロバイダーの種類。省略時はセクション名（`[llm.providers.openai]` なら `openai`）
+    #[serde(default, rename = "type")]
+    pub provider_type: String,
     #[serde(default)]
     pub api_key: String,
     #[serde(default)]
@@ -68,6 +99,51 @@ pub struct ProviderConfig {
     pub site_url: String,
     #[serde(default)]
     pub default_model: String,
+    /// `type = "replay"`: 記録ファイル（JSON）のパス
+    #[serde(default)]
+    pub fixture: String,
+    /// `type = "replay"`: `replay`（記録から応答）または `record`（常に実プロバイダーを呼んで記録）
+    #[serde(default)]
+    pub mode: String,
+    /// `type = "replay"`: 記録にないリクエストをエラーにする
+    #[serde(default)]
+    pub strict: bool,
+    /// `type = "replay"`: 記録に使う実プロバイダーの種類（`openai` など）。
+    /// api_key などの設定は同じセクションのものを使う。空ならネットワークに出ない
+    #[serde(default)]
+    pub inner: String,
+    /// `type = "openai_compatible"`: 全リクエストに付けるヘッダー
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// `type = "openai_compatible"`: APIキーの送り方。`bearer`（既定）/ `header`（`auth_header` に生のキー）/ `none`
+    #[serde(default)]
+    pub auth_scheme: String,
+    /// `auth_scheme = "header"` で使うヘッダー名（既定は `api-key`）
+    #[serde(default)]
+    pub auth_header: String,
+    /// `type = "openai_compatible"`: 提供するモデル。空ならサーバーの `/models` に問い合わせる
+    #[serde(default)]
+    pub models: Vec<String>,
+    /// `type = "openai_compatible"`: モデルのコンテキスト長（トークン）
+    #[serde(default)]
+    pub context_window: Option<u32>,
+    /// `type = "openai_compatible"`: ツール呼び出しに対応しているか（既定 true）
+    #[serde(default)]
+    pub supports_function_calling: Option<bool>,
+    /// `type = "openai_compatible"`: 画像入力に対応しているか（既定 false）
+    #[serde(default)]
+    pub supports_vision: Option<bool>,
+}
+
+impl ProviderConfig {
+    /// プロバイダーの種類（`type` が空ならセクション名）
+    pub fn kind<'a>(&'a self, name: &'a str) -> &'a str {
+        if self.provider_type.is_empty() {
+            name
+        } else {
+            &self.provider_type
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Default)]
@@ -76,12 +152,137 @@ pub struct FallbackConfig {
     pub chain: Vec<String>,
 }
 
+/// `select_llm` で選べるモデルの制限
+#[derive(Debug, Deserialize, Clone)]
+pub struct SelfSelectionConfig {
+    /// false なら select_llm を常に拒否する
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 選択を許可するエイリアスまたは `provider:model`。空なら登録済みプロバイダーの全モデル
+    #[serde(default)]
+    pub allowed_aliases: Vec<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for SelfSelectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            allowed_aliases: Vec::new(),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Default)]
 pub struct AliasConfig {
     pub provider: String,
     pub model: String,
 }
 
+/// LLM利用料（推定コスト, USD）の上限。未設定の上限はチェックしない
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct BudgetConfig {
+    /// エージェントごとの1日（UTC）の上限
+    #[serde(default)]
+    pub agent_daily_usd: Option<f64>,
+    /// セッションごとの上限
+    #[serde(default)]
+    pub session_usd: Option<f64>,
+    /// 全エージェント合計の1か月（UTC）の上限
+    #[serde(default)]
+    pub global_monthly_usd: Option<f64>,
+    /// 上限超過時に切り替えるモデル（エイリアスまたは `provider:model`）。未設定なら呼び出しを拒否する
+    #[serde(default)]
+    pub downgrade_model: Option<String>,
+    /// 上限に対するこの割合を超えたらセッションログに警告を残す
+    #[serde(default = "default_warn_ratio")]
+    pub warn_ratio: f64,
+}
+
+fn default_warn_ratio() -> f64 {
+    0.8
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        Self {
+            agent_daily_usd: None,
+            session_usd: None,
+            global_monthly_usd: None,
+            downgrade_model: None,
+            warn_ratio: default_warn_ratio(),
+        }
+    }
+}
+
+impl BudgetConfig {
+    /// 上限が1つでも設定されているか
+    pub fn is_enabled(&self) -> bool {
+        self.agent_daily_usd.is_some() || self.session_usd.is_some() || self.global_monthly_usd.is_some()
+    }
+}
+
+/// プロバイダー呼び出しのリトライとサーキットブレーカー
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ResilienceConfig {
+    /// 一時的なエラー（429/5xx/通信エラー）のリトライ回数
+    pub max_retries: u32,
+    /// 最初のリトライまでの待ち時間。以降は倍々（ジッター付き）
+    pub base_delay_ms: u64,
+    /// 1回の待ち時間の上限。これより長い Retry-After は待たずに次の候補へ進む
+    pub max_delay_ms: u64,
+    /// この回数続けて失敗したらサーキットを開く（呼び出しを止める）
+    pub failure_threshold: u32,
+    /// サーキットを開いてから試験的な呼び出しを1回通すまでの秒数
+    pub open_secs: u64,
+}
+
+impl Default for ResilienceConfig {
+    fn default() -> Self {
+        let defaults = opencrab_llm::ResilienceConfig::default();
+        Self {
+            max_retries: defaults.max_retries,
+            base_delay_ms: defaults.base_delay.as_millis() as u64,
+            max_delay_ms: defaults.max_delay.as_millis() as u64,
+            failure_threshold: defaults.failure_threshold,
+            open_secs: defaults.open_duration.as_secs(),
+        }
+    }
+}
+
+impl ResilienceConfig {
+    pub fn to_router_config(&self) -> opencrab_llm::ResilienceConfig {
+        opencrab_llm::ResilienceConfig {
+            max_retries: self.max_retries,
+            base_delay: Duration::from_millis(self.base_delay_ms),
+            max_delay: Duration::from_millis(self.max_delay_ms),
+            failure_threshold: self.failure_threshold.max(1),
+            open_duration: Duration::from_secs(self.open_secs),
+        }
+    }
+}
+
+/// 用途ごとのモデル（`[llm.models]` の1エントリ）
+#[derive(Debug, Deserialize, Default)]
+pub struct PurposeModelConfig {
+    pub provider: String,
+    pub model: String,
+    /// 失敗時に順に試すモデル（エイリアスまたは `provider:model`）
+    #[serde(default)]
+    pub fallback: Vec<String>,
+}
+
+impl PurposeModelConfig {
+    /// `provider:model` 形式で返す
+    pub fn target(&self) -> String {
+        format!("{}:{}", self.provider, self.model)
+    }
+}
+
 #[derive(Debug, Deserialize, Default)]
 pub struct GatewayConfig {
     #[serde(default)]
@@ -140,6 +341,305 @@ fn default_db_path() -> String {
     "data/opencrab.db".to_string()
 }
 
+#[derive(Debug, Deserialize)]
+pub struct SkillsConfig {
+    /// 標準スキルファイル（*.skill.md）のディレクトリ
+    #[serde(default = "default_skills_dir")]
+    pub dir: String,
+}
+
+impl Default for SkillsConfig {
+    fn default() -> Self {
+        Self {
+            dir: default_skills_dir(),
+        }
+    }
+}
+
+fn default_skills_dir() -> String {
+    "skills".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AgentConfig {
+    /// ハートビートの間隔（秒）
+    #[serde(default = "default_heartbeat_interval_secs")]
+    pub heartbeat_interval_secs: u64,
+    /// 起動時にハートビートを開始するエージェントのIDリスト
+    #[serde(default)]
+    pub heartbeat_agent_ids: Vec<String>,
+    /// セッション自律実行のターン間隔（ミリ秒）
+    #[serde(default = "default_session_turn_interval_ms")]
+    pub session_turn_interval_ms: u64,
+    /// スキルの宣言に関係なく常にエージェントへ公開するアクション
+    #[serde(default = "default_base_actions")]
+    pub base_actions: Vec<String>,
+    /// システムプロンプトへの記憶注入（`[agent.context]`）
+    #[serde(default)]
+    pub context: ContextConfig,
+    /// Soul の特性の文章化と実行時パラメータへの反映（`[agent.persona]`）
+    #[serde(default)]
+    pub persona: PersonaConfig,
+    /// セッションログからキュレーション記憶への統合（`[agent.consolidation]`）
+    #[serde(default)]
+    pub consolidation: ConsolidationConfig,
+    /// 記憶の重要度・減衰による順位付けと保持ルール（`[agent.memory]`）
+    #[serde(default)]
+    pub memory: MemoryConfig,
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat_interval_secs: default_heartbeat_interval_secs(),
+            heartbeat_agent_ids: Vec::new(),
+            session_turn_interval_ms: default_session_turn_interval_ms(),
+            base_actions: default_base_actions(),
+            context: ContextConfig::default(),
+            persona: PersonaConfig::default(),
+            consolidation: ConsolidationConfig::default(),
+            memory: MemoryConfig::default(),
+        }
+    }
+}
+
+/// キュレーション記憶・印象・関連する過去ログをシステムプロンプトに注入する設定
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ContextConfig {
+    /// 注入ブロック全体のトークン上限（0 で注入しない）
+    pub max_tokens: usize,
+    /// 優先するキュレーション記憶のカテゴリ（順序が優先度。未指定のカテゴリは後ろに名前順）
+    pub categories: Vec<String>,
+    /// カテゴリごとの最大件数
+    pub max_per_category: usize,
+    /// セッション参加者の印象の最大件数
+    pub max_impressions: usize,
+    /// 直近の発言から自動検索する関連ログの最大件数
+    pub max_related_logs: usize,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        let budget = opencrab_core::ContextBudget::default();
+        Self {
+            max_tokens: budget.max_tokens,
+            categories: budget.categories,
+            max_per_category: budget.max_per_category,
+            max_impressions: budget.max_impressions,
+            max_related_logs: budget.max_related_logs,
+        }
+    }
+}
+
+/// Soul（Big Five・ソーシャルスタイル・思考スタイル）をプロンプトの指示と実行時パラメータに変換する設定
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct PersonaConfig {
+    /// 組み込みの文言の言語（`en` / `ja`）
+    pub language: opencrab_core::PersonaLanguage,
+    /// 組み込みの文言の置き換え（キーは `openness_high`, `extraversion_low`, `heading` 等。空文字でその行を出さない）
+    pub wording: HashMap<String, String>,
+    /// この値以下の特性は `_low` の文言を使う
+    pub low_threshold: f32,
+    /// この値以上の特性は `_high` の文言を使う
+    pub high_threshold: f32,
+    /// 開放性 0.0 のときの temperature
+    pub temperature_min: f32,
+    /// 開放性 1.0 のときの temperature
+    pub temperature_max: f32,
+    /// 外向性 0.0 のときの発言の重み（自律セッションでの発言頻度）
+    pub speak_weight_min: f32,
+    /// 外向性 1.0 のときの発言の重み
+    pub speak_weight_max: f32,
+}
+
+impl Default for PersonaConfig {
+    fn default() -> Self {
+        let options = opencrab_core::PersonaOptions::default();
+        Self {
+            language: options.language,
+            wording: options.wording,
+            low_threshold: options.low_threshold,
+            high_threshold: options.high_threshold,
+            temperature_min: options.temperature_range.0,
+            temperature_max: options.temperature_range.1,
+            speak_weight_min: options.speak_weight_range.0,
+            speak_weight_max: options.speak_weight_range.1,
+        }
+    }
+}
+
+impl PersonaConfig {
+    pub fn to_options(&self) -> opencrab_core::PersonaOptions {
+        opencrab_core::PersonaOptions {
+            language: self.language,
+            wording: self.wording.clone(),
+            low_threshold: self.low_threshold,
+            high_threshold: self.high_threshold,
+            temperature_range: (self.temperature_min, self.temperature_max),
+            speak_weight_range: (self.speak_weight_min, self.speak_weight_max),
+        }
+    }
+}
+
+/// セッションログを事実・決定・好み・未解決の問いに抽出してキュレーション記憶に統合する設定
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ConsolidationConfig {
+    /// 定期実行するか（無効でも `POST /api/agents/{id}/memory/consolidate` で実行できる）
+    pub enabled: bool,
+    /// 定期実行の間隔（秒）
+    pub interval_secs: u64,
+    /// 定期実行の対象エージェント（空なら全エージェント）
+    pub agent_ids: Vec<String>,
+    /// 1回で読むセッションログの最大件数
+    pub max_logs: usize,
+    /// この類似度以上の記憶は重複として出典だけを追加する（0.0〜1.0）
+    pub similarity_threshold: f32,
+}
+
+impl Default for ConsolidationConfig {
+    fn default() -> Self {
+        let options = opencrab_core::ConsolidationOptions::default();
+        Self {
+            enabled: false,
+            interval_secs: 3600,
+            agent_ids: Vec::new(),
+            max_logs: options.max_logs,
+            similarity_threshold: options.similarity_threshold,
+        }
+    }
+}
+
+impl ConsolidationConfig {
+    pub fn to_options(&self) -> opencrab_core::ConsolidationOptions {
+        opencrab_core::ConsolidationOptions {
+            max_logs: self.max_logs,
+            similarity_threshold: self.similarity_threshold,
+        }
+    }
+}
+
+/// 記憶の順位付け（重要度 × 最終利用からの減衰 × 利用回数）と、古いセッションログの整理
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct MemoryConfig {
+    /// 使われない記憶の重みが半分になるまでの日数
+    pub half_life_days: f64,
+    /// 利用回数が倍になるごとに加える重み
+    pub access_boost: f64,
+    /// 検索スコアのうち重要度・減衰で決まる割合（0.0 なら関連度のみ）
+    pub retention_weight: f64,
+    /// この日数より古いセッションログをアーカイブの候補にする（未設定ならアーカイブしない）
+    pub archive_after_days: Option<u32>,
+    /// スコアがこの値未満の候補だけをアーカイブする
+    pub archive_below: f64,
+    /// アーカイブからこの日数が経ったログを削除する（未設定なら削除しない）
+    pub delete_after_days: Option<u32>,
+    /// アーカイブしないログの種類
+    pub keep_log_types: Vec<String>,
+    /// ハイブリッド検索（キーワード＋ベクトル）の重み
+    pub search: MemorySearchConfig,
+}
+
+/// ハイブリッド検索の重み（`[agent.memory.search]`）
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct MemorySearchConfig {
+    /// キーワード（BM25）順位の重み
+    pub keyword_weight: f64,
+    /// ベクトル類似度順位の重み
+    pub vector_weight: f64,
+    /// 順位融合（RRF）の定数。大きいほど順位の差が小さくなる
+    pub rrf_k: f64,
+    /// コサイン類似度がこの値未満のベクトル検索結果は使わない
+    pub min_similarity: f32,
+}
+
+impl Default for MemorySearchConfig {
+    fn default() -> Self {
+        let search = opencrab_core::HybridSearchConfig::default();
+        Self {
+            keyword_weight: search.keyword_weight,
+            vector_weight: search.vector_weight,
+            rrf_k: search.rrf_k,
+            min_similarity: search.min_similarity,
+        }
+    }
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        let policy = opencrab_core::MemoryPolicy::default();
+        Self {
+            half_life_days: policy.half_life_days,
+            access_boost: policy.access_boost,
+            retention_weight: policy.retention_weight,
+            archive_after_days: policy.archive_after_days,
+            archive_below: policy.archive_below,
+            delete_after_days: policy.delete_after_days,
+            keep_log_types: policy.keep_log_types,
+            search: MemorySearchConfig::default(),
+        }
+    }
+}
+
+impl MemoryConfig {
+    pub fn to_policy(&self) -> opencrab_core::MemoryPolicy {
+        opencrab_core::MemoryPolicy {
+            half_life_days: self.half_life_days,
+            access_boost: self.access_boost,
+            retention_weight: self.retention_weight,
+            archive_after_days: self.archive_after_days,
+            archive_below: self.archive_below,
+            delete_after_days: self.delete_after_days,
+            keep_log_types: self.keep_log_types.clone(),
+            search: opencrab_core::HybridSearchConfig {
+                keyword_weight: self.search.keyword_weight,
+                vector_weight: self.search.vector_weight,
+                rrf_k: self.search.rrf_k,
+                min_similarity: self.search.min_similarity,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl ContextConfig {
+    pub fn to_budget(&self) -> opencrab_core::ContextBudget {
+        opencrab_core::ContextBudget {
+            max_tokens: self.max_tokens,
+            categories: self.categories.clone(),
+            max_per_category: self.max_per_category,
+            max_impressions: self.max_impressions,
+            max_related_logs: self.max_related_logs,
+        }
+    }
+}
+
+fn default_heartbeat_interval_secs() -> u64 {
+    29
+}
+
+fn default_session_turn_interval_ms() -> u64 {
+    2000
+}
+
+pub fn default_base_actions() -> Vec<String> {
+    [
+        "send_speech",
+        "send_noreact",
+        "generate_inner_voice",
+        "update_impression",
+        "declare_done",
+        "get_system_info",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
 // ---------- Config loading ----------
 
 /// Load config from a TOML file, expanding `${VAR}` placeholders with env vars.
@@ -160,11 +660,7 @@ pub fn load_config(path: &str) -> Result<AppConfig> {
 fn expand_env_vars(input: &str) -> String {
     let mut result = input.to_string();
     // Find all ${...} patterns and replace them
-    loop {
-        let start = match result.find("${") {
-            Some(pos) => pos,
-            None => break,
-        };
+    while let Some(start) = result.find("${") {
         let end = match result[start..].find('}') {
             Some(pos) => start + pos,
             None => break,
@@ -180,84 +676,155 @@ fn expand_env_vars(input: &str) -> String {
 
 /// Build an LlmRouter from the LLM config section.
 /// Only providers with non-empty API keys (or local providers) are registered.
-pub fn build_llm_router(config: &LlmConfig) -> Result<LlmRouter> {
-    let mut router = LlmRouter::new();
-
-    for (name, pconfig) in &config.providers {
-        let provider: Option<Arc<dyn LlmProvider>> = match name.as_str() {
-            "openai" => {
-                if pconfig.api_key.is_empty() {
-                    None
-                } else {
-                    let mut p = OpenAiProvider::new(&pconfig.api_key);
-                    if !pconfig.base_url.is_empty() {
-                        p = p.with_base_url(&pconfig.base_url);
-                    }
-                    if !pconfig.organization.is_empty() {
-                        p = p.with_org_id(&pconfig.organization);
-                    }
-                    Some(Arc::new(p))
-                }
-            }
-            "anthropic" => {
-                if pconfig.api_key.is_empty() {
-                    None
-                } else {
-                    let mut p = AnthropicProvider::new(&pconfig.api_key);
-                    if !pconfig.base_url.is_empty() {
-                        p = p.with_base_url(&pconfig.base_url);
-                    }
-                    Some(Arc::new(p))
+/// 種類 `kind` のプロバイダーを作る。APIキーが必要なのに空、または未知の種類なら None
+fn build_provider(
+    name: &str,
+    kind: &str,
+    pconfig: &ProviderConfig,
+) -> Result<Option<Arc<dyn LlmProvider>>> {
+    let provider: Option<Arc<dyn LlmProvider>> = match kind {
+        "openai" => {
+            if pconfig.api_key.is_empty() {
+                None
+            } else {
+                let mut p = OpenAiProvider::new(&pconfig.api_key);
+                if !pconfig.base_url.is_empty() {
+                    p = p.with_base_url(&pconfig.base_url);
                 }
-            }
-            "google" => {
-                if pconfig.api_key.is_empty() {
-                    None
-                } else {
-                    let mut p = GoogleProvider::new(&pconfig.api_key);
-                    if !pconfig.base_url.is_empty() {
-                        p = p.with_base_url(&pconfig.base_url);
-                    }
-                    Some(Arc::new(p))
+                if !pconfig.organization.is_empty() {
+                    p = p.with_org_id(&pconfig.organization);
                 }
+                Some(Arc::new(p))
             }
-            "openrouter" => {
-                if pconfig.api_key.is_empty() {
-                    None
-                } else {
-                    let mut p = OpenRouterProvider::new(&pconfig.api_key);
-                    if !pconfig.base_url.is_empty() {
-                        p = p.with_base_url(&pconfig.base_url);
-                    }
-                    if !pconfig.app_name.is_empty() {
-                        p = p.with_title(&pconfig.app_name);
-                    }
-                    if !pconfig.site_url.is_empty() {
-                        p = p.with_referer(&pconfig.site_url);
-                    }
-                    Some(Arc::new(p))
+        }
+        "anthropic" => {
+            if pconfig.api_key.is_empty() {
+                None
+            } else {
+                let mut p = AnthropicProvider::new(&pconfig.api_key);
+                if !pconfig.base_url.is_empty() {
+                    p = p.with_base_url(&pconfig.base_url);
                 }
+                Some(Arc::new(p))
             }
-            "ollama" => {
-                let mut p = OllamaProvider::new();
+        }
+        "google" => {
+            if pconfig.api_key.is_empty() {
+                None
+            } else {
+                let mut p = GoogleProvider::new(&pconfig.api_key);
                 if !pconfig.base_url.is_empty() {
                     p = p.with_base_url(&pconfig.base_url);
                 }
                 Some(Arc::new(p))
             }
-            "llamacpp" => {
-                let mut p = LlamaCppProvider::new();
+        }
+        "openrouter" => {
+            if pconfig.api_key.is_empty() {
+                None
+            } else {
+                let mut p = OpenRouterProvider::new(&pconfig.api_key);
                 if !pconfig.base_url.is_empty() {
                     p = p.with_base_url(&pconfig.base_url);
                 }
+                if !pconfig.app_name.is_empty() {
+                    p = p.with_title(&pconfig.app_name);
+                }
+                if !pconfig.site_url.is_empty() {
+                    p = p.with_referer(&pconfig.site_url);
+                }
                 Some(Arc::new(p))
             }
-            other => {
-                info!(provider = %other, "Unknown provider in config, skipping");
-                None
+        }
+        "ollama" => {
+            let mut p = OllamaProvider::new();
+            if !pconfig.base_url.is_empty() {
+                p = p.with_base_url(&pconfig.base_url);
             }
-        };
+            Some(Arc::new(p))
+        }
+        "llamacpp" => {
+            let mut p = LlamaCppProvider::new();
+            if !pconfig.base_url.is_empty() {
+                p = p.with_base_url(&pconfig.base_url);
+            }
+            Some(Arc::new(p))
+        }
+        "openai_compatible" => Some(Arc::new(build_openai_compatible_provider(name, pconfig)?)),
+        "replay" => Some(Arc::new(build_replay_provider(name, pconfig)?)),
+        other => {
+            info!(provider = %other, "Unknown provider in config, skipping");
+            None
+        }
+    };
+    Ok(provider)
+}
+
+/// `type = "openai_compatible"` のプロバイダー。セクション名で登録するので何個でも並べられる
+fn build_openai_compatible_provider(name: &str, pconfig: &ProviderConfig) -> Result<OpenAiCompatibleProvider> {
+    if pconfig.base_url.is_empty() {
+        anyhow::bail!("Provider '{name}': type = \"openai_compatible\" requires `base_url`");
+    }
+    let auth = match pconfig.auth_scheme.as_str() {
+        "" | "bearer" => AuthScheme::Bearer,
+        "header" if pconfig.auth_header.is_empty() => AuthScheme::Header("api-key".to_string()),
+        "header" => AuthScheme::Header(pconfig.auth_header.clone()),
+        "none" => AuthScheme::None,
+        other => anyhow::bail!("Provider '{name}': unknown auth_scheme '{other}'"),
+    };
+    let mut p = OpenAiCompatibleProvider::new(name, &pconfig.base_url)
+        .with_api_key(&pconfig.api_key)
+        .with_auth(auth)
+        .with_models(pconfig.models.clone())
+        .with_context_window(pconfig.context_window.unwrap_or(0))
+        .with_function_calling(pconfig.supports_function_calling.unwrap_or(true))
+        .with_vision(pconfig.supports_vision.unwrap_or(false));
+    for (header, value) in &pconfig.headers {
+        p = p.with_header(header, value);
+    }
+    Ok(p)
+}
+
+/// `type = "replay"` のプロバイダー。`inner` があればそれを包んで記録する
+fn build_replay_provider(name: &str, pconfig: &ProviderConfig) -> Result<ReplayProvider> {
+    if pconfig.fixture.is_empty() {
+        anyhow::bail!("Provider '{name}': type = \"replay\" requires `fixture`");
+    }
+    let mode = match pconfig.mode.as_str() {
+        "" | "replay" => ReplayMode::Replay,
+        "record" => ReplayMode::Record,
+        other => anyhow::bail!("Provider '{name}': unknown replay mode '{other}'"),
+    };
+    if mode == ReplayMode::Record && pconfig.inner.is_empty() {
+        anyhow::bail!("Provider '{name}': mode = \"record\" requires `inner`");
+    }
+    let mut provider = ReplayProvider::new(name, &pconfig.fixture)?
+        .with_mode(mode)
+        .with_strict(pconfig.strict);
+    if !pconfig.inner.is_empty() {
+        if pconfig.inner == "replay" {
+            anyhow::bail!("Provider '{name}': a replay provider cannot wrap another replay provider");
+        }
+        match build_provider(name, &pconfig.inner, pconfig)? {
+            Some(inner) => provider = provider.with_inner(inner),
+            None => info!(provider = %name, inner = %pconfig.inner, "Replay provider has no usable inner provider"),
+        }
+    }
+    info!(
+        provider = %name,
+        fixture = %pconfig.fixture,
+        recordings = provider.len(),
+        ?mode,
+        "Replay provider configured"
+    );
+    Ok(provider)
+}
 
+pub fn build_llm_router(config: &LlmConfig) -> Result<LlmRouter> {
+    let mut router = LlmRouter::new();
+
+    for (name, pconfig) in &config.providers {
+        let provider = build_provider(name, pconfig.kind(name), pconfig)?;
         if let Some(p) = provider {
             router.add_provider(p);
         }
@@ -285,6 +852,13 @@ pub fn build_llm_router(config: &LlmConfig) -> Result<LlmRouter> {
         router.add_model_mapping(alias, target);
     }
 
+    // Set purpose routes
+    for (purpose, mcfg) in &config.models {
+        router.set_purpose_route(purpose, mcfg.target(), mcfg.fallback.clone());
+    }
+
+    router.set_resilience(config.resilience.to_router_config());
+
     info!(
         providers = ?router.provider_names(),
         "LLM router configured"
@@ -331,6 +905,176 @@ mod tests {
         assert_eq!(config.database.path, "data/opencrab.db");
         assert_eq!(config.gateway.rest.port, 8080);
         assert_eq!(config.llm.default_provider, "openai");
+        assert_eq!(config.llm.embedding_model(), None);
+    }
+
+    #[test]
+    fn test_embedding_model() {
+        let toml_str = r#"
+[llm.models]
+embedding = { provider = "ollama", model = "nomic-embed-text" }
+"#;
+        let config: AppConfig = toml::from_str(toml_str).unwrap();
+        assert_eq!(
+            config.llm.embedding_model().as_deref(),
+            Some("ollama:nomic-embed-text")
+        );
+    }
+
+    #[test]
+    fn test_purpose_routes() {
+        let toml_str = r#"
+[llm]
+default_provider = "ollama"
+
+[llm.providers.ollama]
+
+[llm.models]
+conversation = { provider = "ollama", model = "llama3.1:70b" }
+tool_calling = { provider = "ollama", model = "qwen2.5:7b", fallback = ["local", "ollama:llama3.1:8b"] }
+
+[llm.aliases]
+local = { provider = "ollama", model = "mistral" }
+"#;
+        let config: AppConfig = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.llm.models["tool_calling"].fallback, vec!["local", "ollama:llama3.1:8b"]);
+        assert!(config.llm.models["conversation"].fallback.is_empty());
+
+        let router = build_llm_router(&config.llm).unwrap();
+        assert_eq!(router.purpose_model("conversation"), Some("ollama:llama3.1:70b"));
+        assert_eq!(router.purpose_model("tool_calling"), Some("ollama:qwen2.5:7b"));
+        assert_eq!(router.purpose_model("analysis"), None);
+    }
+
+    #[test]
+    fn test_self_selection_config() {
+        let config: AppConfig = toml::from_str("").unwrap();
+        assert!(config.llm.self_selection.enabled);
+        assert!(config.llm.self_selection.allowed_aliases.is_empty());
+
+        let toml_str = r#"
+[llm.self_selection]
+enabled = false
+allowed_aliases = ["fast", "openai:gpt-4o-mini"]
+"#;
+        let config: AppConfig = toml::from_str(toml_str).unwrap();
+        assert!(!config.llm.self_selection.enabled);
+        assert_eq!(config.llm.self_selection.allowed_aliases, vec!["fast", "openai:gpt-4o-mini"]);
+    }
+
+    #[test]
+    fn test_default_config_file_parses() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../config/default.toml");
+        let config = load_config(path).unwrap();
+        assert_eq!(config.llm.resilience.max_retries, 2);
+        assert_eq!(config.agent.context.max_tokens, 1200);
+        assert_eq!(config.agent.memory.to_policy(), opencrab_core::MemoryPolicy::default());
+    }
+
+    #[test]
+    fn test_memory_config() {
+        let config: AppConfig = toml::from_str("").unwrap();
+        assert_eq!(config.agent.memory.to_policy(), opencrab_core::MemoryPolicy::default());
+
+        let toml_str = r#"
+[agent.memory]
+half_life_days = 7.0
+delete_after_days = 30
+keep_log_types = []
+
+[agent.memory.search]
+vector_weight = 2.0
+min_similarity = 0.5
+"#;
+        let config: AppConfig = toml::from_str(toml_str).unwrap();
+        let policy = config.agent.memory.to_policy();
+        assert_eq!(policy.search.keyword_weight, 1.0);
+        assert_eq!(policy.search.vector_weight, 2.0);
+        assert_eq!(policy.search.min_similarity, 0.5);
+        assert_eq!(policy.half_life_days, 7.0);
+        assert_eq!(policy.archive_after_days, Some(90));
+        assert_eq!(policy.delete_after_days, Some(30));
+        assert!(policy.keep_log_types.is_empty());
+    }
+
+    #[test]
+    fn test_context_config() {
+        let config: AppConfig = toml::from_str("").unwrap();
+        assert_eq!(config.agent.context.to_budget(), opencrab_core::ContextBudget::default());
+
+        let toml_str = r#"
+[agent.context]
+max_tokens = 600
+categories = ["decisions"]
+max_related_logs = 0
+"#;
+        let config: AppConfig = toml::from_str(toml_str).unwrap();
+        let budget = config.agent.context.to_budget();
+        assert_eq!(budget.max_tokens, 600);
+        assert_eq!(budget.categories, vec!["decisions".to_string()]);
+        assert_eq!(budget.max_related_logs, 0);
+        assert_eq!(budget.max_per_category, 5);
+    }
+
+    #[test]
+    fn test_persona_config() {
+        let config: AppConfig = toml::from_str("").unwrap();
+        assert_eq!(config.agent.persona.to_options(), opencrab_core::PersonaOptions::default());
+
+        let toml_str = r#"
+[agent.persona]
+language = "ja"
+temperature_max = 0.9
+
+[agent.persona.wording]
+openness_high = "新しいものが好き"
+"#;
+        let config: AppConfig = toml::from_str(toml_str).unwrap();
+        let options = config.agent.persona.to_options();
+        assert_eq!(options.language, opencrab_core::PersonaLanguage::Japanese);
+        assert_eq!(options.temperature_range, (0.4, 0.9));
+        assert_eq!(options.wording["openness_high"], "新しいものが好き");
+    }
+
+    #[test]
+    fn test_budget_config() {
+        let config: AppConfig = toml::from_str("").unwrap();
+        assert!(!config.llm.budget.is_enabled());
+        assert_eq!(config.llm.budget.warn_ratio, 0.8);
+
+        let toml_str = r#"
+[llm.budget]
+agent_daily_usd = 1.5
+global_monthly_usd = 100
+downgrade_model = "cheap"
+"#;
+        let config: AppConfig = toml::from_str(toml_str).unwrap();
+        let budget = &config.llm.budget;
+        assert!(budget.is_enabled());
+        assert_eq!(budget.agent_daily_usd, Some(1.5));
+        assert_eq!(budget.session_usd, None);
+        assert_eq!(budget.global_monthly_usd, Some(100.0));
+        assert_eq!(budget.downgrade_model.as_deref(), Some("cheap"));
+    }
+
+    #[test]
+    fn test_resilience_config() {
+        let config: AppConfig = toml::from_str("").unwrap();
+        assert_eq!(
+            config.llm.resilience.to_router_config(),
+            opencrab_llm::ResilienceConfig::default()
+        );
+
+        let toml_str = r#"
+[llm.resilience]
+max_retries = 0
+open_secs = 5
+"#;
+        let config: AppConfig = toml::from_str(toml_str).unwrap();
+        let resilience = config.llm.resilience.to_router_config();
+        assert_eq!(resilience.max_retries, 0);
+        assert_eq!(resilience.open_duration, Duration::from_secs(5));
+        assert_eq!(resilience.base_delay, Duration::from_millis(500));
     }
 
     #[test]
@@ -359,4 +1103,97 @@ mod tests {
         let router = build_llm_router(&config).unwrap();
         assert!(router.provider_names().contains(&"openrouter"));
     }
+
+    #[tokio::test]
+    async fn test_build_router_with_replay() {
+        use opencrab_llm::message::{ChatRequest, Message};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let fixture = dir.path().join("openai.json");
+        let request = ChatRequest::new("gpt-4o", vec![Message::user("ping")]);
+        let recorded = serde_json::json!({
+            "entries": {
+                ReplayProvider::chat_key(&request): {
+                    "request": request,
+                    "response": {
+                        "id": "rec-1",
+                        "model": "gpt-4o",
+                        "choices": [{
+                            "index": 0,
+                            "message": {"role": "assistant", "content": "pong"},
+                            "finish_reason": "stop"
+                        }],
+                        "usage": {"prompt_tokens": 1, "completion_tokens": 1, "total_tokens": 2},
+                        "created": 0
+                    }
+                }
+            }
+        });
+        std::fs::write(&fixture, recorded.to_string()).unwrap();
+
+        let toml_str = format!(
+            r#"
+[llm.providers.openai]
+type = "replay"
+fixture = "{}"
+strict = true
+"#,
+            fixture.display()
+        );
+        let config: AppConfig = toml::from_str(&toml_str).unwrap();
+        let router = build_llm_router(&config.llm).unwrap();
+        assert_eq!(router.provider_names(), vec!["openai"]);
+
+        let response = router
+            .chat_completion(ChatRequest::new("openai:gpt-4o", vec![Message::user("ping")]))
+            .await
+            .unwrap();
+        assert_eq!(response.choices[0].message.text_content(), Some("pong"));
+        let miss = ChatRequest::new("openai:gpt-4o", vec![Message::user("other")]);
+        assert!(router.chat_completion(miss).await.is_err());
+
+        let record_without_inner = r#"
+[llm.providers.openai]
+type = "replay"
+fixture = "x.json"
+mode = "record"
+"#;
+        let config: AppConfig = toml::from_str(record_without_inner).unwrap();
+        assert!(build_llm_router(&config.llm).is_err());
+    }
+
+    #[test]
+    fn test_build_router_with_openai_compatible() {
+        let toml_str = r#"
+[llm.providers.vllm]
+type = "openai_compatible"
+base_url = "http://gpu-1:8000/v1"
+models = ["qwen2.5-72b"]
+context_window = 32768
+
+[llm.providers.lmstudio]
+type = "openai_compatible"
+base_url = "http://localhost:1234/v1"
+auth_scheme = "none"
+supports_function_calling = false
+headers = { "X-Client" = "opencrab" }
+
+[llm.providers.broken]
+type = "openai_compatible"
+"#;
+        let config: AppConfig = toml::from_str(toml_str).unwrap();
+        assert!(build_llm_router(&config.llm).is_err());
+
+        let mut llm = config.llm;
+        llm.providers.remove("broken");
+        let router = build_llm_router(&llm).unwrap();
+        let mut names = router.provider_names();
+        names.sort();
+        assert_eq!(names, vec!["lmstudio", "vllm"]);
+        assert!(!router.get_provider("lmstudio").unwrap().supports_function_calling());
+        assert!(router.get_provider("vllm").unwrap().supports_function_calling());
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        assert_eq!(rt.block_on(router.context_window("vllm:qwen2.5-72b")), Some(32768));
+    }
 }
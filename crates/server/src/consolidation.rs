@@ -0,0 +1,317 @@
+//! 記憶の統合（consolidation）。
+//!
+//! チェックポイント以降のセッションログを分析用モデル（`analysis`）で
+//! 事実・決定・好み・未解決の問いに抽出し、既存のキュレーション記憶と重複を除いて
+//! `memory_curated` に保存する（出典のログIDも記録する）。
+//! 統合のあとに `[agent.memory]` の保持ルールで古いセッションログを整理し、結果を実行履歴に残す。
+//! 書き込み時に埋め込めなかった記憶のベクトル索引もここで補う。
+//! API から手動で、または `[agent.consolidation]` の間隔で定期的に実行する。
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use opencrab_core::consolidation::{
+    consolidation_input, parse_extraction, plan_consolidation, CONSOLIDATION_PROMPT,
+};
+use opencrab_core::{ConsolidationAction, ConversationTurn, LlmClient, PruneReport};
+
+use crate::llm_adapter::{LlmRouterAdapter, MetricsContext};
+use crate::process;
+use crate::AppState;
+
+/// 重複として統合された記憶の重要度に加える値
+const MERGE_IMPORTANCE_BOOST: f64 = 0.1;
+
+/// 1回の統合の結果
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsolidationOutcome {
+    /// 記録した実行ID（読むログも整理したログもなかった場合は None）
+    pub run_id: Option<i64>,
+    pub processed_logs: usize,
+    pub from_log_id: i64,
+    pub to_log_id: i64,
+    pub added: usize,
+    pub merged: usize,
+    pub actions: Vec<ConsolidationAction>,
+    /// 保持ルールでアーカイブ・削除したセッションログ（整理しなかった場合は None）
+    pub pruned: Option<PruneReport>,
+}
+
+/// チェックポイント以降のログを1回統合する。
+///
+/// 分析用モデルの呼び出しに失敗した場合や、返答から抽出結果を読み取れなかった場合は
+/// チェックポイントを進めずにエラーを返す。
+/// 同じエージェントの統合（手動・定期）は [`ConsolidationManager`] で順に実行し、同じログを二重に取り込まない。
+pub async fn consolidate_once(
+    state: &AppState,
+    agent_id: &str,
+) -> anyhow::Result<ConsolidationOutcome> {
+    state.consolidation_manager.run(state, agent_id).await
+}
+
+/// エージェントごとの統合を直列化する
+#[derive(Default)]
+pub struct ConsolidationManager {
+    /// 実行中・待機中の統合があるエージェントのロック
+    locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl ConsolidationManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 同じエージェントの統合が実行中なら、終わるのを待ってから統合する
+    async fn run(&self, state: &AppState, agent_id: &str) -> anyhow::Result<ConsolidationOutcome> {
+        let lock = self
+            .locks
+            .lock()
+            .unwrap()
+            .entry(agent_id.to_string())
+            .or_default()
+            .clone();
+        let outcome = {
+            let _guard = lock.lock().await;
+            run_consolidation(state, agent_id).await
+        };
+        // 待っている実行がなければロックを片付ける（表と自分だけが参照している）
+        let mut locks = self.locks.lock().unwrap();
+        if Arc::strong_count(&lock) == 2 {
+            locks.remove(agent_id);
+        }
+        outcome
+    }
+
+    /// ロックを保持しているエージェントの数
+    pub fn active_agents(&self) -> usize {
+        self.locks.lock().unwrap().len()
+    }
+}
+
+async fn run_consolidation(
+    state: &AppState,
+    agent_id: &str,
+) -> anyhow::Result<ConsolidationOutcome> {
+    let (checkpoint, turns) = {
+        let conn = state.db.lock().unwrap();
+        let checkpoint = opencrab_db::queries::get_consolidation_checkpoint(&conn, agent_id)?;
+        let logs = opencrab_db::queries::list_logs_for_consolidation(
+            &conn,
+            agent_id,
+            checkpoint,
+            state.consolidation.max_logs,
+        )?;
+        let mut names: HashMap<String, String> = HashMap::new();
+        let turns: Vec<ConversationTurn> = logs
+            .into_iter()
+            .map(|log| {
+                let speaker = log.speaker_id.unwrap_or(log.agent_id);
+                let speaker_name = names
+                    .entry(speaker.clone())
+                    .or_insert_with(|| {
+                        opencrab_db::queries::get_identity(&conn, &speaker)
+                            .ok()
+                            .flatten()
+                            .map(|i| i.name)
+                            .unwrap_or_else(|| speaker.clone())
+                    })
+                    .clone();
+                ConversationTurn::message(
+                    log.id.unwrap_or_default(),
+                    speaker,
+                    speaker_name,
+                    log.content,
+                )
+            })
+            .collect();
+        (checkpoint, turns)
+    };
+
+    let (Some(first), Some(last)) = (turns.first(), turns.last()) else {
+        // 統合するログがなくても整理と索引付けは行い、整理した場合だけ記録する
+        if let Err(e) = process::index_embeddings(state, agent_id).await {
+            warn!(agent_id = %agent_id, error = %e, "Failed to index memory embeddings");
+        }
+        let pruned = process::prune_memories(state, agent_id);
+        let run_id = match &pruned {
+            Some(report) => {
+                let conn = state.db.lock().unwrap();
+                let run = opencrab_db::queries::ConsolidationRunRow {
+                    id: None,
+                    agent_id: agent_id.to_string(),
+                    from_log_id: checkpoint,
+                    to_log_id: checkpoint,
+                    processed_logs: 0,
+                    added: 0,
+                    merged: 0,
+                    result_json: Some(
+                        serde_json::json!({"actions": [], "pruned": report}).to_string(),
+                    ),
+                    created_at: None,
+                };
+                Some(opencrab_db::queries::insert_consolidation_run(&conn, &run)?)
+            }
+            None => None,
+        };
+        return Ok(ConsolidationOutcome {
+            run_id,
+            processed_logs: 0,
+            from_log_id: checkpoint,
+            to_log_id: checkpoint,
+            added: 0,
+            merged: 0,
+            actions: Vec::new(),
+            pruned,
+        });
+    };
+    let (from_log_id, to_log_id) = (first.log_id, last.log_id);
+
+    // 1. 分析用モデルで抽出（コンテキストウィンドウに収まる塊ごと）
+    let model = process::agent_model(state, agent_id, "analysis");
+    let config = opencrab_core::ContextWindowConfig::for_window(
+        process::context_window_for(state, &model).await,
+    );
+    let budget = config.history_budget(opencrab_core::context::estimate_tokens(
+        CONSOLIDATION_PROMPT,
+    ));
+    let metrics_ctx = MetricsContext {
+        db: state.db.clone(),
+        agent_id: agent_id.to_string(),
+        session_id: None,
+        pricing: state.pricing.clone(),
+        last_metrics_id: Arc::new(Mutex::new(None)),
+        current_purpose: Arc::new(Mutex::new("analysis".to_string())),
+    };
+    let llm = LlmRouterAdapter::new(state.llm_router.clone())
+        .with_metrics(metrics_ctx)
+        .with_budget(crate::budget::BudgetGuard::new(state, agent_id, None));
+
+    let mut extracted = Vec::new();
+    for chunk in opencrab_core::context::summary_chunks(&turns, budget) {
+        let request = opencrab_core::ChatRequestSimple {
+            model: model.clone(),
+            messages: vec![
+                opencrab_core::ChatMessage::new("system", CONSOLIDATION_PROMPT),
+                opencrab_core::ChatMessage::new("user", consolidation_input(chunk)),
+            ],
+            tools: vec![],
+            temperature: Some(0.2),
+            max_tokens: None,
+            purpose: None,
+        };
+        let content = llm.chat(request).await?.content.unwrap_or_default();
+        let parsed = parse_extraction(&content, chunk)
+            .ok_or_else(|| anyhow::anyhow!("Analysis model returned no extraction JSON"))?;
+        extracted.extend(parsed);
+    }
+
+    // 2. 既存の記憶と重複を除いて保存し、保持ルールで整理
+    let existing = opencrab_core::MemoryManager::new(agent_id, state.db.clone())
+        .with_policy(state.memory_policy.clone())
+        .get_curated(None)?;
+    let actions = plan_consolidation(
+        &existing,
+        extracted,
+        state.consolidation.similarity_threshold,
+    );
+    let (mut added, mut merged) = (0, 0);
+    let pruned = process::prune_memories(state, agent_id);
+    let run_id = {
+        let conn = state.db.lock().unwrap();
+        for action in &actions {
+            match action {
+                ConsolidationAction::Insert {
+                    category,
+                    content,
+                    sources,
+                } => {
+                    let memory = opencrab_db::queries::CuratedMemoryRow {
+                        id: uuid::Uuid::new_v4().to_string(),
+                        agent_id: agent_id.to_string(),
+                        category: category.clone(),
+                        content: content.clone(),
+                    };
+                    opencrab_db::queries::upsert_curated_memory(&conn, &memory)?;
+                    opencrab_db::queries::add_curated_memory_sources(&conn, &memory.id, sources)?;
+                    added += 1;
+                }
+                ConsolidationAction::Merge { id, sources, .. } => {
+                    // 繰り返し抽出される内容ほど重要とみなす
+                    opencrab_db::queries::add_curated_memory_sources(&conn, id, sources)?;
+                    opencrab_db::queries::boost_curated_memory_importance(
+                        &conn,
+                        id,
+                        MERGE_IMPORTANCE_BOOST,
+                    )?;
+                    merged += 1;
+                }
+            }
+        }
+
+        let run = opencrab_db::queries::ConsolidationRunRow {
+            id: None,
+            agent_id: agent_id.to_string(),
+            from_log_id,
+            to_log_id,
+            processed_logs: turns.len() as i64,
+            added,
+            merged,
+            result_json: Some(
+                serde_json::json!({"model": model, "actions": actions, "pruned": pruned})
+                    .to_string(),
+            ),
+            created_at: None,
+        };
+        opencrab_db::queries::insert_consolidation_run(&conn, &run)?
+    };
+    // 追加した記憶と、書き込み時に埋め込めなかった記憶を索引に加える
+    if let Err(e) = process::index_embeddings(state, agent_id).await {
+        warn!(agent_id = %agent_id, error = %e, "Failed to index memory embeddings");
+    }
+
+    info!(agent_id = %agent_id, processed = turns.len(), added, merged, "Memories consolidated");
+    Ok(ConsolidationOutcome {
+        run_id: Some(run_id),
+        processed_logs: turns.len(),
+        from_log_id,
+        to_log_id,
+        added: added as usize,
+        merged: merged as usize,
+        actions,
+        pruned,
+    })
+}
+
+/// `interval_secs` ごとに統合を実行するタスクを起動する。
+///
+/// `agent_ids` が空なら登録済みの全エージェントが対象。
+pub fn spawn_schedule(
+    state: AppState,
+    interval_secs: u64,
+    agent_ids: Vec<String>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_secs(interval_secs.max(1)));
+        // 最初のティックは即座に完了するので読み捨てる（起動直後には実行しない）
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let targets = if agent_ids.is_empty() {
+                let conn = state.db.lock().unwrap();
+                opencrab_db::queries::list_agent_ids(&conn).unwrap_or_default()
+            } else {
+                agent_ids.clone()
+            };
+            for agent_id in &targets {
+                if let Err(e) = consolidate_once(&state, agent_id).await {
+                    warn!(agent_id = %agent_id, error = %e, "Memory consolidation failed");
+                }
+            }
+        }
+    })
+}
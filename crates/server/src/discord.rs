@@ -96,7 +96,6 @@ pub async fn run_discord_loop(
 
         // Log the user's message.
         {
-            let conn = state.db.lock().unwrap();
             let mut log_meta = serde_json::json!({
                 "source": "discord",
                 "channel_id": channel_id_str,
@@ -115,7 +114,7 @@ pub async fn run_discord_loop(
                 turn_number: None,
                 metadata_json: Some(log_meta.to_string()),
             };
-            opencrab_db::queries::insert_session_log(&conn, &log).ok();
+            process::record_session_log(&state, &log).ok();
         }
 
         // Skip agent processing if no LLM providers are configured.
@@ -126,15 +125,13 @@ pub async fn run_discord_loop(
 
         // Process with each configured agent.
         for agent_id in &agent_ids {
-            let (system_prompt, agent_name) = {
-                let conn = state.db.lock().unwrap();
-                process::build_agent_context(&conn, agent_id, "Discord conversation")
-            };
-
-            let conversation = {
-                let conn = state.db.lock().unwrap();
-                process::build_conversation_string(&conn, &session_id)
+            let input = process::PromptInput {
+                session_id: Some(&session_id),
+                session_theme: "Discord conversation",
+                gateway: "discord",
+                with_memories: true,
             };
+            let (system_prompt, agent_name) = process::build_system_prompt(&state, agent_id, &input).await;
 
             let result = process::run_agent_response(
                 &state,
@@ -142,7 +139,6 @@ pub async fn run_discord_loop(
                 &agent_name,
                 &session_id,
                 &system_prompt,
-                &conversation,
                 "discord",
                 Some(gateway_admin.clone()),
             )
@@ -175,7 +171,6 @@ pub async fn run_discord_loop(
                     }
 
                     // Log agent response to DB.
-                    let conn = state.db.lock().unwrap();
                     let log = opencrab_db::queries::SessionLogRow {
                         id: None,
                         agent_id: agent_id.clone(),
@@ -193,7 +188,7 @@ pub async fn run_discord_loop(
                             .to_string(),
                         ),
                     };
-                    opencrab_db::queries::insert_session_log(&conn, &log).ok();
+                    process::record_session_log(&state, &log).ok();
                 }
                 Ok(_) => debug!(agent_id = %agent_id, "Agent produced empty response"),
                 Err(e) => error!(agent_id = %agent_id, error = %e, "SkillEngine failed"),
@@ -98,6 +98,21 @@ impl DiscordGatewayManager {
             .unwrap_or(false)
     }
 
+    /// Send a message to a channel through the agent's own gateway.
+    ///
+    /// Returns `false` if the agent has no running gateway.
+    pub async fn send_to_channel(&self, agent_id: &str, channel_id: u64, text: &str) -> anyhow::Result<bool> {
+        let gateway = {
+            let gateways = self.gateways.read().await;
+            match gateways.get(agent_id) {
+                Some(entry) if !entry.handle.is_finished() => entry.gateway.clone(),
+                _ => return Ok(false),
+            }
+        };
+        gateway.send_to_channel(channel_id, text).await?;
+        Ok(true)
+    }
+
     /// Restore all enabled agent Discord configs from DB and start their gateways.
     pub async fn restore_from_db(&self) {
         let configs = {
@@ -0,0 +1,453 @@
+//! エージェントごとのハートビート監視。
+//!
+//! 各ティックで SkillEngine 経由でLLMに「発言する / 学習する / 何もしない」を
+//! 判断させ、その結果を実行して `heartbeat_log` に記録する。
+//! 判断には参加中のセッション（`active` / `running`）のうち最も新しいものの文脈を使い、
+//! 発言はそのセッションに記録する（Discordのセッションならチャンネルにも送る）。
+//! あわせて `[agent.memory]` の保持ルールで古いセッションログを整理する。
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::Serialize;
+use tokio::sync::{watch, RwLock};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use opencrab_core::heartbeat::{run_heartbeat, HeartbeatConfig, HeartbeatDecision, HeartbeatHandler};
+
+use crate::process::{self, AgentRun};
+use crate::AppState;
+
+/// 判断用のプロンプト（ツールなしで1回だけ問い合わせる）
+const DECISION_PROMPT: &str = "\
+This is a periodic heartbeat. Decide what you want to do right now.\n\
+Options:\n\
+- speak: post a message to the discussions you are participating in\n\
+- learn: reflect on recent activity and record what you learned\n\
+- idle: do nothing this time\n\
+\n\
+Reply with a single JSON object only, for example:\n\
+{\"decision\": \"speak\", \"message\": \"...\", \"reason\": \"...\"}\n\
+{\"decision\": \"learn\", \"reason\": \"...\"}\n\
+{\"decision\": \"idle\", \"reason\": \"...\"}";
+
+/// 学習（振り返り）時のプロンプト
+const LEARN_PROMPT: &str = "\
+Reflect on your recent activity below and call reflect_and_learn with your \
+reflection, insights and action items. Then reply with a one-line summary.";
+
+/// 1ティックの実行結果
+#[derive(Debug, Clone, Serialize)]
+pub struct HeartbeatOutcome {
+    pub decision: HeartbeatDecision,
+    pub reason: Option<String>,
+    pub result: serde_json::Value,
+}
+
+/// ハートビートを1回実行する（判断 → 実行 → ログ記録）。
+pub async fn tick_once(state: &AppState, agent_id: &str) -> anyhow::Result<HeartbeatOutcome> {
+    // 判断の文脈にするセッション（最も新しいもの）。発言もこのセッションにだけ届ける
+    let session = {
+        let conn = state.db.lock().unwrap();
+        opencrab_db::queries::list_active_sessions_for_agent(&conn, agent_id)?
+    }
+    .into_iter()
+    .next();
+    let session_id = session.as_ref().map(|s| s.id.as_str());
+    let input = process::PromptInput {
+        session_id,
+        session_theme: session
+            .as_ref()
+            .map(|s| s.theme.as_str())
+            .unwrap_or("(no active discussion)"),
+        gateway: "heartbeat",
+        with_memories: true,
+    };
+    let (system_prompt, agent_name) = process::build_system_prompt(state, agent_id, &input).await;
+    let recent = match &session {
+        Some(s) => process::build_conversation(state, agent_id, &s.id, &system_prompt).await,
+        None => "No active discussions.".to_string(),
+    };
+
+    // 1. 判断（ツールなし）
+    let decision_input = format!("{}\n\nRecent conversation:\n{}", DECISION_PROMPT, recent);
+    let decision_run = AgentRun {
+        session_id,
+        gateway: "heartbeat",
+        allowed_actions: Some(Vec::new()),
+        purpose: "thinking",
+        max_iterations: 1,
+        ..AgentRun::new(agent_id, &agent_name, &system_prompt, &decision_input)
+    };
+    let raw = process::run_agent(state, decision_run).await?.response;
+    let (decision, reason) = parse_decision(&raw);
+
+    // 2. 実行
+    let mut result = match &decision {
+        HeartbeatDecision::Speak(message) => deliver_speech(state, agent_id, session.as_ref(), message).await?,
+        HeartbeatDecision::Learn => {
+            let learn_input = format!("{}\n\nRecent conversation:\n{}", LEARN_PROMPT, recent);
+            let learn_run = AgentRun {
+                session_id,
+                gateway: "heartbeat",
+                allowed_actions: Some(vec!["reflect_and_learn".to_string()]),
+                purpose: "analysis",
+                max_iterations: 3,
+                ..AgentRun::new(agent_id, &agent_name, &system_prompt, &learn_input)
+            };
+            let learned = process::run_agent(state, learn_run).await?;
+            serde_json::json!({
+                "summary": learned.response,
+                "tool_calls_made": learned.tool_calls_made,
+            })
+        }
+        HeartbeatDecision::Idle => serde_json::json!({}),
+    };
+
+    // 3. 保持ルールで古いセッションログを整理（整理した場合だけ結果に含める）
+    if let Some(report) = process::prune_memories(state, agent_id) {
+        result["pruned"] = serde_json::json!(report);
+    }
+
+    let outcome = HeartbeatOutcome {
+        decision,
+        reason,
+        result,
+    };
+    log_outcome(state, agent_id, &outcome);
+    Ok(outcome)
+}
+
+/// 発言を判断の文脈にしたセッションに記録し、Discordのセッションならチャンネルにも送る。
+///
+/// 届け先がなければ `delivered: false` として結果に残す（発言は破棄される）。
+async fn deliver_speech(
+    state: &AppState,
+    agent_id: &str,
+    session: Option<&opencrab_db::queries::SessionRow>,
+    message: &str,
+) -> anyhow::Result<serde_json::Value> {
+    let mut posted = Vec::new();
+    if let Some(session) = session {
+        let log = opencrab_db::queries::SessionLogRow {
+            id: None,
+            agent_id: agent_id.to_string(),
+            session_id: session.id.clone(),
+            log_type: "speech".to_string(),
+            content: message.to_string(),
+            speaker_id: Some(agent_id.to_string()),
+            turn_number: None,
+            metadata_json: Some(serde_json::json!({"source": "heartbeat"}).to_string()),
+        };
+        process::record_session_log(state, &log)?;
+        posted.push(session.id.clone());
+    }
+
+    #[cfg(feature = "discord")]
+    let channels = deliver_to_discord(state, agent_id, session, message).await;
+    #[cfg(not(feature = "discord"))]
+    let channels: Vec<String> = Vec::new();
+
+    let delivered = !posted.is_empty();
+    if !delivered {
+        warn!(agent_id = %agent_id, "Heartbeat speech has no session or gateway to go to");
+    }
+    Ok(serde_json::json!({
+        "message": message,
+        "delivered": delivered,
+        "sessions": posted,
+        "discord_channels": channels,
+    }))
+}
+
+/// Discordチャンネルのセッションには、エージェントのDiscordゲートウェイから発言を送る。
+///
+/// 返り値: 送信できたチャンネルID
+#[cfg(feature = "discord")]
+async fn deliver_to_discord(
+    state: &AppState,
+    agent_id: &str,
+    session: Option<&opencrab_db::queries::SessionRow>,
+    message: &str,
+) -> Vec<String> {
+    let (Some(manager), Some(session)) = (state.discord_manager.as_ref(), session) else {
+        return Vec::new();
+    };
+    let metadata: serde_json::Value = session
+        .metadata_json
+        .as_deref()
+        .and_then(|m| serde_json::from_str(m).ok())
+        .unwrap_or_default();
+    if metadata["source"] != "discord" {
+        return Vec::new();
+    }
+    let Some(channel_id) = metadata["channel_id"].as_str() else {
+        return Vec::new();
+    };
+    let Ok(channel) = channel_id.parse::<u64>() else {
+        return Vec::new();
+    };
+    // DMはフィルタリング対象外
+    let writable = metadata["is_dm"].as_bool().unwrap_or(false) || {
+        let conn = state.db.lock().unwrap();
+        opencrab_db::queries::is_channel_writable(&conn, channel_id)
+    };
+    if !writable {
+        return Vec::new();
+    }
+    match manager.send_to_channel(agent_id, channel, message).await {
+        Ok(true) => vec![channel_id.to_string()],
+        Ok(false) => Vec::new(),
+        Err(e) => {
+            warn!(agent_id = %agent_id, channel = %channel_id, error = %e, "Failed to send heartbeat speech to Discord");
+            Vec::new()
+        }
+    }
+}
+
+fn log_outcome(state: &AppState, agent_id: &str, outcome: &HeartbeatOutcome) {
+    let result_json = serde_json::json!({
+        "reason": outcome.reason,
+        "result": outcome.result,
+    })
+    .to_string();
+    let conn = state.db.lock().unwrap();
+    if let Err(e) = opencrab_db::queries::insert_heartbeat_log(
+        &conn,
+        agent_id,
+        outcome.decision.kind(),
+        Some(&result_json),
+    ) {
+        warn!(agent_id = %agent_id, error = %e, "Failed to record heartbeat log");
+    }
+}
+
+/// LLMの応答から判断を取り出す。
+///
+/// JSONオブジェクトを優先し、解釈できない場合は先頭のキーワードで判定する。
+/// どちらにも当てはまらなければ Idle とする。
+pub fn parse_decision(raw: &str) -> (HeartbeatDecision, Option<String>) {
+    let json = raw
+        .find('{')
+        .zip(raw.rfind('}'))
+        .filter(|(start, end)| start < end)
+        .and_then(|(start, end)| serde_json::from_str::<serde_json::Value>(&raw[start..=end]).ok());
+
+    if let Some(v) = json {
+        let reason = v["reason"].as_str().map(String::from);
+        let decision = match v["decision"].as_str().map(|d| d.trim().to_lowercase()).as_deref() {
+            Some("speak") => match v["message"].as_str().map(str::trim) {
+                Some(msg) if !msg.is_empty() => HeartbeatDecision::Speak(msg.to_string()),
+                _ => HeartbeatDecision::Idle,
+            },
+            Some("learn") => HeartbeatDecision::Learn,
+            _ => HeartbeatDecision::Idle,
+        };
+        return (decision, reason);
+    }
+
+    let trimmed = raw.trim();
+    let has_prefix = |prefix: &str| {
+        trimmed
+            .get(..prefix.len())
+            .is_some_and(|p| p.eq_ignore_ascii_case(prefix))
+    };
+    if has_prefix("speak:") {
+        let msg = trimmed["speak:".len()..].trim();
+        if !msg.is_empty() {
+            return (HeartbeatDecision::Speak(msg.to_string()), None);
+        }
+    }
+    if has_prefix("learn") {
+        return (HeartbeatDecision::Learn, None);
+    }
+    (HeartbeatDecision::Idle, None)
+}
+
+/// `run_heartbeat` に渡すハンドラ
+struct AgentHeartbeat {
+    state: AppState,
+    ticks: Arc<AtomicU64>,
+}
+
+#[async_trait]
+impl HeartbeatHandler for AgentHeartbeat {
+    async fn on_tick(&self, agent_id: &str, _tick: u64) -> HeartbeatDecision {
+        self.ticks.fetch_add(1, Ordering::Relaxed);
+        match tick_once(&self.state, agent_id).await {
+            Ok(outcome) => outcome.decision,
+            Err(e) => {
+                warn!(agent_id = %agent_id, error = %e, "Heartbeat tick failed");
+                let outcome = HeartbeatOutcome {
+                    decision: HeartbeatDecision::Idle,
+                    reason: None,
+                    result: serde_json::json!({"error": e.to_string()}),
+                };
+                log_outcome(&self.state, agent_id, &outcome);
+                outcome.decision
+            }
+        }
+    }
+}
+
+struct HeartbeatEntry {
+    shutdown_tx: watch::Sender<bool>,
+    handle: JoinHandle<()>,
+    interval_secs: u64,
+    started_at: String,
+    ticks: Arc<AtomicU64>,
+}
+
+/// ハートビートの稼働状況
+#[derive(Debug, Clone, Serialize)]
+pub struct HeartbeatStatus {
+    pub agent_id: String,
+    pub running: bool,
+    pub interval_secs: Option<u64>,
+    pub started_at: Option<String>,
+    pub ticks: u64,
+}
+
+/// エージェントごとのハートビートループを管理する
+pub struct HeartbeatManager {
+    default_interval_secs: u64,
+    entries: RwLock<HashMap<String, HeartbeatEntry>>,
+}
+
+impl HeartbeatManager {
+    pub fn new(default_interval_secs: u64) -> Self {
+        Self {
+            default_interval_secs,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// ハートビートを開始する（既に動いていれば再起動する）
+    pub async fn start(
+        &self,
+        state: AppState,
+        agent_id: &str,
+        interval_secs: Option<u64>,
+    ) -> HeartbeatStatus {
+        self.stop(agent_id).await;
+
+        let interval_secs = interval_secs
+            .filter(|s| *s > 0)
+            .unwrap_or(self.default_interval_secs);
+        let config = HeartbeatConfig {
+            interval_secs,
+            enabled: true,
+        };
+        let ticks = Arc::new(AtomicU64::new(0));
+        let handler = Arc::new(AgentHeartbeat {
+            state,
+            ticks: ticks.clone(),
+        });
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let handle = tokio::spawn(run_heartbeat(
+            agent_id.to_string(),
+            config,
+            handler,
+            shutdown_rx,
+        ));
+
+        let entry = HeartbeatEntry {
+            shutdown_tx,
+            handle,
+            interval_secs,
+            started_at: chrono::Utc::now().to_rfc3339(),
+            ticks,
+        };
+        let status = Self::entry_status(agent_id, &entry);
+        self.entries.write().await.insert(agent_id.to_string(), entry);
+
+        info!(agent_id = %agent_id, interval_secs, "Heartbeat started");
+        status
+    }
+
+    /// ハートビートを停止する。停止した場合 true を返す。
+    pub async fn stop(&self, agent_id: &str) -> bool {
+        let entry = self.entries.write().await.remove(agent_id);
+        match entry {
+            Some(entry) => {
+                let _ = entry.shutdown_tx.send(true);
+                entry.handle.abort();
+                info!(agent_id = %agent_id, "Heartbeat stopped");
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 稼働状況を取得する
+    pub async fn status(&self, agent_id: &str) -> HeartbeatStatus {
+        let entries = self.entries.read().await;
+        match entries.get(agent_id) {
+            Some(entry) => Self::entry_status(agent_id, entry),
+            None => HeartbeatStatus {
+                agent_id: agent_id.to_string(),
+                running: false,
+                interval_secs: None,
+                started_at: None,
+                ticks: 0,
+            },
+        }
+    }
+
+    /// 全ハートビートを停止する
+    pub async fn shutdown_all(&self) {
+        let ids: Vec<String> = self.entries.read().await.keys().cloned().collect();
+        for id in ids {
+            self.stop(&id).await;
+        }
+    }
+
+    fn entry_status(agent_id: &str, entry: &HeartbeatEntry) -> HeartbeatStatus {
+        HeartbeatStatus {
+            agent_id: agent_id.to_string(),
+            running: !entry.handle.is_finished(),
+            interval_secs: Some(entry.interval_secs),
+            started_at: Some(entry.started_at.clone()),
+            ticks: entry.ticks.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_decision_json() {
+        let (d, reason) = parse_decision(
+            "Sure:\n{\"decision\": \"speak\", \"message\": \"Hello!\", \"reason\": \"quiet\"}",
+        );
+        assert!(matches!(d, HeartbeatDecision::Speak(ref m) if m == "Hello!"));
+        assert_eq!(reason.as_deref(), Some("quiet"));
+
+        let (d, _) = parse_decision(r#"{"decision": "LEARN"}"#);
+        assert!(matches!(d, HeartbeatDecision::Learn));
+
+        // 発言内容が空なら Idle
+        let (d, _) = parse_decision(r#"{"decision": "speak", "message": "  "}"#);
+        assert!(matches!(d, HeartbeatDecision::Idle));
+    }
+
+    #[test]
+    fn test_parse_decision_keywords() {
+        let (d, _) = parse_decision("SPEAK: こんにちは");
+        assert!(matches!(d, HeartbeatDecision::Speak(ref m) if m == "こんにちは"));
+
+        let (d, _) = parse_decision("learn");
+        assert!(matches!(d, HeartbeatDecision::Learn));
+
+        let (d, _) = parse_decision("I'd rather stay quiet.");
+        assert!(matches!(d, HeartbeatDecision::Idle));
+
+        let (d, _) = parse_decision("話");
+        assert!(matches!(d, HeartbeatDecision::Idle));
+    }
+}
@@ -8,9 +8,17 @@ use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
 
 pub mod api;
+pub mod budget;
 pub mod config;
+pub mod consolidation;
+pub mod heartbeat;
 pub mod llm_adapter;
+pub mod orchestrator;
+pub mod pricing;
 pub mod process;
+pub mod session_runner;
+pub mod skill_sync;
+pub mod ws;
 
 #[cfg(feature = "discord")]
 pub mod discord;
@@ -29,6 +37,33 @@ pub struct AppState {
     pub llm_router: Arc<LlmRouter>,
     pub workspace_base: String,
     pub default_model: String,
+    /// 記憶のベクトル検索に使う埋め込みモデル（`provider:model`）。None ならキーワード検索のみ
+    pub embedding_model: Option<String>,
+    /// select_llm で選べるモデルの制限（`[llm.self_selection]`）
+    pub self_selection: config::SelfSelectionConfig,
+    /// LLM利用料の上限（`[llm.budget]`）
+    pub budget: config::BudgetConfig,
+    /// モデル料金表（組み込み + `model_pricing` テーブル）
+    pub pricing: pricing::SharedPricing,
+    /// 標準スキルファイル（*.skill.md）のディレクトリ
+    pub skills_dir: String,
+    /// スキルに関係なく常に公開するベースアクション
+    pub base_actions: Vec<String>,
+    /// システムプロンプトに注入する記憶のトークン予算（`[agent.context]`）
+    pub context_budget: opencrab_core::ContextBudget,
+    /// Soul の文章化と temperature・発言頻度への反映（`[agent.persona]`）
+    pub persona: opencrab_core::PersonaOptions,
+    /// セッションログからキュレーション記憶への統合（`[agent.consolidation]`）
+    pub consolidation: opencrab_core::ConsolidationOptions,
+    /// 記憶の順位付けと保持ルール（`[agent.memory]`）
+    pub memory_policy: opencrab_core::MemoryPolicy,
+    pub heartbeat: Arc<heartbeat::HeartbeatManager>,
+    /// セッションの自律実行ループ
+    pub session_runner: Arc<session_runner::SessionRunnerManager>,
+    /// エージェントごとの記憶の統合（同じエージェントの統合を直列化する）
+    pub consolidation_manager: Arc<consolidation::ConsolidationManager>,
+    /// `/ws` の接続・購読を管理するWebSocketゲートウェイ
+    pub ws_gateway: Arc<opencrab_gateway::WebSocketGateway>,
     #[cfg(feature = "discord")]
     pub discord_manager: Option<Arc<discord_manager::DiscordGatewayManager>>,
 }
@@ -36,6 +71,8 @@ pub struct AppState {
 pub fn create_router(state: AppState) -> Router {
     Router::new()
         .route("/health", get(health_check))
+        // WebSocket（フレームプロトコルは opencrab_gateway::ServerFrame / ClientFrame）
+        .route("/ws", get(ws::ws_handler))
         // エージェント管理
         .route("/api/agents", get(api::agents::list_agents).post(api::agents::create_agent))
         .route("/api/agents/{id}", get(api::agents::get_agent).delete(api::agents::delete_agent))
@@ -45,21 +82,71 @@ pub fn create_router(state: AppState) -> Router {
         .route("/api/agents/{id}/soul/presets", get(api::agents::list_soul_presets).post(api::agents::create_soul_preset))
         .route("/api/agents/{id}/soul/presets/{preset_id}", axum::routing::delete(api::agents::delete_soul_preset))
         .route("/api/agents/{id}/soul/presets/{preset_id}/apply", post(api::agents::apply_soul_preset))
+        // LLM設定（用途別モデル）
+        .route(
+            "/api/agents/{id}/llm",
+            get(api::agents::get_llm_config)
+                .put(api::agents::update_llm_config)
+                .delete(api::agents::delete_llm_config),
+        )
         // スキル管理
         .route("/api/agents/{id}/skills", get(api::skills::list_skills).post(api::skills::add_skill))
+        .route("/api/agents/{id}/skills/reload", post(api::skills::reload_skills))
+        .route("/api/agents/{id}/tools", get(api::skills::list_agent_tools))
         .route("/api/agents/{id}/skills/{skill_id}/toggle", post(api::skills::toggle_skill))
+        // ハートビート
+        .route("/api/agents/{id}/heartbeat", get(api::heartbeat::get_heartbeat_status))
+        .route("/api/agents/{id}/heartbeat/start", post(api::heartbeat::start_heartbeat))
+        .route("/api/agents/{id}/heartbeat/stop", post(api::heartbeat::stop_heartbeat))
+        .route("/api/agents/{id}/heartbeat/tick", post(api::heartbeat::tick_heartbeat))
         // 記憶管理
         .route("/api/agents/{id}/memory/curated", get(api::memory::list_curated_memory))
         .route("/api/agents/{id}/memory/search", post(api::memory::search_memory))
+        .route("/api/agents/{id}/memory/context", get(api::memory::preview_memory_context))
+        .route(
+            "/api/agents/{id}/memory/consolidate",
+            get(api::memory::list_consolidation_runs).post(api::memory::consolidate_memory),
+        )
+        // システムプロンプトのテンプレート
+        .route(
+            "/api/agents/{id}/prompt",
+            get(api::prompt::get_prompt)
+                .put(api::prompt::update_prompt)
+                .delete(api::prompt::delete_prompt),
+        )
+        .route("/api/agents/{id}/prompt/rollback", post(api::prompt::rollback_prompt))
+        .route("/api/agents/{id}/prompt/preview", post(api::prompt::preview_prompt))
+        .route(
+            "/api/prompt",
+            get(api::prompt::get_global_prompt)
+                .put(api::prompt::update_global_prompt)
+                .delete(api::prompt::delete_global_prompt),
+        )
+        .route("/api/prompt/rollback", post(api::prompt::rollback_global_prompt))
         // セッション管理
         .route("/api/sessions", get(api::sessions::list_sessions).post(api::sessions::create_session))
         .route("/api/sessions/{id}", get(api::sessions::get_session))
         .route("/api/sessions/{id}/messages", post(api::sessions::send_message))
         .route("/api/sessions/{id}/logs", get(api::sessions::list_session_logs))
         .route("/api/sessions/{id}/mentor", post(api::sessions::send_mentor_instruction))
+        .route("/api/sessions/{id}/turn", post(api::sessions::advance_turn))
+        .route("/api/sessions/{id}/run", get(api::sessions::get_run_status).post(api::sessions::run_session))
+        .route("/api/sessions/{id}/pause", post(api::sessions::pause_session))
+        .route("/api/sessions/{id}/stop", post(api::sessions::stop_session))
         // アナリティクス
         .route("/api/agents/{id}/analytics", get(api::analytics::get_metrics_summary))
         .route("/api/agents/{id}/analytics/detail", get(api::analytics::get_metrics_detail))
+        // LLMプロバイダーの状態
+        .route("/api/llm/health", get(api::llm::get_llm_health))
+        // モデル料金表
+        .route("/api/pricing", get(api::pricing::list_pricing))
+        .route("/api/pricing/import", post(api::pricing::import_pricing))
+        .route(
+            "/api/pricing/{provider}/{*model}",
+            get(api::pricing::get_pricing)
+                .put(api::pricing::update_pricing)
+                .delete(api::pricing::delete_pricing),
+        )
         // ワークスペース管理
         .route("/api/agents/{id}/workspace", get(api::workspace::list_workspace))
         .route("/api/agents/{id}/workspace/{*path}", get(api::workspace::read_file).put(api::workspace::write_file))
@@ -4,23 +4,27 @@ use anyhow::Result;
 use async_trait::async_trait;
 use chrono::Utc;
 
+use futures::StreamExt;
 use opencrab_core::{
-    ChatMessage, ChatRequestSimple, ChatResponseSimple, LlmClient, ToolCall as CoreToolCall,
-    ToolDefinition, UsageInfo,
+    ChatMessage, ChatRequestSimple, ChatResponseSimple, ContextOverflow, Embedder, LlmClient,
+    TextDeltaCallback, ToolCall as CoreToolCall, ToolDefinition, UsageInfo,
 };
 use opencrab_llm::message::{
-    ChatRequest, Choice, FinishReason, FunctionCall, FunctionDefinition, Message,
+    ChatRequest, Choice, EmbeddingRequest, FinishReason, FunctionCall, FunctionDefinition, Message,
     MessageContent, Role, ToolCall as LlmToolCall, Usage,
 };
-use opencrab_llm::pricing::PricingRegistry;
 use opencrab_llm::router::LlmRouter;
+use opencrab_llm::{LlmError, StreamAccumulator};
+
+use crate::budget::BudgetGuard;
+use crate::pricing::SharedPricing;
 
 /// Configuration for metrics recording.
 pub struct MetricsContext {
     pub db: Arc<Mutex<rusqlite::Connection>>,
     pub agent_id: String,
     pub session_id: Option<String>,
-    pub pricing: PricingRegistry,
+    pub pricing: SharedPricing,
     /// Shared state: updated after each LLM call so actions can reference it.
     pub last_metrics_id: Arc<Mutex<Option<String>>>,
     /// Shared current purpose: actions (e.g. select_llm) can update this
@@ -31,10 +35,12 @@ pub struct MetricsContext {
 /// Adapter that wraps an `LlmRouter` and implements `LlmClient`
 /// so that `SkillEngine` can use it directly.
 ///
-/// Optionally records usage metrics to the DB after each call.
+/// Optionally records usage metrics to the DB after each call, and checks
+/// cost budgets before each call.
 pub struct LlmRouterAdapter {
     router: Arc<LlmRouter>,
     metrics_ctx: Option<MetricsContext>,
+    budget: Option<BudgetGuard>,
 }
 
 impl LlmRouterAdapter {
@@ -42,6 +48,7 @@ impl LlmRouterAdapter {
         Self {
             router,
             metrics_ctx: None,
+            budget: None,
         }
     }
 
@@ -49,75 +56,250 @@ impl LlmRouterAdapter {
         self.metrics_ctx = Some(ctx);
         self
     }
+
+    /// Check budgets before each call (downgrading or refusing when over).
+    pub fn with_budget(mut self, budget: Option<BudgetGuard>) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Apply the budget check to the requested model.
+    fn budgeted_model(&self, model: String) -> Result<String> {
+        match &self.budget {
+            Some(budget) => budget.check(&model),
+            None => Ok(model),
+        }
+    }
+
+    fn after_call(&self, cost_usd: f64) {
+        if let Some(budget) = &self.budget {
+            budget.after_call(cost_usd);
+        }
+    }
+}
+
+impl LlmRouterAdapter {
+    /// Record usage metrics to the DB, if a metrics context is configured.
+    /// Returns the estimated cost of the call.
+    fn record_metrics(
+        &self,
+        model_requested: &str,
+        purpose: &str,
+        usage: Option<&UsageInfo>,
+        latency_ms: i64,
+        time_to_first_token_ms: Option<i64>,
+    ) -> f64 {
+        let Some(ref ctx) = self.metrics_ctx else {
+            return 0.0;
+        };
+        let metrics_id = uuid::Uuid::new_v4().to_string();
+
+        // Resolve provider and model from the alias.
+        let (provider, model) = self
+            .router
+            .resolve_model(model_requested)
+            .unwrap_or_else(|_| ("unknown".to_string(), model_requested.to_string()));
+
+        let (input_tokens, output_tokens, total_tokens) = usage
+            .map(|u| {
+                (
+                    u.prompt_tokens as i32,
+                    u.completion_tokens as i32,
+                    u.total_tokens as i32,
+                )
+            })
+            .unwrap_or((0, 0, 0));
+
+        let estimated_cost = ctx
+            .pricing
+            .read()
+            .unwrap()
+            .calculate_cost(&provider, &model, input_tokens as u32, output_tokens as u32)
+            .unwrap_or(0.0);
+
+        let row = opencrab_db::queries::LlmMetricsRow {
+            id: metrics_id.clone(),
+            agent_id: ctx.agent_id.clone(),
+            session_id: ctx.session_id.clone(),
+            timestamp: Utc::now().to_rfc3339(),
+            provider,
+            model,
+            purpose: purpose.to_string(),
+            task_type: None,
+            complexity: None,
+            input_tokens,
+            output_tokens,
+            total_tokens,
+            estimated_cost_usd: estimated_cost,
+            latency_ms,
+            time_to_first_token_ms,
+        };
+
+        if let Ok(conn) = ctx.db.lock() {
+            if let Err(e) = opencrab_db::queries::insert_llm_metrics(&conn, &row) {
+                tracing::warn!(error = %e, "Failed to record LLM metrics");
+            }
+        }
+
+        // Update shared last_metrics_id so actions can reference it.
+        if let Ok(mut id) = ctx.last_metrics_id.lock() {
+            *id = Some(metrics_id);
+        }
+
+        estimated_cost
+    }
+
+    /// The request's own purpose, else the run's current purpose.
+    fn purpose_of(&self, request: &ChatRequestSimple) -> String {
+        request
+            .purpose
+            .clone()
+            .or_else(|| {
+                self.metrics_ctx
+                    .as_ref()
+                    .and_then(|ctx| ctx.current_purpose.lock().ok().map(|p| p.clone()))
+            })
+            .unwrap_or_else(|| "conversation".to_string())
+    }
 }
 
 #[async_trait]
 impl LlmClient for LlmRouterAdapter {
-    async fn chat(&self, request: ChatRequestSimple) -> Result<ChatResponseSimple> {
+    async fn chat(&self, mut request: ChatRequestSimple) -> Result<ChatResponseSimple> {
+        request.model = self.budgeted_model(request.model)?;
         let model_requested = request.model.clone();
-        let llm_request = to_llm_request(request);
+        let purpose = self.purpose_of(&request);
+        let mut llm_request = to_llm_request(request);
+        llm_request.purpose = Some(purpose.clone());
 
         let start = std::time::Instant::now();
-        let llm_response = self.router.chat_completion(llm_request).await?;
+        let llm_response = self
+            .router
+            .chat_completion(llm_request)
+            .await
+            .map_err(to_engine_error)?;
         let latency_ms = start.elapsed().as_millis() as i64;
 
         let response = from_llm_response(llm_response);
+        let cost = self.record_metrics(
+            &model_requested,
+            &purpose,
+            response.usage.as_ref(),
+            latency_ms,
+            None,
+        );
+        self.after_call(cost);
 
-        // Record metrics to DB if context is available.
-        if let Some(ref ctx) = self.metrics_ctx {
-            let metrics_id = uuid::Uuid::new_v4().to_string();
-
-            // Resolve provider and model from the alias.
-            let (provider, model) = self
-                .router
-                .resolve_model(&model_requested)
-                .unwrap_or_else(|_| ("unknown".to_string(), model_requested.clone()));
-
-            let (input_tokens, output_tokens, total_tokens) = response
-                .usage
-                .as_ref()
-                .map(|u| (u.prompt_tokens as i32, u.completion_tokens as i32, u.total_tokens as i32))
-                .unwrap_or((0, 0, 0));
-
-            let estimated_cost = ctx
-                .pricing
-                .calculate_cost(&provider, &model, input_tokens as u32, output_tokens as u32)
-                .unwrap_or(0.0);
-
-            let row = opencrab_db::queries::LlmMetricsRow {
-                id: metrics_id.clone(),
-                agent_id: ctx.agent_id.clone(),
-                session_id: ctx.session_id.clone(),
-                timestamp: Utc::now().to_rfc3339(),
-                provider,
-                model,
-                purpose: ctx.current_purpose.lock().map(|p| p.clone()).unwrap_or_else(|_| "conversation".to_string()),
-                task_type: None,
-                complexity: None,
-                input_tokens,
-                output_tokens,
-                total_tokens,
-                estimated_cost_usd: estimated_cost,
-                latency_ms,
-                time_to_first_token_ms: None,
-            };
-
-            if let Ok(conn) = ctx.db.lock() {
-                if let Err(e) = opencrab_db::queries::insert_llm_metrics(&conn, &row) {
-                    tracing::warn!(error = %e, "Failed to record LLM metrics");
-                }
-            }
+        Ok(response)
+    }
+
+    async fn chat_streaming(
+        &self,
+        mut request: ChatRequestSimple,
+        on_delta: &TextDeltaCallback<'_>,
+    ) -> Result<ChatResponseSimple> {
+        request.model = self.budgeted_model(request.model)?;
+        let model_requested = request.model.clone();
+        let purpose = self.purpose_of(&request);
+        let mut llm_request = to_llm_request(request);
+        llm_request.stream = Some(true);
+        llm_request.purpose = Some(purpose.clone());
 
-            // Update shared last_metrics_id so actions can reference it.
-            if let Ok(mut id) = ctx.last_metrics_id.lock() {
-                *id = Some(metrics_id);
+        let start = std::time::Instant::now();
+        let mut stream = self
+            .router
+            .chat_completion_stream(llm_request)
+            .await
+            .map_err(to_engine_error)?;
+
+        let mut collected = StreamAccumulator::default();
+        let mut first_token_ms = None;
+        while let Some(delta) = stream.next().await {
+            let delta = delta.map_err(to_engine_error)?;
+            if let Some(text) = collected.push(delta) {
+                first_token_ms.get_or_insert_with(|| start.elapsed().as_millis() as i64);
+                on_delta(&text);
             }
         }
+        let latency_ms = start.elapsed().as_millis() as i64;
+
+        let response = from_stream(collected);
+        let cost = self.record_metrics(
+            &model_requested,
+            &purpose,
+            response.usage.as_ref(),
+            latency_ms,
+            first_token_ms,
+        );
+        self.after_call(cost);
 
         Ok(response)
     }
 }
 
+/// Convert router errors the engine can act on into core error types.
+///
+/// A context overflow becomes `opencrab_core::ContextOverflow`, so the engine
+/// trims the conversation and retries; other errors pass through unchanged.
+fn to_engine_error(e: anyhow::Error) -> anyhow::Error {
+    match e.downcast_ref::<LlmError>() {
+        Some(overflow @ LlmError::ContextOverflow { .. }) => {
+            ContextOverflow(overflow.to_string()).into()
+        }
+        _ => e,
+    }
+}
+
+/// Convert an accumulated stream into a `ChatResponseSimple`, leaving usage
+/// unset when the provider never reported it.
+fn from_stream(collected: StreamAccumulator) -> ChatResponseSimple {
+    let usage_reported = collected.has_usage();
+    let mut response = from_llm_response(collected.finish());
+    if !usage_reported {
+        response.usage = None;
+    }
+    response
+}
+
+/// Adapter that exposes the router's embedding API as a core `Embedder`.
+pub struct EmbeddingAdapter {
+    router: Arc<LlmRouter>,
+    /// Embedding model in `provider:model` (or alias) form.
+    model: String,
+}
+
+impl EmbeddingAdapter {
+    pub fn new(router: Arc<LlmRouter>, model: impl Into<String>) -> Self {
+        Self {
+            router,
+            model: model.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl Embedder for EmbeddingAdapter {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let response = self
+            .router
+            .embed(EmbeddingRequest::new(&self.model, texts.to_vec()))
+            .await?;
+        if response.embeddings.len() != texts.len() {
+            anyhow::bail!(
+                "Embedding model '{}' returned {} vectors for {} texts",
+                self.model,
+                response.embeddings.len(),
+                texts.len()
+            );
+        }
+        Ok(response.embeddings)
+    }
+}
+
 /// Convert core ChatRequestSimple → llm ChatRequest.
 fn to_llm_request(req: ChatRequestSimple) -> ChatRequest {
     let messages: Vec<Message> = req.messages.into_iter().map(to_llm_message).collect();
@@ -138,6 +320,7 @@ fn to_llm_request(req: ChatRequestSimple) -> ChatRequest {
         stop: None,
         stream: None,
         metadata: Default::default(),
+        purpose: req.purpose,
     }
 }
 
@@ -177,7 +360,7 @@ fn to_llm_message(msg: ChatMessage) -> Message {
         } else {
             Some(MessageContent::Text(msg.content))
         },
-        name: None,
+        name: msg.name,
         function_call: None,
         tool_calls,
         tool_call_id: msg.tool_call_id,
@@ -249,6 +432,26 @@ fn from_llm_response(resp: opencrab_llm::message::ChatResponse) -> ChatResponseS
 #[cfg(test)]
 mod tests {
     use super::*;
+    use opencrab_llm::message::{ChatStreamDelta, DeltaMessage, StreamChoice};
+
+    #[test]
+    fn test_to_engine_error() {
+        let overflow: anyhow::Error = LlmError::from_body(
+            "OpenAI",
+            "gpt-4o",
+            400,
+            None,
+            r#"{"error":"context window exceeded"}"#,
+        )
+        .into();
+        let mapped = to_engine_error(overflow.context("All providers failed"));
+        assert!(mapped.downcast_ref::<ContextOverflow>().is_some());
+
+        let auth: anyhow::Error = LlmError::from_body("OpenAI", "gpt-4o", 401, None, "").into();
+        let mapped = to_engine_error(auth);
+        assert!(mapped.downcast_ref::<ContextOverflow>().is_none());
+        assert!(mapped.downcast_ref::<LlmError>().is_some());
+    }
 
     #[test]
     fn test_to_llm_message_system() {
@@ -257,12 +460,24 @@ mod tests {
             content: "You are helpful.".to_string(),
             tool_call_id: None,
             tool_calls: vec![],
+            name: None,
         };
         let llm_msg = to_llm_message(msg);
         assert_eq!(llm_msg.role, Role::System);
         assert_eq!(llm_msg.text_content(), Some("You are helpful."));
     }
 
+    #[test]
+    fn test_to_llm_message_keeps_name() {
+        let msg = ChatMessage {
+            name: Some("Bob".to_string()),
+            ..ChatMessage::new("user", "[Bob]: hi")
+        };
+        let llm_msg = to_llm_message(msg);
+        assert_eq!(llm_msg.role, Role::User);
+        assert_eq!(llm_msg.name.as_deref(), Some("Bob"));
+    }
+
     #[test]
     fn test_to_llm_message_with_tool_calls() {
         let msg = ChatMessage {
@@ -274,6 +489,7 @@ mod tests {
                 name: "search".to_string(),
                 arguments: serde_json::json!({"query": "test"}),
             }],
+            name: None,
         };
         let llm_msg = to_llm_message(msg);
         assert_eq!(llm_msg.role, Role::Assistant);
@@ -337,4 +553,128 @@ mod tests {
         assert_eq!(simple.tool_calls[0].arguments["skill"], "test");
         assert_eq!(simple.finish_reason, "tool_calls");
     }
+
+    fn text_delta(text: &str) -> ChatStreamDelta {
+        ChatStreamDelta {
+            id: "s1".to_string(),
+            model: "test".to_string(),
+            choices: vec![StreamChoice {
+                index: 0,
+                delta: DeltaMessage {
+                    role: None,
+                    content: Some(text.to_string()),
+                    function_call: None,
+                    tool_calls: None,
+                },
+                finish_reason: None,
+            }],
+            usage: None,
+        }
+    }
+
+    /// Streams one text delta, then reports a context overflow inside the stream.
+    struct OverflowMidStream;
+
+    #[async_trait]
+    impl opencrab_llm::traits::LlmProvider for OverflowMidStream {
+        fn name(&self) -> &str {
+            "mock"
+        }
+
+        async fn available_models(&self) -> Result<Vec<opencrab_llm::traits::ModelInfo>> {
+            Ok(vec![])
+        }
+
+        async fn chat_completion(
+            &self,
+            _request: ChatRequest,
+        ) -> Result<opencrab_llm::message::ChatResponse> {
+            anyhow::bail!("streaming only")
+        }
+
+        async fn chat_completion_stream(
+            &self,
+            _request: ChatRequest,
+        ) -> Result<futures::stream::BoxStream<'static, Result<ChatStreamDelta>>> {
+            let overflow = LlmError::from_body(
+                "mock",
+                "gpt-4o",
+                200,
+                None,
+                r#"{"error":"context window exceeded"}"#,
+            );
+            Ok(futures::stream::iter(vec![Ok(text_delta("Hel")), Err(overflow.into())]).boxed())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chat_streaming_maps_in_stream_overflow() {
+        let mut router = LlmRouter::new();
+        router.add_provider(Arc::new(OverflowMidStream));
+        router.set_default_provider("mock");
+        let adapter = LlmRouterAdapter::new(Arc::new(router));
+
+        let request = ChatRequestSimple {
+            model: "gpt-4o".to_string(),
+            messages: vec![ChatMessage::new("user", "hi")],
+            tools: vec![],
+            temperature: None,
+            max_tokens: None,
+            purpose: None,
+        };
+        let err = adapter.chat_streaming(request, &|_| {}).await.unwrap_err();
+        assert!(err.downcast_ref::<ContextOverflow>().is_some(), "{err:#}");
+    }
+
+    #[test]
+    fn test_stream_accumulator_text_and_usage() {
+        let mut collector = StreamAccumulator::default();
+        assert_eq!(collector.push(text_delta("Hel")), Some("Hel".to_string()));
+        assert_eq!(collector.push(text_delta("lo")), Some("lo".to_string()));
+        let mut last = text_delta("");
+        last.choices[0].finish_reason = Some(FinishReason::Stop);
+        last.usage = Some(Usage {
+            prompt_tokens: 7,
+            completion_tokens: 2,
+            total_tokens: 0,
+        });
+        assert_eq!(collector.push(last), None);
+
+        let simple = from_stream(collector);
+        assert_eq!(simple.content, Some("Hello".to_string()));
+        assert_eq!(simple.finish_reason, "stop");
+        let usage = simple.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, 7);
+        assert_eq!(usage.total_tokens, 9);
+    }
+
+    #[test]
+    fn test_stream_accumulator_tool_call_fragments() {
+        let fragment = |id: &str, name: &str, args: &str| {
+            let mut delta = text_delta("");
+            delta.choices[0].delta.content = None;
+            delta.choices[0].delta.tool_calls = Some(vec![LlmToolCall {
+                id: id.to_string(),
+                call_type: "function".to_string(),
+                function: FunctionCall {
+                    name: name.to_string(),
+                    arguments: args.to_string(),
+                },
+            }]);
+            delta
+        };
+
+        let mut collector = StreamAccumulator::default();
+        collector.push(fragment("tc-1", "search", "{\"q\":"));
+        collector.push(fragment("", "", "\"rust\"}"));
+        collector.push(fragment("tc-2", "learn", "{}"));
+
+        let simple = from_stream(collector);
+        assert!(simple.content.is_none());
+        assert!(simple.usage.is_none());
+        assert_eq!(simple.finish_reason, "tool_calls");
+        assert_eq!(simple.tool_calls.len(), 2);
+        assert_eq!(simple.tool_calls[0].arguments["q"], "rust");
+        assert_eq!(simple.tool_calls[1].name, "learn");
+    }
 }
@@ -21,6 +21,26 @@ async fn main() -> anyhow::Result<()> {
     // DB初期化
     let conn = opencrab_db::init_connection(&cfg.database.path)?;
 
+    // 料金表（組み込み + model_pricing テーブル）
+    let pricing = opencrab_server::pricing::load_registry(&conn)?;
+
+    let db = Arc::new(Mutex::new(conn));
+
+    // 標準スキルファイルを全エージェントに同期
+    match opencrab_server::skill_sync::sync_all_agents(&db, &cfg.skills.dir) {
+        Ok(reports) => {
+            for (agent_id, report) in reports {
+                tracing::info!(
+                    agent_id = %agent_id,
+                    added = report.added.len(),
+                    updated = report.updated.len(),
+                    "Standard skills synchronised"
+                );
+            }
+        }
+        Err(e) => tracing::warn!(error = %e, "Failed to synchronise standard skills"),
+    }
+
     // Build LLM router from config
     let llm_router = config::build_llm_router(&cfg.llm)?;
 
@@ -31,10 +51,28 @@ async fn main() -> anyhow::Result<()> {
 
     #[allow(unused_mut)]
     let mut state = AppState {
-        db: Arc::new(Mutex::new(conn)),
+        db,
         llm_router: Arc::new(llm_router),
         workspace_base: "data".to_string(),
         default_model,
+        embedding_model: cfg.llm.embedding_model(),
+        self_selection: cfg.llm.self_selection.clone(),
+        budget: cfg.llm.budget.clone(),
+        pricing: Arc::new(std::sync::RwLock::new(pricing)),
+        skills_dir: cfg.skills.dir.clone(),
+        base_actions: cfg.agent.base_actions.clone(),
+        context_budget: cfg.agent.context.to_budget(),
+        persona: cfg.agent.persona.to_options(),
+        consolidation: cfg.agent.consolidation.to_options(),
+        memory_policy: cfg.agent.memory.to_policy(),
+        heartbeat: Arc::new(opencrab_server::heartbeat::HeartbeatManager::new(
+            cfg.agent.heartbeat_interval_secs,
+        )),
+        session_runner: Arc::new(opencrab_server::session_runner::SessionRunnerManager::new(
+            cfg.agent.session_turn_interval_ms,
+        )),
+        consolidation_manager: Arc::new(opencrab_server::consolidation::ConsolidationManager::new()),
+        ws_gateway: Arc::new(opencrab_gateway::WebSocketGateway::new(64)),
         #[cfg(feature = "discord")]
         discord_manager: None,
     };
@@ -86,6 +124,28 @@ async fn main() -> anyhow::Result<()> {
         tracing::info!("Per-agent Discord gateway manager initialized");
     }
 
+    // 設定されたエージェントのハートビートを開始
+    for agent_id in &cfg.agent.heartbeat_agent_ids {
+        state.heartbeat.start(state.clone(), agent_id, None).await;
+    }
+
+    // 記憶の統合を定期実行
+    let consolidation_cfg = &cfg.agent.consolidation;
+    if consolidation_cfg.enabled {
+        opencrab_server::consolidation::spawn_schedule(
+            state.clone(),
+            consolidation_cfg.interval_secs,
+            consolidation_cfg.agent_ids.clone(),
+        );
+        tracing::info!(interval_secs = consolidation_cfg.interval_secs, "Memory consolidation scheduled");
+    }
+
+    // 前回 running のまま終了したセッションの自律実行を再開
+    state.session_runner.restore_from_db(&state).await;
+
+    // WebSocketから投稿されたメッセージの処理ループ
+    tokio::spawn(opencrab_server::ws::run_message_loop(state.clone()));
+
     let app = create_router(state);
 
     let addr = format!("0.0.0.0:{}", cfg.gateway.rest.port);
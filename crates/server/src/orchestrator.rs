@@ -0,0 +1,441 @@
+//! ファシリテーター付きセッション（`mode = "facilitated"`）の進行。
+//!
+//! 1ターンごとにファシリテーターが次の発言者とフェーズを決め、
+//! 選ばれた参加者が発言する。フェーズは divergent → convergent → closing と
+//! 一方向に進み、`max_turns` 到達または全参加者の `declare_done` で終了する。
+
+use std::collections::{HashMap, HashSet};
+
+use opencrab_core::{
+    session::next_speaker_weighted, AgentRole, FacilitatorDecision, SessionEndReason,
+    SessionPhase, SessionProgress,
+};
+use serde::Serialize;
+
+use crate::process::{
+    behavior, build_conversation, build_history, build_system_prompt, record_session_log, run_agent,
+    AgentRun, PromptInput, SessionEvent, SessionEventSink, SessionReply,
+};
+use crate::AppState;
+
+/// ファシリテーター付きセッションのモード名
+pub const FACILITATED_MODE: &str = "facilitated";
+
+/// ファシリテーターへの指示（候補一覧とフェーズを埋め込む）
+const FACILITATOR_PROMPT: &str = "You are the facilitator of this discussion. \
+Do not take part in the discussion yourself; decide who should speak next and \
+whether the discussion should move to the next phase.\n\
+Phases move forward only: divergent (collect ideas) -> convergent (compare and narrow down) \
+-> closing (summarise and wrap up).\n\
+Each candidate has a speaking weight: give the floor to higher weights more often, \
+but let everyone speak.\n\
+Respond with a single JSON object and nothing else:\n\
+{\"next_speaker\": \"<agent id>\", \"phase\": \"divergent|convergent|closing\", \"reason\": \"<short reason>\"}";
+
+/// 1ターンの結果。
+#[derive(Debug, Clone, Serialize)]
+pub struct TurnOutcome {
+    pub session_id: String,
+    /// このターン終了後のターン番号
+    pub turn_number: i32,
+    pub phase: SessionPhase,
+    pub speaker_id: Option<String>,
+    pub speaker_name: Option<String>,
+    /// 発言内容（`send_noreact` した場合は `None`）
+    pub content: Option<String>,
+    pub noreact: bool,
+    pub tool_calls_made: usize,
+    /// このターンの時点でまだ `declare_done` していない発言者の数
+    pub active_speakers: usize,
+    /// このターンでセッションが終了した理由
+    pub ended: Option<SessionEndReason>,
+    pub status: String,
+}
+
+/// セッションのファシリテーターを決める。
+///
+/// `sessions.facilitator_id` を優先し、無ければ identity の role が
+/// facilitator の最初の参加者を使う。
+pub fn resolve_facilitator(
+    conn: &rusqlite::Connection,
+    session: &opencrab_db::queries::SessionRow,
+    participant_ids: &[String],
+) -> Option<String> {
+    if let Some(id) = &session.facilitator_id {
+        return Some(id.clone());
+    }
+    participant_ids
+        .iter()
+        .find(|id| {
+            opencrab_db::queries::get_identity(conn, id)
+                .ok()
+                .flatten()
+                .is_some_and(|i| AgentRole::from_str_value(&i.role) == AgentRole::Facilitator)
+        })
+        .cloned()
+}
+
+/// ファシリテーター付きセッションを1ターン進める。
+///
+/// `events` を渡すと発言者の開始・エンジンイベント・応答を逐次送る。
+pub async fn run_facilitated_turn(
+    state: &AppState,
+    session_id: &str,
+    events: Option<&SessionEventSink>,
+) -> anyhow::Result<TurnOutcome> {
+    let notify = |event: SessionEvent| {
+        if let Some(sink) = events {
+            let _ = sink.send(event);
+        }
+    };
+
+    if state.llm_router.provider_names().is_empty() {
+        anyhow::bail!("No LLM providers configured");
+    }
+
+    // 1. セッションと参加状態を読み込む
+    let (session, facilitator_id, speakers, done) = {
+        let conn = state.db.lock().unwrap();
+        let session = opencrab_db::queries::get_session(&conn, session_id)?
+            .ok_or_else(|| anyhow::anyhow!("Session not found: {session_id}"))?;
+        let participant_ids: Vec<String> =
+            serde_json::from_str(&session.participant_ids_json).unwrap_or_default();
+        let facilitator_id = resolve_facilitator(&conn, &session, &participant_ids);
+        let speakers: Vec<(String, String)> = participant_ids
+            .iter()
+            .filter(|id| Some(*id) != facilitator_id.as_ref())
+            .map(|id| (id.clone(), agent_name(&conn, id)))
+            .collect();
+        let done = done_speakers(&conn, session_id);
+        (session, facilitator_id, speakers, done)
+    };
+    // `running` はバックグラウンド実行中（`session_runner`）
+    if !matches!(session.status.as_str(), "active" | "running") {
+        anyhow::bail!("Session is not active: {}", session.status);
+    }
+
+    let current_phase = SessionPhase::parse(&session.phase).unwrap_or(SessionPhase::Divergent);
+    let progress = progress_of(&session, session.turn_number, &speakers, &done);
+    if let Some(reason) = progress.end_reason() {
+        finish_session(state, session_id, reason, session.turn_number)?;
+        return Ok(TurnOutcome {
+            session_id: session_id.to_string(),
+            turn_number: session.turn_number,
+            phase: current_phase,
+            speaker_id: None,
+            speaker_name: None,
+            content: None,
+            noreact: false,
+            tool_calls_made: 0,
+            active_speakers: 0,
+            ended: Some(reason),
+            status: "completed".to_string(),
+        });
+    }
+
+    // 2. ファシリテーターに次の発言者を選ばせる（失敗時はラウンドロビン）
+    let candidates: Vec<(String, String)> = speakers
+        .iter()
+        .filter(|(id, _)| !done.contains(id))
+        .cloned()
+        .collect();
+    // 外向性から導いた発言の重み（`[agent.persona]`）
+    let weighted: Vec<(String, f32)> = candidates
+        .iter()
+        .map(|(id, _)| (id.clone(), behavior(state, id).speak_weight))
+        .collect();
+    let decision = match &facilitator_id {
+        Some(facilitator_id) => {
+            ask_facilitator(state, &session, facilitator_id, &candidates, &weighted, current_phase)
+                .await
+        }
+        None => FacilitatorDecision::default(),
+    };
+    let candidate_ids: Vec<String> = candidates.iter().map(|(id, _)| id.clone()).collect();
+    let (last_speaker, spoken) = {
+        let conn = state.db.lock().unwrap();
+        speech_history(&conn, session_id, &candidate_ids)
+    };
+    let speaker_id = decision
+        .speaker_in(&candidates)
+        .or_else(|| next_speaker_weighted(&weighted, &spoken, last_speaker.as_deref()))
+        .ok_or_else(|| anyhow::anyhow!("No participant can speak in session {session_id}"))?;
+    let speaker_name = candidates
+        .iter()
+        .find(|(id, _)| *id == speaker_id)
+        .map(|(_, name)| name.clone())
+        .unwrap_or_else(|| speaker_id.clone());
+
+    // 3. フェーズを決める（後戻りしない）
+    let phase = SessionPhase::resolve(current_phase, decision.phase, &progress);
+    if phase != current_phase {
+        let log = opencrab_db::queries::SessionLogRow {
+            id: None,
+            agent_id: facilitator_id.clone().unwrap_or_else(|| "system".to_string()),
+            session_id: session_id.to_string(),
+            log_type: "system".to_string(),
+            content: format!("Phase changed: {current_phase} -> {phase}"),
+            speaker_id: facilitator_id.clone(),
+            turn_number: Some(session.turn_number),
+            metadata_json: Some(
+                serde_json::json!({"from": current_phase, "to": phase}).to_string(),
+            ),
+        };
+        record_session_log(state, &log)?;
+    }
+
+    // 4. 選ばれた参加者に発言させる
+    let input = PromptInput {
+        session_id: Some(session_id),
+        session_theme: &session.theme,
+        gateway: "session",
+        with_memories: true,
+    };
+    let (system_prompt, _) = build_system_prompt(state, &speaker_id, &input).await;
+    let turn = match session.max_turns {
+        Some(max) => format!("Turn {} of {max}.", session.turn_number + 1),
+        None => format!("Turn {}.", session.turn_number + 1),
+    };
+    let system_prompt = format!("{system_prompt}\n\n{turn} {}", phase.guidance());
+    let history = build_history(state, &speaker_id, session_id, &system_prompt).await;
+
+    notify(SessionEvent::AgentStarted {
+        agent_id: speaker_id.clone(),
+        agent_name: speaker_name.clone(),
+    });
+    let (relay_tx, mut relay_rx) = tokio::sync::mpsc::unbounded_channel();
+    let relay = {
+        let sink = events.cloned();
+        let agent_id = speaker_id.clone();
+        tokio::spawn(async move {
+            let mut noreact = false;
+            while let Some(event) = relay_rx.recv().await {
+                if let opencrab_core::EngineEvent::ToolCallFinished {
+                    name,
+                    success: true,
+                    ..
+                } = &event
+                {
+                    noreact |= name == "send_noreact";
+                }
+                if let Some(sink) = &sink {
+                    let _ = sink.send(SessionEvent::Engine {
+                        agent_id: agent_id.clone(),
+                        event,
+                    });
+                }
+            }
+            noreact
+        })
+    };
+    let run = AgentRun {
+        session_id: Some(session_id),
+        events: Some(relay_tx),
+        history: Some(history),
+        ..AgentRun::new(&speaker_id, &speaker_name, &system_prompt, "")
+    };
+    let result = run_agent(state, run).await;
+    let noreact = relay.await.unwrap_or(false);
+    let engine_result = result?;
+
+    // 5. 発言を記録してターンを進める
+    let turn_number = session.turn_number + 1;
+    if !noreact {
+        let log = opencrab_db::queries::SessionLogRow {
+            id: None,
+            agent_id: speaker_id.clone(),
+            session_id: session_id.to_string(),
+            log_type: "speech".to_string(),
+            content: engine_result.response.clone(),
+            speaker_id: Some(speaker_id.clone()),
+            turn_number: Some(turn_number),
+            metadata_json: Some(
+                serde_json::json!({
+                    "phase": phase,
+                    "iterations": engine_result.iterations,
+                    "tool_calls_made": engine_result.tool_calls_made,
+                    "facilitator_reason": decision.reason,
+                })
+                .to_string(),
+            ),
+        };
+        record_session_log(state, &log)?;
+        notify(SessionEvent::Reply(SessionReply {
+            agent_id: speaker_id.clone(),
+            agent_name: speaker_name.clone(),
+            content: engine_result.response.clone(),
+            tool_calls_made: engine_result.tool_calls_made,
+        }));
+    }
+
+    let done = {
+        let conn = state.db.lock().unwrap();
+        opencrab_db::queries::record_agent_speech(&conn, &speaker_id, session_id)?;
+        opencrab_db::queries::update_session_progress(
+            &conn,
+            session_id,
+            phase.as_str(),
+            turn_number,
+        )?;
+        done_speakers(&conn, session_id)
+    };
+
+    // 6. 終了条件を確認する
+    let ended = progress_of(&session, turn_number, &speakers, &done).end_reason();
+    if let Some(reason) = ended {
+        finish_session(state, session_id, reason, turn_number)?;
+    }
+
+    Ok(TurnOutcome {
+        session_id: session_id.to_string(),
+        turn_number,
+        phase,
+        speaker_id: Some(speaker_id),
+        speaker_name: Some(speaker_name),
+        content: (!noreact).then_some(engine_result.response),
+        noreact,
+        tool_calls_made: engine_result.tool_calls_made,
+        active_speakers: candidates.len(),
+        ended,
+        status: if ended.is_some() { "completed" } else { "active" }.to_string(),
+    })
+}
+
+/// ファシリテーターを（ツール無しで）実行し、判断を得る。失敗時は空の判断を返す。
+async fn ask_facilitator(
+    state: &AppState,
+    session: &opencrab_db::queries::SessionRow,
+    facilitator_id: &str,
+    candidates: &[(String, String)],
+    weights: &[(String, f32)],
+    phase: SessionPhase,
+) -> FacilitatorDecision {
+    let input = PromptInput {
+        session_id: Some(&session.id),
+        session_theme: &session.theme,
+        gateway: "session",
+        with_memories: false,
+    };
+    let (context, name) = build_system_prompt(state, facilitator_id, &input).await;
+    let list: Vec<String> = candidates
+        .iter()
+        .map(|(id, name)| {
+            let weight = weights
+                .iter()
+                .find(|(w_id, _)| w_id == id)
+                .map(|(_, w)| *w)
+                .unwrap_or(1.0);
+            format!("- {id} ({name}, speaking weight {weight:.1})")
+        })
+        .collect();
+    let turn = match session.max_turns {
+        Some(max) => format!("{} of {max}", session.turn_number),
+        None => session.turn_number.to_string(),
+    };
+    let system_prompt = format!(
+        "{context}\n\n{FACILITATOR_PROMPT}\n\n\
+         Current phase: {phase}\nTurns so far: {turn}\nCandidates:\n{}",
+        list.join("\n")
+    );
+    let conversation = build_conversation(state, facilitator_id, &session.id, &system_prompt).await;
+
+    let run = AgentRun {
+        session_id: Some(&session.id),
+        allowed_actions: Some(Vec::new()),
+        purpose: "thinking",
+        max_iterations: 1,
+        ..AgentRun::new(facilitator_id, &name, &system_prompt, &conversation)
+    };
+    match run_agent(state, run).await {
+        Ok(result) => FacilitatorDecision::parse(&result.response),
+        Err(e) => {
+            tracing::warn!(
+                session_id = %session.id,
+                error = %e,
+                "Facilitator failed, falling back to round-robin"
+            );
+            FacilitatorDecision::default()
+        }
+    }
+}
+
+/// セッションを終了状態にし、終了理由をシステムログに残す。
+fn finish_session(
+    state: &AppState,
+    session_id: &str,
+    reason: SessionEndReason,
+    turn_number: i32,
+) -> anyhow::Result<()> {
+    {
+        let conn = state.db.lock().unwrap();
+        opencrab_db::queries::update_session_status(&conn, session_id, "completed")?;
+    }
+    let content = match reason {
+        SessionEndReason::MaxTurns => "Session ended: max_turns reached",
+        SessionEndReason::AllDone => "Session ended: all participants declared done",
+    };
+    let log = opencrab_db::queries::SessionLogRow {
+        id: None,
+        agent_id: "system".to_string(),
+        session_id: session_id.to_string(),
+        log_type: "system".to_string(),
+        content: content.to_string(),
+        speaker_id: None,
+        turn_number: Some(turn_number),
+        metadata_json: Some(serde_json::json!({"ended": reason}).to_string()),
+    };
+    record_session_log(state, &log)?;
+    Ok(())
+}
+
+fn progress_of(
+    session: &opencrab_db::queries::SessionRow,
+    turn_number: i32,
+    speakers: &[(String, String)],
+    done: &HashSet<String>,
+) -> SessionProgress {
+    SessionProgress {
+        turn_number,
+        max_turns: session.max_turns,
+        participants: speakers.len(),
+        done: speakers.iter().filter(|(id, _)| done.contains(id)).count(),
+    }
+}
+
+fn done_speakers(conn: &rusqlite::Connection, session_id: &str) -> HashSet<String> {
+    opencrab_db::queries::list_agent_sessions(conn, session_id)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|r| r.done_declared)
+        .map(|r| r.agent_id)
+        .collect()
+}
+
+fn agent_name(conn: &rusqlite::Connection, agent_id: &str) -> String {
+    opencrab_db::queries::get_identity(conn, agent_id)
+        .ok()
+        .flatten()
+        .map(|i| i.name)
+        .unwrap_or_else(|| agent_id.to_string())
+}
+
+/// 候補の中で最後に発言した参加者と、候補ごとの発言回数を返す。
+fn speech_history(
+    conn: &rusqlite::Connection,
+    session_id: &str,
+    candidates: &[String],
+) -> (Option<String>, HashMap<String, usize>) {
+    let mut last = None;
+    let mut spoken: HashMap<String, usize> = HashMap::new();
+    let speakers = opencrab_db::queries::list_session_logs_by_session(conn, session_id)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|log| log.log_type == "speech")
+        .filter_map(|log| log.speaker_id)
+        .filter(|id| candidates.contains(id));
+    for id in speakers {
+        *spoken.entry(id.clone()).or_insert(0) += 1;
+        last = Some(id);
+    }
+    (last, spoken)
+}
@@ -0,0 +1,80 @@
+//! モデル料金表。
+//!
+//! 組み込みの料金（`PricingRegistry::new()`）に `model_pricing` テーブルの行を上書きしたものを
+//! `AppState.pricing` として共有する。API で料金を変更・インポートしたら `reload` で読み直す。
+
+use std::sync::{Arc, RwLock};
+
+use opencrab_llm::pricing::{ModelPricing, PricingRegistry};
+
+/// 全リクエストで共有する料金表
+pub type SharedPricing = Arc<RwLock<PricingRegistry>>;
+
+pub fn from_row(row: opencrab_db::queries::ModelPricingRow) -> ModelPricing {
+    ModelPricing {
+        provider: row.provider,
+        model: row.model,
+        input_per_million: row.input_price_per_1m,
+        output_per_million: row.output_price_per_1m,
+        context_window: row.context_window.and_then(|w| u32::try_from(w).ok()),
+    }
+}
+
+pub fn to_row(pricing: &ModelPricing) -> opencrab_db::queries::ModelPricingRow {
+    opencrab_db::queries::ModelPricingRow {
+        provider: pricing.provider.clone(),
+        model: pricing.model.clone(),
+        input_price_per_1m: pricing.input_per_million,
+        output_price_per_1m: pricing.output_per_million,
+        context_window: pricing.context_window.and_then(|w| i32::try_from(w).ok()),
+    }
+}
+
+/// 組み込みの料金にDBの行を上書きした料金表
+pub fn load_registry(conn: &rusqlite::Connection) -> anyhow::Result<PricingRegistry> {
+    let mut registry = PricingRegistry::new();
+    for row in opencrab_db::queries::list_model_pricing(conn)? {
+        registry.register(from_row(row));
+    }
+    Ok(registry)
+}
+
+/// 料金をまとめてDBに保存する。保存した件数を返す
+pub fn import(conn: &rusqlite::Connection, prices: &[ModelPricing]) -> anyhow::Result<usize> {
+    for pricing in prices {
+        opencrab_db::queries::upsert_model_pricing(conn, &to_row(pricing))?;
+    }
+    Ok(prices.len())
+}
+
+/// DBから料金表を読み直して共有の料金表を差し替える
+pub fn reload(conn: &rusqlite::Connection, shared: &SharedPricing) -> anyhow::Result<()> {
+    let registry = load_registry(conn)?;
+    *shared.write().unwrap() = registry;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_db_prices_override_defaults() {
+        let conn = opencrab_db::init_memory().unwrap();
+        let shared: SharedPricing = Arc::new(RwLock::new(load_registry(&conn).unwrap()));
+        assert!(shared.read().unwrap().get("openai", "gpt-4o").is_some());
+        assert!(shared.read().unwrap().get("openrouter", "x/model").is_none());
+
+        let mut custom = ModelPricing::new("openai", "gpt-4o", 1.0, 2.0);
+        custom.context_window = Some(128_000);
+        let imported = vec![custom, ModelPricing::new("openrouter", "x/model", 0.5, 1.5)];
+        assert_eq!(import(&conn, &imported).unwrap(), 2);
+        reload(&conn, &shared).unwrap();
+
+        let registry = shared.read().unwrap();
+        let gpt = registry.get("openai", "gpt-4o").unwrap();
+        assert_eq!(gpt.input_per_million, 1.0);
+        assert_eq!(gpt.context_window, Some(128_000));
+        assert_eq!(registry.calculate_cost("openrouter", "x/model", 1_000_000, 0), Some(0.5));
+    }
+}
@@ -3,21 +3,116 @@
 //! REST API (`api/sessions.rs`) と Discordゲートウェイ (`discord.rs`) の
 //! 両方から利用される。
 
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, OnceLock};
 
-use opencrab_llm::pricing::PricingRegistry;
 
 use crate::llm_adapter::{LlmRouterAdapter, MetricsContext};
 use crate::AppState;
 
-/// DBからエージェントのidentity/soul/skillsを読み込んでシステムプロンプトを構築する。
+/// 組み込みのシステムプロンプトテンプレート（エージェント別・共通のテンプレートが無い場合に使う）。
 ///
-/// 返り値: (system_prompt, agent_name)
-pub fn build_agent_context(
+/// 変数は [`opencrab_core::PROMPT_VARIABLES`] を参照。
+pub const DEFAULT_PROMPT_TEMPLATE: &str = "You are {{agent_name}} ({{persona_name}}), role: {{role}}.
+Current date and time: {{now}}
+Current discussion topic: {{session_theme}}
+
+You are an autonomous agent participating in a discussion. \
+Respond thoughtfully to the conversation. \
+You can use tools to search your history, learn from experience, \
+create new skills, and manage your workspace.
+
+The conversation history uses the format \"[speaker]: message\" for context, \
+but you must NOT include your own name prefix in your response. \
+Just reply with the message content directly.\
+{{#skills}}
+
+Your skills:
+{{skills}}{{/skills}}{{#persona}}
+
+{{persona}}{{/persona}}{{#custom_traits}}
+
+{{custom_traits}}{{/custom_traits}}{{#memories}}
+
+The following is what you remember from earlier conversations. \
+Use it when it is relevant; do not recite it.
+
+{{memories}}{{/memories}}";
+
+/// システムプロンプトを組み立てる場面の情報
+#[derive(Debug, Clone, Copy)]
+pub struct PromptInput<'a> {
+    pub session_id: Option<&'a str>,
+    pub session_theme: &'a str,
+    /// 発言する経路（rest, ws, discord, session, heartbeat）
+    pub gateway: &'a str,
+    /// 記憶（`{{memories}}`）を組み立てるか。ファシリテーターの判断などでは false
+    pub with_memories: bool,
+}
+
+/// 使用するテンプレートと、その出どころ
+#[derive(Debug, Clone)]
+pub struct ResolvedPromptTemplate {
+    pub template: opencrab_core::PromptTemplate,
+    /// "agent" / "global" / "builtin" / "draft"
+    pub source: &'static str,
+    pub version: Option<i64>,
+}
+
+/// 組み立てたシステムプロンプト
+#[derive(Debug, Clone)]
+pub struct RenderedPrompt {
+    pub prompt: String,
+    pub agent_name: String,
+    pub source: &'static str,
+    pub version: Option<i64>,
+    /// 注入した記憶（テンプレートが `memories` を使わない場合は None）
+    pub context: Option<opencrab_core::AssembledContext>,
+}
+
+/// エージェントのテンプレートを解決する: エージェント別 → 共通 → 組み込み。
+///
+/// 保存済みテンプレートが解析できない場合は警告を出して次の候補に進む。
+pub fn resolve_prompt_template(conn: &rusqlite::Connection, agent_id: &str) -> ResolvedPromptTemplate {
+    let scopes = [
+        (agent_id, "agent"),
+        (opencrab_db::queries::PROMPT_TEMPLATE_GLOBAL, "global"),
+    ];
+    for (scope, source) in scopes {
+        let Some(row) = opencrab_db::queries::get_active_prompt_template(conn, scope).ok().flatten() else {
+            continue;
+        };
+        match opencrab_core::PromptTemplate::parse(&row.content) {
+            Ok(template) => {
+                return ResolvedPromptTemplate {
+                    template,
+                    source,
+                    version: Some(row.version),
+                }
+            }
+            Err(e) => {
+                tracing::warn!(agent_id = %agent_id, scope = %scope, version = row.version, error = %e, "Invalid prompt template, skipping");
+            }
+        }
+    }
+    ResolvedPromptTemplate {
+        template: opencrab_core::PromptTemplate::parse(DEFAULT_PROMPT_TEMPLATE)
+            .expect("built-in prompt template parses"),
+        source: "builtin",
+        version: None,
+    }
+}
+
+/// DBからエージェントのidentity/soul/skillsを読み込んでテンプレート変数を作る（`memories` 以外）。
+///
+/// Soul の特性は `persona_options`（`[agent.persona]`）の文言で振る舞いの指示に変換する。
+/// 返り値: (変数, agent_name)
+pub fn prompt_variables(
     conn: &rusqlite::Connection,
     agent_id: &str,
-    session_theme: &str,
-) -> (String, String) {
+    input: &PromptInput<'_>,
+    persona_options: &opencrab_core::PersonaOptions,
+) -> (HashMap<String, String>, String) {
     let identity = opencrab_db::queries::get_identity(conn, agent_id)
         .ok()
         .flatten();
@@ -29,94 +124,739 @@ pub fn build_agent_context(
         .map(|i| i.name.clone())
         .unwrap_or_else(|| agent_id.to_string());
 
-    let role = identity
-        .as_ref()
-        .map(|i| i.role.clone())
-        .unwrap_or_else(|| "discussant".to_string());
-
     let persona = soul
         .as_ref()
-        .map(|s| s.persona_name.clone())
+        .map(|s| opencrab_core::PersonaRenderer::new(persona_options).render(&opencrab_core::Soul::from_row(s)))
         .unwrap_or_default();
 
-    let custom_traits = soul
-        .as_ref()
-        .and_then(|s| s.custom_traits_json.clone())
-        .unwrap_or_default();
+    let skill_list: Vec<String> = skills
+        .iter()
+        .map(|s| format!("- {}: {}", s.name, s.description))
+        .collect();
+    let skill_names: Vec<&str> = skills.iter().map(|s| s.name.as_str()).collect();
 
-    let skills_text = if skills.is_empty() {
-        String::new()
+    let now = chrono::Local::now();
+    let vars = [
+        ("agent_id", agent_id.to_string()),
+        ("agent_name", agent_name.clone()),
+        (
+            "role",
+            identity
+                .as_ref()
+                .map(|i| i.role.clone())
+                .unwrap_or_else(|| "discussant".to_string()),
+        ),
+        ("job_title", identity.as_ref().and_then(|i| i.job_title.clone()).unwrap_or_default()),
+        ("organization", identity.as_ref().and_then(|i| i.organization.clone()).unwrap_or_default()),
+        ("persona_name", soul.as_ref().map(|s| s.persona_name.clone()).unwrap_or_default()),
+        ("persona", persona),
+        ("custom_traits", soul.as_ref().and_then(|s| s.custom_traits_json.clone()).unwrap_or_default()),
+        ("skills", skill_list.join("\n")),
+        ("skill_names", skill_names.join(", ")),
+        ("session_id", input.session_id.unwrap_or_default().to_string()),
+        ("session_theme", input.session_theme.to_string()),
+        ("gateway", input.gateway.to_string()),
+        ("now", now.format("%Y-%m-%d %H:%M:%S %Z").to_string()),
+        ("date", now.format("%Y-%m-%d").to_string()),
+    ];
+    (
+        vars.into_iter().map(|(k, v)| (k.to_string(), v)).collect(),
+        agent_name,
+    )
+}
+
+/// テンプレートでシステムプロンプトを組み立てる。
+///
+/// `template` を渡すとそれを使う（プレビュー用の下書き）。渡さなければ [`resolve_prompt_template`] で解決する。
+/// テンプレートが `memories` を使う場合のみ記憶を組み立てる。
+pub async fn render_system_prompt(
+    state: &AppState,
+    agent_id: &str,
+    input: &PromptInput<'_>,
+    template: Option<opencrab_core::PromptTemplate>,
+) -> RenderedPrompt {
+    let (resolved, mut vars, agent_name) = {
+        let conn = state.db.lock().unwrap();
+        let resolved = match template {
+            Some(template) => ResolvedPromptTemplate {
+                template,
+                source: "draft",
+                version: None,
+            },
+            None => resolve_prompt_template(&conn, agent_id),
+        };
+        let (vars, agent_name) = prompt_variables(&conn, agent_id, input, &state.persona);
+        (resolved, vars, agent_name)
+    };
+
+    let context = if input.with_memories && resolved.template.uses("memories") {
+        match assemble_memory_context(state, agent_id, input.session_id, None).await {
+            Ok(assembled) => Some(assembled),
+            Err(e) => {
+                tracing::warn!(agent_id = %agent_id, error = %e, "Failed to assemble memory context");
+                None
+            }
+        }
     } else {
-        let list: Vec<String> = skills
+        None
+    };
+    if let Some(assembled) = &context {
+        vars.insert("memories".to_string(), assembled.text.trim_end().to_string());
+    }
+
+    RenderedPrompt {
+        prompt: resolved.template.render(&vars),
+        agent_name,
+        source: resolved.source,
+        version: resolved.version,
+        context,
+    }
+}
+
+/// エージェントのシステムプロンプトを組み立て、注入した記憶をセッションログに記録する。
+///
+/// 注入した記憶は利用として記録する（減衰が遅くなる）。
+///
+/// 返り値: (system_prompt, agent_name)
+pub async fn build_system_prompt(
+    state: &AppState,
+    agent_id: &str,
+    input: &PromptInput<'_>,
+) -> (String, String) {
+    let rendered = render_system_prompt(state, agent_id, input, None).await;
+    if let Some(assembled) = &rendered.context {
+        record_memory_access(state, agent_id, assembled);
+        if let Some(session_id) = input.session_id.filter(|_| !assembled.is_empty()) {
+            record_context_log(state, agent_id, session_id, assembled);
+        }
+    }
+    (rendered.prompt, rendered.agent_name)
+}
+
+/// 注入したキュレーション記憶と関連ログのアクセス回数・最終アクセス日時を更新する。
+fn record_memory_access(state: &AppState, agent_id: &str, assembled: &opencrab_core::AssembledContext) {
+    let memory = opencrab_core::MemoryManager::new(agent_id, state.db.clone());
+    for (kind, source) in [
+        ("curated", opencrab_db::queries::EMBEDDING_SOURCE_CURATED),
+        ("related_log", opencrab_db::queries::EMBEDDING_SOURCE_SESSION),
+    ] {
+        let ids: Vec<String> = assembled
+            .items
             .iter()
-            .map(|s| format!("- {}: {}", s.name, s.description))
+            .filter(|item| item.kind == kind)
+            .map(|item| item.id.clone())
             .collect();
-        format!("\n\nYour skills:\n{}", list.join("\n"))
+        if let Err(e) = memory.record_access(source, &ids) {
+            tracing::warn!(agent_id = %agent_id, error = %e, "Failed to record memory access");
+        }
+    }
+}
+
+/// `[agent.memory]` の保持ルールで古いセッションログをアーカイブ・削除する。
+///
+/// 何もしなかった場合と失敗した場合は None（失敗は警告ログのみ）。
+pub fn prune_memories(state: &AppState, agent_id: &str) -> Option<opencrab_core::PruneReport> {
+    let memory = opencrab_core::MemoryManager::new(agent_id, state.db.clone())
+        .with_policy(state.memory_policy.clone());
+    match memory.prune(chrono::Utc::now()) {
+        Ok(report) if !report.is_empty() => Some(report),
+        Ok(_) => None,
+        Err(e) => {
+            tracing::warn!(agent_id = %agent_id, error = %e, "Failed to prune memories");
+            None
+        }
+    }
+}
+
+/// ローリング要約を保存するセッションログの `log_type`
+pub const SUMMARY_LOG_TYPE: &str = "summary";
+
+/// システムプロンプトに注入した記憶の記録（デバッグ用、会話履歴には含めない）
+pub const CONTEXT_LOG_TYPE: &str = opencrab_db::queries::LOG_TYPE_CONTEXT;
+
+/// キュレーション記憶・参加者の印象・関連する過去ログを組み立てる（`[agent.context]` の予算内）。
+///
+/// セッションがあれば参加者（他のエージェントと発言者）と、自分以外の最新の発言を検索語に使う。
+/// `query` を渡すとそれを検索語として優先する。
+pub async fn assemble_memory_context(
+    state: &AppState,
+    agent_id: &str,
+    session_id: Option<&str>,
+    query: Option<String>,
+) -> anyhow::Result<opencrab_core::AssembledContext> {
+    let mut request = opencrab_core::ContextRequest {
+        session_id: session_id.map(str::to_string),
+        query,
+        ..Default::default()
     };
+    if let Some(session_id) = session_id {
+        let (session, logs) = {
+            let conn = state.db.lock().unwrap();
+            (
+                opencrab_db::queries::get_session(&conn, session_id)?,
+                opencrab_db::queries::list_session_logs_by_session(&conn, session_id)?,
+            )
+        };
+        let mut participants: Vec<String> = session
+            .map(|s| serde_json::from_str(&s.participant_ids_json).unwrap_or_default())
+            .unwrap_or_default();
+        participants.extend(logs.iter().filter_map(|log| log.speaker_id.clone()));
+        let mut seen = std::collections::HashSet::new();
+        participants.retain(|id| id != agent_id && seen.insert(id.clone()));
+        request.participant_ids = participants;
 
-    let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S %Z");
+        if request.query.is_none() {
+            request.query = logs
+                .iter()
+                .rev()
+                .find(|log| {
+                    matches!(log.log_type.as_str(), "message" | "speech")
+                        && log.speaker_id.as_deref() != Some(agent_id)
+                        && log.agent_id != agent_id
+                })
+                .map(|log| log.content.clone());
+        }
+    }
 
-    let character_section = if custom_traits.is_empty() {
-        String::new()
-    } else {
-        format!("\n\n{custom_traits}")
-    };
-
-    let prompt = format!(
-        "You are {agent_name} ({persona}), role: {role}.\n\
-         Current date and time: {now}\n\
-         Current discussion topic: {session_theme}\n\
-         \n\
-         You are an autonomous agent participating in a discussion. \
-         Respond thoughtfully to the conversation. \
-         You can use tools to search your history, learn from experience, \
-         create new skills, and manage your workspace.\n\
-         \n\
-         The conversation history uses the format \"[speaker]: message\" for context, \
-         but you must NOT include your own name prefix in your response. \
-         Just reply with the message content directly.{skills_text}{character_section}"
-    );
+    let memory = opencrab_core::MemoryManager::new(agent_id, state.db.clone())
+        .with_policy(state.memory_policy.clone());
+    let embedder = embedder(state, agent_id);
+    opencrab_core::ContextAssembler::new(&memory, &state.context_budget)
+        .with_embedder(embedder.as_deref())
+        .assemble(&request)
+        .await
+}
 
-    (prompt, agent_name)
+/// 注入した記憶の項目をセッションログに記録する（会話履歴には含めない）。
+fn record_context_log(
+    state: &AppState,
+    agent_id: &str,
+    session_id: &str,
+    assembled: &opencrab_core::AssembledContext,
+) {
+    let log = opencrab_db::queries::SessionLogRow {
+        id: None,
+        agent_id: agent_id.to_string(),
+        session_id: session_id.to_string(),
+        log_type: CONTEXT_LOG_TYPE.to_string(),
+        content: format!(
+            "Injected {} memory items ({}/{} tokens)",
+            assembled.items.len(),
+            assembled.used_tokens,
+            assembled.budget_tokens
+        ),
+        speaker_id: None,
+        turn_number: None,
+        metadata_json: Some(
+            serde_json::json!({
+                "items": assembled.items,
+                "used_tokens": assembled.used_tokens,
+                "budget_tokens": assembled.budget_tokens,
+                "skipped": assembled.skipped,
+            })
+            .to_string(),
+        ),
+    };
+    if let Err(e) = record_session_log(state, &log) {
+        tracing::warn!(agent_id = %agent_id, error = %e, "Failed to record context log");
+    }
 }
 
-/// セッションログから会話文字列を構築する。
-pub fn build_conversation_string(
+/// セッションログを（最新のローリング要約, 要約以降のターン）に分けて読み込む。
+pub fn load_conversation(
     conn: &rusqlite::Connection,
     session_id: &str,
-) -> String {
+) -> (
+    Option<opencrab_core::ConversationSummary>,
+    Vec<opencrab_core::ConversationTurn>,
+) {
     let logs =
         opencrab_db::queries::list_session_logs_by_session(conn, session_id).unwrap_or_default();
 
-    if logs.is_empty() {
-        return "No messages yet.".to_string();
+    let summary = logs
+        .iter()
+        .rev()
+        .find(|log| log.log_type == SUMMARY_LOG_TYPE)
+        .map(|log| opencrab_core::ConversationSummary {
+            content: log.content.clone(),
+            covers_until: log
+                .metadata_json
+                .as_deref()
+                .and_then(|m| serde_json::from_str::<serde_json::Value>(m).ok())
+                .and_then(|m| m["covers_until"].as_i64())
+                .unwrap_or_default(),
+        });
+    let covers_until = summary.as_ref().map(|s| s.covers_until).unwrap_or(0);
+
+    let mut names: HashMap<String, String> = HashMap::new();
+    let turns = logs
+        .into_iter()
+        .filter(|log| {
+            !matches!(
+                log.log_type.as_str(),
+                SUMMARY_LOG_TYPE | CONTEXT_LOG_TYPE | crate::budget::BUDGET_LOG_TYPE
+            )
+        })
+        .filter(|log| log.id.unwrap_or_default() > covers_until)
+        .filter_map(|log| {
+            let metadata = log
+                .metadata_json
+                .as_deref()
+                .and_then(|m| serde_json::from_str::<serde_json::Value>(m).ok())
+                .unwrap_or_default();
+            let kind = match log.log_type.as_str() {
+                opencrab_db::queries::LOG_TYPE_TOOL_CALL => opencrab_core::TurnKind::ToolCall {
+                    calls: serde_json::from_value(metadata["tool_calls"].clone()).ok()?,
+                },
+                opencrab_db::queries::LOG_TYPE_TOOL_RESULT => opencrab_core::TurnKind::ToolResult {
+                    tool_call_id: metadata["tool_call_id"].as_str()?.to_string(),
+                },
+                _ => opencrab_core::TurnKind::Message,
+            };
+            let speaker = log.speaker_id.unwrap_or(log.agent_id);
+            // Discordなど外部ユーザーは metadata の user_name、エージェントは identity の名前
+            let speaker_name = match metadata["user_name"].as_str() {
+                Some(name) => name.to_string(),
+                None => names
+                    .entry(speaker.clone())
+                    .or_insert_with(|| {
+                        opencrab_db::queries::get_identity(conn, &speaker)
+                            .ok()
+                            .flatten()
+                            .map(|i| i.name)
+                            .unwrap_or_else(|| speaker.clone())
+                    })
+                    .clone(),
+            };
+            Some(opencrab_core::ConversationTurn {
+                log_id: log.id.unwrap_or_default(),
+                speaker,
+                speaker_name,
+                content: log.content,
+                kind,
+            })
+        })
+        .collect();
+
+    (summary, turns)
+}
+
+/// セッションログから会話文字列を構築する（最新の要約 + それ以降の全ターン）。
+///
+/// トークン数の調整はしない。LLMに渡す履歴には `build_conversation` を使う。
+pub fn build_conversation_string(
+    conn: &rusqlite::Connection,
+    session_id: &str,
+) -> String {
+    let (summary, turns) = load_conversation(conn, session_id);
+    opencrab_core::ConversationWindow {
+        summary: summary.map(|s| s.content),
+        turns,
+        omitted: 0,
+    }
+    .render()
+}
+
+/// モデルのコンテキスト長（トークン）。
+///
+/// プロバイダの `ModelInfo::context_window` → `model_pricing.context_window` → 既定値の順に使う。
+pub async fn context_window_for(state: &AppState, model: &str) -> usize {
+    if let Some(window) = state.llm_router.context_window(model).await {
+        return window as usize;
+    }
+    let from_pricing = state.llm_router.resolve_model(model).ok().and_then(|(provider, model)| {
+        let conn = state.db.lock().unwrap();
+        opencrab_db::queries::get_model_pricing(&conn, &provider, &model)
+            .ok()
+            .flatten()
+            .and_then(|p| p.context_window)
+    });
+    from_pricing
+        .filter(|w| *w > 0)
+        .map(|w| w as usize)
+        .unwrap_or(opencrab_core::DEFAULT_CONTEXT_WINDOW)
+}
+
+/// モデルのコンテキストウィンドウに収まる会話履歴をテキストで構築する。
+///
+/// ファシリテーターやハートビートの判断など、会話を外から読む用途向け。
+/// 参加者として応答させる場合は `build_history` を使う。
+pub async fn build_conversation(
+    state: &AppState,
+    agent_id: &str,
+    session_id: &str,
+    system_prompt: &str,
+) -> String {
+    conversation_window(state, agent_id, session_id, system_prompt)
+        .await
+        .render()
+}
+
+/// モデルのコンテキストウィンドウに収まる会話履歴を、`agent_id` から見た
+/// チャットメッセージ列として構築する。
+///
+/// 自分の発言とツール呼び出し・結果は assistant / tool、他の参加者の発言は
+/// 名前付きの user メッセージになる。
+pub async fn build_history(
+    state: &AppState,
+    agent_id: &str,
+    session_id: &str,
+    system_prompt: &str,
+) -> Vec<opencrab_core::ChatMessage> {
+    conversation_window(state, agent_id, session_id, system_prompt)
+        .await
+        .to_messages(agent_id)
+}
+
+/// 会話履歴をコンテキストウィンドウに収める。
+///
+/// 収まらない場合は古いターンをローリング要約（`log_type = 'summary'`）に畳み込み、
+/// 最近のターンはそのまま残す。要約に失敗した場合は古いターンを省略する。
+async fn conversation_window(
+    state: &AppState,
+    agent_id: &str,
+    session_id: &str,
+    system_prompt: &str,
+) -> opencrab_core::ConversationWindow {
+    let model = agent_model(state, agent_id, "conversation");
+    let config =
+        opencrab_core::ContextWindowConfig::for_window(context_window_for(state, &model).await);
+    let budget = config.history_budget(opencrab_core::context::estimate_tokens(system_prompt));
+
+    let (mut summary, mut turns) = {
+        let conn = state.db.lock().unwrap();
+        load_conversation(&conn, session_id)
+    };
+
+    let fold = config.turns_to_fold(summary.as_ref(), &turns, budget);
+    if fold > 0 {
+        let summary_model = agent_model(state, agent_id, "analysis");
+        match summarize_turns(state, agent_id, session_id, &summary_model, summary.clone(), &turns[..fold], &config, budget).await {
+            Ok(updated) => {
+                summary = Some(updated);
+                turns.drain(..fold);
+            }
+            Err(e) => {
+                tracing::warn!(session_id = %session_id, error = %e, "Failed to summarise conversation, omitting older turns");
+            }
+        }
+    }
+
+    opencrab_core::ConversationWindow::fit(summary, turns, budget)
+}
+
+/// 古いターンを既存の要約と合わせて要約し直し、`summary` ログとして保存する。
+///
+/// 畳み込むターンが多い場合は予算に収まる塊ごとに順に要約する。
+#[allow(clippy::too_many_arguments)]
+async fn summarize_turns(
+    state: &AppState,
+    agent_id: &str,
+    session_id: &str,
+    model: &str,
+    mut summary: Option<opencrab_core::ConversationSummary>,
+    turns: &[opencrab_core::ConversationTurn],
+    config: &opencrab_core::ContextWindowConfig,
+    budget: usize,
+) -> anyhow::Result<opencrab_core::ConversationSummary> {
+    use opencrab_core::LlmClient;
+
+    let max_tokens = config.summary_max_tokens(budget);
+    let metrics_ctx = MetricsContext {
+        db: state.db.clone(),
+        agent_id: agent_id.to_string(),
+        session_id: Some(session_id.to_string()),
+        pricing: state.pricing.clone(),
+        last_metrics_id: Arc::new(std::sync::Mutex::new(None)),
+        current_purpose: Arc::new(std::sync::Mutex::new("analysis".to_string())),
+    };
+    let llm = LlmRouterAdapter::new(state.llm_router.clone())
+        .with_metrics(metrics_ctx)
+        .with_budget(crate::budget::BudgetGuard::new(state, agent_id, Some(session_id)));
+
+    for chunk in opencrab_core::context::summary_chunks(turns, budget.saturating_sub(max_tokens)) {
+        let request = opencrab_core::ChatRequestSimple {
+            model: model.to_string(),
+            messages: vec![
+                opencrab_core::ChatMessage::new("system", opencrab_core::context::SUMMARY_PROMPT),
+                opencrab_core::ChatMessage::new(
+                    "user",
+                    opencrab_core::context::summary_input(
+                        summary.as_ref().map(|s| s.content.as_str()),
+                        chunk,
+                    ),
+                ),
+            ],
+            tools: vec![],
+            temperature: Some(0.2),
+            max_tokens: Some(max_tokens as u32),
+            purpose: None,
+        };
+        let content = llm.chat(request).await?.content.unwrap_or_default();
+        let content = content.trim();
+        if content.is_empty() {
+            anyhow::bail!("Summary model returned an empty response");
+        }
+
+        let covers_until = chunk.last().map(|t| t.log_id).unwrap_or_default();
+        let log = opencrab_db::queries::SessionLogRow {
+            id: None,
+            agent_id: "system".to_string(),
+            session_id: session_id.to_string(),
+            log_type: SUMMARY_LOG_TYPE.to_string(),
+            content: content.to_string(),
+            speaker_id: None,
+            turn_number: None,
+            metadata_json: Some(
+                serde_json::json!({
+                    "covers_until": covers_until,
+                    "summarized_turns": chunk.len(),
+                    "model": model,
+                })
+                .to_string(),
+            ),
+        };
+        record_session_log(state, &log)?;
+        summary = Some(opencrab_core::ConversationSummary {
+            content: content.to_string(),
+            covers_until,
+        });
+    }
+
+    summary.ok_or_else(|| anyhow::anyhow!("Nothing to summarise"))
+}
+
+/// Discord管理アクション（gateway_admin が渡された場合のみ公開する）
+const GATEWAY_ADMIN_ACTIONS: &[&str] = &[
+    "discord_list_guilds",
+    "discord_list_channels",
+    "discord_channel_config",
+];
+
+/// エージェントに公開するツールの許可リストを計算する。
+///
+/// 有効なスキルが宣言する `actions` とベースアクションの和集合。
+/// どのスキルもアクションを宣言していない場合はベースアクションだけになる（制限を外すことはない）。
+/// スキルを読めなかった場合はエラーを返す。
+pub fn agent_tool_allowlist(
+    db: &Arc<std::sync::Mutex<rusqlite::Connection>>,
+    agent_id: &str,
+    base_actions: &[String],
+    with_gateway_admin: bool,
+) -> anyhow::Result<Vec<String>> {
+    let declared = opencrab_core::SkillManager::new(agent_id, db.clone()).declared_actions()?;
+
+    let mut allowed: Vec<String> = base_actions.iter().cloned().chain(declared).collect();
+    if with_gateway_admin {
+        allowed.extend(GATEWAY_ADMIN_ACTIONS.iter().map(|s| s.to_string()));
+    }
+    allowed.sort();
+    allowed.dedup();
+    Ok(allowed)
+}
+
+/// エージェントの埋め込みモデル（用途別設定 → `[llm.models] embedding`）を `Embedder` として返す（未設定なら None）。
+pub fn embedder(state: &AppState, agent_id: &str) -> Option<Arc<dyn opencrab_core::Embedder>> {
+    let model = agent_llm_config(state, agent_id)
+        .and_then(|c| c.models.embedding)
+        .map(|m| m.to_string())
+        .or_else(|| state.embedding_model.clone())?;
+    Some(Arc::new(crate::llm_adapter::EmbeddingAdapter::new(
+        state.llm_router.clone(),
+        model,
+    )) as Arc<dyn opencrab_core::Embedder>)
+}
+
+/// 1回の索引付けで埋め込む記憶の上限
+const EMBEDDING_INDEX_BATCH: usize = 256;
+
+/// まだベクトルのない記憶（セッションログ・キュレーション記憶）を埋め込む。
+///
+/// 検索時には埋め込まないので、記憶を書き込んだあとや統合ジョブから呼ぶ。
+/// 埋め込みモデルが未設定なら何もせず 0 を返す。
+pub async fn index_embeddings(state: &AppState, agent_id: &str) -> anyhow::Result<usize> {
+    let Some(embedder) = embedder(state, agent_id) else {
+        return Ok(0);
+    };
+    opencrab_core::MemoryManager::new(agent_id, state.db.clone())
+        .index_embeddings(embedder.as_ref(), EMBEDDING_INDEX_BATCH)
+        .await
+}
+
+/// [`index_embeddings`] をバックグラウンドで実行する。
+///
+/// 同じエージェントの索引付けが実行中なら新たに起動しない（取りこぼした記憶は次の書き込みで拾う）。
+pub fn spawn_embedding_index(state: &AppState, agent_id: &str) {
+    static INDEXING: OnceLock<std::sync::Mutex<HashSet<String>>> = OnceLock::new();
+
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    if embedder(state, agent_id).is_none() {
+        return;
+    }
+    let indexing = INDEXING.get_or_init(Default::default);
+    if !indexing.lock().unwrap().insert(agent_id.to_string()) {
+        return;
+    }
+    let (state, agent_id) = (state.clone(), agent_id.to_string());
+    runtime.spawn(async move {
+        if let Err(e) = index_embeddings(&state, &agent_id).await {
+            tracing::warn!(agent_id = %agent_id, error = %e, "Failed to index memory embeddings");
+        }
+        indexing.lock().unwrap().remove(&agent_id);
+    });
+}
+
+/// Soul から導いた実行時パラメータ（temperature・発言の重み）。Soul がなければ既定の特性で計算する
+pub fn behavior(state: &AppState, agent_id: &str) -> opencrab_core::BehaviorParams {
+    let soul = {
+        let conn = state.db.lock().unwrap();
+        opencrab_db::queries::get_soul(&conn, agent_id).ok().flatten()
+    };
+    let soul = soul
+        .map(|row| opencrab_core::Soul::from_row(&row))
+        .unwrap_or_else(|| opencrab_core::Soul::new(agent_id));
+    state.persona.behavior(&soul)
+}
+
+/// エージェント別のLLM設定（`agent_llm_config`）。未設定・読み込み失敗時は None
+pub fn agent_llm_config(state: &AppState, agent_id: &str) -> Option<opencrab_core::AgentLlmConfig> {
+    let conn = state.db.lock().unwrap();
+    opencrab_core::AgentLlmConfig::load(&conn, agent_id)
+        .inspect_err(|e| tracing::warn!(agent_id = %agent_id, error = %e, "Failed to load agent LLM config"))
+        .ok()
+        .flatten()
+}
+
+/// select_llm で選べるモデルの制約を組み立てる。
+///
+/// `[llm.self_selection]` の `enabled` と `allowed_aliases` を基本とし、エージェント別設定があれば
+/// `allow_self_selection` で無効化でき、`selectable_models`（空でなければ）で許可リストをさらに絞れる。
+/// 用途別モデル（エージェント別設定または `[llm.models]`）とサーバーの既定モデルには常に戻せる。
+pub fn model_policy(state: &AppState, agent_id: &str) -> opencrab_actions::ModelSelectionPolicy {
+    let mut policy = opencrab_actions::ModelSelectionPolicy {
+        router: state.llm_router.clone(),
+        enabled: state.self_selection.enabled,
+        allowed: state.self_selection.allowed_aliases.clone(),
+        agent_allowed: Vec::new(),
+        always_allowed: vec![state.default_model.clone()],
+    };
+    if let Some(config) = agent_llm_config(state, agent_id) {
+        policy.enabled &= config.allow_self_selection;
+        policy.agent_allowed = config.selectable_models.iter().map(|m| m.to_string()).collect();
+    }
+    policy.always_allowed.extend(
+        opencrab_core::MODEL_PURPOSES
+            .iter()
+            .map(|purpose| agent_model(state, agent_id, purpose)),
+    );
+    policy
+}
+
+/// 用途（conversation, thinking 等）に使うモデル（`provider:model`）を決める。
+///
+/// エージェント別設定があればその用途別モデル → その既定モデル、
+/// なければ `[llm.models]` の用途別モデル → サーバーの既定モデルの順。
+pub fn agent_model(state: &AppState, agent_id: &str, purpose: &str) -> String {
+    match agent_llm_config(state, agent_id) {
+        Some(config) => config.model_for(purpose).to_string(),
+        None => state
+            .llm_router
+            .purpose_model(purpose)
+            .map(String::from)
+            .unwrap_or_else(|| state.default_model.clone()),
     }
+}
 
-    let mut parts = Vec::new();
-    for log in &logs {
-        let speaker = log
-            .speaker_id
-            .as_deref()
-            .unwrap_or(&log.agent_id);
-        parts.push(format!("[{}]: {}", speaker, log.content));
+/// 用途に明示的に設定されたモデル（エージェント別設定、なければ `[llm.models]`）。
+///
+/// 既定モデルへのフォールバックはしない。
+fn configured_purpose_model(state: &AppState, agent_id: &str, purpose: &str) -> Option<String> {
+    match agent_llm_config(state, agent_id) {
+        Some(config) => config.models.for_purpose(purpose).map(|m| m.to_string()),
+        None => state.llm_router.purpose_model(purpose).map(String::from),
     }
+}
 
-    parts.join("\n")
+/// SkillEngine 実行時のパラメータ。
+///
+/// `run_agent_response` はセッション内の通常応答用のショートカット。
+/// ハートビートなどセッション外の実行やツールを絞った実行ではこちらを使う。
+pub struct AgentRun<'a> {
+    pub agent_id: &'a str,
+    pub agent_name: &'a str,
+    pub session_id: Option<&'a str>,
+    pub system_prompt: &'a str,
+    pub user_message: &'a str,
+    /// 会話履歴。指定時は `user_message` の代わりにこのメッセージ列を渡す
+    pub history: Option<Vec<opencrab_core::ChatMessage>>,
+    pub gateway: &'a str,
+    pub gateway_admin: Option<Arc<dyn opencrab_actions::GatewayAdmin>>,
+    /// 公開するアクションを明示的に指定する（`None` ならスキルから計算）
+    pub allowed_actions: Option<Vec<String>>,
+    /// メトリクスに記録する用途（conversation, thinking 等）
+    pub purpose: &'a str,
+    pub max_iterations: usize,
+    /// エンジンイベントの追加の受信先。指定するとテキスト差分もストリーミングする
+    pub events: Option<opencrab_core::EngineEventSink>,
+}
+
+impl<'a> AgentRun<'a> {
+    pub fn new(
+        agent_id: &'a str,
+        agent_name: &'a str,
+        system_prompt: &'a str,
+        user_message: &'a str,
+    ) -> Self {
+        Self {
+            agent_id,
+            agent_name,
+            session_id: None,
+            system_prompt,
+            user_message,
+            history: None,
+            gateway: "internal",
+            gateway_admin: None,
+            allowed_actions: None,
+            purpose: "conversation",
+            max_iterations: 5,
+            events: None,
+        }
+    }
 }
 
-/// エージェントにメッセージを処理させ、応答テキストを返す。
+/// セッションの会話履歴に対してエージェントに応答させる。
 ///
-/// SkillEngine + BridgedExecutor + LlmRouterAdapter のフルパイプラインを実行する。
+/// 履歴は `build_history` で構築し、SkillEngine + BridgedExecutor + LlmRouterAdapter の
+/// フルパイプラインを実行する。
 pub async fn run_agent_response(
     state: &AppState,
     agent_id: &str,
     agent_name: &str,
     session_id: &str,
     system_prompt: &str,
-    conversation: &str,
     gateway: &str,
     gateway_admin: Option<Arc<dyn opencrab_actions::GatewayAdmin>>,
 ) -> anyhow::Result<opencrab_core::EngineResult> {
+    let history = build_history(state, agent_id, session_id, system_prompt).await;
+    let run = AgentRun {
+        session_id: Some(session_id),
+        history: Some(history),
+        gateway,
+        gateway_admin,
+        ..AgentRun::new(agent_id, agent_name, system_prompt, "")
+    };
+    run_agent(state, run).await
+}
+
+/// `AgentRun` の設定で SkillEngine を実行する。
+pub async fn run_agent(
+    state: &AppState,
+    run: AgentRun<'_>,
+) -> anyhow::Result<opencrab_core::EngineResult> {
+    let agent_id = run.agent_id;
+
     // Build workspace path for this agent.
     let ws_path = format!("{}/{}", state.workspace_base, agent_id);
     std::fs::create_dir_all(&ws_path).ok();
@@ -126,55 +866,515 @@ pub async fn run_agent_response(
     // Create BridgedExecutor with ActionContext.
     let last_metrics_id = Arc::new(std::sync::Mutex::new(None));
     let model_override = Arc::new(std::sync::Mutex::new(None));
-    let current_purpose =
-        Arc::new(std::sync::Mutex::new("conversation".to_string()));
+    let current_purpose = Arc::new(std::sync::Mutex::new(run.purpose.to_string()));
+    let default_model = agent_model(state, agent_id, run.purpose);
+    let policy = Arc::new(model_policy(state, agent_id));
+    if let Some(session_id) = run.session_id {
+        *model_override.lock().unwrap() =
+            session_model_override(state, &policy, agent_id, session_id);
+    }
 
     let runtime_info = opencrab_actions::RuntimeInfo {
-        default_model: state.default_model.clone(),
+        default_model: default_model.clone(),
         active_model: model_override.lock().unwrap().clone(),
         available_providers: state.llm_router.provider_names().into_iter().map(String::from).collect(),
-        gateway: gateway.to_string(),
+        gateway: run.gateway.to_string(),
+    };
+
+    let allowlist = match run.allowed_actions {
+        Some(allowed) => allowed,
+        None => agent_tool_allowlist(
+            &state.db,
+            agent_id,
+            &state.base_actions,
+            run.gateway_admin.is_some(),
+        )?,
     };
 
     let ctx = opencrab_actions::ActionContext {
         agent_id: agent_id.to_string(),
-        agent_name: agent_name.to_string(),
-        session_id: Some(session_id.to_string()),
+        agent_name: run.agent_name.to_string(),
+        session_id: run.session_id.map(String::from),
         db: state.db.clone(),
         workspace: Arc::new(workspace),
         last_metrics_id: last_metrics_id.clone(),
         model_override: model_override.clone(),
         current_purpose: current_purpose.clone(),
         runtime_info: Arc::new(std::sync::Mutex::new(runtime_info)),
-        gateway_admin,
+        gateway_admin: run.gateway_admin,
+        embedder: embedder(state, agent_id),
+        memory_policy: state.memory_policy.clone(),
+        model_policy: Some(policy),
+        session_log_sink: Some(Arc::new(SessionLogPublisher::new(state))),
     };
     let dispatcher = opencrab_actions::ActionDispatcher::new();
-    let executor = opencrab_actions::BridgedExecutor::new(dispatcher, ctx);
+    let executor = opencrab_actions::BridgedExecutor::new(dispatcher, ctx).with_allowed_actions(allowlist);
 
     // Create LlmRouterAdapter with metrics recording.
     let metrics_ctx = MetricsContext {
         db: state.db.clone(),
         agent_id: agent_id.to_string(),
-        session_id: Some(session_id.to_string()),
-        pricing: PricingRegistry::default(),
+        session_id: run.session_id.map(String::from),
+        pricing: state.pricing.clone(),
         last_metrics_id: last_metrics_id.clone(),
         current_purpose: current_purpose.clone(),
     };
-    let llm_client = LlmRouterAdapter::new(state.llm_router.clone()).with_metrics(metrics_ctx);
+    let llm_client = LlmRouterAdapter::new(state.llm_router.clone())
+        .with_metrics(metrics_ctx)
+        .with_budget(crate::budget::BudgetGuard::new(state, agent_id, run.session_id));
+
+    // Forward engine events to WebSocket subscribers and the caller's sink.
+    let streaming = run.events.is_some();
+    let (event_tx, event_rx) = tokio::sync::mpsc::unbounded_channel();
+    let forwarder = tokio::spawn(forward_engine_events(
+        state.ws_gateway.clone(),
+        run.session_id.map(String::from),
+        agent_id.to_string(),
+        event_rx,
+        run.events,
+    ));
 
     // Run SkillEngine with model_override for dynamic switching.
     let engine = opencrab_core::SkillEngine::new(
         Box::new(llm_client),
         Box::new(executor),
-        5, // max iterations
+        run.max_iterations,
+    )
+    .with_event_sink(event_tx)
+    .with_streaming(streaming)
+    .with_tool_calling_model(configured_purpose_model(state, agent_id, "tool_calling"))
+    .with_temperature(behavior(state, agent_id).temperature);
+
+    let result = match run.history {
+        Some(history) => {
+            engine
+                .run_with_history(
+                    run.system_prompt,
+                    history,
+                    &default_model,
+                    Some(model_override),
+                )
+                .await
+        }
+        None => {
+            engine
+                .run_with_model_override(
+                    run.system_prompt,
+                    run.user_message,
+                    &default_model,
+                    Some(model_override),
+                )
+                .await
+        }
+    };
+
+    // エンジンを破棄して送信口を閉じ、残りのイベントを流し切る
+    drop(engine);
+    forwarder.await.ok();
+
+    // 次回以降の履歴に含めるため、ツール呼び出しと結果をセッションログに残す
+    if let (Ok(result), Some(session_id)) = (&result, run.session_id) {
+        if let Err(e) = record_tool_transcript(state, agent_id, session_id, &result.transcript) {
+            tracing::warn!(agent_id = %agent_id, error = %e, "Failed to record tool calls");
+        }
+    }
+    // アクションが保存した記憶も検索できるように埋め込む
+    spawn_embedding_index(state, agent_id);
+
+    result
+}
+
+/// select_llm で this_session / until_task_complete を指定したモデルを読み込む。
+///
+/// 設定変更などで現在のポリシーでは選べなくなっていれば解除する。
+fn session_model_override(
+    state: &AppState,
+    policy: &opencrab_actions::ModelSelectionPolicy,
+    agent_id: &str,
+    session_id: &str,
+) -> Option<String> {
+    let conn = state.db.lock().unwrap();
+    let (model, _) =
+        opencrab_db::queries::get_agent_model_override(&conn, agent_id, session_id).ok()??;
+    match policy.check(&model) {
+        Ok(model) => Some(model),
+        Err(e) => {
+            tracing::warn!(agent_id = %agent_id, model = %model, error = %e, "Dropping saved model selection");
+            opencrab_db::queries::set_agent_model_override(&conn, agent_id, session_id, None, None)
+                .ok();
+            None
+        }
+    }
+}
+
+/// ツール結果をセッションログに保存する際の上限（トークン概算）
+const MAX_TOOL_RESULT_TOKENS: usize = 2_000;
+
+/// エンジンのツール呼び出し（assistant）と結果（tool）を
+/// `tool_call` / `tool_result` ログとして保存する。
+fn record_tool_transcript(
+    state: &AppState,
+    agent_id: &str,
+    session_id: &str,
+    transcript: &[opencrab_core::ChatMessage],
+) -> anyhow::Result<()> {
+    let mut names: HashMap<String, String> = HashMap::new();
+    for message in transcript {
+        let (log_type, content, metadata) = if !message.tool_calls.is_empty() {
+            let calls: Vec<String> = message
+                .tool_calls
+                .iter()
+                .map(|c| {
+                    names.insert(c.id.clone(), c.name.clone());
+                    format!("{}({})", c.name, c.arguments)
+                })
+                .collect();
+            let content = if message.content.is_empty() {
+                calls.join("\n")
+            } else {
+                format!("{}\n{}", message.content, calls.join("\n"))
+            };
+            (
+                opencrab_db::queries::LOG_TYPE_TOOL_CALL,
+                content,
+                serde_json::json!({"tool_calls": message.tool_calls}),
+            )
+        } else if let Some(tool_call_id) = &message.tool_call_id {
+            (
+                opencrab_db::queries::LOG_TYPE_TOOL_RESULT,
+                opencrab_core::context::truncate_to_tokens(&message.content, MAX_TOOL_RESULT_TOKENS),
+                serde_json::json!({
+                    "tool_call_id": tool_call_id,
+                    "name": names.get(tool_call_id),
+                }),
+            )
+        } else {
+            continue;
+        };
+        let log = opencrab_db::queries::SessionLogRow {
+            id: None,
+            agent_id: agent_id.to_string(),
+            session_id: session_id.to_string(),
+            log_type: log_type.to_string(),
+            content,
+            speaker_id: Some(agent_id.to_string()),
+            turn_number: None,
+            metadata_json: Some(metadata.to_string()),
+        };
+        record_session_log(state, &log)?;
+    }
+    Ok(())
+}
+
+async fn forward_engine_events(
+    ws_gateway: Arc<opencrab_gateway::WebSocketGateway>,
+    session_id: Option<String>,
+    agent_id: String,
+    mut rx: tokio::sync::mpsc::UnboundedReceiver<opencrab_core::EngineEvent>,
+    sink: Option<opencrab_core::EngineEventSink>,
+) {
+    while let Some(event) = rx.recv().await {
+        let is_tool_event = matches!(
+            event,
+            opencrab_core::EngineEvent::ToolCallStarted { .. }
+                | opencrab_core::EngineEvent::ToolCallFinished { .. }
+        );
+        if let (true, Some(session_id)) = (is_tool_event, &session_id) {
+            ws_gateway.publish(
+                session_id,
+                opencrab_gateway::ServerFrame::ToolEvent {
+                    session_id: session_id.clone(),
+                    agent_id: agent_id.clone(),
+                    event: serde_json::to_value(&event).unwrap_or_default(),
+                },
+            );
+        }
+        if let Some(sink) = &sink {
+            let _ = sink.send(event);
+        }
+    }
+}
+
+/// アクションが書くセッションログを `record_session_log` に通す記録先
+pub struct SessionLogPublisher {
+    state: AppState,
+}
+
+impl SessionLogPublisher {
+    pub fn new(state: &AppState) -> Self {
+        Self { state: state.clone() }
+    }
+}
+
+impl opencrab_actions::SessionLogSink for SessionLogPublisher {
+    fn record(&self, log: &opencrab_db::queries::SessionLogRow) -> anyhow::Result<i64> {
+        record_session_log(&self.state, log)
+    }
+}
+
+/// セッションログを保存し、WebSocketの購読者へ `log` フレームとして配信する。
+pub fn record_session_log(
+    state: &AppState,
+    log: &opencrab_db::queries::SessionLogRow,
+) -> anyhow::Result<i64> {
+    let log_id = {
+        let conn = state.db.lock().unwrap();
+        opencrab_db::queries::insert_session_log(&conn, log)?
+    };
+
+    let mut saved = log.clone();
+    saved.id = Some(log_id);
+    state.ws_gateway.publish(
+        &log.session_id,
+        opencrab_gateway::ServerFrame::Log {
+            session_id: log.session_id.clone(),
+            log: serde_json::to_value(&saved).unwrap_or_default(),
+        },
     );
+    spawn_embedding_index(state, &log.agent_id);
 
-    engine
-        .run_with_model_override(
-            system_prompt,
-            conversation,
-            &state.default_model,
-            Some(model_override),
-        )
-        .await
+    Ok(log_id)
+}
+
+/// セッション内の1エージェントの応答。
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SessionReply {
+    pub agent_id: String,
+    pub agent_name: String,
+    pub content: String,
+    pub tool_calls_made: usize,
+}
+
+/// セッション処理の進捗イベント（SSE配信用）。
+#[derive(Debug, Clone)]
+pub enum SessionEvent {
+    /// 投稿メッセージを記録した
+    Accepted { log_id: i64 },
+    /// エージェントが応答を開始した
+    AgentStarted { agent_id: String, agent_name: String },
+    /// エージェント実行中のエンジンイベント
+    Engine {
+        agent_id: String,
+        event: opencrab_core::EngineEvent,
+    },
+    /// エージェントの応答が確定した
+    Reply(SessionReply),
+}
+
+pub type SessionEventSink = tokio::sync::mpsc::UnboundedSender<SessionEvent>;
+
+/// セッションへの投稿を処理した結果。
+#[derive(Debug, Clone)]
+pub struct SessionMessageOutcome {
+    /// 投稿メッセージのログID
+    pub log_id: i64,
+    /// LLMプロバイダーが無い場合は `None`（投稿の記録のみ）
+    pub replies: Option<Vec<SessionReply>>,
+}
+
+/// セッションへの投稿の送信者を確認する（REST・WebSocket 共通の規則）。
+///
+/// 登録済みのエージェントIDは参加者に限り、それ以外のID（`user` など）は人間の送信者として受け付ける。
+/// 送信者IDの認証は行わない。
+pub fn check_sender(state: &AppState, session_id: &str, sender_id: &str) -> anyhow::Result<()> {
+    if sender_id.trim().is_empty() {
+        anyhow::bail!("agent_id is required");
+    }
+    let conn = state.db.lock().unwrap();
+    let Some(session) = opencrab_db::queries::get_session(&conn, session_id)? else {
+        anyhow::bail!("Session not found: {session_id}");
+    };
+    let participants: Vec<String> = serde_json::from_str(&session.participant_ids_json).unwrap_or_default();
+    if !participants.iter().any(|id| id == sender_id)
+        && opencrab_db::queries::get_identity(&conn, sender_id)?.is_some()
+    {
+        anyhow::bail!("Agent {sender_id} is not a participant of session {session_id}");
+    }
+    Ok(())
+}
+
+/// セッションへの投稿を記録し、送信者以外の参加者に応答させる。
+///
+/// REST API (`POST /api/sessions/{id}/messages`) と WebSocketゲートウェイの
+/// 両方から利用される。送信者は [`check_sender`] の規則で確認する。
+/// `events` を渡すと進捗とテキスト差分を逐次送る。
+pub async fn handle_session_message(
+    state: &AppState,
+    session_id: &str,
+    sender_id: &str,
+    content: &str,
+    gateway: &str,
+    events: Option<&SessionEventSink>,
+) -> anyhow::Result<SessionMessageOutcome> {
+    let notify = |event: SessionEvent| {
+        if let Some(sink) = events {
+            let _ = sink.send(event);
+        }
+    };
+
+    // 1. Log the sender's message.
+    check_sender(state, session_id, sender_id)?;
+    let log = opencrab_db::queries::SessionLogRow {
+        id: None,
+        agent_id: sender_id.to_string(),
+        session_id: session_id.to_string(),
+        log_type: "speech".to_string(),
+        content: content.to_string(),
+        speaker_id: Some(sender_id.to_string()),
+        turn_number: None,
+        metadata_json: None,
+    };
+    let log_id = record_session_log(state, &log)?;
+    notify(SessionEvent::Accepted { log_id });
+
+    // 2. Check if LLM providers are available. If none, fall back to legacy behavior.
+    if state.llm_router.provider_names().is_empty() {
+        return Ok(SessionMessageOutcome {
+            log_id,
+            replies: None,
+        });
+    }
+
+    // 3. Get session and participant IDs.
+    let session = {
+        let conn = state.db.lock().unwrap();
+        opencrab_db::queries::get_session(&conn, session_id)?
+    }
+    .ok_or_else(|| anyhow::anyhow!("Session not found: {session_id}"))?;
+    let participant_ids: Vec<String> =
+        serde_json::from_str(&session.participant_ids_json).unwrap_or_default();
+
+    // A background runner is driving this session; it will pick up the message.
+    if session.status == crate::session_runner::STATUS_RUNNING {
+        return Ok(SessionMessageOutcome {
+            log_id,
+            replies: Some(Vec::new()),
+        });
+    }
+
+    // Facilitated sessions advance one turn; the facilitator picks who replies.
+    if session.mode == crate::orchestrator::FACILITATED_MODE {
+        let mut replies = Vec::new();
+        if session.status == "active" {
+            let turn = crate::orchestrator::run_facilitated_turn(state, session_id, events).await?;
+            if let (Some(agent_id), Some(agent_name), Some(content)) =
+                (turn.speaker_id, turn.speaker_name, turn.content)
+            {
+                replies.push(SessionReply {
+                    agent_id,
+                    agent_name,
+                    content,
+                    tool_calls_made: turn.tool_calls_made,
+                });
+            }
+        }
+        return Ok(SessionMessageOutcome {
+            log_id,
+            replies: Some(replies),
+        });
+    }
+
+    // 4. For each participant (except the sender), run SkillEngine.
+    let mut replies = Vec::new();
+
+    for agent_id in &participant_ids {
+        if agent_id == sender_id {
+            continue;
+        }
+
+        // Build agent context and conversation history from DB.
+        let input = PromptInput {
+            session_id: Some(session_id),
+            session_theme: &session.theme,
+            gateway,
+            with_memories: true,
+        };
+        let (system_prompt, agent_name) = build_system_prompt(state, agent_id, &input).await;
+        let history = build_history(state, agent_id, session_id, &system_prompt).await;
+
+        notify(SessionEvent::AgentStarted {
+            agent_id: agent_id.clone(),
+            agent_name: agent_name.clone(),
+        });
+
+        // Relay this agent's engine events to the session sink.
+        let (relay_tx, relay_task) = match events {
+            Some(sink) => {
+                let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+                let sink = sink.clone();
+                let agent_id = agent_id.clone();
+                let task = tokio::spawn(async move {
+                    while let Some(event) = rx.recv().await {
+                        let _ = sink.send(SessionEvent::Engine {
+                            agent_id: agent_id.clone(),
+                            event,
+                        });
+                    }
+                });
+                (Some(tx), Some(task))
+            }
+            None => (None, None),
+        };
+
+        // Run agent through the shared pipeline.
+        let run = AgentRun {
+            session_id: Some(session_id),
+            gateway,
+            events: relay_tx,
+            history: Some(history),
+            ..AgentRun::new(agent_id, &agent_name, &system_prompt, "")
+        };
+        let result = run_agent(state, run).await;
+        if let Some(task) = relay_task {
+            task.await.ok();
+        }
+
+        match result {
+            Ok(engine_result) => {
+                // Log the agent's response.
+                let response_log = opencrab_db::queries::SessionLogRow {
+                    id: None,
+                    agent_id: agent_id.clone(),
+                    session_id: session_id.to_string(),
+                    log_type: "speech".to_string(),
+                    content: engine_result.response.clone(),
+                    speaker_id: Some(agent_id.clone()),
+                    turn_number: None,
+                    metadata_json: Some(
+                        serde_json::json!({
+                            "iterations": engine_result.iterations,
+                            "tool_calls_made": engine_result.tool_calls_made,
+                        })
+                        .to_string(),
+                    ),
+                };
+                record_session_log(state, &response_log).ok();
+
+                let reply = SessionReply {
+                    agent_id: agent_id.clone(),
+                    agent_name,
+                    content: engine_result.response,
+                    tool_calls_made: engine_result.tool_calls_made,
+                };
+                notify(SessionEvent::Reply(reply.clone()));
+                replies.push(reply);
+            }
+            Err(e) => {
+                tracing::error!(agent_id = %agent_id, error = %e, "SkillEngine failed");
+                let reply = SessionReply {
+                    agent_id: agent_id.clone(),
+                    agent_name,
+                    content: format!("(Error: {})", e),
+                    tool_calls_made: 0,
+                };
+                notify(SessionEvent::Reply(reply.clone()));
+                replies.push(reply);
+            }
+        }
+    }
+
+    Ok(SessionMessageOutcome {
+        log_id,
+        replies: Some(replies),
+    })
 }
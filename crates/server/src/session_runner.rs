@@ -0,0 +1,353 @@
+//! セッションの自律実行（人の入力なしでターンを進め続ける）。
+//!
+//! 各セッションごとにバックグラウンドタスクを持ち、`orchestrator` の1ターンを
+//! 繰り返す。状態は `sessions.status` に保存する:
+//! `running`（実行中）/ `paused`（一時停止）/ `stopped`（停止）/ `completed`（終了条件到達）。
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::{watch, Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::orchestrator;
+use crate::process;
+use crate::AppState;
+
+pub const STATUS_RUNNING: &str = "running";
+pub const STATUS_PAUSED: &str = "paused";
+pub const STATUS_STOPPED: &str = "stopped";
+
+struct RunnerEntry {
+    shutdown_tx: watch::Sender<bool>,
+    handle: JoinHandle<()>,
+    /// ループの終了で閉じる（送信側はループのタスクが持つ）
+    exited: watch::Receiver<()>,
+    interval_ms: u64,
+    started_at: String,
+    turns: Arc<AtomicU64>,
+}
+
+/// セッション実行の稼働状況
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionRunnerStatus {
+    pub session_id: String,
+    pub running: bool,
+    /// `sessions.status` の値
+    pub status: Option<String>,
+    pub interval_ms: Option<u64>,
+    pub started_at: Option<String>,
+    /// このランナーで進めたターン数
+    pub turns: u64,
+}
+
+/// セッションごとの自律実行ループを管理する
+pub struct SessionRunnerManager {
+    default_interval_ms: u64,
+    entries: RwLock<HashMap<String, RunnerEntry>>,
+    /// 開始・停止を直列化する（ループの終了はロックの外で待つ）
+    control: Mutex<()>,
+}
+
+impl SessionRunnerManager {
+    pub fn new(default_interval_ms: u64) -> Self {
+        Self {
+            default_interval_ms,
+            entries: RwLock::new(HashMap::new()),
+            control: Mutex::new(()),
+        }
+    }
+
+    /// 実行を開始（一時停止中なら再開）する。
+    ///
+    /// 終了・停止済みのセッションは開始できない。
+    pub async fn run(
+        &self,
+        state: AppState,
+        session_id: &str,
+        interval_ms: Option<u64>,
+    ) -> anyhow::Result<SessionRunnerStatus> {
+        loop {
+            let control = self.control.lock().await;
+            let previous = match self.entries.read().await.get(session_id) {
+                Some(entry) if !entry.handle.is_finished() && *entry.shutdown_tx.borrow() => {
+                    Some(entry.exited.clone())
+                }
+                _ => None,
+            };
+            // 停止を通知した旧ループが残りのターンを終えるまで待つ（並行して新しいループを起動しない）
+            let Some(mut exited) = previous else {
+                return self.start(state, session_id, interval_ms).await;
+            };
+            drop(control);
+            let _ = exited.changed().await;
+        }
+    }
+
+    /// `control` を保持した状態でループを起動する
+    async fn start(
+        &self,
+        state: AppState,
+        session_id: &str,
+        interval_ms: Option<u64>,
+    ) -> anyhow::Result<SessionRunnerStatus> {
+        let session = {
+            let conn = state.db.lock().unwrap();
+            opencrab_db::queries::get_session(&conn, session_id)?
+        }
+        .ok_or_else(|| anyhow::anyhow!("Session not found: {session_id}"))?;
+        if !matches!(session.status.as_str(), "active" | STATUS_RUNNING | STATUS_PAUSED) {
+            anyhow::bail!("Session cannot be run: {}", session.status);
+        }
+        if state.llm_router.provider_names().is_empty() {
+            anyhow::bail!("No LLM providers configured");
+        }
+
+        // 既に実行中ならそのまま（ターンが並行して進まないようにする）
+        if let Some(entry) = self.entries.read().await.get(session_id) {
+            if !entry.handle.is_finished() && session.status == STATUS_RUNNING {
+                let mut status = Self::entry_status(session_id, entry);
+                status.status = Some(session.status);
+                return Ok(status);
+            }
+        }
+
+        {
+            let conn = state.db.lock().unwrap();
+            opencrab_db::queries::update_session_status(&conn, session_id, STATUS_RUNNING)?;
+        }
+
+        let interval_ms = interval_ms.unwrap_or(self.default_interval_ms);
+        let turns = Arc::new(AtomicU64::new(0));
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let (exited_tx, exited) = watch::channel(());
+        let handle = tokio::spawn({
+            let (state, session_id, turns) = (state.clone(), session_id.to_string(), turns.clone());
+            async move {
+                let _exited = exited_tx;
+                run_loop(state, session_id, interval_ms, turns, shutdown_rx).await;
+            }
+        });
+
+        let entry = RunnerEntry {
+            shutdown_tx,
+            handle,
+            exited,
+            interval_ms,
+            started_at: chrono::Utc::now().to_rfc3339(),
+            turns,
+        };
+        let mut status = Self::entry_status(session_id, &entry);
+        status.status = Some(STATUS_RUNNING.to_string());
+        self.entries
+            .write()
+            .await
+            .insert(session_id.to_string(), entry);
+
+        info!(session_id = %session_id, interval_ms, "Session runner started");
+        Ok(status)
+    }
+
+    /// 一時停止する。実行中のターンはバックグラウンドで最後まで進む（終了は待たない）。
+    /// 一時停止した場合 true を返す。
+    pub async fn pause(&self, state: &AppState, session_id: &str) -> anyhow::Result<bool> {
+        self.transition(state, session_id, STATUS_PAUSED, &[STATUS_RUNNING])
+            .await
+    }
+
+    /// 停止する。停止後は再開できない。停止した場合 true を返す。
+    pub async fn stop(&self, state: &AppState, session_id: &str) -> anyhow::Result<bool> {
+        self.transition(
+            state,
+            session_id,
+            STATUS_STOPPED,
+            &["active", STATUS_RUNNING, STATUS_PAUSED],
+        )
+        .await
+    }
+
+    /// 稼働状況を取得する
+    pub async fn status(&self, state: &AppState, session_id: &str) -> SessionRunnerStatus {
+        let db_status = {
+            let conn = state.db.lock().unwrap();
+            opencrab_db::queries::get_session(&conn, session_id)
+                .ok()
+                .flatten()
+                .map(|s| s.status)
+        };
+        let entries = self.entries.read().await;
+        let mut status = match entries.get(session_id) {
+            Some(entry) => Self::entry_status(session_id, entry),
+            None => SessionRunnerStatus {
+                session_id: session_id.to_string(),
+                running: false,
+                status: None,
+                interval_ms: None,
+                started_at: None,
+                turns: 0,
+            },
+        };
+        status.status = db_status;
+        status
+    }
+
+    /// `running` のまま残っているセッションの実行を再開する（サーバー起動時）
+    pub async fn restore_from_db(&self, state: &AppState) {
+        let sessions = {
+            let conn = state.db.lock().unwrap();
+            opencrab_db::queries::list_sessions(&conn).unwrap_or_default()
+        };
+        for session in sessions.iter().filter(|s| s.status == STATUS_RUNNING) {
+            if let Err(e) = self.run(state.clone(), &session.id, None).await {
+                warn!(session_id = %session.id, error = %e, "Failed to resume session runner");
+            }
+        }
+    }
+
+    /// 全ランナーを止め、ループの終了を待つ（`sessions.status` は変更しない）
+    pub async fn shutdown_all(&self) {
+        let exits: Vec<watch::Receiver<()>> = {
+            let _control = self.control.lock().await;
+            let entries = self.entries.read().await;
+            entries
+                .values()
+                .map(|entry| {
+                    let _ = entry.shutdown_tx.send(true);
+                    entry.exited.clone()
+                })
+                .collect()
+        };
+        for mut exited in exits {
+            let _ = exited.changed().await;
+        }
+    }
+
+    async fn transition(
+        &self,
+        state: &AppState,
+        session_id: &str,
+        to: &str,
+        from: &[&str],
+    ) -> anyhow::Result<bool> {
+        let _control = self.control.lock().await;
+        let changed = {
+            let conn = state.db.lock().unwrap();
+            let session = opencrab_db::queries::get_session(&conn, session_id)?
+                .ok_or_else(|| anyhow::anyhow!("Session not found: {session_id}"))?;
+            let changed = from.contains(&session.status.as_str());
+            if changed {
+                opencrab_db::queries::update_session_status(&conn, session_id, to)?;
+            }
+            changed
+        };
+        self.halt(session_id).await;
+        if changed {
+            info!(session_id = %session_id, status = %to, "Session runner state changed");
+        }
+        Ok(changed)
+    }
+
+    /// ループに停止を通知する。進行中のターンは中断せず最後まで進める。
+    ///
+    /// 終了は待たない。次の `run` が旧ループの終了を待ってから新しいループを起動する。
+    async fn halt(&self, session_id: &str) {
+        if let Some(entry) = self.entries.read().await.get(session_id) {
+            let _ = entry.shutdown_tx.send(true);
+        }
+    }
+
+    fn entry_status(session_id: &str, entry: &RunnerEntry) -> SessionRunnerStatus {
+        SessionRunnerStatus {
+            session_id: session_id.to_string(),
+            running: !entry.handle.is_finished(),
+            status: None,
+            interval_ms: Some(entry.interval_ms),
+            started_at: Some(entry.started_at.clone()),
+            turns: entry.turns.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 終了条件・一時停止・停止まで1ターンずつ進める。
+async fn run_loop(
+    state: AppState,
+    session_id: String,
+    interval_ms: u64,
+    turns: Arc<AtomicU64>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) {
+    // 全員が続けて send_noreact した場合は話が尽きたとみなして一時停止する
+    let mut consecutive_noreact = 0usize;
+
+    loop {
+        if *shutdown_rx.borrow() {
+            break;
+        }
+
+        let outcome = match orchestrator::run_facilitated_turn(&state, &session_id, None).await {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                warn!(session_id = %session_id, error = %e, "Session turn failed, pausing");
+                pause_with_log(&state, &session_id, &format!("Session paused: {e}"));
+                break;
+            }
+        };
+        turns.fetch_add(1, Ordering::Relaxed);
+
+        if outcome.ended.is_some() {
+            info!(session_id = %session_id, reason = ?outcome.ended, "Session completed");
+            break;
+        }
+        consecutive_noreact = if outcome.noreact {
+            consecutive_noreact + 1
+        } else {
+            0
+        };
+        if consecutive_noreact > 0 && consecutive_noreact >= outcome.active_speakers {
+            pause_with_log(
+                &state,
+                &session_id,
+                "Session paused: every participant passed (send_noreact)",
+            );
+            break;
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_millis(interval_ms)) => {}
+            _ = shutdown_rx.changed() => {}
+        }
+    }
+}
+
+fn pause_with_log(state: &AppState, session_id: &str, content: &str) {
+    {
+        let conn = state.db.lock().unwrap();
+        // 停止などで既に状態が変わっていれば上書きしない
+        let running = opencrab_db::queries::get_session(&conn, session_id)
+            .ok()
+            .flatten()
+            .is_some_and(|s| s.status == STATUS_RUNNING);
+        if !running {
+            return;
+        }
+        if let Err(e) = opencrab_db::queries::update_session_status(&conn, session_id, STATUS_PAUSED)
+        {
+            warn!(session_id = %session_id, error = %e, "Failed to pause session");
+        }
+    }
+    let log = opencrab_db::queries::SessionLogRow {
+        id: None,
+        agent_id: "system".to_string(),
+        session_id: session_id.to_string(),
+        log_type: "system".to_string(),
+        content: content.to_string(),
+        speaker_id: None,
+        turn_number: None,
+        metadata_json: None,
+    };
+    process::record_session_log(state, &log).ok();
+}
@@ -0,0 +1,36 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use rusqlite::Connection;
+
+use opencrab_core::skill_loader::{self, SkillFile, SkillSyncReport};
+use opencrab_core::SkillManager;
+
+/// スキルファイルを読み込み、指定エージェントの標準スキルとして同期する
+pub fn sync_agent_skills(
+    db: &Arc<Mutex<Connection>>,
+    skills_dir: &str,
+    agent_id: &str,
+) -> Result<SkillSyncReport> {
+    let files = skill_loader::load_skill_dir(skills_dir)?;
+    SkillManager::new(agent_id, db.clone()).sync_skill_files(&files)
+}
+
+/// 全エージェントに標準スキルを同期する（スキルファイルは一度だけ読み込む）
+pub fn sync_all_agents(
+    db: &Arc<Mutex<Connection>>,
+    skills_dir: &str,
+) -> Result<Vec<(String, SkillSyncReport)>> {
+    let files: Vec<SkillFile> = skill_loader::load_skill_dir(skills_dir)?;
+    let agent_ids = {
+        let conn = db.lock().unwrap();
+        opencrab_db::queries::list_agent_ids(&conn)?
+    };
+
+    let mut reports = Vec::with_capacity(agent_ids.len());
+    for agent_id in agent_ids {
+        let report = SkillManager::new(agent_id.as_str(), db.clone()).sync_skill_files(&files)?;
+        reports.push((agent_id, report));
+    }
+    Ok(reports)
+}
@@ -0,0 +1,140 @@
+//! WebSocketゲートウェイのHTTP側（`/ws` ルート）とメッセージ処理ループ。
+//!
+//! フレームプロトコルは `opencrab_gateway::adapters::websocket` の
+//! `ClientFrame` / `ServerFrame` を参照。
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        State, WebSocketUpgrade,
+    },
+    response::Response,
+};
+use futures::{SinkExt, StreamExt};
+use opencrab_gateway::{IncomingMessage, MessageSource, OutgoingMessage, ServerFrame};
+
+use crate::process;
+use crate::AppState;
+
+/// `GET /ws` — WebSocketへアップグレードする。
+pub async fn ws_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
+    ws.on_upgrade(move |socket| handle_socket(state, socket))
+}
+
+async fn handle_socket(state: AppState, socket: WebSocket) {
+    let gateway = state.ws_gateway.clone();
+    let (connection_id, mut frames) = gateway.open_connection();
+    let (mut sink, mut stream) = socket.split();
+
+    // ゲートウェイからのフレームをソケットへ書き出す
+    let writer = tokio::spawn(async move {
+        while let Some(frame) = frames.recv().await {
+            let text = match serde_json::to_string(&frame) {
+                Ok(text) => text,
+                Err(e) => {
+                    tracing::warn!(error = %e, "Failed to serialize WebSocket frame");
+                    continue;
+                }
+            };
+            if sink.send(Message::Text(text.into())).await.is_err() {
+                break;
+            }
+        }
+        let _ = sink.close().await;
+    });
+
+    while let Some(Ok(message)) = stream.next().await {
+        match message {
+            Message::Text(text) => gateway.handle_text(&connection_id, text.as_str()).await,
+            Message::Close(_) => break,
+            // Ping/Pong はaxum側で処理される。バイナリフレームは非対応。
+            _ => {}
+        }
+    }
+
+    gateway.close_connection(&connection_id);
+    writer.abort();
+}
+
+/// WebSocketから投稿されたメッセージを処理し続けるループ。
+///
+/// 各メッセージは `POST /api/sessions/{id}/messages` と同じ処理を行い、
+/// エージェントの応答を `response` フレームとしてセッションの購読者へ配信する。
+pub async fn run_message_loop(state: AppState) {
+    loop {
+        let incoming = match state.ws_gateway.next_message().await {
+            Ok(incoming) => incoming,
+            Err(e) => {
+                tracing::info!(error = %e, "WebSocket message loop ended");
+                break;
+            }
+        };
+        let state = state.clone();
+        tokio::spawn(async move { handle_incoming(&state, incoming).await });
+    }
+}
+
+async fn handle_incoming(state: &AppState, incoming: IncomingMessage) {
+    let gateway = &state.ws_gateway;
+    let Some(session_id) = incoming.channel.as_ref().map(|c| c.id.clone()) else {
+        tracing::warn!(message_id = %incoming.id, "WebSocket message without session, dropped");
+        return;
+    };
+    let content = incoming.content.as_text().unwrap_or_default();
+
+    // REST と同じ規則で送信者を確認してから受け付ける
+    if let Err(e) = process::check_sender(state, &session_id, &incoming.sender.id) {
+        tracing::warn!(session_id = %session_id, agent_id = %incoming.sender.id, error = %e, "WebSocket message rejected");
+        send_error(state, &incoming, e.to_string());
+        return;
+    }
+    gateway.accept(&incoming);
+
+    let outcome = process::handle_session_message(
+        state,
+        &session_id,
+        &incoming.sender.id,
+        content,
+        "websocket",
+        None,
+    )
+    .await;
+
+    match outcome {
+        Ok(outcome) => {
+            for reply in outcome.replies.unwrap_or_default() {
+                let message = OutgoingMessage {
+                    reply_to: Some(incoming.id.clone()),
+                    ..OutgoingMessage::text_to_channel(reply.content, &session_id)
+                }
+                .with_metadata("agent_id", serde_json::json!(reply.agent_id))
+                .with_metadata("agent_name", serde_json::json!(reply.agent_name))
+                .with_metadata("tool_calls_made", serde_json::json!(reply.tool_calls_made));
+                if let Err(e) = gateway.deliver(message) {
+                    tracing::warn!(error = %e, "Failed to deliver WebSocket response");
+                }
+            }
+        }
+        Err(e) => {
+            tracing::error!(session_id = %session_id, error = %e, "WebSocket message failed");
+            send_error(state, &incoming, e.to_string());
+        }
+    }
+}
+
+/// 送信元の接続に `error` フレームを返す
+fn send_error(state: &AppState, incoming: &IncomingMessage, message: String) {
+    if let MessageSource::WebSocket { connection_id } = &incoming.source {
+        state.ws_gateway.send_to(
+            connection_id,
+            ServerFrame::Error {
+                message,
+                request_id: incoming
+                    .metadata
+                    .get("request_id")
+                    .and_then(|v| v.as_str())
+                    .map(String::from),
+            },
+        );
+    }
+}
@@ -19,6 +19,20 @@ fn create_test_app() -> Router {
         llm_router: Arc::new(LlmRouter::new()),
         workspace_base: std::env::temp_dir().to_string_lossy().to_string(),
         default_model: "mock:test".to_string(),
+        embedding_model: None,
+        self_selection: Default::default(),
+        budget: Default::default(),
+        pricing: Default::default(),
+        skills_dir: "../../skills".to_string(),
+        base_actions: opencrab_server::config::default_base_actions(),
+        context_budget: Default::default(),
+        persona: Default::default(),
+        consolidation: Default::default(),
+        memory_policy: Default::default(),
+        heartbeat: Arc::new(opencrab_server::heartbeat::HeartbeatManager::new(29)),
+        session_runner: Arc::new(opencrab_server::session_runner::SessionRunnerManager::new(0)),
+        consolidation_manager: Arc::new(opencrab_server::consolidation::ConsolidationManager::new()),
+        ws_gateway: Arc::new(opencrab_gateway::WebSocketGateway::new(64)),
     };
     create_router(state)
 }
@@ -221,7 +235,7 @@ async fn test_create_and_list_sessions() {
     assert!(resp["id"].as_str().is_some());
 
     let (_, resp) = send_request(app, "GET", "/api/sessions", None).await;
-    assert!(resp.as_array().unwrap().len() >= 1);
+    assert!(!resp.as_array().unwrap().is_empty());
 }
 
 #[tokio::test]
@@ -307,8 +321,10 @@ async fn test_add_and_list_skills() {
     )
     .await;
     let skills = resp.as_array().unwrap();
-    assert_eq!(skills.len(), 1);
-    assert_eq!(skills[0]["name"], "Test Skill");
+    let skill = skills.iter().find(|s| s["name"] == "Test Skill").unwrap();
+    assert_eq!(skill["source_type"], "manual");
+    // 作成時に標準スキルも付与されている
+    assert!(skills.iter().any(|s| s["source_type"] == "standard"));
 }
 
 #[tokio::test]
@@ -353,6 +369,46 @@ async fn test_toggle_skill() {
     assert_eq!(skill["is_active"], false);
 }
 
+#[tokio::test]
+async fn test_reload_standard_skills() {
+    let app = create_test_app();
+    let (agent_id, app) = create_test_agent(app).await;
+
+    // 標準スキルは作成時に同期済みなので、再読み込みでは変更なし
+    let (status, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/agents/{agent_id}/skills/reload"),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(resp["ok"], true);
+    assert!(resp["added"].as_array().unwrap().is_empty());
+    let unchanged = resp["unchanged"].as_array().unwrap();
+    assert!(unchanged.iter().any(|n| n == "autonomous"));
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "GET",
+        &format!("/api/agents/{agent_id}/skills"),
+        None,
+    )
+    .await;
+    let skills = resp.as_array().unwrap();
+    let skill = skills.iter().find(|s| s["name"] == "autonomous").unwrap();
+    assert_eq!(skill["source_type"], "standard");
+    assert_eq!(skill["version"], "1");
+    assert!(skill["file_path"].as_str().unwrap().ends_with("autonomous.skill.md"));
+
+    // 存在しないエージェントにはスキルを作らない
+    let (_, resp) = send_request(app.clone(), "POST", "/api/agents/bogus/skills/reload", None).await;
+    assert_eq!(resp["ok"], false);
+    assert_eq!(resp["error"], "Agent not found");
+    let (_, resp) = send_request(app, "GET", "/api/agents/bogus/skills", None).await;
+    assert!(resp.as_array().unwrap().is_empty());
+}
+
 #[tokio::test]
 async fn test_list_curated_memory_empty() {
     let app = create_test_app();
@@ -649,15 +705,78 @@ async fn test_delete_nonexistent_agent() {
 /// A mock LLM provider that returns pre-queued responses.
 struct MockLlmProvider {
     responses: Mutex<VecDeque<ChatResponse>>,
+    requests: Mutex<Vec<ChatRequest>>,
+    /// `available_models` が報告するコンテキスト長
+    context_window: Mutex<u32>,
+    /// 要約リクエスト（SUMMARY_PROMPT）への固定応答。設定時はキューを消費しない
+    summary_reply: Mutex<Option<String>>,
+    /// 次の呼び出しで返すエラー（応答キューより先に消費する）
+    errors: Mutex<VecDeque<opencrab_llm::LlmError>>,
+    /// 各 chat_completion の応答までの待ち時間
+    latency: Mutex<std::time::Duration>,
+    /// 実行中の chat_completion 数と、その最大値
+    in_flight: std::sync::atomic::AtomicUsize,
+    max_in_flight: std::sync::atomic::AtomicUsize,
 }
 
 impl MockLlmProvider {
     fn new() -> Self {
         Self {
             responses: Mutex::new(VecDeque::new()),
+            requests: Mutex::new(Vec::new()),
+            context_window: Mutex::new(128_000),
+            summary_reply: Mutex::new(None),
+            errors: Mutex::new(VecDeque::new()),
+            latency: Mutex::new(std::time::Duration::ZERO),
+            in_flight: std::sync::atomic::AtomicUsize::new(0),
+            max_in_flight: std::sync::atomic::AtomicUsize::new(0),
         }
     }
 
+    /// Delay every chat completion by `latency`.
+    fn set_latency(&self, latency: std::time::Duration) {
+        *self.latency.lock().unwrap() = latency;
+    }
+
+    /// Number of chat completions received so far.
+    fn request_count(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    /// Highest number of chat completions that ran at the same time.
+    fn max_in_flight(&self) -> usize {
+        self.max_in_flight.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Fail the next call with the error the provider would map `body` to.
+    fn push_error(&self, status: u16, body: &str) {
+        let error = opencrab_llm::LlmError::from_body("mock", "gpt-4o", status, None, body);
+        self.errors.lock().unwrap().push_back(error);
+    }
+
+    /// Set the reported context window (call before the first request; the
+    /// router caches model lists).
+    fn set_context_window(&self, tokens: u32) {
+        *self.context_window.lock().unwrap() = tokens;
+    }
+
+    /// Answer every conversation-summary request with `text`.
+    fn set_summary_reply(&self, text: &str) {
+        *self.summary_reply.lock().unwrap() = Some(text.to_string());
+    }
+
+    /// Tool names offered in the most recent request.
+    fn last_tool_names(&self) -> Vec<String> {
+        let requests = self.requests.lock().unwrap();
+        let mut names: Vec<String> = requests
+            .last()
+            .and_then(|r| r.functions.as_ref())
+            .map(|fns| fns.iter().map(|f| f.name.clone()).collect())
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+
     fn push_text_response(&self, text: &str) {
         let response = ChatResponse {
             id: uuid::Uuid::new_v4().to_string(),
@@ -712,15 +831,67 @@ impl LlmProvider for MockLlmProvider {
     async fn available_models(
         &self,
     ) -> anyhow::Result<Vec<opencrab_llm::traits::ModelInfo>> {
-        Ok(vec![])
+        Ok(vec![opencrab_llm::traits::ModelInfo {
+            id: "gpt-4o".to_string(),
+            name: "Mock GPT-4o".to_string(),
+            context_window: *self.context_window.lock().unwrap(),
+            supports_function_calling: true,
+            supports_vision: false,
+        }])
     }
 
-    async fn chat_completion(&self, _request: ChatRequest) -> anyhow::Result<ChatResponse> {
+    async fn chat_completion(&self, request: ChatRequest) -> anyhow::Result<ChatResponse> {
+        let is_summary = request.messages.first().and_then(|m| m.text_content())
+            == Some(opencrab_core::context::SUMMARY_PROMPT);
+        self.requests.lock().unwrap().push(request);
+        let latency = *self.latency.lock().unwrap();
+        if !latency.is_zero() {
+            use std::sync::atomic::Ordering;
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(latency).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        }
+        if is_summary {
+            if let Some(text) = self.summary_reply.lock().unwrap().clone() {
+                return Ok(ChatResponse {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    model: "mock-model".to_string(),
+                    choices: vec![Choice {
+                        index: 0,
+                        message: Message::assistant(text),
+                        finish_reason: Some(FinishReason::Stop),
+                    }],
+                    usage: Usage::default(),
+                    created: 0,
+                });
+            }
+        }
+        if let Some(error) = self.errors.lock().unwrap().pop_front() {
+            return Err(error.into());
+        }
         let mut queue = self.responses.lock().unwrap();
         queue
             .pop_front()
             .ok_or_else(|| anyhow::anyhow!("MockLlmProvider: no more queued responses"))
     }
+
+    fn supports_embeddings(&self) -> bool {
+        true
+    }
+
+    /// 決定的なハッシュ埋め込みを返す（キューの応答は消費しない）
+    async fn embed(
+        &self,
+        request: opencrab_llm::message::EmbeddingRequest,
+    ) -> anyhow::Result<opencrab_llm::message::EmbeddingResponse> {
+        let embedder = opencrab_core::HashEmbedder::default();
+        Ok(opencrab_llm::message::EmbeddingResponse {
+            model: request.model,
+            embeddings: request.input.iter().map(|t| embedder.embed_text(t)).collect(),
+            usage: Usage::default(),
+        })
+    }
 }
 
 // ==================== LLM-integrated helpers ====================
@@ -728,6 +899,21 @@ impl LlmProvider for MockLlmProvider {
 /// Create test app with a MockLlmProvider registered in the LlmRouter.
 /// Returns (Router, Arc<Mutex<Connection>>, Arc<MockLlmProvider>).
 fn create_test_app_with_llm() -> (Router, Arc<Mutex<rusqlite::Connection>>, Arc<MockLlmProvider>) {
+    let (state, mock) = create_test_state_with_llm();
+    let db = state.db.clone();
+    (create_router(state), db, mock)
+}
+
+/// Build the AppState used by `create_test_app_with_llm` (for tests that need
+/// to run background loops against the same state).
+fn create_test_state_with_llm() -> (AppState, Arc<MockLlmProvider>) {
+    create_test_state_with_router(|_| {})
+}
+
+/// `create_test_state_with_llm` with extra router settings (purpose routes etc.).
+fn create_test_state_with_router(
+    configure: impl FnOnce(&mut LlmRouter),
+) -> (AppState, Arc<MockLlmProvider>) {
     let conn = opencrab_db::init_memory().unwrap();
     let db = Arc::new(Mutex::new(conn));
 
@@ -735,6 +921,7 @@ fn create_test_app_with_llm() -> (Router, Arc<Mutex<rusqlite::Connection>>, Arc<
     let mut router = LlmRouter::new();
     router.add_provider(mock.clone() as Arc<dyn LlmProvider>);
     router.set_default_provider("mock");
+    configure(&mut router);
 
     let state = AppState {
         db: db.clone(),
@@ -744,9 +931,22 @@ fn create_test_app_with_llm() -> (Router, Arc<Mutex<rusqlite::Connection>>, Arc<
             .to_string_lossy()
             .to_string(),
         default_model: "mock:gpt-4o".to_string(),
+        embedding_model: Some("mock:hash-256".to_string()),
+        self_selection: Default::default(),
+        budget: Default::default(),
+        pricing: Default::default(),
+        skills_dir: "../../skills".to_string(),
+        base_actions: opencrab_server::config::default_base_actions(),
+        context_budget: Default::default(),
+        persona: Default::default(),
+        consolidation: Default::default(),
+        memory_policy: Default::default(),
+        heartbeat: Arc::new(opencrab_server::heartbeat::HeartbeatManager::new(29)),
+        session_runner: Arc::new(opencrab_server::session_runner::SessionRunnerManager::new(0)),
+        consolidation_manager: Arc::new(opencrab_server::consolidation::ConsolidationManager::new()),
+        ws_gateway: Arc::new(opencrab_gateway::WebSocketGateway::new(64)),
     };
-    let app = create_router(state);
-    (app, db, mock)
+    (state, mock)
 }
 
 /// Create a named agent with a specific persona via the API.
@@ -824,6 +1024,288 @@ async fn test_send_message_triggers_agent_response() {
     assert_eq!(responses[0]["tool_calls_made"], 0);
 }
 
+/// Test: Once standard skills are loaded, only their actions plus the base set are offered.
+#[tokio::test]
+async fn test_tool_allowlist_follows_active_skills() {
+    let (app, _db, mock) = create_test_app_with_llm();
+    let (agent_a, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+    let (agent_b, app) = create_test_agent_named(app, "Bob", "Creative Thinker").await;
+
+    let (status, resp) = send_request(app.clone(), "GET", &format!("/api/agents/{agent_b}/tools"), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(resp["ok"], true);
+    let tools: Vec<&str> = resp["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["name"].as_str().unwrap())
+        .collect();
+    assert!(tools.contains(&"ws_read"));
+    assert!(tools.contains(&"send_speech"));
+    assert!(!tools.contains(&"discord_channel_config"));
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({
+            "theme": "Tools",
+            "participant_ids": [&agent_a, &agent_b]
+        })),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+
+    mock.push_text_response("ok");
+    send_request(
+        app,
+        "POST",
+        &format!("/api/sessions/{session_id}/messages"),
+        Some(serde_json::json!({"agent_id": agent_a, "content": "hi"})),
+    )
+    .await;
+
+    let offered = mock.last_tool_names();
+    assert_eq!(offered, tools.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+}
+
+/// Test: Disabling every skill narrows the tools to the base set instead of lifting the restriction.
+#[tokio::test]
+async fn test_tool_allowlist_without_skills_is_base_set() {
+    let (app, _db, mock) = create_test_app_with_llm();
+    let (agent_a, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+    let (agent_b, app) = create_test_agent_named(app, "Bob", "Creative Thinker").await;
+
+    let (_, skills) = send_request(app.clone(), "GET", &format!("/api/agents/{agent_b}/skills"), None).await;
+    for skill in skills.as_array().unwrap() {
+        let skill_id = skill["id"].as_str().unwrap();
+        send_request(
+            app.clone(),
+            "POST",
+            &format!("/api/agents/{agent_b}/skills/{skill_id}/toggle"),
+            Some(serde_json::json!({"active": false})),
+        )
+        .await;
+    }
+
+    let mut base = opencrab_server::config::default_base_actions();
+    base.sort();
+    let (_, resp) = send_request(app.clone(), "GET", &format!("/api/agents/{agent_b}/tools"), None).await;
+    assert_eq!(resp["ok"], true);
+    assert_eq!(resp["allowed_actions"], serde_json::json!(base));
+    let tools: Vec<&str> = resp["tools"].as_array().unwrap().iter().map(|t| t["name"].as_str().unwrap()).collect();
+    assert!(!tools.contains(&"ws_delete"));
+    assert!(!tools.contains(&"discord_channel_config"));
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({"theme": "No skills", "participant_ids": [&agent_a, &agent_b]})),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+
+    mock.push_text_response("ok");
+    send_request(
+        app,
+        "POST",
+        &format!("/api/sessions/{session_id}/messages"),
+        Some(serde_json::json!({"agent_id": agent_a, "content": "hi"})),
+    )
+    .await;
+    assert_eq!(mock.last_tool_names(), tools.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+}
+
+/// Test: A heartbeat tick that decides to speak posts into the agent's active session.
+#[tokio::test]
+async fn test_heartbeat_tick_speak_posts_to_session() {
+    let (app, db, mock) = create_test_app_with_llm();
+    let (agent_a, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+    let (agent_b, app) = create_test_agent_named(app, "Bob", "Creative Thinker").await;
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({
+            "theme": "Heartbeats",
+            "participant_ids": [&agent_a, &agent_b]
+        })),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+
+    mock.push_text_response(r#"{"decision": "speak", "message": "Anyone still here?", "reason": "quiet"}"#);
+
+    let (status, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/agents/{agent_a}/heartbeat/tick"),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(resp["ok"], true);
+    assert_eq!(resp["outcome"]["decision"]["type"], "speak");
+    // 判断はツールなしで行う
+    assert!(mock.last_tool_names().is_empty());
+    assert_eq!(resp["outcome"]["result"]["delivered"], true);
+
+    let logs = {
+        let conn = db.lock().unwrap();
+        opencrab_db::queries::list_session_logs_by_session(&conn, &session_id).unwrap()
+    };
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].content, "Anyone still here?");
+    assert_eq!(logs[0].speaker_id.as_deref(), Some(agent_a.as_str()));
+
+    let (_, resp) = send_request(app, "GET", &format!("/api/agents/{agent_a}/heartbeat"), None).await;
+    assert_eq!(resp["status"]["running"], false);
+    let recent = resp["recent"].as_array().unwrap();
+    assert_eq!(recent.len(), 1);
+    assert_eq!(recent[0]["decision"], "speak");
+}
+
+/// Test: Heartbeat speech reaches running sessions and is marked undelivered when there is nowhere to post.
+#[tokio::test]
+async fn test_heartbeat_speak_to_running_session_or_undelivered() {
+    let (app, db, mock) = create_test_app_with_llm();
+    let (agent_a, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+
+    mock.push_text_response(r#"{"decision": "speak", "message": "Hello?"}"#);
+    let (_, resp) = send_request(app.clone(), "POST", &format!("/api/agents/{agent_a}/heartbeat/tick"), None).await;
+    assert_eq!(resp["outcome"]["decision"]["type"], "speak");
+    assert_eq!(resp["outcome"]["result"]["delivered"], false);
+    assert!(resp["outcome"]["result"]["sessions"].as_array().unwrap().is_empty());
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({"theme": "Running", "participant_ids": [&agent_a]})),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+    {
+        let conn = db.lock().unwrap();
+        opencrab_db::queries::update_session_status(&conn, &session_id, "running").unwrap();
+    }
+
+    mock.push_text_response(r#"{"decision": "speak", "message": "Still running."}"#);
+    let (_, resp) = send_request(app.clone(), "POST", &format!("/api/agents/{agent_a}/heartbeat/tick"), None).await;
+    assert_eq!(resp["outcome"]["result"]["delivered"], true);
+    assert_eq!(resp["outcome"]["result"]["sessions"], serde_json::json!([session_id]));
+
+    // With several sessions, the speech only goes to the one the decision was made in.
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({"theme": "Second topic", "participant_ids": [&agent_a]})),
+    )
+    .await;
+    let second_id = resp["id"].as_str().unwrap().to_string();
+    mock.push_text_response(r#"{"decision": "speak", "message": "On topic."}"#);
+    let (_, resp) = send_request(app, "POST", &format!("/api/agents/{agent_a}/heartbeat/tick"), None).await;
+    let posted = resp["outcome"]["result"]["sessions"].as_array().unwrap().clone();
+    assert_eq!(posted.len(), 1, "{resp}");
+    let theme = if posted[0] == second_id.as_str() { "Second topic" } else { "Running" };
+    let system_prompt = {
+        let requests = mock.requests.lock().unwrap();
+        requests.last().unwrap().messages[0].text_content().unwrap().to_string()
+    };
+    assert!(system_prompt.contains(&format!("Current discussion topic: {theme}")), "{system_prompt}");
+    let conn = db.lock().unwrap();
+    let spoken = |id: &str| {
+        opencrab_db::queries::list_session_logs_by_session(&conn, id)
+            .unwrap()
+            .iter()
+            .any(|l| l.content == "On topic.")
+    };
+    assert_ne!(spoken(&session_id), spoken(&second_id));
+}
+
+/// Test: A heartbeat tick that decides to learn runs reflect_and_learn.
+#[tokio::test]
+async fn test_heartbeat_tick_learn_runs_reflection() {
+    let (app, db, mock) = create_test_app_with_llm();
+    let (agent_a, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+
+    mock.push_text_response(r#"{"decision": "learn"}"#);
+    mock.push_tool_call_response(vec![ToolCall {
+        id: "tc-reflect-1".to_string(),
+        call_type: "function".to_string(),
+        function: FunctionCall {
+            name: "reflect_and_learn".to_string(),
+            arguments: serde_json::json!({
+                "reflection": "I have been quiet lately",
+                "insights": ["ask more questions"]
+            })
+            .to_string(),
+        },
+    }]);
+    mock.push_text_response("Reflected on recent quietness.");
+
+    let (_, resp) = send_request(
+        app,
+        "POST",
+        &format!("/api/agents/{agent_a}/heartbeat/tick"),
+        None,
+    )
+    .await;
+    assert_eq!(resp["outcome"]["decision"]["type"], "learn");
+    assert_eq!(resp["outcome"]["result"]["tool_calls_made"], 1);
+    assert_eq!(mock.last_tool_names(), vec!["reflect_and_learn"]);
+
+    let memories = {
+        let conn = db.lock().unwrap();
+        opencrab_db::queries::list_curated_memories(&conn, &agent_a)
+            .unwrap()
+            .into_iter()
+            .filter(|m| m.category == "reflection")
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(memories.len(), 1);
+}
+
+/// Test: Heartbeat start/status/stop lifecycle.
+#[tokio::test]
+async fn test_heartbeat_start_stop() {
+    let (app, _db, _mock) = create_test_app_with_llm();
+    let (agent_id, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/agents/unknown-agent/heartbeat/start",
+        Some(serde_json::json!({})),
+    )
+    .await;
+    assert_eq!(resp["ok"], false);
+
+    let (status, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/agents/{agent_id}/heartbeat/start"),
+        Some(serde_json::json!({"interval_secs": 3600})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(resp["ok"], true);
+    assert_eq!(resp["status"]["running"], true);
+    assert_eq!(resp["status"]["interval_secs"], 3600);
+
+    let (_, resp) = send_request(app.clone(), "GET", &format!("/api/agents/{agent_id}/heartbeat"), None).await;
+    assert_eq!(resp["status"]["running"], true);
+
+    let (_, resp) = send_request(app.clone(), "POST", &format!("/api/agents/{agent_id}/heartbeat/stop"), None).await;
+    assert_eq!(resp["stopped"], true);
+
+    let (_, resp) = send_request(app, "GET", &format!("/api/agents/{agent_id}/heartbeat"), None).await;
+    assert_eq!(resp["status"]["running"], false);
+}
+
 /// Test: Two rounds of discussion, second round agent calls learn_from_experience
 /// which creates a skill in the DB.
 #[tokio::test]
@@ -904,7 +1386,7 @@ async fn test_agents_discuss_and_generate_skill() {
     // Verify skill was created in the DB.
     let skills = {
         let conn = db.lock().unwrap();
-        opencrab_db::queries::list_skills(&conn, &responses[0]["agent_id"].as_str().unwrap(), false)
+        opencrab_db::queries::list_skills(&conn, responses[0]["agent_id"].as_str().unwrap(), false)
             .unwrap()
     };
     assert!(
@@ -1106,3 +1588,2079 @@ async fn test_send_message_without_llm_falls_back() {
     // No "responses" field in legacy mode.
     assert!(resp.get("responses").is_none());
 }
+
+/// Test: A WebSocket client subscribes to a session, submits a message and
+/// receives the logs, tool events and agent response as they happen.
+#[tokio::test]
+async fn test_websocket_session_stream() {
+    use futures::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    let (state, mock) = create_test_state_with_llm();
+    tokio::spawn(opencrab_server::ws::run_message_loop(state.clone()));
+    let app = create_router(state);
+
+    let (agent_a, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+    let (agent_b, app) = create_test_agent_named(app, "Bob", "Creative Thinker").await;
+    let (outsider, app) = create_test_agent_named(app, "Carol", "Critic").await;
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({
+            "theme": "Realtime",
+            "participant_ids": [&agent_a, &agent_b]
+        })),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = app.clone();
+    tokio::spawn(async move { axum::serve(listener, server).await.unwrap() });
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/ws"))
+        .await
+        .unwrap();
+
+    async fn next_frame(
+        ws: &mut (impl futures::Stream<Item = Result<WsMessage, tokio_tungstenite::tungstenite::Error>>
+                  + Unpin),
+    ) -> serde_json::Value {
+        loop {
+            let msg = tokio::time::timeout(std::time::Duration::from_secs(5), ws.next())
+                .await
+                .expect("timed out waiting for frame")
+                .unwrap()
+                .unwrap();
+            if let WsMessage::Text(text) = msg {
+                return serde_json::from_str(text.as_str()).unwrap();
+            }
+        }
+    }
+
+    ws.send(WsMessage::Text(
+        serde_json::json!({"type": "subscribe", "session_id": session_id})
+            .to_string()
+            .into(),
+    ))
+    .await
+    .unwrap();
+    assert_eq!(next_frame(&mut ws).await["type"], "subscribed");
+
+    // Bob calls a tool, then answers.
+    mock.push_tool_call_response(vec![ToolCall {
+        id: "tc-info-1".to_string(),
+        call_type: "function".to_string(),
+        function: FunctionCall {
+            name: "get_system_info".to_string(),
+            arguments: "{}".to_string(),
+        },
+    }]);
+    mock.push_text_response("Hello from Bob over the socket.");
+
+    ws.send(WsMessage::Text(
+        serde_json::json!({
+            "type": "send_message",
+            "session_id": session_id,
+            "agent_id": agent_a,
+            "content": "Anyone there?",
+            "request_id": "req-1"
+        })
+        .to_string()
+        .into(),
+    ))
+    .await
+    .unwrap();
+
+    let mut frames = Vec::new();
+    loop {
+        let frame = next_frame(&mut ws).await;
+        let done = frame["type"] == "response";
+        frames.push(frame);
+        if done {
+            break;
+        }
+    }
+    let types: Vec<&str> = frames.iter().map(|f| f["type"].as_str().unwrap()).collect();
+    assert_eq!(
+        types,
+        vec!["accepted", "log", "tool_event", "tool_event", "log", "log", "log", "response"]
+    );
+
+    assert_eq!(frames[0]["request_id"], "req-1");
+    assert_eq!(frames[1]["log"]["content"], "Anyone there?");
+    assert_eq!(frames[1]["log"]["speaker_id"], agent_a);
+    assert!(frames[1]["log"]["id"].as_i64().is_some());
+    assert_eq!(frames[2]["agent_id"], agent_b);
+    assert_eq!(frames[2]["event"]["type"], "tool_call_started");
+    assert_eq!(frames[2]["event"]["name"], "get_system_info");
+    assert_eq!(frames[3]["event"]["type"], "tool_call_finished");
+    assert_eq!(frames[4]["log"]["log_type"], "tool_call");
+    assert_eq!(frames[5]["log"]["log_type"], "tool_result");
+    assert_eq!(frames[6]["log"]["content"], "Hello from Bob over the socket.");
+    assert_eq!(frames[7]["content"], "Hello from Bob over the socket.");
+    assert_eq!(frames[7]["metadata"]["agent_id"], agent_b);
+    assert_eq!(frames[7]["metadata"]["tool_calls_made"], 1);
+    assert_eq!(frames[7]["reply_to"], frames[0]["message_id"]);
+
+    // A registered agent outside the session is rejected before `accepted`,
+    // as over REST.
+    ws.send(WsMessage::Text(
+        serde_json::json!({
+            "type": "send_message",
+            "session_id": session_id,
+            "agent_id": outsider,
+            "content": "Let me in",
+            "request_id": "req-2"
+        })
+        .to_string()
+        .into(),
+    ))
+    .await
+    .unwrap();
+    let frame = next_frame(&mut ws).await;
+    assert_eq!(frame["type"], "error", "{frame}");
+    assert_eq!(frame["request_id"], "req-2");
+    assert!(frame["message"].as_str().unwrap().contains("not a participant"));
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/messages"),
+        Some(serde_json::json!({"agent_id": outsider, "content": "Let me in"})),
+    )
+    .await;
+    assert_eq!(resp["ok"], false);
+
+    // A human sender such as "user" is accepted.
+    mock.push_text_response("Hi user.");
+    mock.push_text_response("Hello user.");
+    ws.send(WsMessage::Text(
+        serde_json::json!({
+            "type": "send_message",
+            "session_id": session_id,
+            "agent_id": "user",
+            "content": "Hi all",
+            "request_id": "req-3"
+        })
+        .to_string()
+        .into(),
+    ))
+    .await
+    .unwrap();
+    let frame = next_frame(&mut ws).await;
+    assert_eq!(frame["type"], "accepted", "{frame}");
+    assert_eq!(frame["request_id"], "req-3");
+    let frame = next_frame(&mut ws).await;
+    assert_eq!(frame["log"]["speaker_id"], "user");
+    let mut responders = Vec::new();
+    while responders.len() < 2 {
+        let frame = next_frame(&mut ws).await;
+        assert_ne!(frame["type"], "error", "{frame}");
+        if frame["type"] == "response" {
+            responders.push(frame["metadata"]["agent_id"].as_str().unwrap().to_string());
+        }
+    }
+    responders.sort();
+    let mut participants = vec![agent_a.clone(), agent_b.clone()];
+    participants.sort();
+    assert_eq!(responders, participants);
+
+    ws.send(WsMessage::Text(r#"{"type":"ping"}"#.into())).await.unwrap();
+    assert_eq!(next_frame(&mut ws).await["type"], "pong");
+}
+
+/// Test: `stream: true` turns send_message into an SSE stream of progress events.
+#[tokio::test]
+async fn test_send_message_sse_stream() {
+    let (app, _db, mock) = create_test_app_with_llm();
+
+    let (agent_a, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+    let (agent_b, app) = create_test_agent_named(app, "Bob", "Creative Thinker").await;
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({
+            "theme": "Streaming",
+            "participant_ids": [&agent_a, &agent_b]
+        })),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+
+    mock.push_tool_call_response(vec![ToolCall {
+        id: "tc-info-1".to_string(),
+        call_type: "function".to_string(),
+        function: FunctionCall {
+            name: "get_system_info".to_string(),
+            arguments: "{}".to_string(),
+        },
+    }]);
+    mock.push_text_response("Streaming works.");
+
+    let req = Request::builder()
+        .method("POST")
+        .uri(format!("/api/sessions/{session_id}/messages"))
+        .header("content-type", "application/json")
+        .body(Body::from(
+            serde_json::to_vec(&serde_json::json!({
+                "agent_id": agent_a,
+                "content": "Stream please",
+                "stream": true
+            }))
+            .unwrap(),
+        ))
+        .unwrap();
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/event-stream"));
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+
+    let mut events: Vec<(String, serde_json::Value)> = Vec::new();
+    for block in text.split("\n\n").filter(|b| !b.trim().is_empty()) {
+        let mut name = String::new();
+        let mut data = String::new();
+        for line in block.lines() {
+            if let Some(v) = line.strip_prefix("event: ") {
+                name = v.to_string();
+            } else if let Some(v) = line.strip_prefix("data: ") {
+                data.push_str(v);
+            }
+        }
+        events.push((name, serde_json::from_str(&data).unwrap()));
+    }
+
+    let names: Vec<&str> = events.iter().map(|(n, _)| n.as_str()).collect();
+    assert_eq!(
+        names,
+        vec![
+            "accepted",
+            "agent_start",
+            "usage",
+            "tool_call_started",
+            "tool_call_finished",
+            "text_delta",
+            "usage",
+            "response",
+            "done"
+        ]
+    );
+    assert_eq!(events[1].1["agent_id"], agent_b);
+    assert_eq!(events[3].1["name"], "get_system_info");
+    assert_eq!(events[5].1["text"], "Streaming works.");
+    assert_eq!(events[6].1["total_tokens"], 15);
+    assert_eq!(events[7].1["content"], "Streaming works.");
+    assert_eq!(events[8].1["responses"][0]["tool_calls_made"], 1);
+}
+
+/// Create an agent with the given role via the API.
+async fn create_test_agent_with_role(app: Router, name: &str, role: &str) -> String {
+    let (_, resp) = send_request(
+        app,
+        "POST",
+        "/api/agents",
+        Some(serde_json::json!({
+            "name": name,
+            "persona_name": name,
+            "role": role
+        })),
+    )
+    .await;
+    resp["id"].as_str().unwrap().to_string()
+}
+
+/// Test: The facilitator picks speakers, the phase advances and the session ends at max_turns.
+#[tokio::test]
+async fn test_facilitated_session_turns() {
+    let (app, db, mock) = create_test_app_with_llm();
+
+    let facilitator = create_test_agent_with_role(app.clone(), "Fran", "facilitator").await;
+    let alice = create_test_agent_with_role(app.clone(), "Alice", "discussant").await;
+    let bob = create_test_agent_with_role(app.clone(), "Bob", "discussant").await;
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({
+            "theme": "City planning",
+            "mode": "facilitated",
+            "participant_ids": [&facilitator, &alice, &bob],
+            "max_turns": 2
+        })),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+
+    // Turn 1: the facilitator picks Bob by name.
+    mock.push_text_response(r#"{"next_speaker": "Bob", "phase": "divergent", "reason": "Bob has not spoken"}"#);
+    mock.push_text_response("More parks, fewer parking lots.");
+    let (status, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/turn"),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(resp["ok"], true, "{resp}");
+    assert_eq!(resp["turn"]["turn_number"], 1);
+    assert_eq!(resp["turn"]["speaker_id"], bob);
+    assert_eq!(resp["turn"]["phase"], "divergent");
+    assert_eq!(resp["turn"]["status"], "active");
+
+    // Turn 2: the facilitator moves to convergent and picks Alice by ID.
+    mock.push_text_response(&format!(
+        r#"{{"next_speaker": "{alice}", "phase": "convergent", "reason": "compare ideas"}}"#
+    ));
+    mock.push_text_response("Parks win on every metric.");
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/turn"),
+        None,
+    )
+    .await;
+    assert_eq!(resp["turn"]["turn_number"], 2);
+    assert_eq!(resp["turn"]["speaker_id"], alice);
+    assert_eq!(resp["turn"]["phase"], "convergent");
+    assert_eq!(resp["turn"]["ended"], "max_turns");
+    assert_eq!(resp["turn"]["status"], "completed");
+
+    {
+        let conn = db.lock().unwrap();
+        let session = opencrab_db::queries::get_session(&conn, &session_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.turn_number, 2);
+        assert_eq!(session.phase, "convergent");
+        assert_eq!(session.status, "completed");
+
+        let logs = opencrab_db::queries::list_session_logs_by_session(&conn, &session_id).unwrap();
+        let speeches: Vec<_> = logs.iter().filter(|l| l.log_type == "speech").collect();
+        assert_eq!(speeches.len(), 2);
+        assert_eq!(speeches[0].turn_number, Some(1));
+        assert_eq!(speeches[1].speaker_id.as_deref(), Some(alice.as_str()));
+        assert!(logs.iter().any(|l| l.content.contains("Phase changed")));
+        assert!(logs.iter().any(|l| l.content.contains("max_turns")));
+    }
+
+    // The session no longer accepts turns.
+    let (_, resp) = send_request(
+        app,
+        "POST",
+        &format!("/api/sessions/{session_id}/turn"),
+        None,
+    )
+    .await;
+    assert_eq!(resp["ok"], false);
+}
+
+/// Test: Without a facilitator, speakers rotate and the session ends when everyone declares done.
+#[tokio::test]
+async fn test_facilitated_session_ends_when_all_done() {
+    let (app, db, mock) = create_test_app_with_llm();
+
+    let alice = create_test_agent_with_role(app.clone(), "Alice", "discussant").await;
+    let bob = create_test_agent_with_role(app.clone(), "Bob", "discussant").await;
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({
+            "theme": "Lunch",
+            "mode": "facilitated",
+            "participant_ids": [&alice, &bob]
+        })),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+
+    let declare_done = |id: &str| {
+        vec![ToolCall {
+            id: id.to_string(),
+            call_type: "function".to_string(),
+            function: FunctionCall {
+                name: "declare_done".to_string(),
+                arguments: r#"{"reason": "agreed"}"#.to_string(),
+            },
+        }]
+    };
+
+    // Alice speaks first (round-robin) and declares done.
+    mock.push_tool_call_response(declare_done("tc-done-1"));
+    mock.push_text_response("Sushi it is.");
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/turn"),
+        None,
+    )
+    .await;
+    assert_eq!(resp["turn"]["speaker_id"], alice);
+    assert_eq!(resp["turn"]["status"], "active");
+    assert_eq!(resp["turn"]["phase"], "divergent");
+
+    // Bob is the only remaining speaker; a human message triggers their turn.
+    // With half of the participants done, the discussion becomes convergent.
+    mock.push_tool_call_response(declare_done("tc-done-2"));
+    mock.push_text_response("Agreed, sushi.");
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/messages"),
+        Some(serde_json::json!({"agent_id": "human", "content": "Final answer?"})),
+    )
+    .await;
+    let responses = resp["responses"].as_array().unwrap();
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0]["agent_id"], bob);
+
+    let conn = db.lock().unwrap();
+    let session = opencrab_db::queries::get_session(&conn, &session_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(session.done_count, 2);
+    assert_eq!(session.turn_number, 2);
+    assert_eq!(session.phase, "convergent");
+    assert_eq!(session.status, "completed");
+}
+
+/// Poll `sessions.status` until it matches (runner tests).
+async fn wait_for_session_status(
+    db: &Arc<Mutex<rusqlite::Connection>>,
+    session_id: &str,
+    status: &str,
+) -> opencrab_db::queries::SessionRow {
+    for _ in 0..200 {
+        {
+            let conn = db.lock().unwrap();
+            let session = opencrab_db::queries::get_session(&conn, session_id)
+                .unwrap()
+                .unwrap();
+            if session.status == status {
+                return session;
+            }
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    panic!("session {session_id} never reached status {status}");
+}
+
+/// Test: A background runner keeps agents talking until max_turns without human input.
+#[tokio::test]
+async fn test_session_runner_runs_until_max_turns() {
+    let (app, db, mock) = create_test_app_with_llm();
+
+    let alice = create_test_agent_with_role(app.clone(), "Alice", "discussant").await;
+    let bob = create_test_agent_with_role(app.clone(), "Bob", "discussant").await;
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({
+            "theme": "Debate: tabs vs spaces",
+            "participant_ids": [&alice, &bob],
+            "max_turns": 3
+        })),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+
+    mock.push_text_response("Tabs.");
+    mock.push_text_response("Spaces.");
+    mock.push_text_response("Tabs, obviously.");
+
+    let (status, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/run"),
+        Some(serde_json::json!({"interval_ms": 0})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(resp["ok"], true, "{resp}");
+    assert_eq!(resp["status"]["status"], "running");
+
+    let session = wait_for_session_status(&db, &session_id, "completed").await;
+    assert_eq!(session.turn_number, 3);
+
+    {
+        let conn = db.lock().unwrap();
+        let logs = opencrab_db::queries::list_session_logs_by_session(&conn, &session_id).unwrap();
+        let speakers: Vec<_> = logs
+            .iter()
+            .filter(|l| l.log_type == "speech")
+            .map(|l| l.speaker_id.clone().unwrap())
+            .collect();
+        assert_eq!(speakers, vec![alice.clone(), bob.clone(), alice.clone()]);
+    }
+
+    let (_, resp) = send_request(
+        app,
+        "GET",
+        &format!("/api/sessions/{session_id}/run"),
+        None,
+    )
+    .await;
+    assert_eq!(resp["status"]["status"], "completed");
+    assert_eq!(resp["status"]["turns"], 3);
+}
+
+/// Test: The runner honours send_noreact, pauses when everyone passes, and can be stopped.
+#[tokio::test]
+async fn test_session_runner_pause_and_stop() {
+    let (app, db, mock) = create_test_app_with_llm();
+
+    let alice = create_test_agent_with_role(app.clone(), "Alice", "discussant").await;
+    let bob = create_test_agent_with_role(app.clone(), "Bob", "discussant").await;
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({
+            "theme": "Nothing much",
+            "participant_ids": [&alice, &bob]
+        })),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+
+    // Pausing a session that is not running is a no-op.
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/pause"),
+        None,
+    )
+    .await;
+    assert_eq!(resp["ok"], true);
+    assert_eq!(resp["paused"], false);
+
+    // Both agents pass their turn.
+    for id in ["tc-pass-1", "tc-pass-2"] {
+        mock.push_tool_call_response(vec![ToolCall {
+            id: id.to_string(),
+            call_type: "function".to_string(),
+            function: FunctionCall {
+                name: "send_noreact".to_string(),
+                arguments: r#"{"reason": "nothing to add"}"#.to_string(),
+            },
+        }]);
+        mock.push_text_response("");
+    }
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/run"),
+        Some(serde_json::json!({"interval_ms": 0})),
+    )
+    .await;
+    assert_eq!(resp["ok"], true, "{resp}");
+
+    let session = wait_for_session_status(&db, &session_id, "paused").await;
+    assert_eq!(session.turn_number, 2);
+    {
+        let conn = db.lock().unwrap();
+        let logs = opencrab_db::queries::list_session_logs_by_session(&conn, &session_id).unwrap();
+        assert!(logs.iter().all(|l| l.log_type != "speech"), "noreact must not be logged as speech");
+        assert!(logs.iter().any(|l| l.content.contains("every participant passed")));
+    }
+
+    // Stop is terminal: the session cannot be run again.
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/stop"),
+        None,
+    )
+    .await;
+    assert_eq!(resp["stopped"], true);
+    wait_for_session_status(&db, &session_id, "stopped").await;
+
+    let (_, resp) = send_request(
+        app,
+        "POST",
+        &format!("/api/sessions/{session_id}/run"),
+        Some(serde_json::json!({})),
+    )
+    .await;
+    assert_eq!(resp["ok"], false);
+}
+
+/// Test: Pausing returns without waiting for the turn in progress, and an
+/// immediate re-run waits for the old loop so the two never overlap.
+#[tokio::test]
+async fn test_session_runner_pause_then_run_does_not_overlap() {
+    let (app, db, mock) = create_test_app_with_llm();
+
+    let alice = create_test_agent_with_role(app.clone(), "Alice", "discussant").await;
+    let bob = create_test_agent_with_role(app.clone(), "Bob", "discussant").await;
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({
+            "theme": "Slow talk",
+            "participant_ids": [&alice, &bob],
+            "max_turns": 3
+        })),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+
+    mock.set_latency(std::time::Duration::from_millis(200));
+    mock.push_text_response("One.");
+    mock.push_text_response("Two.");
+    mock.push_text_response("Three.");
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/run"),
+        Some(serde_json::json!({"interval_ms": 0})),
+    )
+    .await;
+    assert_eq!(resp["ok"], true, "{resp}");
+
+    // Pause while the first turn is waiting on the LLM, then run straight away.
+    for _ in 0..100 {
+        if mock.request_count() > 0 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/pause"),
+        None,
+    )
+    .await;
+    assert_eq!(resp["paused"], true);
+    {
+        let conn = db.lock().unwrap();
+        let session = opencrab_db::queries::get_session(&conn, &session_id).unwrap().unwrap();
+        assert_eq!(session.turn_number, 0, "pause does not wait for the turn in progress");
+    }
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/run"),
+        Some(serde_json::json!({"interval_ms": 0})),
+    )
+    .await;
+    assert_eq!(resp["ok"], true, "{resp}");
+
+    let session = wait_for_session_status(&db, &session_id, "completed").await;
+    assert_eq!(session.turn_number, 3);
+    assert_eq!(mock.max_in_flight(), 1);
+
+    let conn = db.lock().unwrap();
+    let logs = opencrab_db::queries::list_session_logs_by_session(&conn, &session_id).unwrap();
+    let speakers: Vec<_> = logs
+        .iter()
+        .filter(|l| l.log_type == "speech")
+        .map(|l| l.speaker_id.clone().unwrap())
+        .collect();
+    assert_eq!(speakers, vec![alice.clone(), bob, alice]);
+}
+
+/// Test: memory search ranks semantically close memories (including curated
+/// ones that FTS does not index) when an embedding model is configured.
+#[tokio::test]
+async fn test_memory_search_hybrid_with_embeddings() {
+    let (state, _mock) = create_test_state_with_llm();
+    let db = state.db.clone();
+    let app = create_router(state.clone());
+    let (agent_id, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+
+    {
+        let conn = db.lock().unwrap();
+        opencrab_db::queries::upsert_curated_memory(
+            &conn,
+            &opencrab_db::queries::CuratedMemoryRow {
+                id: "mem-cat".to_string(),
+                agent_id: agent_id.clone(),
+                category: "preference".to_string(),
+                content: "私は猫が大好きで毎日一緒に寝ている".to_string(),
+            },
+        )
+        .unwrap();
+        opencrab_db::queries::insert_session_log(
+            &conn,
+            &opencrab_db::queries::SessionLogRow {
+                id: None,
+                agent_id: agent_id.clone(),
+                session_id: "session-weather".to_string(),
+                log_type: "speech".to_string(),
+                content: "明日の天気予報は雨らしい".to_string(),
+                speaker_id: Some(agent_id.clone()),
+                turn_number: Some(1),
+                metadata_json: None,
+            },
+        )
+        .unwrap();
+    }
+
+    // Searching never embeds stored memories; the curated one is invisible to FTS.
+    let search = serde_json::json!({"query": "猫が好き", "limit": 5});
+    let (status, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/agents/{agent_id}/memory/search"),
+        Some(search.clone()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(resp["count"], 0, "{resp}");
+    {
+        let conn = db.lock().unwrap();
+        let stored = opencrab_db::queries::list_embedded_memories(&conn, &agent_id, "mock:hash-256")
+            .unwrap();
+        assert!(stored.is_empty());
+    }
+
+    // Indexing (normally done after writes and by the consolidation job) stores the vectors.
+    assert_eq!(opencrab_server::process::index_embeddings(&state, &agent_id).await.unwrap(), 2);
+
+    let (_, resp) = send_request(
+        app,
+        "POST",
+        &format!("/api/agents/{agent_id}/memory/search"),
+        Some(search),
+    )
+    .await;
+    let results = resp["results"].as_array().unwrap();
+    assert!(!results.is_empty(), "{resp}");
+    assert_eq!(results[0]["source"], "curated");
+    assert_eq!(results[0]["id"], "mem-cat");
+    assert!(results[0]["vector_score"].as_f64().unwrap() > 0.2);
+
+    // Logs written through the server are embedded in the background.
+    let log = opencrab_db::queries::SessionLogRow {
+        id: None,
+        agent_id: agent_id.clone(),
+        session_id: "session-weather".to_string(),
+        log_type: "speech".to_string(),
+        content: "傘を持っていこう".to_string(),
+        speaker_id: Some(agent_id.clone()),
+        turn_number: Some(2),
+        metadata_json: None,
+    };
+    opencrab_server::process::record_session_log(&state, &log).unwrap();
+    for _ in 0..100 {
+        let stored = {
+            let conn = db.lock().unwrap();
+            opencrab_db::queries::list_embedded_memories(&conn, &agent_id, "mock:hash-256").unwrap()
+        };
+        if stored.len() == 3 {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    panic!("the new log was never embedded");
+}
+
+/// Test: a conversation that exceeds the model's context window is folded
+/// into a rolling summary while recent turns stay verbatim.
+#[tokio::test]
+async fn test_long_conversation_is_summarised() {
+    let (app, db, mock) = create_test_app_with_llm();
+    mock.set_context_window(2_048);
+
+    let (alice, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+    let (bob, app) = create_test_agent_named(app, "Bob", "Creative Thinker").await;
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({
+            "theme": "Long discussion",
+            "participant_ids": [&alice, &bob]
+        })),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+
+    // ~40 turns of ~60 tokens each: far beyond the history budget.
+    {
+        let conn = db.lock().unwrap();
+        for i in 0..40 {
+            let speaker = if i % 2 == 0 { &alice } else { &bob };
+            opencrab_db::queries::insert_session_log(
+                &conn,
+                &opencrab_db::queries::SessionLogRow {
+                    id: None,
+                    agent_id: speaker.clone(),
+                    session_id: session_id.clone(),
+                    log_type: "speech".to_string(),
+                    content: format!("point-{i:02} {}", "detail ".repeat(30)),
+                    speaker_id: Some(speaker.clone()),
+                    turn_number: Some(i),
+                    metadata_json: None,
+                },
+            )
+            .unwrap();
+        }
+    }
+
+    mock.set_summary_reply("We agreed to ship on Friday.");
+    mock.push_text_response("Sounds good.");
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/messages"),
+        Some(serde_json::json!({"agent_id": alice, "content": "Any objections?"})),
+    )
+    .await;
+    assert_eq!(resp["responses"][0]["content"], "Sounds good.", "{resp}");
+
+    // The summary was persisted and covers the folded turns.
+    let summary = {
+        let conn = db.lock().unwrap();
+        opencrab_db::queries::list_session_logs_by_session(&conn, &session_id)
+            .unwrap()
+            .into_iter()
+            .rfind(|l| l.log_type == "summary")
+            .expect("summary log")
+    };
+    assert_eq!(summary.content, "We agreed to ship on Friday.");
+    let metadata: serde_json::Value =
+        serde_json::from_str(summary.metadata_json.as_deref().unwrap()).unwrap();
+    assert!(metadata["summarized_turns"].as_u64().unwrap() > 0);
+
+    // The agent saw the summary plus recent turns, not the oldest ones.
+    let requests = mock.requests.lock().unwrap();
+    let agent_request = requests.last().unwrap();
+    let history = agent_request
+        .messages
+        .iter()
+        .filter_map(|m| m.text_content())
+        .collect::<Vec<_>>()
+        .join("\n");
+    assert!(history.contains("We agreed to ship on Friday."));
+    assert!(history.contains("Any objections?"));
+    assert!(!history.contains("point-00"));
+    assert!(history.contains("point-39"));
+}
+
+/// Test: Follow-up turns carry the earlier exchange as structured messages,
+/// including the agent's own tool calls and their results.
+#[tokio::test]
+async fn test_history_is_sent_as_structured_messages() {
+    let (app, _db, mock) = create_test_app_with_llm();
+
+    let (alice, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+    let (bob, app) = create_test_agent_named(app, "Bob", "Creative Thinker").await;
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({
+            "theme": "Structured history",
+            "participant_ids": [&alice, &bob]
+        })),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+
+    mock.push_tool_call_response(vec![ToolCall {
+        id: "tc-info-1".to_string(),
+        call_type: "function".to_string(),
+        function: FunctionCall {
+            name: "get_system_info".to_string(),
+            arguments: "{}".to_string(),
+        },
+    }]);
+    mock.push_text_response("First answer");
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/messages"),
+        Some(serde_json::json!({"agent_id": alice, "content": "Which system is this?"})),
+    )
+    .await;
+    assert_eq!(resp["responses"][0]["agent_id"], bob);
+    assert_eq!(resp["responses"][0]["content"], "First answer", "{resp}");
+
+    mock.push_text_response("Second answer");
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/messages"),
+        Some(serde_json::json!({"agent_id": alice, "content": "And the follow-up?"})),
+    )
+    .await;
+    assert_eq!(resp["responses"][0]["content"], "Second answer", "{resp}");
+
+    let requests = mock.requests.lock().unwrap();
+    let messages: Vec<&Message> = requests
+        .last()
+        .unwrap()
+        .messages
+        .iter()
+        .filter(|m| m.role != Role::System)
+        .collect();
+    assert_eq!(messages.len(), 5, "{messages:?}");
+
+    assert_eq!(messages[0].role, Role::User);
+    assert_eq!(messages[0].name.as_deref(), Some("Alice"));
+    assert!(messages[0]
+        .text_content()
+        .unwrap()
+        .contains("Which system is this?"));
+
+    assert_eq!(messages[1].role, Role::Assistant);
+    let calls = messages[1].tool_calls.as_ref().expect("tool calls");
+    assert_eq!(calls[0].id, "tc-info-1");
+    assert_eq!(calls[0].function.name, "get_system_info");
+
+    assert_eq!(messages[2].role, Role::Tool);
+    assert_eq!(messages[2].tool_call_id.as_deref(), Some("tc-info-1"));
+
+    assert_eq!(messages[3].role, Role::Assistant);
+    assert_eq!(messages[3].text_content(), Some("First answer"));
+
+    assert_eq!(messages[4].role, Role::User);
+    assert_eq!(messages[4].name.as_deref(), Some("Alice"));
+    assert!(messages[4]
+        .text_content()
+        .unwrap()
+        .contains("And the follow-up?"));
+}
+
+/// Test: Per-agent LLM config is stored via the API and picks the model per purpose.
+#[tokio::test]
+async fn test_agent_llm_config_selects_model() {
+    let (app, _db, mock) = create_test_app_with_llm();
+
+    let (alice, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+    let (bob, app) = create_test_agent_named(app, "Bob", "Creative Thinker").await;
+
+    let (_, resp) = send_request(app.clone(), "GET", &format!("/api/agents/{bob}/llm"), None).await;
+    assert_eq!(resp["configured"], false);
+    assert_eq!(resp["resolved"]["conversation"], "mock:gpt-4o");
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "PUT",
+        &format!("/api/agents/{bob}/llm"),
+        Some(serde_json::json!({
+            "default_provider": "mock",
+            "default_model": "cheap-chat",
+            "models": {"analysis": {"provider": "mock", "model": "frontier"}},
+            "selectable_models": [{"provider": "elsewhere", "model": "big"}]
+        })),
+    )
+    .await;
+    assert_eq!(resp["ok"], true, "{resp}");
+    assert_eq!(resp["resolved"]["conversation"], "mock:cheap-chat");
+    assert_eq!(resp["resolved"]["analysis"], "mock:frontier");
+    assert_eq!(resp["resolved"]["embedding"], "mock:hash-256");
+    assert_eq!(resp["warnings"].as_array().unwrap().len(), 1, "{resp}");
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "PUT",
+        &format!("/api/agents/{bob}/llm"),
+        Some(serde_json::json!({"default_provider": "", "default_model": "x"})),
+    )
+    .await;
+    assert_eq!(resp["ok"], false);
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({"theme": "Models", "participant_ids": [&alice, &bob]})),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+
+    mock.push_text_response("Answered by the cheap model.");
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/messages"),
+        Some(serde_json::json!({"agent_id": alice, "content": "Which model are you?"})),
+    )
+    .await;
+    assert_eq!(resp["responses"][0]["agent_id"], bob);
+    assert_eq!(mock.requests.lock().unwrap().last().unwrap().model, "cheap-chat");
+
+    // Removing the config falls back to the server default.
+    let (_, resp) = send_request(app.clone(), "DELETE", &format!("/api/agents/{bob}/llm"), None).await;
+    assert_eq!(resp["deleted"], true);
+    mock.push_text_response("Answered by the default model.");
+    send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/messages"),
+        Some(serde_json::json!({"agent_id": alice, "content": "And now?"})),
+    )
+    .await;
+    assert_eq!(mock.requests.lock().unwrap().last().unwrap().model, "gpt-4o");
+}
+
+/// Record spending for an agent as if an LLM call had been made now.
+fn record_spend(db: &Arc<Mutex<rusqlite::Connection>>, agent_id: &str, session_id: &str, cost: f64) {
+    let conn = db.lock().unwrap();
+    opencrab_db::queries::insert_llm_metrics(
+        &conn,
+        &opencrab_db::queries::LlmMetricsRow {
+            id: uuid::Uuid::new_v4().to_string(),
+            agent_id: agent_id.to_string(),
+            session_id: Some(session_id.to_string()),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            provider: "mock".to_string(),
+            model: "gpt-4o".to_string(),
+            purpose: "conversation".to_string(),
+            task_type: None,
+            complexity: None,
+            input_tokens: 0,
+            output_tokens: 0,
+            total_tokens: 0,
+            estimated_cost_usd: cost,
+            latency_ms: 0,
+            time_to_first_token_ms: None,
+        },
+    )
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_budget_downgrades_or_refuses() {
+    for downgrade in [true, false] {
+        let (mut state, mock) = create_test_state_with_llm();
+        state.budget = opencrab_server::config::BudgetConfig {
+            agent_daily_usd: Some(1.0),
+            downgrade_model: downgrade.then(|| "mock:budget-model".to_string()),
+            ..Default::default()
+        };
+        let db = state.db.clone();
+        let app = create_router(state);
+
+        let (alice, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+        let (bob, app) = create_test_agent_named(app, "Bob", "Creative Thinker").await;
+        let (_, resp) = send_request(
+            app.clone(),
+            "POST",
+            "/api/sessions",
+            Some(serde_json::json!({"theme": "Budget", "participant_ids": [&alice, &bob]})),
+        )
+        .await;
+        let session_id = resp["id"].as_str().unwrap().to_string();
+
+        record_spend(&db, &bob, &session_id, 0.9);
+        let (_, resp) = send_request(app.clone(), "GET", &format!("/api/agents/{bob}/analytics"), None).await;
+        let limit = &resp["budget"]["limits"][0];
+        assert_eq!(resp["budget"]["enabled"], true);
+        assert_eq!(limit["scope"], "agent_daily");
+        assert_eq!(limit["warning"], true);
+        assert_eq!(limit["exceeded"], false);
+
+        record_spend(&db, &bob, &session_id, 0.2);
+        let (_, resp) = send_request(app.clone(), "GET", &format!("/api/agents/{bob}/analytics"), None).await;
+        assert_eq!(resp["budget"]["limits"][0]["exceeded"], true);
+
+        mock.push_text_response("Cheap answer.");
+        let (_, resp) = send_request(
+            app.clone(),
+            "POST",
+            &format!("/api/sessions/{session_id}/messages"),
+            Some(serde_json::json!({"agent_id": alice, "content": "Still there?"})),
+        )
+        .await;
+        let reply = resp["responses"][0]["content"].as_str().unwrap().to_string();
+
+        let (_, logs) = send_request(app.clone(), "GET", &format!("/api/sessions/{session_id}/logs"), None).await;
+        let budget_logs: Vec<&serde_json::Value> =
+            logs.as_array().unwrap().iter().filter(|l| l["log_type"] == "budget").collect();
+        assert_eq!(budget_logs.len(), 1, "{logs}");
+        let metadata: serde_json::Value =
+            serde_json::from_str(budget_logs[0]["metadata_json"].as_str().unwrap()).unwrap();
+
+        if downgrade {
+            assert_eq!(reply, "Cheap answer.");
+            assert_eq!(mock.requests.lock().unwrap().last().unwrap().model, "budget-model");
+            assert_eq!(metadata["action"], "downgrade");
+
+            // Budget notices are not part of the conversation history.
+            mock.push_text_response("Another cheap answer.");
+            send_request(
+                app.clone(),
+                "POST",
+                &format!("/api/sessions/{session_id}/messages"),
+                Some(serde_json::json!({"agent_id": alice, "content": "And now?"})),
+            )
+            .await;
+            let requests = mock.requests.lock().unwrap();
+            let history = &requests.last().unwrap().messages;
+            assert!(history
+                .iter()
+                .all(|m| !m.text_content().unwrap_or_default().contains("LLM budget")));
+        } else {
+            assert!(reply.starts_with("(Error: LLM budget exceeded"), "{reply}");
+            assert!(mock.requests.lock().unwrap().is_empty());
+            assert_eq!(metadata["action"], "refuse");
+            assert_eq!(metadata["budgets"][0]["scope"], "agent_daily");
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_purpose_routes_send_tool_iterations_to_tool_model() {
+    let (state, mock) = create_test_state_with_router(|router| {
+        router.set_purpose_route("conversation", "mock:chat-model", vec![]);
+        router.set_purpose_route("tool_calling", "mock:tool-model", vec![]);
+    });
+    let db = state.db.clone();
+    let app = create_router(state);
+
+    let (alice, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+    let (bob, app) = create_test_agent_named(app, "Bob", "Creative Thinker").await;
+
+    let (_, resp) = send_request(app.clone(), "GET", &format!("/api/agents/{bob}/llm"), None).await;
+    assert_eq!(resp["resolved"]["conversation"], "mock:chat-model");
+    assert_eq!(resp["resolved"]["tool_calling"], "mock:tool-model");
+    assert_eq!(resp["resolved"]["analysis"], "mock:gpt-4o");
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({"theme": "Routing", "participant_ids": [&alice, &bob]})),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+
+    mock.push_tool_call_response(vec![ToolCall {
+        id: "tc-usage".to_string(),
+        call_type: "function".to_string(),
+        function: FunctionCall {
+            name: "analyze_llm_usage".to_string(),
+            arguments: serde_json::json!({"period": "all"}).to_string(),
+        },
+    }]);
+    mock.push_text_response("Answer written by the tool model.");
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/messages"),
+        Some(serde_json::json!({"agent_id": alice, "content": "How much have we spent?"})),
+    )
+    .await;
+    // The answer after tool results is not asked again from the conversation model.
+    assert_eq!(resp["responses"][0]["content"], "Answer written by the tool model.");
+    let models: Vec<String> = mock.requests.lock().unwrap().iter().map(|r| r.model.clone()).collect();
+    assert_eq!(models, vec!["chat-model", "tool-model"]);
+
+    // Metrics are tagged with the purpose of each call.
+    let conn = db.lock().unwrap();
+    let stats =
+        opencrab_db::queries::get_llm_metrics_by_model_and_purpose(&conn, &bob, "1970-01-01").unwrap();
+    let mut tagged: Vec<(String, String, i64)> =
+        stats.into_iter().map(|s| (s.model, s.purpose, s.count)).collect();
+    tagged.sort();
+    assert_eq!(
+        tagged,
+        vec![
+            ("chat-model".to_string(), "conversation".to_string(), 1),
+            ("tool-model".to_string(), "tool_calling".to_string(), 1),
+        ]
+    );
+}
+
+/// Test: An agent's selectable_models can only narrow `[llm.self_selection] allowed_aliases`.
+#[tokio::test]
+async fn test_agent_selectable_models_cannot_bypass_server_list() {
+    let (mut state, _mock) = create_test_state_with_llm();
+    state.self_selection.allowed_aliases = vec!["mock:cheap-chat".to_string()];
+    let app = create_router(state.clone());
+
+    let (bob, app) = create_test_agent_named(app, "Bob", "Creative Thinker").await;
+    let (_, resp) = send_request(
+        app,
+        "PUT",
+        &format!("/api/agents/{bob}/llm"),
+        Some(serde_json::json!({
+            "default_provider": "mock",
+            "default_model": "gpt-4o",
+            "allow_self_selection": true,
+            "selectable_models": [
+                {"provider": "mock", "model": "frontier"},
+                {"provider": "mock", "model": "cheap-chat"}
+            ]
+        })),
+    )
+    .await;
+    assert_eq!(resp["ok"], true, "{resp}");
+
+    let policy = opencrab_server::process::model_policy(&state, &bob);
+    let err = policy.check("mock:frontier").unwrap_err();
+    assert!(err.contains("not allowed"), "{err}");
+    assert_eq!(policy.check("mock:cheap-chat").unwrap(), "mock:cheap-chat");
+}
+
+/// Test: select_llm only accepts allowed models, and `this_session` carries over to later turns.
+#[tokio::test]
+async fn test_select_llm_policy_and_duration() {
+    let (app, _db, mock) = create_test_app_with_llm();
+
+    let (alice, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+    let (bob, app) = create_test_agent_named(app, "Bob", "Creative Thinker").await;
+    let (_, resp) = send_request(
+        app.clone(),
+        "PUT",
+        &format!("/api/agents/{bob}/llm"),
+        Some(serde_json::json!({
+            "default_provider": "mock",
+            "default_model": "cheap-chat",
+            "allow_self_selection": true,
+            "selectable_models": [{"provider": "mock", "model": "frontier"}]
+        })),
+    )
+    .await;
+    assert_eq!(resp["ok"], true, "{resp}");
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({"theme": "Models", "participant_ids": [&alice, &bob]})),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+
+    let select = |id: &str, model: &str, duration: &str| ToolCall {
+        id: id.to_string(),
+        call_type: "function".to_string(),
+        function: FunctionCall {
+            name: "select_llm".to_string(),
+            arguments: serde_json::json!({
+                "model_alias": model,
+                "reason": "test",
+                "duration": duration,
+            })
+            .to_string(),
+        },
+    };
+    // A model outside the allow-list is refused; an allowed one is kept for the session.
+    mock.push_tool_call_response(vec![select("tc-1", "mock:expensive", "this_turn")]);
+    mock.push_tool_call_response(vec![select("tc-2", "mock:frontier", "this_session")]);
+    mock.push_text_response("Switched.");
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/messages"),
+        Some(serde_json::json!({"agent_id": alice, "content": "Think hard."})),
+    )
+    .await;
+    assert_eq!(resp["responses"][0]["content"], "Switched.", "{resp}");
+    {
+        let requests = mock.requests.lock().unwrap();
+        let models: Vec<&str> = requests.iter().map(|r| r.model.as_str()).collect();
+        assert_eq!(models, vec!["cheap-chat", "cheap-chat", "frontier"]);
+        let refused = requests[1]
+            .messages
+            .iter()
+            .find(|m| m.tool_call_id.as_deref() == Some("tc-1"))
+            .and_then(|m| m.text_content())
+            .unwrap();
+        assert!(refused.contains("not allowed"), "{refused}");
+    }
+
+    mock.push_text_response("Still on the frontier model.");
+    send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/messages"),
+        Some(serde_json::json!({"agent_id": alice, "content": "Next question."})),
+    )
+    .await;
+    assert_eq!(mock.requests.lock().unwrap().last().unwrap().model, "frontier");
+}
+
+#[tokio::test]
+async fn test_pricing_endpoints_drive_cost() {
+    let (state, mock) = create_test_state_with_llm();
+    let app = create_router(state);
+
+    // Built-in prices are listed but the mock provider has none yet.
+    let (_, resp) = send_request(app.clone(), "GET", "/api/pricing", None).await;
+    assert!(resp["count"].as_u64().unwrap() > 0);
+    assert!(resp["prices"].as_array().unwrap().iter().all(|p| p["source"] == "default"));
+    let (_, resp) = send_request(app.clone(), "GET", "/api/pricing/mock/gpt-4o", None).await;
+    assert_eq!(resp["ok"], false);
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "PUT",
+        "/api/pricing/mock/gpt-4o",
+        Some(serde_json::json!({"input_per_million": -1.0, "output_per_million": 1.0})),
+    )
+    .await;
+    assert_eq!(resp["ok"], false);
+    let (_, resp) = send_request(
+        app.clone(),
+        "PUT",
+        "/api/pricing/mock/gpt-4o",
+        Some(serde_json::json!({
+            "input_per_million": 100000.0,
+            "output_per_million": 200000.0,
+            "context_window": 8192,
+        })),
+    )
+    .await;
+    assert_eq!(resp["ok"], true, "{resp}");
+    let (_, resp) = send_request(app.clone(), "GET", "/api/pricing/mock/gpt-4o", None).await;
+    assert_eq!(resp["pricing"]["source"], "db");
+    assert_eq!(resp["pricing"]["context_window"], 8192);
+
+    // The updated price is used for the next call's cost (10 in + 5 out tokens).
+    let (alice, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+    let (bob, app) = create_test_agent_named(app, "Bob", "Creative Thinker").await;
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({"theme": "Pricing", "participant_ids": [&alice, &bob]})),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+    mock.push_text_response("Priced answer.");
+    send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/messages"),
+        Some(serde_json::json!({"agent_id": alice, "content": "How much?"})),
+    )
+    .await;
+    let (_, resp) = send_request(app.clone(), "GET", &format!("/api/agents/{bob}/analytics"), None).await;
+    assert!((resp["total_cost"].as_f64().unwrap() - 2.0).abs() < 1e-9, "{resp}");
+
+    // Import an OpenRouter /models payload; model IDs may contain slashes.
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/pricing/import",
+        Some(serde_json::json!({"models": {"data": [
+            {"id": "deepseek/deepseek-chat", "context_length": 64000,
+             "pricing": {"prompt": "0.00000014", "completion": "0.00000028"}},
+            {"id": "broken/model", "pricing": {"prompt": "-1", "completion": "-1"}},
+        ]}})),
+    )
+    .await;
+    assert_eq!(resp["imported"], 1, "{resp}");
+    let (_, resp) =
+        send_request(app.clone(), "GET", "/api/pricing/openrouter/deepseek/deepseek-chat", None).await;
+    assert!((resp["pricing"]["input_per_million"].as_f64().unwrap() - 0.14).abs() < 1e-9);
+    assert_eq!(resp["pricing"]["context_window"], 64000);
+
+    let (_, resp) = send_request(app.clone(), "POST", "/api/pricing/import", Some(serde_json::json!({"provider": "nope"}))).await;
+    assert_eq!(resp["ok"], false);
+
+    let (_, resp) = send_request(app.clone(), "DELETE", "/api/pricing/mock/gpt-4o", None).await;
+    assert_eq!(resp["deleted"], true);
+    let (_, resp) = send_request(app.clone(), "GET", "/api/pricing/mock/gpt-4o", None).await;
+    assert_eq!(resp["ok"], false);
+    let (_, resp) = send_request(app, "GET", "/api/pricing", None).await;
+    let stored: Vec<&serde_json::Value> =
+        resp["prices"].as_array().unwrap().iter().filter(|p| p["source"] == "db").collect();
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[0]["model"], "deepseek/deepseek-chat");
+}
+
+#[tokio::test]
+async fn test_llm_health_reports_circuit_state() {
+    let (state, _mock) = create_test_state_with_llm();
+    let app = create_router(state);
+
+    let (status, resp) = send_request(app, "GET", "/api/llm/health", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(resp["healthy"], true);
+    assert_eq!(resp["providers"]["mock"]["reachable"], true);
+    assert_eq!(resp["providers"]["mock"]["circuit"], "closed");
+    assert_eq!(resp["providers"]["mock"]["consecutive_failures"], 0);
+}
+
+#[tokio::test]
+async fn test_context_overflow_trims_history_and_retries() {
+    let (app, _db, mock) = create_test_app_with_llm();
+    let (alice, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+    let (bob, app) = create_test_agent_named(app, "Bob", "Creative Thinker").await;
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({"theme": "Overflow", "participant_ids": [&alice, &bob]})),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+
+    for i in 0..3 {
+        mock.push_text_response(&format!("Reply {i}"));
+        send_request(
+            app.clone(),
+            "POST",
+            &format!("/api/sessions/{session_id}/messages"),
+            Some(serde_json::json!({"agent_id": alice, "content": format!("Question {i}")})),
+        )
+        .await;
+    }
+
+    mock.push_error(
+        400,
+        r#"{"error":{"message":"This model's maximum context length is 8192 tokens","code":"context_length_exceeded"}}"#,
+    );
+    mock.push_text_response("Short answer.");
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/messages"),
+        Some(serde_json::json!({"agent_id": alice, "content": "Last question"})),
+    )
+    .await;
+    assert_eq!(resp["responses"][0]["content"], "Short answer.", "{resp}");
+
+    let requests = mock.requests.lock().unwrap();
+    let (rejected, retried) = (&requests[requests.len() - 2], &requests[requests.len() - 1]);
+    assert!(retried.messages.len() < rejected.messages.len());
+    assert_eq!(
+        retried.messages.last().unwrap().text_content(),
+        rejected.messages.last().unwrap().text_content()
+    );
+}
+
+/// Test: curated memories, impressions of participants and related past logs
+/// are injected into the system prompt, recorded as a `context` log, and can
+/// be previewed.
+#[tokio::test]
+async fn test_memory_context_injected_into_system_prompt() {
+    let (app, db, mock) = create_test_app_with_llm();
+    let (alice, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+    let (bob, app) = create_test_agent_named(app, "Bob", "Creative Thinker").await;
+
+    {
+        let conn = db.lock().unwrap();
+        opencrab_db::queries::upsert_curated_memory(
+            &conn,
+            &opencrab_db::queries::CuratedMemoryRow {
+                id: "mem-reflection".to_string(),
+                agent_id: bob.clone(),
+                category: "reflection".to_string(),
+                content: "Ground abstract claims in concrete examples".to_string(),
+            },
+        )
+        .unwrap();
+        opencrab_db::queries::upsert_impression(
+            &conn,
+            &opencrab_db::queries::ImpressionRow {
+                id: "imp-alice".to_string(),
+                agent_id: bob.clone(),
+                session_id: "session-old".to_string(),
+                target_id: alice.clone(),
+                target_name: "Alice".to_string(),
+                personality: "asks sharp questions".to_string(),
+                communication_style: String::new(),
+                recent_behavior: String::new(),
+                agreement: String::new(),
+                notes: String::new(),
+                last_updated_turn: 3,
+            },
+        )
+        .unwrap();
+        opencrab_db::queries::insert_session_log(
+            &conn,
+            &opencrab_db::queries::SessionLogRow {
+                id: None,
+                agent_id: bob.clone(),
+                session_id: "session-old".to_string(),
+                log_type: "speech".to_string(),
+                content: "Transparency reports helped the moderation team".to_string(),
+                speaker_id: Some(bob.clone()),
+                turn_number: Some(1),
+                metadata_json: None,
+            },
+        )
+        .unwrap();
+    }
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({
+            "theme": "AI Ethics",
+            "participant_ids": [&alice, &bob]
+        })),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+
+    mock.push_text_response("Concrete examples help here.");
+    let (status, _) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/messages"),
+        Some(serde_json::json!({
+            "agent_id": alice,
+            "content": "Should transparency be mandatory?"
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let system_prompt = {
+        let requests = mock.requests.lock().unwrap();
+        requests.last().unwrap().messages[0].text_content().unwrap().to_string()
+    };
+    assert!(system_prompt.contains("Alice — personality: asks sharp questions"), "{system_prompt}");
+    assert!(system_prompt.contains("[reflection] Ground abstract claims"));
+    assert!(system_prompt.contains("Transparency reports helped"));
+
+    // The injected items are logged for debugging but kept out of the history.
+    let logs = {
+        let conn = db.lock().unwrap();
+        opencrab_db::queries::list_session_logs_by_session(&conn, &session_id).unwrap()
+    };
+    let context_log = logs
+        .iter()
+        .find(|l| l.log_type == opencrab_server::process::CONTEXT_LOG_TYPE)
+        .expect("context log");
+    assert_eq!(context_log.agent_id, bob);
+    let metadata: serde_json::Value =
+        serde_json::from_str(context_log.metadata_json.as_deref().unwrap()).unwrap();
+    let kinds: Vec<&str> = metadata["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| i["kind"].as_str().unwrap())
+        .collect();
+    assert_eq!(kinds, vec!["impression", "curated", "related_log"]);
+    let (_, turns) = {
+        let conn = db.lock().unwrap();
+        opencrab_server::process::load_conversation(&conn, &session_id)
+    };
+    assert!(turns.iter().all(|t| !t.content.starts_with("Injected")));
+
+    let (status, preview) = send_request(
+        app,
+        "GET",
+        &format!("/api/agents/{bob}/memory/context?session_id={session_id}&query=moderation"),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(preview["budget_tokens"], 1200);
+    assert_eq!(preview["items"].as_array().unwrap().len(), 3, "{preview}");
+    assert!(preview["text"].as_str().unwrap().contains("moderation team"));
+}
+
+/// Test: the soul's traits are rendered into behavioural instructions and
+/// openness sets the sampling temperature.
+#[tokio::test]
+async fn test_soul_traits_shape_prompt_and_temperature() {
+    let (app, _db, mock) = create_test_app_with_llm();
+    let (alice, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+    let (bob, app) = create_test_agent_named(app, "Bob", "Creative Thinker").await;
+
+    let (status, _) = send_request(
+        app.clone(),
+        "PUT",
+        &format!("/api/agents/{bob}/soul"),
+        Some(serde_json::json!({
+            "agent_id": bob,
+            "persona_name": "Dreamer",
+            "social_style_json": r#"{"assertiveness":0.9,"responsiveness":0.5,"style_name":"Driver"}"#,
+            "personality_json": r#"{"openness":1.0,"extraversion":0.5}"#,
+            "thinking_style_json": r#"{"primary":"Intuitive","secondary":"Analytical"}"#,
+            "custom_traits_json": null
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({"theme": "Product ideas", "participant_ids": [&alice, &bob]})),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+
+    mock.push_text_response("What if the app dreamed with you?");
+    send_request(
+        app,
+        "POST",
+        &format!("/api/sessions/{session_id}/messages"),
+        Some(serde_json::json!({"agent_id": alice, "content": "Any wild ideas?"})),
+    )
+    .await;
+
+    let requests = mock.requests.lock().unwrap();
+    let request = requests.last().unwrap();
+    let system_prompt = request.messages[0].text_content().unwrap();
+    assert!(system_prompt.contains("## Persona: Dreamer"), "{system_prompt}");
+    assert!(system_prompt.contains("Your social style is Driver."));
+    assert!(system_prompt.contains("Your thinking is mainly Intuitive"));
+    assert!(system_prompt.contains("- You are curious and imaginative"));
+    assert!(system_prompt.contains("- State your views directly"));
+    assert!(!system_prompt.contains("You are reserved"));
+    assert!((request.temperature.unwrap() - 1.0).abs() < 1e-6);
+}
+
+#[tokio::test]
+async fn test_prompt_templates_versioning_and_preview() {
+    let (app, db, mock) = create_test_app_with_llm();
+    let (alice, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+    let (bob, app) = create_test_agent_named(app, "Bob", "Creative Thinker").await;
+    {
+        let conn = db.lock().unwrap();
+        opencrab_db::queries::upsert_curated_memory(
+            &conn,
+            &opencrab_db::queries::CuratedMemoryRow {
+                id: "mem-tea".to_string(),
+                agent_id: bob.clone(),
+                category: "preferences".to_string(),
+                content: "Prefers green tea".to_string(),
+            },
+        )
+        .unwrap();
+    }
+
+    // Without templates the built-in one is used.
+    let (_, resp) = send_request(app.clone(), "GET", &format!("/api/agents/{bob}/prompt"), None).await;
+    assert_eq!(resp["effective"]["source"], "builtin");
+    assert!(resp["variables"].as_array().unwrap().iter().any(|v| v["name"] == "memories"));
+
+    // Invalid templates are rejected.
+    let (_, resp) = send_request(
+        app.clone(),
+        "PUT",
+        &format!("/api/agents/{bob}/prompt"),
+        Some(serde_json::json!({"content": "{{#memories}}unclosed"})),
+    )
+    .await;
+    assert_eq!(resp["ok"], false);
+    let (_, resp) = send_request(
+        app.clone(),
+        "PUT",
+        &format!("/api/agents/{bob}/prompt"),
+        Some(serde_json::json!({"content": "{{mood}}"})),
+    )
+    .await;
+    assert!(resp["error"].as_str().unwrap().contains("mood"));
+
+    // A global default, then an agent-specific Japanese template.
+    let (_, resp) = send_request(
+        app.clone(),
+        "PUT",
+        "/api/prompt",
+        Some(serde_json::json!({"content": "Global prompt for {{agent_name}}"})),
+    )
+    .await;
+    assert_eq!(resp["template"]["version"], 1);
+    let (_, resp) = send_request(
+        app.clone(),
+        "PUT",
+        &format!("/api/agents/{bob}/prompt"),
+        Some(serde_json::json!({
+            "content": "あなたは{{agent_name}}です。テーマ: {{session_theme}}（{{gateway}}）{{#memories}}\n覚えていること:\n{{memories}}{{/memories}}",
+            "note": "日本語版"
+        })),
+    )
+    .await;
+    assert_eq!(resp["template"]["version"], 1);
+    send_request(
+        app.clone(),
+        "PUT",
+        &format!("/api/agents/{bob}/prompt"),
+        Some(serde_json::json!({"content": "v2 {{agent_name}}"})),
+    )
+    .await;
+
+    let (_, resp) = send_request(app.clone(), "GET", &format!("/api/agents/{bob}/prompt"), None).await;
+    assert_eq!(resp["active_version"], 2);
+    assert_eq!(resp["versions"].as_array().unwrap().len(), 2);
+    assert_eq!(resp["effective"]["content"], "v2 {{agent_name}}");
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/agents/{bob}/prompt/rollback"),
+        Some(serde_json::json!({"version": 1})),
+    )
+    .await;
+    assert_eq!(resp["ok"], true);
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/agents/{bob}/prompt/rollback"),
+        Some(serde_json::json!({"version": 7})),
+    )
+    .await;
+    assert_eq!(resp["ok"], false);
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({"theme": "新商品", "participant_ids": [&alice, &bob]})),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+
+    // The preview renders exactly what the agent receives.
+    let (_, preview) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/agents/{bob}/prompt/preview"),
+        Some(serde_json::json!({"session_id": session_id})),
+    )
+    .await;
+    assert_eq!(preview["source"], "agent");
+    assert_eq!(preview["version"], 1);
+    let expected = preview["prompt"].as_str().unwrap().to_string();
+    assert!(expected.starts_with("あなたはBobです。テーマ: 新商品（rest）\n覚えていること:\n"), "{expected}");
+    assert!(expected.contains("Prefers green tea"));
+
+    mock.push_text_response("緑茶はいかがですか");
+    send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/sessions/{session_id}/messages"),
+        Some(serde_json::json!({"agent_id": alice, "content": "飲み物は？"})),
+    )
+    .await;
+    let system_prompt = {
+        let requests = mock.requests.lock().unwrap();
+        requests.last().unwrap().messages[0].text_content().unwrap().to_string()
+    };
+    assert_eq!(system_prompt, expected);
+
+    // A draft can be previewed without saving it.
+    let (_, preview) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/agents/{bob}/prompt/preview"),
+        Some(serde_json::json!({"content": "Draft {{agent_id}} via {{gateway}}", "gateway": "discord"})),
+    )
+    .await;
+    assert_eq!(preview["source"], "draft");
+    assert_eq!(preview["prompt"], format!("Draft {bob} via discord"));
+
+    // The global scope and unknown agents are not addressable as agents.
+    let (_, resp) = send_request(app.clone(), "GET", "/api/agents/%2A/prompt", None).await;
+    assert_eq!(resp["ok"], false);
+    let (_, resp) = send_request(
+        app.clone(),
+        "PUT",
+        "/api/agents/no-such-agent/prompt",
+        Some(serde_json::json!({"content": "Orphan {{agent_id}}"})),
+    )
+    .await;
+    assert_eq!(resp["ok"], false);
+    assert_eq!(resp["error"], "Agent not found");
+
+    // Clearing the agent template falls back to the global default.
+    let (_, resp) = send_request(app.clone(), "DELETE", &format!("/api/agents/{bob}/prompt"), None).await;
+    assert_eq!(resp["deactivated"], true);
+    let (_, preview) = send_request(app, "POST", &format!("/api/agents/{bob}/prompt/preview"), Some(serde_json::json!({}))).await;
+    assert_eq!(preview["source"], "global");
+    assert_eq!(preview["prompt"], "Global prompt for Bob");
+}
+
+#[tokio::test]
+async fn test_memory_consolidation_extracts_and_deduplicates() {
+    let (app, db, mock) = create_test_app_with_llm();
+    let (alice, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+    let (bob, app) = create_test_agent_named(app, "Bob", "Creative Thinker").await;
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({"theme": "Launch", "participant_ids": [&alice, &bob]})),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+    let speak = |speaker: &str, content: &str| {
+        let conn = db.lock().unwrap();
+        opencrab_db::queries::insert_session_log(
+            &conn,
+            &opencrab_db::queries::SessionLogRow {
+                id: None,
+                agent_id: speaker.to_string(),
+                session_id: session_id.clone(),
+                log_type: "speech".to_string(),
+                content: content.to_string(),
+                speaker_id: Some(speaker.to_string()),
+                turn_number: None,
+                metadata_json: None,
+            },
+        )
+        .unwrap()
+    };
+    let tea = speak(&alice, "I only drink green tea.");
+    let launch = speak(&bob, "Let's launch the beta on Friday.");
+
+    mock.push_text_response(&format!(
+        r#"{{"preferences": [{{"content": "Alice only drinks green tea", "sources": [{tea}]}}],
+            "decisions": [{{"content": "The beta launches on Friday", "sources": [{launch}]}}],
+            "facts": [], "questions": ["Who writes the release notes?"]}}"#
+    ));
+    let (_, resp) = send_request(app.clone(), "POST", &format!("/api/agents/{bob}/memory/consolidate"), None).await;
+    assert_eq!(resp["ok"], true, "{resp}");
+    assert_eq!(resp["outcome"]["processed_logs"], 2);
+    assert_eq!(resp["outcome"]["added"], 3);
+    {
+        let requests = mock.requests.lock().unwrap();
+        let request = requests.last().unwrap();
+        assert!(request.messages[0].text_content().unwrap().contains("long-term memories"));
+        assert!(request.messages[1].text_content().unwrap().contains(&format!("[#{tea}] [Alice]: I only drink green tea.")));
+    }
+
+    let (_, memories) = send_request(app.clone(), "GET", &format!("/api/agents/{bob}/memory/curated"), None).await;
+    let memories = memories.as_array().unwrap().clone();
+    let find = |category: &str| memories.iter().find(|m| m["category"] == category).unwrap()["id"].as_str().unwrap().to_string();
+    let preference = find("preferences");
+    {
+        let conn = db.lock().unwrap();
+        assert_eq!(opencrab_db::queries::get_curated_memory_sources(&conn, &preference).unwrap(), vec![tea]);
+        let question = find("questions");
+        assert_eq!(opencrab_db::queries::get_curated_memory_sources(&conn, &question).unwrap(), vec![tea, launch]);
+    }
+
+    // Nothing new since the checkpoint: no model call, no run.
+    let calls = mock.requests.lock().unwrap().len();
+    let (_, resp) = send_request(app.clone(), "POST", &format!("/api/agents/{bob}/memory/consolidate"), None).await;
+    assert_eq!(resp["outcome"]["processed_logs"], 0);
+    assert_eq!(mock.requests.lock().unwrap().len(), calls);
+
+    // A restated preference only adds provenance.
+    let again = speak(&alice, "Green tea for me, as always.");
+    mock.push_text_response(&format!(
+        r#"{{"preferences": [{{"content": "Alice only drinks green tea.", "sources": [{again}]}}]}}"#
+    ));
+    let (_, resp) = send_request(app.clone(), "POST", &format!("/api/agents/{bob}/memory/consolidate"), None).await;
+    assert_eq!(resp["outcome"]["added"], 0);
+    assert_eq!(resp["outcome"]["merged"], 1);
+    {
+        let conn = db.lock().unwrap();
+        assert_eq!(opencrab_db::queries::get_curated_memory_sources(&conn, &preference).unwrap(), vec![tea, again]);
+        assert_eq!(opencrab_db::queries::list_curated_memories(&conn, &bob).unwrap().len(), 3);
+    }
+
+    let (_, resp) = send_request(app, "GET", &format!("/api/agents/{bob}/memory/consolidate"), None).await;
+    assert_eq!(resp["checkpoint"], again);
+    assert_eq!(resp["runs"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_memory_consolidation_keeps_checkpoint_on_bad_reply_and_runs_serially() {
+    let (state, mock) = create_test_state_with_llm();
+    let db = state.db.clone();
+    let consolidation = state.consolidation_manager.clone();
+    let app = create_router(state);
+    let (alice, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({"theme": "Tea", "participant_ids": [&alice]})),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+    let tea = {
+        let conn = db.lock().unwrap();
+        opencrab_db::queries::insert_session_log(
+            &conn,
+            &opencrab_db::queries::SessionLogRow {
+                id: None,
+                agent_id: alice.clone(),
+                session_id: session_id.clone(),
+                log_type: "speech".to_string(),
+                content: "I only drink green tea.".to_string(),
+                speaker_id: Some(alice.clone()),
+                turn_number: None,
+                metadata_json: None,
+            },
+        )
+        .unwrap()
+    };
+
+    // A reply without extraction JSON is a failure: the logs stay unconsumed.
+    mock.push_text_response("Sorry, I cannot help with that.");
+    let (_, resp) = send_request(app.clone(), "POST", &format!("/api/agents/{alice}/memory/consolidate"), None).await;
+    assert_eq!(resp["ok"], false, "{resp}");
+    let (_, resp) = send_request(app.clone(), "GET", &format!("/api/agents/{alice}/memory/consolidate"), None).await;
+    assert_eq!(resp["checkpoint"], 0, "{resp}");
+    assert!(resp["runs"].as_array().unwrap().is_empty());
+
+    // Concurrent runs for the same agent do not read the same logs twice.
+    mock.set_latency(std::time::Duration::from_millis(50));
+    mock.push_text_response(r#"{"preferences": ["Alice only drinks green tea"]}"#);
+    let calls = mock.request_count();
+    let path = format!("/api/agents/{alice}/memory/consolidate");
+    let (first, second) = tokio::join!(
+        send_request(app.clone(), "POST", &path, None),
+        send_request(app.clone(), "POST", &path, None),
+    );
+    assert_eq!(first.1["ok"], true, "{}", first.1);
+    assert_eq!(second.1["ok"], true, "{}", second.1);
+    let processed = first.1["outcome"]["processed_logs"].as_u64().unwrap() + second.1["outcome"]["processed_logs"].as_u64().unwrap();
+    assert_eq!(processed, 1);
+    assert_eq!(mock.request_count(), calls + 1);
+    // Finished runs release their per-agent lock.
+    assert_eq!(consolidation.active_agents(), 0);
+
+    let (_, resp) = send_request(app, "GET", &format!("/api/agents/{alice}/memory/consolidate"), None).await;
+    assert_eq!(resp["checkpoint"], tea);
+    assert_eq!(resp["runs"].as_array().unwrap().len(), 1);
+    assert_eq!(opencrab_db::queries::list_curated_memories(&db.lock().unwrap(), &alice).unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_memory_pruning_archives_stale_logs_and_reports_it() {
+    let (app, db, mock) = create_test_app_with_llm();
+    let (alice, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        "/api/sessions",
+        Some(serde_json::json!({"theme": "Archive", "participant_ids": [&alice]})),
+    )
+    .await;
+    let session_id = resp["id"].as_str().unwrap().to_string();
+    let log = |log_type: &str, content: &str, days_ago: i64| {
+        let conn = db.lock().unwrap();
+        let id = opencrab_db::queries::insert_session_log(
+            &conn,
+            &opencrab_db::queries::SessionLogRow {
+                id: None,
+                agent_id: alice.clone(),
+                session_id: session_id.clone(),
+                log_type: log_type.to_string(),
+                content: content.to_string(),
+                speaker_id: Some(alice.clone()),
+                turn_number: None,
+                metadata_json: None,
+            },
+        )
+        .unwrap();
+        let created_at = (chrono::Utc::now() - chrono::Duration::days(days_ago)).to_rfc3339();
+        conn.execute(
+            "UPDATE memory_sessions SET created_at = ?2 WHERE id = ?1",
+            rusqlite::params![id, created_at],
+        )
+        .unwrap();
+        id
+    };
+    log("speech", "The old kettle is broken", 200);
+    log("summary", "We discussed the old kettle", 200);
+    log("speech", "The new kettle arrived", 1);
+
+    mock.push_text_response("{}");
+    let (_, resp) = send_request(app.clone(), "POST", &format!("/api/agents/{alice}/memory/consolidate"), None).await;
+    assert_eq!(resp["ok"], true, "{resp}");
+    assert_eq!(resp["outcome"]["pruned"]["archived"], 1, "{resp}");
+    assert_eq!(resp["outcome"]["pruned"]["deleted"], 0);
+
+    // Archived logs drop out of search but summaries are kept.
+    let (_, resp) = send_request(
+        app.clone(),
+        "POST",
+        &format!("/api/agents/{alice}/memory/search"),
+        Some(serde_json::json!({"query": "kettle"})),
+    )
+    .await;
+    let contents: Vec<&str> = resp["results"].as_array().unwrap().iter().filter_map(|r| r["content"].as_str()).collect();
+    assert!(!contents.contains(&"The old kettle is broken"), "{contents:?}");
+    assert!(contents.contains(&"We discussed the old kettle"), "{contents:?}");
+
+    let (_, resp) = send_request(app.clone(), "GET", &format!("/api/agents/{alice}/memory/consolidate"), None).await;
+    let result: serde_json::Value = serde_json::from_str(resp["runs"][0]["result_json"].as_str().unwrap()).unwrap();
+    assert_eq!(result["pruned"]["archived"], 1);
+
+    // Nothing left to consolidate or prune: no run is recorded.
+    let (_, resp) = send_request(app, "POST", &format!("/api/agents/{alice}/memory/consolidate"), None).await;
+    assert!(resp["outcome"]["run_id"].is_null(), "{resp}");
+    assert!(resp["outcome"]["pruned"].is_null());
+}
+
+/// Test: A server turn recorded through a replay provider replays in strict
+/// mode on another server, although the system prompt carries the current
+/// time and the tools are listed in a different order.
+#[tokio::test]
+async fn test_replay_provider_replays_server_requests() {
+    use opencrab_llm::providers::replay::{ReplayMode, ReplayProvider};
+
+    let path = std::env::temp_dir().join(format!("opencrab-replay-{}.json", uuid::Uuid::new_v4()));
+    let (state, mock) = create_test_state_with_llm();
+    let converse = |provider: ReplayProvider| {
+        let mut router = LlmRouter::new();
+        router.add_provider(Arc::new(provider) as Arc<dyn LlmProvider>);
+        router.set_default_provider("mock");
+        // A fresh server each time, so no memories carry over.
+        let app = create_router(AppState {
+            db: Arc::new(Mutex::new(opencrab_db::init_memory().unwrap())),
+            llm_router: Arc::new(router),
+            embedding_model: None,
+            ..state.clone()
+        });
+        async move {
+            let (alice, app) = create_test_agent_named(app, "Alice", "Curious Researcher").await;
+            let (bob, app) = create_test_agent_named(app, "Bob", "Creative Thinker").await;
+            let (_, resp) = send_request(
+                app.clone(),
+                "POST",
+                "/api/sessions",
+                Some(serde_json::json!({"theme": "Tea", "participant_ids": [&alice, &bob]})),
+            )
+            .await;
+            let session_id = resp["id"].as_str().unwrap().to_string();
+            let (_, resp) = send_request(
+                app,
+                "POST",
+                &format!("/api/sessions/{session_id}/messages"),
+                Some(serde_json::json!({"agent_id": alice, "content": "Green or black?"})),
+            )
+            .await;
+            resp
+        }
+    };
+
+    mock.push_text_response("Green, always.");
+    let recorder = ReplayProvider::new("mock", &path)
+        .unwrap()
+        .with_inner(mock.clone())
+        .with_mode(ReplayMode::Record);
+    let resp = converse(recorder).await;
+    assert_eq!(resp["responses"][0]["content"], "Green, always.", "{resp}");
+    assert_eq!(mock.request_count(), 1);
+
+    // `{{now}}` has second resolution: replay once it has moved on.
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    let resp = converse(ReplayProvider::new("mock", &path).unwrap().with_strict(true)).await;
+    assert_eq!(resp["responses"][0]["content"], "Green, always.", "{resp}");
+    assert_eq!(mock.request_count(), 1);
+    std::fs::remove_file(&path).unwrap();
+}
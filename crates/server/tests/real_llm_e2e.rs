@@ -47,6 +47,20 @@ fn create_real_llm_app() -> (Router, Arc<Mutex<rusqlite::Connection>>) {
         llm_router: Arc::new(router),
         workspace_base,
         default_model: "openrouter:openai/gpt-4o".to_string(),
+        embedding_model: None,
+        self_selection: Default::default(),
+        budget: Default::default(),
+        pricing: Default::default(),
+        skills_dir: "../../skills".to_string(),
+        base_actions: opencrab_server::config::default_base_actions(),
+        context_budget: Default::default(),
+        persona: Default::default(),
+        consolidation: Default::default(),
+        memory_policy: Default::default(),
+        heartbeat: Arc::new(opencrab_server::heartbeat::HeartbeatManager::new(29)),
+        session_runner: Arc::new(opencrab_server::session_runner::SessionRunnerManager::new(0)),
+        consolidation_manager: Arc::new(opencrab_server::consolidation::ConsolidationManager::new()),
+        ws_gateway: Arc::new(opencrab_gateway::WebSocketGateway::new(64)),
     };
     let app = create_router(state);
     (app, db)